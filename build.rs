@@ -0,0 +1,52 @@
+//! Records build-time facts reported by `trible version --verbose`.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+    let lock_path = Path::new(&manifest_dir).join("Cargo.lock");
+    let lock = fs::read_to_string(&lock_path).unwrap_or_default();
+    for (name, var) in [
+        ("triblespace", "TRIBLE_BUILD_TRIBLESPACE"),
+        ("triblespace-core", "TRIBLE_BUILD_TRIBLESPACE_CORE"),
+    ] {
+        let version = locked_version(&lock, name).unwrap_or("unknown");
+        println!("cargo:rustc-env={var}={version}");
+    }
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|f| f.to_ascii_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    println!(
+        "cargo:rustc-env=TRIBLE_BUILD_FEATURES={}",
+        features.join(",")
+    );
+    println!(
+        "cargo:rustc-env=TRIBLE_BUILD_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", lock_path.display());
+}
+
+/// The version of `name` pinned in a `Cargo.lock`.
+fn locked_version<'a>(lock: &'a str, name: &str) -> Option<&'a str> {
+    let needle = format!("name = \"{name}\"");
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line == needle {
+            return lines
+                .next()?
+                .strip_prefix("version = \"")?
+                .strip_suffix('"');
+        }
+    }
+    None
+}
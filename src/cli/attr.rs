@@ -0,0 +1,309 @@
+//! Attribute names: the registry under `~/.config/trible/attributes.toml`.
+//!
+//! Names make attribute ids readable in command output and can be used
+//! wherever an attribute is expected. They come from three places, in order
+//! of precedence: the `[attributes]` table of the config file, names
+//! registered with `trible attr register`, and the built-in names of the
+//! repository, metadata and snapshot attributes.
+
+use anyhow::Result;
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use triblespace_core::id::Id;
+
+use crate::cli::config;
+use crate::cli::output::{Field, Output};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttrSchema {
+    /// Inline UTF-8 text of at most 32 bytes
+    Shortstring,
+    /// Handle of a UTF-8 text blob
+    Longstring,
+    /// Reference to another entity
+    Genid,
+    /// Handle of any other blob
+    Handle,
+}
+
+impl AttrSchema {
+    fn name(self) -> &'static str {
+        match self {
+            AttrSchema::Shortstring => "shortstring",
+            AttrSchema::Longstring => "longstring",
+            AttrSchema::Genid => "genid",
+            AttrSchema::Handle => "handle",
+        }
+    }
+}
+
+#[derive(Parser)]
+pub enum AttrCommand {
+    /// Register NAME for an attribute id.
+    Register {
+        /// Name to show instead of the id
+        name: String,
+        /// Attribute id (32 hex chars)
+        id: String,
+        /// Value schema of the attribute, for reference
+        #[arg(long, value_enum)]
+        schema: Option<AttrSchema>,
+        /// Replace an existing registration of NAME
+        #[arg(long)]
+        force: bool,
+    },
+    /// List known attribute names: config, registry and built-in entries.
+    #[command(visible_alias = "ls")]
+    List,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Registration {
+    id: String,
+    schema: Option<AttrSchema>,
+}
+
+pub fn run(cmd: AttrCommand) -> Result<()> {
+    match cmd {
+        AttrCommand::Register {
+            name,
+            id,
+            schema,
+            force,
+        } => {
+            check_name(&name)?;
+            let id = parse_id(&id)?;
+            let mut registry = load_registry()?;
+            if let Some(existing) = registry.get(&name) {
+                if !force && !existing.id.eq_ignore_ascii_case(&id) {
+                    return Err(crate::cli::error::conflict(format!(
+                        "{name:?} is already registered as {}; pass --force to replace it",
+                        existing.id
+                    )));
+                }
+            }
+            registry.insert(name, Registration { id, schema });
+            save_registry(&registry)?;
+        }
+        AttrCommand::List => {
+            let mut out = Output::stdout();
+            for (name, id, schema, source) in entries()? {
+                out.record(&[
+                    ("name", Field::from(name)),
+                    ("id", Field::from(id)),
+                    (
+                        "schema",
+                        schema
+                            .map(|s| Field::from(s.name()))
+                            .unwrap_or_else(|| Field::missing("-")),
+                    ),
+                    ("source", Field::from(source)),
+                ])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The name of an attribute, if it has one.
+pub(crate) fn label(attribute: &Id) -> Option<String> {
+    let hex = format!("{attribute:X}");
+    let configured = config::get()
+        .attributes
+        .iter()
+        .find(|(_, id)| id.eq_ignore_ascii_case(&hex));
+    if let Some((name, _)) = configured {
+        return Some(name.clone());
+    }
+    let registered = registry()
+        .iter()
+        .find(|(_, r)| r.id.eq_ignore_ascii_case(&hex));
+    if let Some((name, _)) = registered {
+        return Some(name.clone());
+    }
+    builtins()
+        .into_iter()
+        .find(|(id, _, _)| id == attribute)
+        .map(|(_, name, _)| name.to_string())
+}
+
+/// `name (HEX)` for named attributes, otherwise the bare hex id.
+pub(crate) fn display(attribute: &Id) -> String {
+    match label(attribute) {
+        Some(name) => format!("{name} ({attribute:X})"),
+        None => format!("{attribute:X}"),
+    }
+}
+
+/// The hex id registered under `name`, if any.
+pub(crate) fn resolve(name: &str) -> Option<String> {
+    if let Some(id) = config::get().attributes.get(name) {
+        return Some(id.clone());
+    }
+    if let Some(registration) = registry().get(name) {
+        return Some(registration.id.clone());
+    }
+    builtins()
+        .into_iter()
+        .find(|(_, builtin, _)| *builtin == name)
+        .map(|(id, _, _)| format!("{id:X}"))
+}
+
+fn builtins() -> [(Id, &'static str, Option<AttrSchema>); 23] {
+    use crate::cli::pile::snapshot::schema as snapshot;
+    use triblespace_core::{metadata, repo};
+    use AttrSchema::*;
+
+    [
+        (metadata::name.id(), "metadata::name", Some(Longstring)),
+        (metadata::created_at.id(), "metadata::created_at", None),
+        (metadata::tag.id(), "metadata::tag", Some(Genid)),
+        (metadata::attribute.id(), "metadata::attribute", Some(Genid)),
+        (repo::branch.id(), "repo::branch", Some(Genid)),
+        (repo::head.id(), "repo::head", Some(Handle)),
+        (repo::content.id(), "repo::content", Some(Handle)),
+        (repo::metadata.id(), "repo::metadata", Some(Handle)),
+        (repo::parent.id(), "repo::parent", Some(Handle)),
+        (repo::message.id(), "repo::message", Some(Longstring)),
+        (
+            repo::short_message.id(),
+            "repo::short_message",
+            Some(Shortstring),
+        ),
+        (repo::timestamp.id(), "repo::timestamp", None),
+        (repo::signed_by.id(), "repo::signed_by", None),
+        (repo::signature_r.id(), "repo::signature_r", None),
+        (repo::signature_s.id(), "repo::signature_s", None),
+        (snapshot::root.id(), "snapshot::root", Some(Longstring)),
+        (snapshot::taken_at.id(), "snapshot::taken_at", None),
+        (snapshot::snapshot.id(), "snapshot::snapshot", Some(Genid)),
+        (snapshot::path.id(), "snapshot::path", Some(Longstring)),
+        (snapshot::size.id(), "snapshot::size", None),
+        (snapshot::modified_at.id(), "snapshot::modified_at", None),
+        (snapshot::content.id(), "snapshot::content", Some(Handle)),
+        (
+            snapshot::symlink_target.id(),
+            "snapshot::symlink_target",
+            Some(Longstring),
+        ),
+    ]
+}
+
+/// Every name with its id, schema and source, in precedence order.
+fn entries() -> Result<Vec<(String, String, Option<AttrSchema>, &'static str)>> {
+    let mut entries: Vec<_> = config::get()
+        .attributes
+        .iter()
+        .map(|(name, id)| (name.clone(), id.to_ascii_uppercase(), None, "config"))
+        .collect();
+    for (name, registration) in load_registry()? {
+        entries.push((name, registration.id, registration.schema, "registry"));
+    }
+    for (id, name, schema) in builtins() {
+        entries.push((name.to_string(), format!("{id:X}"), schema, "builtin"));
+    }
+    Ok(entries)
+}
+
+fn registry_path() -> Result<PathBuf> {
+    config::dir()
+        .map(|dir| dir.join("attributes.toml"))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "cannot locate the attribute registry: neither XDG_CONFIG_HOME nor HOME is set"
+            )
+        })
+}
+
+fn load_registry() -> Result<BTreeMap<String, Registration>> {
+    let path = registry_path()?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => anyhow::bail!("failed to read {}: {e}", path.display()),
+    };
+    let registry: BTreeMap<String, Registration> = toml::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("invalid attribute registry {}: {e}", path.display()))?;
+    for (name, registration) in &registry {
+        parse_id(&registration.id).map_err(|_| {
+            anyhow::anyhow!(
+                "invalid attribute registry {}: `{name}.id` is not a 32-char hex id",
+                path.display()
+            )
+        })?;
+    }
+    Ok(registry)
+}
+
+fn save_registry(registry: &BTreeMap<String, Registration>) -> Result<()> {
+    let path = registry_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", dir.display()))?;
+    }
+    fs::write(&path, toml::to_string(registry)?)
+        .map_err(|e| anyhow::anyhow!("failed to write {}: {e}", path.display()))
+}
+
+/// The registry as loaded once per process; lookups treat an unreadable
+/// registry as empty after warning about it.
+fn registry() -> &'static BTreeMap<String, Registration> {
+    static REGISTRY: OnceLock<BTreeMap<String, Registration>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        load_registry().unwrap_or_else(|e| {
+            tracing::warn!("ignoring attribute registry: {e:#}");
+            BTreeMap::new()
+        })
+    })
+}
+
+/// Names may not contain whitespace and may not look like ids themselves.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) || parse_id(name).is_ok() {
+        return Err(crate::cli::error::invalid_argument(format!(
+            "invalid attribute name {name:?}: use a non-empty name without spaces that is not an id"
+        )));
+    }
+    Ok(())
+}
+
+/// Validate a 32-char hex id, returning it in upper case.
+fn parse_id(hex: &str) -> Result<String> {
+    let mut raw = [0u8; 16];
+    hex::decode_to_slice(hex, &mut raw)
+        .ok()
+        .and_then(|_| Id::new(raw))
+        .ok_or_else(|| {
+            crate::cli::error::invalid_argument(format!(
+                "invalid attribute id {hex:?}: expected 32 hex chars, not all zero"
+            ))
+        })?;
+    Ok(hex.to_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_and_names_are_validated() {
+        assert_eq!(
+            parse_id("a6cfae6ee9aa0582b3212172b6c4801a").unwrap(),
+            "A6CFAE6EE9AA0582B3212172B6C4801A"
+        );
+        for bad in ["", "xyz", "A6CFAE6E", &"0".repeat(32)] {
+            assert!(parse_id(bad).is_err(), "{bad}");
+        }
+        assert!(check_name("title").is_ok());
+        for bad in ["", "two words", "A6CFAE6EE9AA0582B3212172B6C4801A"] {
+            assert!(check_name(bad).is_err(), "{bad}");
+        }
+    }
+}
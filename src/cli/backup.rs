@@ -0,0 +1,149 @@
+//! `trible backup`: snapshot a directory, commit it and optionally push it.
+//!
+//! The one-command form of `pile snapshot create` followed by `branch push`,
+//! meant for cron. Blobs are content addressed, so files unchanged since the
+//! last backup add nothing to the pile, and the push only uploads what the
+//! remote does not hold yet.
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BranchStore;
+use triblespace_core::id::Id;
+use triblespace_core::repo::objectstore::ObjectStoreRemote;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::PushResult;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::Blake3;
+
+use crate::cli::branch::{self, Selected};
+use crate::cli::error::{self, ErrorKind};
+use crate::cli::output::{Field, Output, OutputFormat};
+use crate::cli::pile::resolve::{resolve_branch, BranchRef};
+use crate::cli::pile::signing::SigningArgs;
+use crate::cli::pile::snapshot;
+use crate::cli::pile::ClosingRepo;
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    dir: PathBuf,
+    pile: Option<PathBuf>,
+    branch: String,
+    remote: Option<String>,
+    force: bool,
+    limit_rate: Option<u64>,
+    signing: SigningArgs,
+) -> Result<()> {
+    let pile_path = crate::cli::pile::resolve_pile(pile)?;
+    let url = remote
+        .as_deref()
+        .map(crate::cli::config::remote_url)
+        .transpose()?;
+    let scan = snapshot::scan(&dir)?;
+    let key = signing.load()?;
+    let pile: Pile<Blake3> = Pile::open(&pile_path)?;
+    let repo = Repository::new(pile, key, TribleSet::new())?;
+    ClosingRepo::new(repo).run(|repo| {
+        repo.storage_mut()
+            .refresh()
+            .map_err(|e| anyhow::anyhow!("refresh pile: {e:?}"))?;
+        let branch_id = backup_branch(repo, &branch, force)?;
+        let recorded = snapshot::record(repo, branch_id, &dir, &scan)?;
+        let uploaded = match &url {
+            Some(url) => Some(push(
+                repo.storage_mut(),
+                url,
+                branch_id,
+                &branch,
+                limit_rate,
+            )?),
+            None => None,
+        };
+
+        let commit = recorded
+            .commit
+            .map(|h| format!("blake3:{}", hex::encode(h.raw)))
+            .unwrap_or_else(|| "-".to_string());
+        let mut out = Output::stdout();
+        if out.format() == OutputFormat::Plain {
+            let uploaded = match uploaded {
+                Some(bytes) => format!(", {bytes} byte(s) uploaded"),
+                None => String::new(),
+            };
+            println!(
+                "{} file(s) scanned, {} new blob(s) stored{uploaded}",
+                recorded.files, recorded.new_blobs
+            );
+            println!("commit {commit}");
+        } else {
+            out.record(&[
+                ("files", Field::from(recorded.files)),
+                ("new_blobs", Field::from(recorded.new_blobs)),
+                (
+                    "uploaded_bytes",
+                    uploaded.map_or(Field::missing("-"), Field::from),
+                ),
+                ("commit", Field::from(commit)),
+            ])?;
+        }
+        Ok(())
+    })
+}
+
+/// The branch named `name`, created on the first backup. An existing branch
+/// whose head is not a snapshot commit is refused unless `force` is set, so a
+/// typo cannot bury a project branch under directory snapshots.
+fn backup_branch(repo: &mut Repository<Pile<Blake3>>, name: &str, force: bool) -> Result<Id> {
+    let pile = repo.storage_mut();
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    match resolve_branch(pile, &reader, BranchRef::Name(name)) {
+        Ok(info) => {
+            if let Some(head) = info.head {
+                if !force && !snapshot::is_snapshot_commit(&reader, head)? {
+                    return Err(error::invalid_argument(format!(
+                        "the head of {} is not a snapshot commit; pass --force to back up onto it anyway",
+                        info.label()
+                    )));
+                }
+            }
+            Ok(info.id)
+        }
+        Err(e) if error::kind_of(&e) == Some(ErrorKind::NotFound) => Ok(*repo
+            .create_branch(name, None)
+            .map_err(|e| anyhow::anyhow!("create branch: {e:?}"))?),
+        Err(e) => Err(e),
+    }
+}
+
+/// Push `branch_id` to `url` (see [`branch::push`]). Returns the bytes
+/// uploaded.
+fn push(
+    pile: &mut Pile<Blake3>,
+    url: &url::Url,
+    branch_id: Id,
+    label: &str,
+    limit_rate: Option<u64>,
+) -> Result<u64> {
+    let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(url)?;
+    let meta = pile
+        .head(branch_id)?
+        .ok_or_else(|| error::not_found(format!("branch {branch_id:X} not found")))?;
+    let selected = [Selected {
+        id: branch_id,
+        label: label.to_string(),
+        meta,
+    }];
+    let pushed = branch::push(pile, &mut remote, url, &selected, false, limit_rate)?;
+    match pushed.updates.into_iter().next() {
+        Some(Ok(PushResult::Success())) => Ok(pushed.uploaded),
+        Some(Ok(PushResult::Conflict(_))) => Err(error::conflict(
+            "remote branch advanced concurrently; the snapshot is committed locally, push it with `trible branch push`",
+        )),
+        Some(Err(e)) => Err(e),
+        None => unreachable!("one branch selected"),
+    }
+}
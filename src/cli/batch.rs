@@ -0,0 +1,147 @@
+//! `trible batch`: run many blob operations against one open pile.
+//!
+//! Commands are read from stdin, one per line, and each produces exactly one
+//! result line on stdout, flushed immediately so another process can drive
+//! the session interactively:
+//!
+//! ```text
+//! put PATH            -> blake3:HEX<TAB>new | existing
+//! get HANDLE PATH     -> ok LENGTH
+//! exists HANDLE       -> yes | no
+//! inspect HANDLE      -> blake3:HEX<TAB>CREATED<TAB>LENGTH<TAB>TYPE
+//! ```
+//!
+//! Paths extend to the end of the line, so they may contain spaces. Blank
+//! lines and lines starting with `#` are skipped. A failing or unknown
+//! command prints `error: MESSAGE` and the session continues.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use file_type::FileType;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use triblespace::prelude::blobschemas::FileBytes;
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::blob::Bytes;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::value::schemas::hash::{Blake3, Handle, Hash};
+use triblespace_core::value::Value;
+
+use crate::cli::pile::ClosingPile;
+use crate::cli::util::parse_blob_handle;
+use crate::cli::verify;
+
+pub fn run(pile: Option<PathBuf>) -> Result<()> {
+    let path = crate::cli::pile::resolve_pile(pile)?;
+    ClosingPile::open(&path)?.run(|pile| {
+        pile.refresh()?;
+        let mut out = io::stdout().lock();
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match execute(pile, line) {
+                Ok(result) => writeln!(out, "{result}")?,
+                Err(e) => writeln!(out, "error: {e:#}")?,
+            }
+            out.flush()?;
+        }
+        Ok(())
+    })
+}
+
+fn execute(pile: &mut Pile<Blake3>, line: &str) -> Result<String> {
+    let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let args = args.trim_start();
+    match command {
+        "put" => put(pile, Path::new(required(args, "PATH")?)),
+        "get" => {
+            let (handle, path) = args
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| anyhow::anyhow!("usage: get HANDLE PATH"))?;
+            get(pile, handle, Path::new(path.trim_start()))
+        }
+        "exists" => {
+            let handle = blob_handle(required(args, "HANDLE")?)?;
+            let reader = pile
+                .reader()
+                .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+            let present = reader.metadata(handle)?.is_some();
+            Ok(if present { "yes" } else { "no" }.to_string())
+        }
+        "inspect" => inspect(pile, required(args, "HANDLE")?),
+        other => anyhow::bail!("unknown command {other:?} (expected put, get, exists or inspect)"),
+    }
+}
+
+fn required<'a>(args: &'a str, name: &str) -> Result<&'a str> {
+    if args.is_empty() {
+        anyhow::bail!("missing {name}");
+    }
+    Ok(args)
+}
+
+fn blob_handle(handle: &str) -> Result<Value<Handle<Blake3, UnknownBlob>>> {
+    Ok(parse_blob_handle(handle)?.into())
+}
+
+fn put(pile: &mut Pile<Blake3>, path: &Path) -> Result<String> {
+    let file =
+        File::open(path).map_err(|e| anyhow::anyhow!("failed to open {}: {e}", path.display()))?;
+    let bytes = unsafe { Bytes::map_file(&file)? };
+    let handle: Value<Handle<Blake3, FileBytes>> = Value::new(*blake3::hash(&bytes).as_bytes());
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let new = reader.metadata(handle)?.is_none();
+    if new {
+        verify::put::<FileBytes, _>(pile, bytes)?;
+    }
+    let hash: Value<Hash<Blake3>> = Handle::to_hash(handle);
+    let hex: String = hash.from_value();
+    Ok(format!("{hex}\t{}", if new { "new" } else { "existing" }))
+}
+
+fn get(pile: &mut Pile<Blake3>, handle: &str, path: &Path) -> Result<String> {
+    let handle = blob_handle(handle)?;
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    if reader.metadata(handle)?.is_none() {
+        return Err(crate::cli::error::not_found("blob not found"));
+    }
+    let bytes: Bytes = reader.get(handle)?;
+    let mut file = File::create(path)
+        .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", path.display()))?;
+    file.write_all(&bytes)?;
+    Ok(format!("ok {}", bytes.len()))
+}
+
+fn inspect(pile: &mut Pile<Blake3>, handle: &str) -> Result<String> {
+    let hash = parse_blob_handle(handle)?;
+    let handle: Value<Handle<Blake3, UnknownBlob>> = hash.into();
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let metadata = reader
+        .metadata(handle)?
+        .ok_or_else(|| crate::cli::error::not_found("blob not found"))?;
+    let bytes: Bytes = reader.get(handle)?;
+    let created: DateTime<Utc> =
+        DateTime::<Utc>::from(UNIX_EPOCH + Duration::from_millis(metadata.timestamp));
+    let hash: String = hash.from_value();
+    Ok(format!(
+        "{hash}\t{}\t{}\t{}",
+        created.to_rfc3339(),
+        metadata.length,
+        FileType::from_bytes(&bytes).name()
+    ))
+}
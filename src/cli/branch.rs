@@ -1,116 +1,607 @@
 use anyhow::Result;
 use clap::Parser;
-use std::convert::TryInto;
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::path::PathBuf;
 
 // DEFAULT_MAX_PILE_SIZE removed; the new Pile API no longer uses a size const generic
+use triblespace::prelude::blobschemas::SimpleArchive;
 use triblespace::prelude::BlobStore;
-use triblespace::prelude::BlobStoreList;
+use triblespace::prelude::BlobStoreGet;
 use triblespace::prelude::BranchStore;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::id::Id;
+use triblespace_core::repo;
+use triblespace_core::repo::objectstore::ObjectStoreRemote;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::repo::PushResult;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::Blake3;
+use triblespace_core::value::schemas::hash::Handle;
+use triblespace_core::value::Value;
+
+use crate::cli::error;
+use crate::cli::pile::branch::{
+    enforce_signer_policy, extract_repo_head, is_ancestor_of, parse_public_key, unapproved_commits,
+};
+use crate::cli::pile::resolve::{all_branches, resolve_branch, BranchRef};
+use crate::cli::pile::ClosingPile;
+use crate::cli::remote_state;
+use crate::cli::stats;
+use crate::cli::style::{self, Color};
+use crate::cli::throttle::RateLimiter;
+use crate::cli::util::skip_present;
+use tracing::{debug, trace, warn};
+
+type BlobHandle = Value<Handle<Blake3, UnknownBlob>>;
+type MetaHandle = Value<Handle<Blake3, SimpleArchive>>;
+type CommitHandle = Value<Handle<Blake3, SimpleArchive>>;
 
 #[derive(Parser)]
 pub enum BranchCommand {
-    /// Push a branch from a pile to a remote object store.
+    /// Push branches from a pile to a remote object store.
+    ///
+    /// Blobs shared between the listed branches are transferred once; each
+    /// remote head is then updated individually, and only after every blob
+    /// has been uploaded, so an interrupted push never leaves a remote head
+    /// pointing at missing history. A branch the remote does not have yet is
+    /// only created with `--create` (implied by `--all`), so a mistyped id
+    /// cannot spawn an orphan remote branch.
     Push {
-        /// URL of the target object store
+        /// URL of the target object store, or a remote name from the config
         url: String,
         /// Path to the source pile file
         pile: PathBuf,
-        /// Branch identifier to push (hex encoded)
-        branch: String,
+        /// Branches to push (hex id, unique id prefix or name)
+        #[arg(required_unless_present = "all", conflicts_with = "all", num_args = 1..)]
+        branches: Vec<String>,
+        /// Push every branch in the pile
+        #[arg(long)]
+        all: bool,
+        /// Create branches the remote does not have yet
+        #[arg(long)]
+        create: bool,
+        /// Ignore the recorded last-pushed head and walk the full history
+        #[arg(long)]
+        no_cache: bool,
+        /// Limit the transfer rate in bytes per second (e.g. `500k`, `5M`)
+        #[arg(long, value_parser = crate::cli::throttle::parse_rate)]
+        limit_rate: Option<u64>,
     },
-    /// Pull a branch from a remote object store into a pile.
+    /// Pull branches from a remote object store into a pile.
     Pull {
-        /// URL of the source object store
+        /// URL of the source object store, or a remote name from the config
         url: String,
         /// Path to the destination pile file
         pile: PathBuf,
-        /// Branch identifier to pull (hex encoded)
-        branch: String,
+        /// Branches to pull (hex id, unique id prefix or remote branch name)
+        #[arg(required = true, num_args = 1..)]
+        branches: Vec<String>,
+        /// Limit the transfer rate in bytes per second (e.g. `500k`, `5M`)
+        #[arg(long, value_parser = crate::cli::throttle::parse_rate)]
+        limit_rate: Option<u64>,
+        /// Refuse the pull unless every new commit is validly signed by this
+        /// public key (64 hex chars); repeat to allow several keys
+        #[arg(long, value_name = "PUBKEY_HEX")]
+        require_signed_by: Vec<String>,
+        /// Fetch only the newest N commits of each branch (with their
+        /// content); older parents are recorded as a shallow boundary
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+        /// Fetch the full history of previously shallow branches (what
+        /// every pull without `--depth` does)
+        #[arg(long, conflicts_with = "depth")]
+        unshallow: bool,
     },
 }
 
+/// A branch selected for push/pull together with its branch metadata handle.
+pub(crate) struct Selected {
+    pub id: Id,
+    pub label: String,
+    pub meta: MetaHandle,
+}
+
+/// What [`push`] did.
+pub(crate) struct Pushed {
+    /// Blobs the selected branches reach beyond what was last pushed.
+    pub discovered: usize,
+    /// Per branch: blobs it reaches, and how many no earlier branch did.
+    pub attribution: Vec<(usize, usize)>,
+    /// Blobs the remote already held.
+    pub skipped: usize,
+    /// Bytes uploaded.
+    pub uploaded: u64,
+    /// The head update of each selected branch, in selection order.
+    pub updates: Vec<Result<PushResult<Blake3>>>,
+}
+
 pub fn run(cmd: BranchCommand) -> Result<()> {
     match cmd {
-        BranchCommand::Push { url, pile, branch } => {
-            use triblespace_core::id::Id;
-            use triblespace_core::repo;
-            use triblespace_core::repo::objectstore::ObjectStoreRemote;
-            use triblespace_core::repo::pile::Pile;
-            use triblespace_core::value::schemas::hash::Blake3;
-            use url::Url;
-
-            let url = Url::parse(&url)?;
+        BranchCommand::Push {
+            url,
+            pile,
+            branches,
+            all,
+            create,
+            no_cache,
+            limit_rate,
+        } => {
+            let url = crate::cli::config::remote_url(&url)?;
             let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-
-            let res = (|| -> Result<(), anyhow::Error> {
+            debug!(path = %pile.display(), "opening pile");
+            let open = stats::phase!("pile open");
+            ClosingPile::open(&pile)?.run(|pile| {
+                pile.refresh()?;
+                drop(open);
+                debug!("refreshed pile");
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
 
-                // The transfer API now takes an explicit iterator of handles to
-                // copy. Use the reader's blobs() listing and filter out any
-                // listing errors so we have a plain iterator of handles.
-                for r in repo::transfer(&reader, &mut remote, reader.blobs().filter_map(|r| r.ok()))
-                // TODO: We should log these errors to stderr.
-                {
-                    r?;
+                let mut selected: Vec<Selected> = Vec::new();
+                if all {
+                    let mut infos = all_branches(pile, &reader)?;
+                    infos.sort_by_key(|info| info.id);
+                    selected.extend(infos.into_iter().map(|info| Selected {
+                        id: info.id,
+                        label: info.name.unwrap_or_else(|| format!("{:X}", info.id)),
+                        meta: info.meta,
+                    }));
+                }
+                for raw in &branches {
+                    let info = resolve_branch(pile, &reader, BranchRef::Any(raw))?;
+                    debug!(branch = %raw, id = %format!("{:X}", info.id), "resolved branch");
+                    if selected.iter().any(|s| s.id == info.id) {
+                        continue;
+                    }
+                    selected.push(Selected {
+                        id: info.id,
+                        label: raw.clone(),
+                        meta: info.meta,
+                    });
                 }
 
-                let raw = hex::decode(branch)?;
-                let raw: [u8; 16] = raw.as_slice().try_into()?;
-                let id = Id::new(raw).ok_or_else(|| anyhow::anyhow!("bad id"))?;
+                // Refuse unknown remote branches before uploading anything.
+                if !create && !all {
+                    for s in &selected {
+                        if remote.head(s.id)?.is_none() {
+                            return Err(error::not_found(format!(
+                                "{}: remote branch {:X} does not exist; pass --create",
+                                s.label, s.id
+                            )));
+                        }
+                    }
+                }
+
+                let pushed = push(pile, &mut remote, &url, &selected, no_cache, limit_rate)?;
+                println!("discovered {} blob(s)", pushed.discovered);
+                if selected.len() > 1 {
+                    for (s, (reached, new)) in selected.iter().zip(&pushed.attribution) {
+                        println!(
+                            "{}: {reached} blob(s), {new} not shared with an earlier branch",
+                            s.label
+                        );
+                    }
+                }
+                println!("skipped {} blob(s) already on the remote", pushed.skipped);
 
-                let handle = pile
-                    .head(id)?
-                    .ok_or_else(|| anyhow::anyhow!("branch not found"))?;
-                let old = remote.head(id)?;
-                remote.update(id, old, Some(handle))?;
+                let mut failed = 0usize;
+                let mut conflicts = 0usize;
+                for (s, outcome) in selected.iter().zip(pushed.updates) {
+                    match outcome {
+                        Ok(PushResult::Success()) => println!(
+                            "{}: {}",
+                            s.label,
+                            style::paint(Color::Green, format!("pushed {:X}", s.id))
+                        ),
+                        Ok(PushResult::Conflict(_)) => {
+                            eprintln!(
+                                "{}: {}",
+                                s.label,
+                                style::epaint(Color::Red, "remote branch advanced concurrently")
+                            );
+                            conflicts += 1;
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "{}: {}",
+                                s.label,
+                                style::epaint(Color::Red, format!("update failed: {e:#}"))
+                            );
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed + conflicts > 0 {
+                    let msg = format!(
+                        "{} of {} branch update(s) failed",
+                        failed + conflicts,
+                        selected.len()
+                    );
+                    // Only report a conflict when nothing else went wrong, so
+                    // callers can safely retry on that exit code.
+                    return Err(if failed == 0 {
+                        error::conflict(msg)
+                    } else {
+                        anyhow::anyhow!(msg)
+                    });
+                }
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
-        BranchCommand::Pull { url, pile, branch } => {
-            use triblespace_core::id::Id;
-            use triblespace_core::repo;
-            use triblespace_core::repo::objectstore::ObjectStoreRemote;
-            use triblespace_core::repo::pile::Pile;
-            use triblespace_core::value::schemas::hash::Blake3;
-            use url::Url;
-
-            let url = Url::parse(&url)?;
+        BranchCommand::Pull {
+            url,
+            pile,
+            branches,
+            limit_rate,
+            require_signed_by,
+            depth,
+            unshallow: _,
+        } => {
+            if depth == Some(0) {
+                return Err(error::invalid_argument("--depth must be at least 1"));
+            }
+            let allowed = require_signed_by
+                .iter()
+                .map(|k| parse_public_key(k))
+                .collect::<Result<Vec<_>>>()?;
+            let url = crate::cli::config::remote_url(&url)?;
             let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-
-            let res = (|| -> Result<(), anyhow::Error> {
+            debug!(path = %pile.display(), "opening pile");
+            let pile = {
+                let _phase = stats::phase!("pile open");
+                ClosingPile::open(&pile)?
+            };
+            pile.run(|pile| {
                 let reader = remote
                     .reader()
                     .map_err(|e| anyhow::anyhow!("remote reader error: {e:?}"))?;
 
-                // Copy all blobs reported by the remote reader into the local
-                // pile. Ignore transient listing errors and rely on transfer()
-                // to surface actual copy failures.
-                for r in repo::transfer(&reader, &mut pile, reader.blobs().filter_map(|r| r.ok())) {
+                let mut selected: Vec<Selected> = Vec::new();
+                for raw in &branches {
+                    let info = resolve_branch(&mut remote, &reader, BranchRef::Any(raw))?;
+                    debug!(branch = %raw, id = %format!("{:X}", info.id), "resolved remote branch");
+                    if selected.iter().any(|s| s.id == info.id) {
+                        continue;
+                    }
+                    selected.push(Selected {
+                        id: info.id,
+                        label: raw.clone(),
+                        meta: info.meta,
+                    });
+                }
+
+                // Copy everything reachable from the selected branches; shared
+                // history is only visited once. A full pull (the default, or
+                // `--unshallow`) also completes earlier shallow pulls.
+                let mut boundaries: Vec<Vec<CommitHandle>> = vec![Vec::new(); selected.len()];
+                let handles: Vec<BlobHandle> = {
+                    let _phase = stats::phase!("reachability");
+                    match depth {
+                        None => {
+                            let roots: Vec<BlobHandle> =
+                                selected.iter().map(|s| s.meta.transmute()).collect();
+                            repo::reachable(&reader, roots).collect()
+                        }
+                        Some(depth) => {
+                            let mut seen: HashSet<[u8; 32]> = HashSet::new();
+                            let mut handles = Vec::new();
+                            for (s, boundary) in selected.iter().zip(&mut boundaries) {
+                                let (reached, cut) = shallow_reachable(&reader, s.meta, depth)?;
+                                handles.extend(reached.into_iter().filter(|h| seen.insert(h.raw)));
+                                *boundary = cut;
+                            }
+                            handles
+                        }
+                    }
+                };
+                println!("discovered {} blob(s)", handles.len());
+
+                pile.refresh()?;
+                let local_reader = pile
+                    .reader()
+                    .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                let skipped = Cell::new(0usize);
+                let limiter = limit_rate.map(RateLimiter::new);
+                let handles = skip_present(handles.into_iter(), &local_reader, &skipped);
+                let handles = handles.inspect(|h| {
+                    // Metadata lookups can be remote requests; skip them when
+                    // nothing consumes the length.
+                    if limiter.is_none() && !stats::enabled() {
+                        return;
+                    }
+                    if let Ok(Some(meta)) = reader.metadata(*h) {
+                        stats::read(meta.length);
+                        stats::written(meta.length);
+                        if let Some(limiter) = &limiter {
+                            limiter.acquire(meta.length);
+                        }
+                    }
+                });
+                let transfer = stats::phase!("transfer");
+                for r in repo::transfer(&reader, &mut *pile, handles) {
                     // TODO: We should log these errors to stderr.
-                    r?;
+                    let (src, _) = r?;
+                    trace!(blob = %hex::encode(src.raw), "transferred blob");
                 }
+                drop(transfer);
+                println!("skipped {} blob(s) already in the pile", skipped.get());
 
-                let raw = hex::decode(branch)?;
-                let raw: [u8; 16] = raw.as_slice().try_into()?;
-                let id = Id::new(raw).ok_or_else(|| anyhow::anyhow!("bad id"))?;
+                // Check every branch before moving any head; the transferred
+                // blobs stay, which is harmless.
+                if !allowed.is_empty() {
+                    let mut local_metas = Vec::new();
+                    for s in &selected {
+                        local_metas.push(pile.head(s.id)?);
+                    }
+                    let local_reader = pile
+                        .reader()
+                        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                    let commit_head = |meta| {
+                        local_reader
+                            .get::<TribleSet, SimpleArchive>(meta)
+                            .ok()
+                            .and_then(|meta| extract_repo_head(&meta))
+                    };
+                    let mut offending = Vec::new();
+                    for (s, local_meta) in selected.iter().zip(local_metas) {
+                        if let Some(incoming) = commit_head(s.meta) {
+                            let local = local_meta.and_then(commit_head);
+                            offending.extend(unapproved_commits(
+                                &local_reader,
+                                incoming,
+                                local,
+                                &allowed,
+                            ));
+                        }
+                    }
+                    enforce_signer_policy(&offending)?;
+                }
 
-                let handle = remote
-                    .head(id)?
-                    .ok_or_else(|| anyhow::anyhow!("branch not found"))?;
-                let old = pile.head(id)?;
-                pile.update(id, old, Some(handle))?;
+                let mut failed = 0usize;
+                let mut conflicts = 0usize;
+                for (s, boundary) in selected.iter().zip(&boundaries) {
+                    let outcome = (|| -> Result<PushResult<Blake3>, anyhow::Error> {
+                        let _phase = stats::phase!("head update");
+                        let old = pile.head(s.id)?;
+                        debug!(
+                            branch = %s.label,
+                            old = ?old.map(|h| hex::encode(h.raw)),
+                            new = %hex::encode(s.meta.raw),
+                            "updating local head"
+                        );
+                        let result = pile.update(s.id, old, Some(s.meta))?;
+                        if let PushResult::Success() = result {
+                            remote_state::store_shallow(pile, s.id, boundary)?;
+                        }
+                        Ok(result)
+                    })();
+                    match outcome {
+                        Ok(PushResult::Success()) if !boundary.is_empty() => println!(
+                            "{}: {}",
+                            s.label,
+                            style::paint(
+                                Color::Green,
+                                format!(
+                                    "pulled {:X} (shallow, {} older commit(s) not fetched)",
+                                    s.id,
+                                    boundary.len()
+                                )
+                            )
+                        ),
+                        Ok(PushResult::Success()) => println!(
+                            "{}: {}",
+                            s.label,
+                            style::paint(Color::Green, format!("pulled {:X}", s.id))
+                        ),
+                        Ok(PushResult::Conflict(_)) => {
+                            eprintln!(
+                                "{}: {}",
+                                s.label,
+                                style::epaint(Color::Red, "local branch advanced concurrently")
+                            );
+                            conflicts += 1;
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "{}: {}",
+                                s.label,
+                                style::epaint(Color::Red, format!("update failed: {e:#}"))
+                            );
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed + conflicts > 0 {
+                    let msg = format!(
+                        "{} of {} branch update(s) failed",
+                        failed + conflicts,
+                        selected.len()
+                    );
+                    // Only report a conflict when nothing else went wrong, so
+                    // callers can safely retry on that exit code.
+                    return Err(if failed == 0 {
+                        error::conflict(msg)
+                    } else {
+                        anyhow::anyhow!(msg)
+                    });
+                }
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
     }
     Ok(())
 }
+
+/// Upload the blobs the `selected` branches reach that the remote lacks, then
+/// move each remote head to the local branch metadata and record the pushed
+/// head for `url`. Per branch, only history newer than the head last pushed
+/// to this remote is walked, as long as the remote still holds that head, it
+/// is still an ancestor and `no_cache` is not set. A failed upload aborts before a single head moves;
+/// a failed head update is reported in [`Pushed::updates`] and leaves the
+/// other branches alone.
+pub(crate) fn push(
+    pile: &mut Pile<Blake3>,
+    remote: &mut ObjectStoreRemote<Blake3>,
+    url: &url::Url,
+    selected: &[Selected],
+    no_cache: bool,
+    limit_rate: Option<u64>,
+) -> Result<Pushed> {
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let remote_reader = remote
+        .reader()
+        .map_err(|e| anyhow::anyhow!("remote reader error: {e:?}"))?;
+
+    // Union the blobs to send.
+    let parent_attr = triblespace_core::repo::parent.id();
+    let mut seen: HashSet<[u8; 32]> = HashSet::new();
+    let mut handles: Vec<BlobHandle> = Vec::new();
+    let mut heads = Vec::new();
+    let mut attribution: Vec<(usize, usize)> = Vec::new();
+    let walk = stats::phase!("reachability");
+    for s in selected {
+        let head = reader
+            .get::<TribleSet, SimpleArchive>(s.meta)
+            .ok()
+            .and_then(|meta| extract_repo_head(&meta));
+        heads.push(head);
+
+        // A wiped or restored remote may have lost the recorded head, and
+        // with it the history below; then everything is sent again.
+        let pushed = if no_cache {
+            None
+        } else {
+            remote_state::load(pile, &reader, remote_state::record_id(url, s.id))?
+                .filter(|pushed| matches!(remote_reader.metadata(pushed.transmute()), Ok(Some(_))))
+        };
+        let root: BlobHandle = s.meta.transmute();
+        let found: Vec<BlobHandle> = match (pushed, head) {
+            (Some(pushed), Some(head)) if is_ancestor_of(pushed, head, &reader, &parent_attr)? => {
+                remote_state::reachable_since(&reader, root, pushed)
+            }
+            _ => repo::reachable(&reader, std::iter::once(root)).collect(),
+        };
+        let reached = found.len();
+        let before = handles.len();
+        handles.extend(found.into_iter().filter(|h| seen.insert(h.raw)));
+        attribution.push((reached, handles.len() - before));
+    }
+    drop(walk);
+    let discovered = handles.len();
+
+    let skipped = Cell::new(0usize);
+    let uploaded = Cell::new(0u64);
+    let limiter = limit_rate.map(RateLimiter::new);
+    let handles = skip_present(handles.into_iter(), &remote_reader, &skipped).inspect(|h| {
+        if let Ok(Some(meta)) = reader.metadata(*h) {
+            uploaded.set(uploaded.get() + meta.length);
+            stats::read(meta.length);
+            stats::written(meta.length);
+            if let Some(limiter) = &limiter {
+                limiter.acquire(meta.length);
+            }
+        }
+    });
+    let transfer = stats::phase!("transfer");
+    for r in repo::transfer(&reader, remote, handles) {
+        let (src, _) = r?;
+        trace!(blob = %hex::encode(src.raw), "transferred blob");
+    }
+    drop(transfer);
+
+    let mut updates = Vec::with_capacity(selected.len());
+    for (s, head) in selected.iter().zip(heads) {
+        let outcome = (|| -> Result<PushResult<Blake3>, anyhow::Error> {
+            let _phase = stats::phase!("head update");
+            let old = remote.head(s.id)?;
+            debug!(
+                branch = %s.label,
+                old = ?old.map(|h| hex::encode(h.raw)),
+                new = %hex::encode(s.meta.raw),
+                "updating remote head"
+            );
+            Ok(remote.update(s.id, old, Some(s.meta))?)
+        })();
+        if let (Ok(PushResult::Success()), Some(head)) = (&outcome, head) {
+            // The head did move; a stale record only makes the next push
+            // walk more history, so it must not hold back the other branches.
+            let state_id = remote_state::record_id(url, s.id);
+            if let Err(e) = remote_state::store(pile, state_id, url, s.id, head) {
+                warn!(branch = %s.label, "could not record the pushed head: {e:#}");
+            }
+        }
+        updates.push(outcome);
+    }
+    Ok(Pushed {
+        discovered,
+        attribution,
+        skipped: skipped.get(),
+        uploaded: uploaded.get(),
+        updates,
+    })
+}
+
+/// Blobs of a pull cut off `depth` commits below the head of `meta`: the
+/// branch metadata and what it names, the newest `depth` commits and
+/// everything their content, metadata and messages reach. Also returns the
+/// parents left out, the shallow boundary.
+fn shallow_reachable<R>(
+    reader: &R,
+    meta: MetaHandle,
+    depth: usize,
+) -> Result<(Vec<BlobHandle>, Vec<CommitHandle>)>
+where
+    R: BlobStoreGet<Blake3> + BlobStoreMeta<Blake3>,
+{
+    let meta_set: TribleSet = reader
+        .get::<TribleSet, SimpleArchive>(meta)
+        .map_err(|e| error::corrupt(format!("read remote branch metadata: {e:?}")))?;
+    let head_attr = repo::head.id();
+    let parent_attr = repo::parent.id();
+
+    let mut out: Vec<BlobHandle> = vec![meta.transmute()];
+    // Candidate references; `reachable` skips values that are not blobs.
+    let mut roots: Vec<BlobHandle> = meta_set
+        .iter()
+        .filter(|t| t.a() != &head_attr)
+        .map(|t| Value::new(t.data[32..].try_into().expect("32 byte value")))
+        .collect();
+
+    let mut kept: HashSet<[u8; 32]> = HashSet::new();
+    let mut parents: Vec<CommitHandle> = Vec::new();
+    let mut level: Vec<CommitHandle> = extract_repo_head(&meta_set).into_iter().collect();
+    for _ in 0..depth {
+        let mut next = Vec::new();
+        for commit in level {
+            if !kept.insert(commit.raw) {
+                continue;
+            }
+            out.push(commit.transmute());
+            let Ok(commit_set) = reader.get::<TribleSet, SimpleArchive>(commit) else {
+                continue;
+            };
+            for t in commit_set.iter() {
+                if t.a() == &parent_attr {
+                    next.push(*t.v::<Handle<Blake3, SimpleArchive>>());
+                } else {
+                    roots.push(Value::new(t.data[32..].try_into().expect("32 byte value")));
+                }
+            }
+        }
+        parents.extend(next.iter().copied());
+        level = next;
+    }
+
+    out.extend(repo::reachable(reader, roots));
+    let mut seen: HashSet<[u8; 32]> = HashSet::new();
+    let boundary = parents
+        .into_iter()
+        .filter(|p| !kept.contains(&p.raw) && seen.insert(p.raw))
+        .collect();
+    Ok((out, boundary))
+}
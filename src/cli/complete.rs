@@ -0,0 +1,107 @@
+//! Helpers called by the shell completion scripts to offer live values.
+//!
+//! The hidden `trible __complete ...` commands print one candidate per line
+//! and never fail: if the pile cannot be read they print nothing, so a
+//! half-typed command line never spams the terminal with errors.
+
+use anyhow::Result;
+use clap::Parser;
+use clap_complete::Shell;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::BranchStore;
+use triblespace_core::trible::TribleSet;
+
+use crate::cli::pile::branch::load_branch_name;
+use crate::cli::pile::ClosingPile;
+use crate::cli::remote_state;
+
+#[derive(Parser)]
+pub enum CompleteCommand {
+    /// Print the names of the branches stored in a pile.
+    BranchNames {
+        /// Pile to read (defaults to --pile / TRIBLES_PILE)
+        pile: Option<PathBuf>,
+    },
+}
+
+pub fn run(cmd: CompleteCommand) -> Result<()> {
+    match cmd {
+        CompleteCommand::BranchNames { pile } => {
+            for name in branch_names(pile).unwrap_or_default() {
+                println!("{name}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn branch_names(pile: Option<PathBuf>) -> Result<BTreeSet<String>> {
+    let path = crate::cli::pile::resolve_pile(pile)?;
+    // Opening a pile creates it; completion must not leave files behind.
+    if !path.is_file() {
+        anyhow::bail!("no pile at {}", path.display());
+    }
+    ClosingPile::open(&path)?.run(|pile| {
+        pile.refresh()?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let ids = pile.branches()?.collect::<Result<Vec<_>, _>>()?;
+        let mut names = BTreeSet::new();
+        for id in ids {
+            let Some(meta) = pile.head(id)? else {
+                continue;
+            };
+            let Ok(meta) = reader.get::<TribleSet, SimpleArchive>(meta) else {
+                continue;
+            };
+            if remote_state::is_remote_state(&meta) {
+                continue;
+            }
+            if let Ok(Some(name)) = load_branch_name(&reader, &meta) {
+                names.insert(name);
+            }
+        }
+        Ok(names)
+    })
+}
+
+/// Bash glue layered on top of the static clap script: completes branch names
+/// for `branch push URL PILE <branch>...` and `pile branch rename PILE <branch>`
+/// and defers to the generated `_trible` function everywhere else.
+const BASH_DYNAMIC: &str = r#"
+_trible_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local -a pos=()
+    local i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        [[ "${COMP_WORDS[i]}" == -* ]] || pos+=("${COMP_WORDS[i]}")
+    done
+    local pile=""
+    if [[ "${pos[0]}" =~ ^(branch|br)$ && "${pos[1]}" == push && ${#pos[@]} -ge 4 ]]; then
+        pile="${pos[3]}"
+    elif [[ "${pos[0]}" == pile && "${pos[1]}" =~ ^(branch|br)$ && "${pos[2]}" == rename && ${#pos[@]} -eq 4 ]]; then
+        pile="${pos[3]}"
+    fi
+    if [[ -n "$pile" && "$cur" != -* ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -W "$(trible __complete branch-names "$pile" 2>/dev/null)" -- "$cur"))
+        return 0
+    fi
+    _trible "$@"
+}
+complete -F _trible_dynamic -o bashdefault -o default trible
+"#;
+
+/// Extra script appended to the generated completions for `shell`, if any.
+pub fn dynamic_script(shell: Shell) -> Option<&'static str> {
+    match shell {
+        Shell::Bash => Some(BASH_DYNAMIC),
+        _ => None,
+    }
+}
@@ -0,0 +1,192 @@
+//! User configuration loaded from `~/.config/trible/config.toml`.
+//!
+//! Every setting can also be given on the command line or through an
+//! environment variable; CLI flags win over environment variables, which win
+//! over the configuration file (see [`layered`]).
+//!
+//! ```toml
+//! pile = "/data/main.pile"
+//! signing_key = "/home/me/.config/trible/signing.key"
+//! output = "json"
+//! concurrency = 8
+//! verify_writes = true
+//!
+//! [remotes]
+//! origin = "s3://bucket/prefix"
+//!
+//! [attributes]
+//! title = "A6CFAE6EE9AA0582B3212172B6C4801A"
+//! ```
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use url::Url;
+
+use crate::cli::output::OutputFormat;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Pile used when a `pile` subcommand omits its path.
+    pub pile: Option<PathBuf>,
+    /// Signing key used when none of `--signing-key`, `TRIBLES_SIGNING_KEY`
+    /// or `TRIBLES_SIGNING_KEY_HEX` is given.
+    pub signing_key: Option<PathBuf>,
+    /// Named object store URLs accepted wherever a remote URL is expected.
+    #[serde(default)]
+    pub remotes: BTreeMap<String, String>,
+    /// Names for attribute ids, accepted wherever an attribute is expected.
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    /// Default output format.
+    pub output: Option<OutputFormat>,
+    /// Read back and rehash every blob written to a pile (`--verify-writes`).
+    pub verify_writes: Option<bool>,
+    /// Default for `pile blob put --threads` and the number of remote
+    /// requests `verify-replica` keeps in flight.
+    pub concurrency: Option<usize>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config> {
+        let config: Config = toml::from_str(text)?;
+        if config.concurrency == Some(0) {
+            anyhow::bail!("key `concurrency` must be at least 1");
+        }
+        for (name, url) in &config.remotes {
+            Url::parse(url).map_err(|e| anyhow::anyhow!("key `remotes.{name}`: {e}"))?;
+        }
+        for (name, id) in &config.attributes {
+            if id.len() != 32 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+                anyhow::bail!("key `attributes.{name}`: expected a 32-char hex id");
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// The `trible` configuration directory, `~/.config/trible` by default.
+pub fn dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("trible"))
+}
+
+/// Default location of the configuration file.
+fn default_path() -> Option<PathBuf> {
+    Some(dir()?.join("config.toml"))
+}
+
+fn load_file(path: &Path) -> Result<Config> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read config {}: {e}", path.display()))?;
+    Config::parse(&text).map_err(|e| anyhow::anyhow!("invalid config {}: {e}", path.display()))
+}
+
+/// Load the configuration once at startup.
+///
+/// An explicit `--config` path must exist; the default location is optional.
+pub fn init(explicit: Option<PathBuf>) -> Result<()> {
+    let config = match explicit {
+        Some(path) => load_file(&path)?,
+        None => match default_path() {
+            Some(path) if path.exists() => load_file(&path)?,
+            _ => Config::default(),
+        },
+    };
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// The loaded configuration (empty when [`init`] has not run).
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Apply the flag > environment > config precedence to one setting.
+pub fn layered<T>(flag: Option<T>, env: Option<T>, config: Option<T>) -> Option<T> {
+    flag.or(env).or(config)
+}
+
+/// Read a path from the environment, treating an empty value as unset.
+pub fn env_path(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Resolve a remote argument: a name from `[remotes]` or a literal URL.
+pub fn remote_url(remote: &str) -> Result<Url> {
+    if let Some(url) = get().remotes.get(remote) {
+        return Ok(Url::parse(url)?);
+    }
+    Url::parse(remote).map_err(|e| anyhow::anyhow!("invalid remote {remote:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_all_keys() {
+        let config = Config::parse(
+            r#"
+            pile = "/tmp/main.pile"
+            signing_key = "/tmp/key"
+            output = "json"
+            concurrency = 4
+
+            [remotes]
+            origin = "file:///tmp/remote"
+
+            [attributes]
+            title = "A6CFAE6EE9AA0582B3212172B6C4801A"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.pile, Some(PathBuf::from("/tmp/main.pile")));
+        assert_eq!(config.signing_key, Some(PathBuf::from("/tmp/key")));
+        assert_eq!(config.output, Some(OutputFormat::Json));
+        assert_eq!(config.concurrency, Some(4));
+        assert_eq!(config.remotes["origin"], "file:///tmp/remote");
+        assert_eq!(
+            config.attributes["title"],
+            "A6CFAE6EE9AA0582B3212172B6C4801A"
+        );
+    }
+
+    #[test]
+    fn errors_name_the_offending_key() {
+        let err = Config::parse("pilee = \"x\"").unwrap_err().to_string();
+        assert!(err.contains("pilee"), "{err}");
+        let err = Config::parse("output = \"yaml\"").unwrap_err().to_string();
+        assert!(err.contains("output") || err.contains("yaml"), "{err}");
+        let err = Config::parse("concurrency = 0").unwrap_err().to_string();
+        assert!(err.contains("concurrency"), "{err}");
+        let err = Config::parse("[remotes]\norigin = \"not a url\"")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("remotes.origin"), "{err}");
+        let err = Config::parse("[attributes]\ntitle = \"xyz\"")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("attributes.title"), "{err}");
+    }
+
+    #[test]
+    fn flags_beat_env_beat_config() {
+        assert_eq!(layered(Some(1), Some(2), Some(3)), Some(1));
+        assert_eq!(layered(None, Some(2), Some(3)), Some(2));
+        assert_eq!(layered(None, None, Some(3)), Some(3));
+        assert_eq!(layered::<u8>(None, None, None), None);
+    }
+}
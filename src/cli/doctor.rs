@@ -0,0 +1,285 @@
+//! `trible doctor`: one-stop checks for common setup problems.
+//!
+//! Every check reports `pass`, `warn` or `fail` together with a one-line
+//! remedy, and the command's exit status reflects the worst finding. Checks
+//! only read state; in particular a missing signing key is reported instead of
+//! silently replaced by an ephemeral one.
+
+use anyhow::Result;
+use fs2::FileExt;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use triblespace::prelude::BlobStore;
+
+use crate::cli::config;
+use crate::cli::output::{Field, Output, OutputFormat};
+use crate::cli::pile::signing::{self, SigningArgs};
+use crate::cli::pile::ClosingPile;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Severity {
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Pass => "pass",
+            Severity::Warn => "warn",
+            Severity::Fail => "fail",
+        }
+    }
+}
+
+struct Finding {
+    check: &'static str,
+    severity: Severity,
+    message: String,
+    remedy: Option<String>,
+}
+
+impl Finding {
+    fn pass(check: &'static str, message: impl Into<String>) -> Finding {
+        Finding {
+            check,
+            severity: Severity::Pass,
+            message: message.into(),
+            remedy: None,
+        }
+    }
+
+    fn warn(check: &'static str, message: impl Into<String>, remedy: impl Into<String>) -> Finding {
+        Finding {
+            check,
+            severity: Severity::Warn,
+            message: message.into(),
+            remedy: Some(remedy.into()),
+        }
+    }
+
+    fn fail(check: &'static str, message: impl Into<String>, remedy: impl Into<String>) -> Finding {
+        Finding {
+            check,
+            severity: Severity::Fail,
+            message: message.into(),
+            remedy: Some(remedy.into()),
+        }
+    }
+}
+
+/// Run all checks, print the findings and return the worst severity.
+pub fn run(pile: Option<PathBuf>, signing: SigningArgs) -> Result<Severity> {
+    let mut findings = vec![check_signing_key(&signing)];
+    match crate::cli::pile::resolve_pile(pile) {
+        Ok(path) => findings.extend(check_pile(&path)),
+        Err(_) => findings.push(Finding::warn(
+            "pile",
+            "no pile specified",
+            "pass a pile path, set TRIBLES_PILE or add `pile` to the config",
+        )),
+    }
+    findings.extend(check_remotes());
+
+    let mut out = Output::stdout();
+    for finding in &findings {
+        if out.format() == OutputFormat::Plain {
+            println!(
+                "{:<4}  {}: {}",
+                finding.severity.label(),
+                finding.check,
+                finding.message
+            );
+            if let Some(remedy) = &finding.remedy {
+                println!("      fix: {remedy}");
+            }
+        } else {
+            out.record(&[
+                ("check", Field::from(finding.check)),
+                ("status", Field::from(finding.severity.label())),
+                ("message", Field::from(finding.message.as_str())),
+                (
+                    "remedy",
+                    finding
+                        .remedy
+                        .as_deref()
+                        .map_or(Field::missing("-"), Field::from),
+                ),
+            ])?;
+        }
+    }
+    Ok(findings
+        .iter()
+        .map(|f| f.severity)
+        .max()
+        .unwrap_or(Severity::Pass))
+}
+
+fn check_signing_key(args: &SigningArgs) -> Finding {
+    const CHECK: &str = "signing key";
+    if args.ephemeral {
+        return Finding::pass(CHECK, "--ephemeral signs with a throwaway key");
+    }
+    let Some(source) = signing::signing_key_source(&args.signing_key) else {
+        return Finding::warn(
+            CHECK,
+            "not configured; signing commands will fail unless --ephemeral is passed",
+            "set TRIBLES_SIGNING_KEY or `signing_key` in the config to a file holding a 64-char hex seed",
+        );
+    };
+    match source.load() {
+        Ok(_) => Finding::pass(CHECK, format!("{source} is valid")),
+        Err(e) => Finding::fail(
+            CHECK,
+            format!("{e:#}"),
+            "point TRIBLES_SIGNING_KEY at a readable file containing a 64-char hex seed",
+        ),
+    }
+}
+
+fn check_pile(path: &Path) -> Vec<Finding> {
+    if !path.is_file() {
+        return vec![Finding::fail(
+            "pile",
+            format!("{} does not exist", path.display()),
+            "create it with `trible pile create` or fix the path",
+        )];
+    }
+    // Probe the lock before opening so the check does not contend with itself.
+    let lock = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => match file.try_lock_exclusive() {
+            Ok(()) => {
+                let _ = file.unlock();
+                Finding::pass("lock", "pile is not locked by another process")
+            }
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => Finding::warn(
+                "lock",
+                "another process holds a lock on the pile",
+                "wait for the other trible process to finish",
+            ),
+            Err(e) => Finding::warn(
+                "lock",
+                format!("file locking unavailable: {e}"),
+                "keep the pile on a local filesystem that supports locks",
+            ),
+        },
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => Finding::fail(
+            "lock",
+            format!("{} is not writable", path.display()),
+            "fix the file permissions or run as the pile's owner",
+        ),
+        Err(e) => Finding::fail(
+            "lock",
+            format!("cannot open {}: {e}", path.display()),
+            "check the path and file permissions",
+        ),
+    };
+
+    let open = match open_pile(path) {
+        Ok(branches) => Finding::pass(
+            "pile",
+            format!("{} opened ({branches} branch(es))", path.display()),
+        ),
+        Err(e) => Finding::fail(
+            "pile",
+            format!("{} cannot be read: {e:#}", path.display()),
+            format!("run `trible pile diagnose check {}`", path.display()),
+        ),
+    };
+
+    vec![open, lock, check_disk_space(path)]
+}
+
+fn open_pile(path: &Path) -> Result<usize> {
+    ClosingPile::open(path)?.run(|pile| {
+        pile.refresh()?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        Ok(crate::cli::remote_state::branch_ids(pile, &reader)?.len())
+    })
+}
+
+fn check_disk_space(path: &Path) -> Finding {
+    const CHECK: &str = "disk space";
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+    match fs2::available_space(dir) {
+        // Merges and consolidation append to the pile; being unable to grow
+        // it by its own size is worth a warning.
+        Ok(free) if free < size => Finding::warn(
+            CHECK,
+            format!("{free} bytes free, pile is {size} bytes"),
+            "free up space on the pile's filesystem before large merges or imports",
+        ),
+        Ok(free) => Finding::pass(CHECK, format!("{free} bytes free, pile is {size} bytes")),
+        Err(e) => Finding::warn(
+            CHECK,
+            format!("cannot determine free space: {e}"),
+            "check the filesystem holding the pile",
+        ),
+    }
+}
+
+fn check_remotes() -> Vec<Finding> {
+    config::get()
+        .remotes
+        .iter()
+        .map(|(name, url)| check_remote(name, url))
+        .collect()
+}
+
+fn check_remote(name: &str, url: &str) -> Finding {
+    const CHECK: &str = "remote";
+    // The config loader already validated the URL.
+    let Ok(url) = Url::parse(url) else {
+        return Finding::fail(
+            CHECK,
+            format!("{name}: invalid URL"),
+            "fix `remotes` in the config",
+        );
+    };
+    match url.scheme() {
+        "s3" => {
+            let env_keys = std::env::var_os("AWS_ACCESS_KEY_ID").is_some()
+                && std::env::var_os("AWS_SECRET_ACCESS_KEY").is_some();
+            let other = ["AWS_PROFILE", "AWS_WEB_IDENTITY_TOKEN_FILE"]
+                .iter()
+                .any(|var| std::env::var_os(var).is_some());
+            let file = std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".aws").join("credentials"))
+                .is_some_and(|p| p.is_file());
+            if env_keys || other || file {
+                Finding::pass(CHECK, format!("{name}: AWS credentials found"))
+            } else {
+                Finding::warn(
+                    CHECK,
+                    format!("{name}: no AWS credentials found"),
+                    "set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY or AWS_PROFILE",
+                )
+            }
+        }
+        "file" => match url.to_file_path() {
+            Ok(dir) if dir.is_dir() => {
+                Finding::pass(CHECK, format!("{name}: {} exists", dir.display()))
+            }
+            _ => Finding::warn(
+                CHECK,
+                format!("{name}: directory {} does not exist", url.path()),
+                "create the directory or fix `remotes` in the config",
+            ),
+        },
+        scheme => Finding::warn(
+            CHECK,
+            format!("{name}: unsupported scheme `{scheme}`"),
+            "use an s3:// or file:// URL",
+        ),
+    }
+}
@@ -0,0 +1,85 @@
+//! Fetching HTTP(S) resources for `pile blob put --url` and
+//! `store blob put --url`.
+//!
+//! The body is hashed as it arrives but kept in memory until it is complete:
+//! piles and stores only take whole blobs, so a download needs as much
+//! memory as the resource is large. Nothing is written until the caller
+//! stores the returned bytes, so a failed or mismatched download leaves no
+//! trace in the pile or store.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+
+use triblespace_core::blob::Bytes;
+
+use crate::cli::stats;
+use crate::cli::util::parse_blob_handle;
+
+/// Redirects followed before a download is given up.
+pub const MAX_REDIRECTS: u32 = 10;
+
+const CHUNK: usize = 64 * 1024;
+
+/// Most bytes reserved up front from a Content-Length header.
+const MAX_PREALLOCATE: u64 = 64 * 1024 * 1024;
+
+/// Download `url`, failing on a non-2xx response and, when `expected` (a
+/// `blake3:` handle) is given, on content that does not hash to it.
+pub fn fetch(url: &str, expected: Option<&str>) -> Result<Bytes> {
+    let expected = expected.map(parse_blob_handle).transpose()?;
+    let agent = ureq::AgentBuilder::new().redirects(MAX_REDIRECTS).build();
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, _)) => return Err(status_error(url, code)),
+        Err(e) => return Err(anyhow::Error::new(e).context(format!("GET {url}"))),
+    };
+    // A redirect past the limit comes back as the last 3xx response.
+    if !(200..300).contains(&response.status()) {
+        return Err(status_error(url, response.status()));
+    }
+
+    // Receiving the body is the transfer of a `--url` put.
+    let _phase = stats::phase!("transfer");
+    // The server controls Content-Length, so it is only a hint.
+    let mut data = Vec::with_capacity(
+        response
+            .header("Content-Length")
+            .and_then(|len| len.parse::<u64>().ok())
+            .map_or(0, |len| len.min(MAX_PREALLOCATE) as usize),
+    );
+    let mut hasher = blake3::Hasher::new();
+    let mut body = response.into_reader();
+    let mut chunk = vec![0u8; CHUNK];
+    loop {
+        let n = body
+            .read(&mut chunk)
+            .with_context(|| format!("read response body of {url}"))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&chunk[..n]);
+        data.extend_from_slice(&chunk[..n]);
+    }
+    stats::read(data.len() as u64);
+
+    let actual = hasher.finalize();
+    if let Some(expected) = expected {
+        if actual.as_bytes() != &expected.raw {
+            return Err(crate::cli::error::corrupt(format!(
+                "{url} hashed to blake3:{}, expected blake3:{}",
+                actual.to_hex(),
+                hex::encode(expected.raw)
+            )));
+        }
+    }
+    Ok(Bytes::from_source(data))
+}
+
+fn status_error(url: &str, code: u16) -> anyhow::Error {
+    let msg = format!("GET {url} failed with HTTP status {code}");
+    if code == 404 || code == 410 {
+        crate::cli::error::not_found(msg)
+    } else {
+        anyhow::anyhow!(msg)
+    }
+}
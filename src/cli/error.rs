@@ -0,0 +1,63 @@
+//! Typed failure classes surfaced by command handlers.
+//!
+//! Handlers return `anyhow::Error` as usual; wrapping a failure in a
+//! [`CliError`] tags it with an [`ErrorKind`] that `main` maps onto a distinct
+//! process exit code. Untagged errors exit with the generic failure code.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A pile, branch, blob or remote object does not exist.
+    NotFound,
+    /// A compare-and-swap update lost a race or was not a fast-forward.
+    Conflict,
+    /// Stored data failed validation.
+    Corrupt,
+    /// Arguments parsed but do not make sense together or are ambiguous.
+    InvalidArgument,
+}
+
+#[derive(Debug)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    message: String,
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+fn tagged(kind: ErrorKind, message: impl Into<String>) -> anyhow::Error {
+    anyhow::Error::new(CliError {
+        kind,
+        message: message.into(),
+    })
+}
+
+pub fn not_found(message: impl Into<String>) -> anyhow::Error {
+    tagged(ErrorKind::NotFound, message)
+}
+
+pub fn conflict(message: impl Into<String>) -> anyhow::Error {
+    tagged(ErrorKind::Conflict, message)
+}
+
+pub fn corrupt(message: impl Into<String>) -> anyhow::Error {
+    tagged(ErrorKind::Corrupt, message)
+}
+
+pub fn invalid_argument(message: impl Into<String>) -> anyhow::Error {
+    tagged(ErrorKind::InvalidArgument, message)
+}
+
+/// The kind of the first tagged error in `err`'s cause chain, if any.
+pub fn kind_of(err: &anyhow::Error) -> Option<ErrorKind> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<CliError>())
+        .map(|e| e.kind)
+}
@@ -0,0 +1,299 @@
+use anyhow::{Context, Result};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use ed25519_dalek::SigningKey;
+use triblespace::prelude::blobschemas::LongString;
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::BranchStore;
+use triblespace_core::blob::ToBlob;
+use triblespace_core::id::Id;
+use triblespace_core::repo;
+use triblespace_core::repo::objectstore::ObjectStoreRemote;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::PushResult;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::error;
+use crate::cli::pile::branch::{extract_repo_head, load_branch_name};
+use crate::cli::pile::signing::SigningArgs;
+use crate::cli::pile::ClosingRepo;
+use crate::cli::remote_state;
+use crate::cli::stats;
+use crate::cli::util::skip_present;
+use crate::cli::verify;
+use tracing::{debug, trace};
+
+type CommitHandle = Value<Handle<Blake3, SimpleArchive>>;
+type BranchMetaHandle = Value<Handle<Blake3, SimpleArchive>>;
+
+/// A local branch as seen by fetch: id, current metadata handle and commit head.
+#[derive(Clone, Debug)]
+struct LocalBranch {
+    id: Id,
+    meta: BranchMetaHandle,
+    head: Option<CommitHandle>,
+}
+
+/// Fetch remote branches into `<remote>/<name>` tracking branches.
+///
+/// Blobs reachable from each remote head are copied into the pile, but local
+/// branches are never moved; only the tracking branch for the remote is
+/// created or advanced (CAS on its metadata handle). With `prune`, tracking
+/// branches of this remote whose counterpart no longer exists are tombstoned.
+/// `dry_run` reports what would happen without writing to the pile.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    url: String,
+    pile_path: PathBuf,
+    branch: Option<String>,
+    remote_name: String,
+    prune: bool,
+    dry_run: bool,
+    signing: SigningArgs,
+) -> Result<()> {
+    // A dry run never signs, so it does not need a configured key.
+    let key = if dry_run {
+        crate::cli::pile::signing::generate_key()?
+    } else {
+        signing.load()?
+    };
+    let url = crate::cli::config::remote_url(&url)?;
+    let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
+    debug!(path = %pile_path.display(), "opening pile");
+    let pile: Pile<Blake3> = {
+        let _phase = stats::phase!("pile open");
+        Pile::open(&pile_path)?
+    };
+    let repo = Repository::new(pile, key.clone(), TribleSet::new())?;
+    ClosingRepo::new(repo).run(|repo| {
+        repo.storage_mut().refresh()?;
+        debug!("refreshed pile");
+        let remote_reader = remote
+            .reader()
+            .map_err(|e| anyhow::anyhow!("remote reader error: {e:?}"))?;
+
+        let mut remote_ids: Vec<Id> = Vec::new();
+        for branch_res in remote.branches()? {
+            remote_ids.push(branch_res?);
+        }
+
+        let mut locals = local_branches_by_name(repo.storage_mut())?;
+        let mut remote_names: HashSet<String> = HashSet::new();
+        let mut matched = 0usize;
+
+        for remote_id in remote_ids {
+            let Some(remote_meta) = remote.head(remote_id)? else {
+                continue;
+            };
+            let meta: TribleSet = remote_reader
+                .get::<TribleSet, SimpleArchive>(remote_meta)
+                .map_err(|e| anyhow::anyhow!("remote branch metadata {remote_id:X}: {e:?}"))?;
+            let name = load_branch_name(&remote_reader, &meta)?
+                .unwrap_or_else(|| format!("{remote_id:X}"));
+            debug!(branch = %name, id = %format!("{remote_id:X}"), "resolved remote branch");
+            remote_names.insert(name.clone());
+
+            if let Some(filter) = branch.as_deref() {
+                if filter != name && !filter.eq_ignore_ascii_case(&format!("{remote_id:X}")) {
+                    continue;
+                }
+            }
+            matched += 1;
+
+            let tracking_name = format!("{remote_name}/{name}");
+            let Some(head) = extract_repo_head(&meta) else {
+                println!("{tracking_name}: remote branch has no head, skipping");
+                continue;
+            };
+
+            if dry_run {
+                match locals.get(&tracking_name) {
+                    Some(local) if local.head == Some(head) => {
+                        println!("{tracking_name}: up to date")
+                    }
+                    Some(_) => println!("{tracking_name}: would update"),
+                    None => println!("{tracking_name}: would create"),
+                }
+                continue;
+            }
+
+            // The walk is lazy, so reachability time is part of the transfer.
+            let local_reader = repo
+                .storage_mut()
+                .reader()
+                .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+            let skipped = Cell::new(0usize);
+            let handles = skip_present(
+                repo::reachable(&remote_reader, std::iter::once(head.transmute())),
+                &local_reader,
+                &skipped,
+            );
+            let mut copied = 0usize;
+            let transfer = stats::phase!("transfer");
+            for r in repo::transfer(&remote_reader, repo.storage_mut(), handles) {
+                let (src, _) = r.map_err(|e| anyhow::anyhow!("transfer failed: {e}"))?;
+                trace!(blob = %hex::encode(src.raw), "transferred blob");
+                copied += 1;
+            }
+            drop(transfer);
+
+            let head_hex = hex::encode(head.raw);
+            match locals.get(&tracking_name).cloned() {
+                Some(local) if local.head == Some(head) => {
+                    println!("{tracking_name}: up to date (blake3:{head_hex})");
+                }
+                Some(local) => {
+                    set_tracking_head(repo.storage_mut(), &key, &local, &tracking_name, head)?;
+                    println!(
+                        "{tracking_name}: updated to blake3:{head_hex} ({copied} blobs, {} already present)",
+                        skipped.get()
+                    );
+                }
+                None => {
+                    let id = *repo
+                        .create_branch_with_key(&tracking_name, Some(head), key.clone())
+                        .map_err(|e| {
+                            anyhow::anyhow!(
+                                "failed to create tracking branch '{tracking_name}': {e:?}"
+                            )
+                        })?;
+                    println!(
+                        "{tracking_name}: created {id:X} at blake3:{head_hex} ({copied} blobs, {} already present)",
+                        skipped.get()
+                    );
+                    if let Some(meta) = repo.storage_mut().head(id)? {
+                        locals.insert(
+                            tracking_name,
+                            LocalBranch {
+                                id,
+                                meta,
+                                head: Some(head),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(filter) = branch.as_deref() {
+            if matched == 0 {
+                return Err(error::not_found(format!(
+                    "remote branch not found: {filter}"
+                )));
+            }
+        }
+
+        if prune {
+            prune_tracking(
+                repo.storage_mut(),
+                &locals,
+                &remote_name,
+                &remote_names,
+                dry_run,
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Tombstone `<remote>/<name>` branches whose remote counterpart is gone.
+/// Branches without the remote prefix are never considered.
+fn prune_tracking(
+    pile: &mut Pile<Blake3>,
+    locals: &HashMap<String, LocalBranch>,
+    remote_name: &str,
+    remote_names: &HashSet<String>,
+    dry_run: bool,
+) -> Result<()> {
+    let prefix = format!("{remote_name}/");
+    let mut stale: Vec<(&String, &LocalBranch)> = locals
+        .iter()
+        .filter(|(name, _)| {
+            name.strip_prefix(&prefix)
+                .is_some_and(|rest| !remote_names.contains(rest))
+        })
+        .collect();
+    stale.sort_by(|a, b| a.0.cmp(b.0));
+
+    for (name, local) in stale {
+        if dry_run {
+            println!("{name}: would prune {:X}", local.id);
+            continue;
+        }
+        match pile.update(local.id, Some(local.meta), None)? {
+            PushResult::Success() => println!("{name}: pruned {:X}", local.id),
+            PushResult::Conflict(_) => {
+                eprintln!("warning: {name} changed concurrently; not pruned");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Index the active branches of a pile by name. Unnamed branches are skipped;
+/// on duplicate names the first branch encountered wins.
+fn local_branches_by_name(pile: &mut Pile<Blake3>) -> Result<HashMap<String, LocalBranch>> {
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let ids = remote_state::branch_ids(pile, &reader)?;
+
+    let mut out: HashMap<String, LocalBranch> = HashMap::new();
+    for id in ids {
+        let Some(meta) = pile.head(id)? else {
+            continue;
+        };
+        let Ok(meta_set) = reader.get::<TribleSet, SimpleArchive>(meta) else {
+            continue;
+        };
+        let Ok(Some(name)) = load_branch_name(&reader, &meta_set) else {
+            continue;
+        };
+        let head = extract_repo_head(&meta_set);
+        out.entry(name).or_insert(LocalBranch { id, meta, head });
+    }
+    Ok(out)
+}
+
+/// Point an existing tracking branch at `head` by writing fresh signed branch
+/// metadata and swapping it in with CAS against the metadata we last saw.
+fn set_tracking_head(
+    pile: &mut Pile<Blake3>,
+    key: &SigningKey,
+    local: &LocalBranch,
+    name: &str,
+    head: CommitHandle,
+) -> Result<()> {
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let commit: TribleSet = reader
+        .get(head)
+        .map_err(|e| anyhow::anyhow!("read commit: {e:?}"))?;
+
+    let name_handle =
+        verify::put::<LongString, _>(pile, name.to_string()).context("put name blob")?;
+    let new_meta =
+        repo::branch::branch_metadata(key, local.id, name_handle, Some(commit.to_blob()));
+    let new_meta_handle = verify::put(pile, new_meta).context("put branch meta")?;
+
+    debug!(
+        branch = %name,
+        old = %hex::encode(local.meta.raw),
+        new = %hex::encode(new_meta_handle.raw),
+        "updating tracking head"
+    );
+    match pile.update(local.id, Some(local.meta), Some(new_meta_handle))? {
+        PushResult::Success() => Ok(()),
+        PushResult::Conflict(_) => Err(error::conflict(format!(
+            "tracking branch {name} advanced concurrently; rerun fetch"
+        ))),
+    }
+}
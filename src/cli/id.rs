@@ -0,0 +1,228 @@
+//! Identifier generation and conversion between textual encodings.
+
+use anyhow::Result;
+use clap::Parser;
+use clap::ValueEnum;
+
+use crate::cli::output::{Field, Output};
+
+/// Crockford base32 alphabet (no I, L, O or U).
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IdFormat {
+    /// 32 uppercase hex characters
+    Hex,
+    /// Hyphenated 8-4-4-4-12 UUID form
+    Uuid,
+    /// 26 characters of unpadded Crockford base32
+    Base32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IdCase {
+    Upper,
+    Lower,
+}
+
+#[derive(Parser)]
+pub enum IdCommand {
+    /// Parse an id in any supported encoding and print it as uppercase hex.
+    Parse {
+        /// Id as hex, hyphenated UUID or Crockford base32
+        value: String,
+    },
+    /// Check that a value is a valid 32-character hex id; exits 0 or 1.
+    Check {
+        /// Candidate id
+        value: String,
+    },
+    /// Normalize an id in any supported encoding to 32 hex characters.
+    Fmt {
+        /// Id as hex, hyphenated UUID or Crockford base32
+        value: String,
+        /// Letter case of the hex digits
+        #[arg(long, value_enum, default_value_t = IdCase::Upper)]
+        case: IdCase,
+    },
+}
+
+pub fn run(cmd: IdCommand) -> Result<()> {
+    match cmd {
+        IdCommand::Parse { value } => {
+            let raw = parse(&value)?;
+            println!("{}", encode(raw, IdFormat::Hex));
+        }
+        IdCommand::Check { value } => {
+            check_hex(&value)?;
+        }
+        IdCommand::Fmt { value, case } => {
+            let raw = parse(&value)?;
+            match case {
+                IdCase::Upper => println!("{}", hex::encode_upper(raw)),
+                IdCase::Lower => println!("{}", hex::encode(raw)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print `count` fresh random ids in the requested encoding.
+pub fn genid(count: u64, format: IdFormat) -> Result<()> {
+    let mut out = Output::stdout();
+    for _ in 0..count {
+        let mut id = [0u8; 16];
+        getrandom::fill(&mut id)?;
+        out.record(&[("id", Field::from(encode(id, format)))])?;
+    }
+    Ok(())
+}
+
+pub fn encode(raw: [u8; 16], format: IdFormat) -> String {
+    match format {
+        IdFormat::Hex => hex::encode_upper(raw),
+        IdFormat::Uuid => {
+            let h = hex::encode(raw);
+            format!(
+                "{}-{}-{}-{}-{}",
+                &h[0..8],
+                &h[8..12],
+                &h[12..16],
+                &h[16..20],
+                &h[20..32]
+            )
+        }
+        IdFormat::Base32 => {
+            let value = u128::from_be_bytes(raw);
+            // 26 digits cover 130 bits; the two lowest bits are zero padding.
+            let mut out = String::with_capacity(26);
+            for i in (0..26u32).rev() {
+                let shift = i * 5;
+                let digit = if shift >= 2 {
+                    (value >> (shift - 2)) & 0x1F
+                } else {
+                    (value << 2) & 0x1F
+                };
+                out.push(CROCKFORD[digit as usize] as char);
+            }
+            out
+        }
+    }
+}
+
+/// Validate an id in the canonical 32-character hex form, in either case.
+pub fn check_hex(value: &str) -> Result<[u8; 16]> {
+    let len = value.chars().count();
+    if len != 32 {
+        anyhow::bail!("expected 32 hex chars, got {len}");
+    }
+    non_nil(decode_hex(value)?)
+}
+
+fn non_nil(raw: [u8; 16]) -> Result<[u8; 16]> {
+    if raw == [0u8; 16] {
+        anyhow::bail!("the nil id is not a valid identifier");
+    }
+    Ok(raw)
+}
+
+/// Decode an id from hex, hyphenated UUID or Crockford base32.
+pub fn parse(value: &str) -> Result<[u8; 16]> {
+    let value = value.trim();
+    let raw = match value.len() {
+        32 => decode_hex(value)?,
+        36 => {
+            let groups: Vec<&str> = value.split('-').collect();
+            let lens: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+            if lens != [8, 4, 4, 4, 12] {
+                anyhow::bail!("invalid uuid {value:?}: expected 8-4-4-4-12 hex groups");
+            }
+            decode_hex(&groups.concat())?
+        }
+        26 => decode_base32(value)?,
+        len => anyhow::bail!(
+            "invalid id {value:?}: expected 32 hex chars, a hyphenated uuid or 26 base32 chars, got {len}"
+        ),
+    };
+    non_nil(raw)
+}
+
+fn decode_hex(value: &str) -> Result<[u8; 16]> {
+    if let Some((i, c)) = value
+        .chars()
+        .enumerate()
+        .find(|(_, c)| !c.is_ascii_hexdigit())
+    {
+        anyhow::bail!("invalid hex character {c:?} at position {i}");
+    }
+    let mut raw = [0u8; 16];
+    hex::decode_to_slice(value, &mut raw)
+        .map_err(|e| anyhow::anyhow!("invalid hex id {value:?}: {e}"))?;
+    Ok(raw)
+}
+
+fn decode_base32(value: &str) -> Result<[u8; 16]> {
+    let mut acc: u128 = 0;
+    for (i, c) in value.chars().enumerate() {
+        let digit = match c.to_ascii_uppercase() {
+            'O' => 0,
+            'I' | 'L' => 1,
+            c => CROCKFORD
+                .iter()
+                .position(|&d| d as char == c)
+                .ok_or_else(|| anyhow::anyhow!("invalid base32 character {c:?} in {value:?}"))?
+                as u128,
+        };
+        // The final digit carries three id bits followed by two padding bits.
+        if i == 25 {
+            if digit & 0b11 != 0 {
+                anyhow::bail!("invalid base32 id {value:?}: non-zero padding bits");
+            }
+            acc = (acc << 3) | (digit >> 2);
+        } else {
+            acc = (acc << 5) | digit;
+        }
+    }
+    Ok(acc.to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_round_trip() {
+        let raw: [u8; 16] = *b"\x01\x23\x45\x67\x89\xAB\xCD\xEF\xFE\xDC\xBA\x98\x76\x54\x32\x10";
+        for format in [IdFormat::Hex, IdFormat::Uuid, IdFormat::Base32] {
+            assert_eq!(parse(&encode(raw, format)).unwrap(), raw, "{format:?}");
+        }
+        assert_eq!(
+            encode(raw, IdFormat::Uuid),
+            "01234567-89ab-cdef-fedc-ba9876543210"
+        );
+        assert_eq!(
+            encode([0xFF; 16], IdFormat::Base32),
+            "ZZZZZZZZZZZZZZZZZZZZZZZZZW"
+        );
+        assert!(parse("ZZZZZZZZZZZZZZZZZZZZZZZZZZ").is_err());
+        assert!(parse("not an id").is_err());
+    }
+
+    #[test]
+    fn check_hex_explains_rejections() {
+        let err = |v: &str| check_hex(v).unwrap_err().to_string();
+        assert_eq!(err(&"A".repeat(31)), "expected 32 hex chars, got 31");
+        assert_eq!(err(&"A".repeat(33)), "expected 32 hex chars, got 33");
+        assert_eq!(err(""), "expected 32 hex chars, got 0");
+        assert_eq!(
+            err(&format!("{}G", "A".repeat(31))),
+            "invalid hex character 'G' at position 31"
+        );
+        assert_eq!(err(&"0".repeat(32)), "the nil id is not a valid identifier");
+        let mixed = "0123456789abcdefABCDEF0123456789";
+        assert_eq!(
+            hex::encode_upper(check_hex(mixed).unwrap()),
+            mixed.to_ascii_uppercase()
+        );
+    }
+}
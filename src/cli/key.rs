@@ -0,0 +1,223 @@
+//! Signing key management.
+
+use anyhow::Result;
+use clap::{ArgGroup, Parser, ValueEnum};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cli::keyring;
+use crate::cli::output::{Field, Output};
+use crate::cli::pile::signing::{
+    encrypt_key, generate_key, key_passphrase, load_key_from_file, signing_key_source,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PubkeyFormat {
+    /// 64 lowercase hex characters
+    Hex,
+    /// `ssh-ed25519 AAAA...`, as used in authorized-signers files
+    Openssh,
+}
+
+#[derive(Parser)]
+pub enum KeyCommand {
+    /// Generate a new ed25519 signing key.
+    ///
+    /// With --out the 64-char hex seed is written to PATH (mode 0600) and the
+    /// public key is printed; otherwise the seed itself is printed.
+    Generate {
+        /// File to write the seed to
+        #[arg(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+        /// Overwrite an existing file at PATH
+        #[arg(long, requires = "out")]
+        force: bool,
+        /// Encrypt the seed with a passphrase (read from TRIBLES_KEY_PASSPHRASE
+        /// or asked for on the terminal)
+        #[arg(long, requires = "out")]
+        encrypt: bool,
+    },
+    /// Print the public key of the configured signing key.
+    ///
+    /// Unlike signing commands this never falls back to an ephemeral key.
+    Pubkey {
+        /// Signing key file or keyring name (defaults to TRIBLES_SIGNING_KEY,
+        /// TRIBLES_SIGNING_KEY_HEX, then the config)
+        #[arg(long)]
+        signing_key: Option<PathBuf>,
+        /// Output encoding
+        #[arg(long, value_enum, default_value_t = PubkeyFormat::Hex)]
+        format: PubkeyFormat,
+    },
+    /// Store a key in the keyring under NAME and print its public key.
+    ///
+    /// Stored keys can be passed to --signing-key by name.
+    #[command(group(ArgGroup::new("source").required(true).args(["file", "generate"])))]
+    Add {
+        /// Keyring name for the key
+        name: String,
+        /// Import an existing key file (hex seed or OpenSSH ed25519)
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+        /// Generate a new key
+        #[arg(long)]
+        generate: bool,
+    },
+    /// List the keys in the keyring with their public keys.
+    #[command(visible_alias = "ls")]
+    List,
+    /// Delete a key from the keyring.
+    #[command(visible_alias = "rm")]
+    Remove {
+        /// Keyring name of the key
+        name: String,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+pub fn run(cmd: KeyCommand) -> Result<()> {
+    match cmd {
+        KeyCommand::Generate {
+            out,
+            force,
+            encrypt,
+        } => {
+            let key = generate_key()?;
+            let seed = hex::encode(key.to_bytes());
+            match out {
+                Some(path) => {
+                    let contents = if encrypt {
+                        encrypt_key(&key, &new_passphrase()?)?
+                    } else {
+                        format!("{seed}\n")
+                    };
+                    write_secret(&path, &contents, force)?;
+                    println!("{}", hex::encode(key.verifying_key().to_bytes()));
+                }
+                None => {
+                    eprintln!("warning: printing the secret seed; pass --out PATH to write it to a file instead");
+                    println!("{seed}");
+                }
+            }
+        }
+        KeyCommand::Pubkey {
+            signing_key,
+            format,
+        } => {
+            let source = signing_key_source(&signing_key).ok_or_else(|| {
+                crate::cli::error::not_found(
+                    "no signing key configured; pass --signing-key or set TRIBLES_SIGNING_KEY",
+                )
+            })?;
+            let public = source.load()?.verifying_key().to_bytes();
+            match format {
+                PubkeyFormat::Hex => println!("{}", hex::encode(public)),
+                PubkeyFormat::Openssh => println!("{}", openssh_public_key(&public)),
+            }
+        }
+        KeyCommand::Add { name, file, .. } => {
+            let path = keyring::path(&name)?;
+            if path.exists() {
+                return Err(crate::cli::error::conflict(format!(
+                    "key {name:?} already exists; remove it first with `trible key remove`"
+                )));
+            }
+            let key = match file {
+                Some(file) => load_key_from_file(&file)?,
+                // clap requires either --file or --generate.
+                None => generate_key()?,
+            };
+            keyring::create_dir()?;
+            write_secret(&path, &format!("{}\n", hex::encode(key.to_bytes())), false)?;
+            println!("{}", hex::encode(key.verifying_key().to_bytes()));
+        }
+        KeyCommand::List => {
+            let mut out = Output::stdout();
+            for name in keyring::names()? {
+                let public = load_key_from_file(&keyring::path(&name)?)
+                    .map(|key| Field::from(hex::encode(key.verifying_key().to_bytes())))
+                    .unwrap_or_else(|_| Field::missing("<unreadable>"));
+                out.record(&[("name", Field::from(name)), ("public_key", public)])?;
+            }
+        }
+        KeyCommand::Remove { name, yes } => {
+            let path = keyring::path(&name)?;
+            if !path.is_file() {
+                return Err(crate::cli::error::not_found(format!(
+                    "no key named {name:?} in the keyring"
+                )));
+            }
+            crate::cli::util::confirm(&format!("This will delete signing key {name:?}"), yes)?;
+            std::fs::remove_file(&path)
+                .map_err(|e| anyhow::anyhow!("failed to remove {}: {e}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// A passphrase for a newly encrypted key, asked for twice on a terminal.
+fn new_passphrase() -> Result<String> {
+    if std::env::var_os("TRIBLES_KEY_PASSPHRASE").is_some_and(|v| !v.is_empty()) {
+        return key_passphrase("");
+    }
+    let passphrase = key_passphrase("New passphrase: ")?;
+    if passphrase.is_empty() {
+        anyhow::bail!("empty passphrase");
+    }
+    if key_passphrase("Repeat passphrase: ")? != passphrase {
+        anyhow::bail!("passphrases do not match");
+    }
+    Ok(passphrase)
+}
+
+/// Encode an ed25519 public key as an OpenSSH `authorized_keys` line.
+fn openssh_public_key(public: &[u8; 32]) -> String {
+    use base64::Engine;
+
+    let mut blob = Vec::with_capacity(51);
+    for field in [&b"ssh-ed25519"[..], &public[..]] {
+        blob.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        blob.extend_from_slice(field);
+    }
+    format!(
+        "ssh-ed25519 {}",
+        base64::engine::general_purpose::STANDARD.encode(blob)
+    )
+}
+
+/// Write `contents` to a file only the current user can read.
+fn write_secret(path: &Path, contents: &str, force: bool) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| {
+        if e.kind() == ErrorKind::AlreadyExists {
+            crate::cli::error::conflict(format!(
+                "{} already exists; pass --force to overwrite it",
+                path.display()
+            ))
+        } else {
+            anyhow::anyhow!("failed to create {}: {e}", path.display())
+        }
+    })?;
+    // `mode` only applies to newly created files.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! Named signing keys stored under `~/.config/trible/keys/`.
+//!
+//! Each key is a `NAME.key` file holding a 64-char hex seed that only the
+//! current user can read. Wherever a signing key file is expected, a keyring
+//! name works too (see [`resolve`]).
+
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::cli::config;
+
+const EXTENSION: &str = "key";
+
+/// The keyring directory; it may not exist yet.
+pub fn dir() -> Result<PathBuf> {
+    config::dir().map(|dir| dir.join("keys")).ok_or_else(|| {
+        anyhow::anyhow!("cannot locate the keyring: neither XDG_CONFIG_HOME nor HOME is set")
+    })
+}
+
+/// Create the keyring directory, accessible only to the current user.
+pub fn create_dir() -> Result<()> {
+    let dir = dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", dir.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// Key names are limited to ASCII letters, digits, `.`, `_` and `-` and may
+/// not start with a dot, so they are always a single safe file name.
+pub fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        return Err(crate::cli::error::invalid_argument(format!(
+            "invalid key name {name:?}: use letters, digits, '.', '_' or '-'"
+        )));
+    }
+    Ok(())
+}
+
+/// File holding the key `name`, whether or not it exists.
+pub fn path(name: &str) -> Result<PathBuf> {
+    check_name(name)?;
+    Ok(dir()?.join(format!("{name}.{EXTENSION}")))
+}
+
+/// Names of all stored keys, sorted.
+pub fn names() -> Result<Vec<String>> {
+    let dir = dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => anyhow::bail!("failed to read {}: {e}", dir.display()),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == EXTENSION) {
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                if check_name(name).is_ok() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Interpret a `--signing-key` value: an existing file wins, then a key of
+/// that name in the keyring; anything else is returned unchanged so the
+/// caller reports the missing file.
+pub fn resolve(value: PathBuf) -> PathBuf {
+    if value.exists() {
+        return value;
+    }
+    let stored = value
+        .to_str()
+        .and_then(|name| path(name).ok())
+        .filter(|p| p.is_file());
+    stored.unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_are_single_file_names() {
+        for name in ["work", "ci-2024", "alice.laptop", "a_b"] {
+            assert!(check_name(name).is_ok(), "{name}");
+        }
+        for name in ["", ".hidden", "a/b", "../x", "has space", "ключ"] {
+            assert!(check_name(name).is_err(), "{name}");
+        }
+    }
+}
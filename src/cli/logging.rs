@@ -0,0 +1,48 @@
+//! Diagnostic logging to stderr, controlled by the global `-v`/`-q` flags.
+//!
+//! By default only warnings are shown, plus this crate's info events (the
+//! cycle outcomes of `sync-daemon`). `-v` enables this crate's debug events
+//! (pile access, branch resolution, CAS updates), `-vv` adds per-blob transfer
+//! traces and debug output from the underlying libraries, and `-q` limits
+//! output to errors. `RUST_LOG` overrides the flags entirely. With `--stats`
+//! the phase spans are additionally timed by [`stats::StatsLayer`].
+
+use anyhow::Result;
+use std::sync::OnceLock;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::cli::stats;
+
+static VERBOSE: OnceLock<u8> = OnceLock::new();
+
+pub fn init(verbose: u8, quiet: bool, stats: bool) -> Result<()> {
+    let _ = VERBOSE.set(verbose);
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::new(match (quiet, verbose) {
+            (true, _) => "error",
+            (false, 0) => "warn,trible=info",
+            (false, 1) => "warn,trible=debug",
+            (false, _) => "debug,trible=trace",
+        }),
+    };
+    let logs = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .with_filter(filter);
+    let timing =
+        stats.then(|| stats::layer().with_filter(filter_fn(|meta| meta.target() == stats::TARGET)));
+    tracing_subscriber::registry()
+        .with(logs)
+        .with(timing)
+        .try_init()
+        .map_err(|e| anyhow::anyhow!("failed to initialise logging: {e}"))
+}
+
+/// How often `-v` was given; some commands also print more detail.
+pub fn verbose() -> u8 {
+    *VERBOSE.get_or_init(|| 0)
+}
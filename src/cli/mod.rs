@@ -1,4 +1,29 @@
+pub mod attr;
+pub mod backup;
+pub mod batch;
 pub mod branch;
+pub mod complete;
+pub mod config;
+pub mod doctor;
+mod download;
+pub mod error;
+pub mod fetch;
+pub mod id;
+pub mod key;
+mod keyring;
+pub mod logging;
+pub mod mount;
+pub mod output;
 pub mod pile;
+mod remote_state;
+pub mod replica;
+pub mod report;
+pub mod serve;
+pub mod stats;
 pub mod store;
+pub mod style;
+pub mod sync;
+mod throttle;
 mod util;
+pub mod verify;
+pub mod version;
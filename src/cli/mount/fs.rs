@@ -0,0 +1,374 @@
+//! The FUSE side of `trible mount`, on top of [`super::tree::Tree`].
+
+use anyhow::Result;
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
+    ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow,
+};
+use libc::{EISDIR, ENOENT, ENOTDIR, EROFS};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::BlobStoreList;
+use triblespace::prelude::BranchStore;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::blob::Bytes;
+use triblespace_core::repo::pile::{Pile, PileReader};
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::output::note;
+use crate::cli::pile::branch::{extract_repo_head, load_branch_name, read_commit_fields};
+use crate::cli::pile::ClosingPile;
+
+use super::tree::{Branch, Node, Tree, ROOT};
+
+type BlobHandle = Value<Handle<Blake3, UnknownBlob>>;
+
+/// How long the kernel may cache attributes and lookups.
+const TTL: Duration = Duration::from_secs(1);
+
+/// Mount the pile at `mountpoint` until SIGINT, then unmount.
+pub fn run(pile: PathBuf, mountpoint: PathBuf, refresh_interval: u64) -> Result<()> {
+    // Dropped on a failed refresh, which closes it and logs a close error.
+    let mut pile = ClosingPile::open(&pile)?;
+    pile.refresh()?;
+    let mut fs = PileFs {
+        pile: Some(pile.into_inner()),
+        tree: Tree::new(),
+        reader: None,
+        interval: Duration::from_secs(refresh_interval),
+        refreshed: Instant::now(),
+        mounted: SystemTime::now(),
+    };
+    fs.load()?;
+
+    let (stop, stopped) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = stop.send(());
+    })
+    .map_err(|e| anyhow::anyhow!("cannot install the SIGINT handler: {e}"))?;
+    let options = [
+        MountOption::RO,
+        MountOption::FSName("trible".to_string()),
+        MountOption::Subtype("pile".to_string()),
+        MountOption::DefaultPermissions,
+    ];
+    let session = fuser::spawn_mount2(fs, &mountpoint, &options)
+        .map_err(|e| anyhow::anyhow!("cannot mount at {}: {e}", mountpoint.display()))?;
+    note!(
+        "mounted at {}; press Ctrl-C to unmount",
+        mountpoint.display()
+    );
+    let _ = stopped.recv();
+    // Dropping the session unmounts and closes the pile in `destroy`.
+    drop(session);
+    note!("unmounted {}", mountpoint.display());
+    Ok(())
+}
+
+struct PileFs {
+    /// Taken and closed when the filesystem is unmounted.
+    pile: Option<Pile<Blake3>>,
+    tree: Tree,
+    reader: Option<PileReader<Blake3>>,
+    interval: Duration,
+    refreshed: Instant,
+    mounted: SystemTime,
+}
+
+impl PileFs {
+    /// Rebuild the tree from the pile's current blobs and branch heads.
+    fn load(&mut self) -> Result<()> {
+        let pile = self.pile.as_mut().expect("pile is open while mounted");
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let mut blobs = Vec::new();
+        for handle in reader.blobs() {
+            let handle: BlobHandle = handle?;
+            if let Some(meta) = reader.metadata(handle)? {
+                blobs.push((handle.raw, meta.length));
+            }
+        }
+        let ids = crate::cli::remote_state::branch_ids(pile, &reader)?;
+        let mut branches = Vec::new();
+        for id in ids {
+            let Some(meta) = pile.head(id)? else {
+                continue;
+            };
+            let (name, content) = match reader.get::<TribleSet, SimpleArchive>(meta) {
+                Ok(meta) => (
+                    load_branch_name(&reader, &meta).ok().flatten(),
+                    head_content_blob(&reader, &meta),
+                ),
+                Err(_) => (None, None),
+            };
+            branches.push(Branch { id, name, content });
+        }
+        self.tree.update(blobs, branches);
+        self.reader = Some(reader);
+        Ok(())
+    }
+
+    /// Pick up new blobs and branch moves once the refresh interval passed.
+    fn maybe_refresh(&mut self) {
+        if self.refreshed.elapsed() < self.interval {
+            return;
+        }
+        self.refreshed = Instant::now();
+        let refreshed = self
+            .pile
+            .as_mut()
+            .expect("pile is open while mounted")
+            .refresh()
+            .map_err(anyhow::Error::from)
+            .and_then(|()| self.load());
+        if let Err(e) = refreshed {
+            warn!("pile refresh failed: {e:#}");
+        }
+    }
+
+    fn attr(&self, ino: u64, node: Node) -> FileAttr {
+        let (kind, size, perm, nlink, mtime) = match node {
+            Node::Dir => (FileType::Directory, 0, 0o555, 2, self.mounted),
+            Node::File { hash, length } => {
+                let created = self
+                    .reader
+                    .as_ref()
+                    .and_then(|r| r.metadata(BlobHandle::new(hash)).ok().flatten())
+                    .map(|m| UNIX_EPOCH + Duration::from_millis(m.timestamp))
+                    .unwrap_or(self.mounted);
+                (FileType::RegularFile, length, 0o444, 1, created)
+            }
+        };
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind,
+            perm,
+            nlink,
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        }
+    }
+}
+
+/// The content blob of the commit a branch head points at.
+fn head_content_blob(reader: &PileReader<Blake3>, meta: &TribleSet) -> Option<([u8; 32], u64)> {
+    let head = extract_repo_head(meta)?;
+    let commit: TribleSet = reader.get(head).ok()?;
+    let content = read_commit_fields(&commit).content?;
+    let handle: BlobHandle = content.transmute();
+    let meta = reader.metadata(handle).ok()??;
+    Some((handle.raw, meta.length))
+}
+
+impl Filesystem for PileFs {
+    fn destroy(&mut self) {
+        if let Some(pile) = self.pile.take() {
+            if let Err(e) = pile.close() {
+                warn!("closing the pile failed: {e:?}");
+            }
+        }
+    }
+
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        self.maybe_refresh();
+        let found = name
+            .to_str()
+            .and_then(|name| self.tree.lookup(parent, name))
+            .and_then(|ino| Some((ino, self.tree.node(ino)?)));
+        match found {
+            Some((ino, node)) => reply.entry(&TTL, &self.attr(ino, node), 0),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.tree.node(ino) {
+            Some(node) => reply.attr(&TTL, &self.attr(ino, node)),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            return reply.error(EROFS);
+        }
+        match self.tree.node(ino) {
+            Some(Node::File { .. }) => reply.opened(0, 0),
+            Some(Node::Dir) => reply.error(EISDIR),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Some(Node::File { hash, .. }) = self.tree.node(ino) else {
+            return reply.error(ENOENT);
+        };
+        let Some(reader) = &self.reader else {
+            return reply.error(ENOENT);
+        };
+        let bytes: Bytes = match reader.get(BlobHandle::new(hash)) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                debug!(blob = %hex::encode(hash), "read failed: {e:?}");
+                return reply.error(ENOENT);
+            }
+        };
+        let start = (offset.max(0) as usize).min(bytes.len());
+        let end = start.saturating_add(size as usize).min(bytes.len());
+        reply.data(&bytes[start..end]);
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        if offset == 0 {
+            self.maybe_refresh();
+        }
+        let Some(children) = self.tree.children(ino) else {
+            return reply.error(if self.tree.node(ino).is_some() {
+                ENOTDIR
+            } else {
+                ENOENT
+            });
+        };
+        let parent = self.tree.parent(ino).unwrap_or(ROOT);
+        let entries = [
+            (ino, FileType::Directory, ".".to_string()),
+            (parent, FileType::Directory, "..".to_string()),
+        ]
+        .into_iter()
+        .chain(children.into_iter().map(|(ino, node, name)| {
+            let kind = match node {
+                Node::Dir => FileType::Directory,
+                Node::File { .. } => FileType::RegularFile,
+            };
+            (ino, kind, name)
+        }));
+        for (i, (ino, kind, name)) in entries.enumerate().skip(offset.max(0) as usize) {
+            // The offset passed back to us is that of the next entry.
+            if reply.add(ino, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    // Everything below would modify the filesystem.
+
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        _size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        reply.error(EROFS);
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        _fh: u64,
+        _offset: i64,
+        _data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        reply.error(EROFS);
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        _parent: u64,
+        _name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        _flags: i32,
+        reply: ReplyCreate,
+    ) {
+        reply.error(EROFS);
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        _parent: u64,
+        _name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        reply.error(EROFS);
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, _parent: u64, _name: &OsStr, reply: ReplyEmpty) {
+        reply.error(EROFS);
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, _parent: u64, _name: &OsStr, reply: ReplyEmpty) {
+        reply.error(EROFS);
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        _parent: u64,
+        _name: &OsStr,
+        _newparent: u64,
+        _newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        reply.error(EROFS);
+    }
+}
@@ -0,0 +1,12 @@
+//! `trible mount`: a pile's blobs as a read-only FUSE filesystem.
+//!
+//! The filesystem itself needs the `fuse` feature; the tree layout in
+//! [`tree`] is plain data and always built.
+
+mod tree;
+
+#[cfg(feature = "fuse")]
+mod fs;
+
+#[cfg(feature = "fuse")]
+pub use fs::run;
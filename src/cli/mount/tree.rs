@@ -0,0 +1,398 @@
+//! The directory tree of a mounted pile and its inode numbers.
+//!
+//! ```text
+//! /by-hash/HEX               every blob
+//! /branches/NAME/content     the content blob of each branch head
+//! ```
+//!
+//! Inodes are handed out the first time a blob or branch is seen and kept
+//! across refreshes, so a file keeps its inode while it stays listed.
+#![cfg_attr(not(feature = "fuse"), allow(dead_code))]
+
+use std::collections::{BTreeMap, HashMap};
+
+use triblespace_core::id::Id;
+
+pub const ROOT: u64 = 1;
+pub const BY_HASH: u64 = 2;
+pub const BRANCHES: u64 = 3;
+/// First inode handed out for blobs and branches.
+const FIRST_DYNAMIC: u64 = 4;
+
+/// A branch as the tree shows it: its id, name and head content blob.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Branch {
+    pub id: Id,
+    pub name: Option<String>,
+    /// Hash and length of the head commit's content blob.
+    pub content: Option<([u8; 32], u64)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Blob([u8; 32]),
+    BranchDir(Id),
+    BranchContent(Id),
+}
+
+/// What an inode is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Node {
+    Dir,
+    /// A file with the contents of the blob with this hash.
+    File {
+        hash: [u8; 32],
+        length: u64,
+    },
+}
+
+#[derive(Default)]
+pub struct Tree {
+    inodes: HashMap<Key, u64>,
+    keys: Vec<Key>,
+    /// Blob hashes with their lengths, by hash.
+    blobs: BTreeMap<[u8; 32], u64>,
+    /// Branches by directory name.
+    branches: BTreeMap<String, Branch>,
+}
+
+impl Tree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the listed blobs and branches, keeping known inodes.
+    pub fn update(
+        &mut self,
+        blobs: impl IntoIterator<Item = ([u8; 32], u64)>,
+        branches: Vec<Branch>,
+    ) {
+        self.blobs = blobs.into_iter().collect();
+        let keys: Vec<Key> = self
+            .blobs
+            .keys()
+            .map(|hash| Key::Blob(*hash))
+            .chain(
+                branches
+                    .iter()
+                    .flat_map(|b| [Key::BranchDir(b.id), Key::BranchContent(b.id)]),
+            )
+            .collect();
+        for key in keys {
+            self.inode(key);
+        }
+        self.branches = dir_names(&branches).into_iter().zip(branches).collect();
+    }
+
+    fn inode(&mut self, key: Key) -> u64 {
+        if let Some(&ino) = self.inodes.get(&key) {
+            return ino;
+        }
+        let ino = FIRST_DYNAMIC + self.keys.len() as u64;
+        self.keys.push(key);
+        self.inodes.insert(key, ino);
+        ino
+    }
+
+    fn key(&self, ino: u64) -> Option<Key> {
+        let index = ino.checked_sub(FIRST_DYNAMIC)?;
+        self.keys.get(usize::try_from(index).ok()?).copied()
+    }
+
+    fn branch(&self, id: Id) -> Option<&Branch> {
+        self.branches.values().find(|b| b.id == id)
+    }
+
+    /// The node behind an inode, if it is currently listed.
+    pub fn node(&self, ino: u64) -> Option<Node> {
+        match ino {
+            ROOT | BY_HASH | BRANCHES => return Some(Node::Dir),
+            _ => {}
+        }
+        match self.key(ino)? {
+            Key::Blob(hash) => {
+                let length = *self.blobs.get(&hash)?;
+                Some(Node::File { hash, length })
+            }
+            Key::BranchDir(id) => self.branch(id).map(|_| Node::Dir),
+            Key::BranchContent(id) => {
+                let (hash, length) = self.branch(id)?.content?;
+                Some(Node::File { hash, length })
+            }
+        }
+    }
+
+    /// The inode of `name` inside the directory `parent`.
+    pub fn lookup(&self, parent: u64, name: &str) -> Option<u64> {
+        match parent {
+            ROOT => match name {
+                "by-hash" => Some(BY_HASH),
+                "branches" => Some(BRANCHES),
+                _ => None,
+            },
+            BY_HASH => {
+                let mut hash = [0u8; 32];
+                // Listings are lower case; only accept what they show.
+                if name.bytes().any(|b| b.is_ascii_uppercase()) {
+                    return None;
+                }
+                hex::decode_to_slice(name, &mut hash).ok()?;
+                self.blobs
+                    .contains_key(&hash)
+                    .then(|| self.inodes[&Key::Blob(hash)])
+            }
+            BRANCHES => {
+                let branch = self.branches.get(name)?;
+                Some(self.inodes[&Key::BranchDir(branch.id)])
+            }
+            _ => match self.key(parent)? {
+                Key::BranchDir(id) if name == "content" => {
+                    self.branch(id)?.content?;
+                    Some(self.inodes[&Key::BranchContent(id)])
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// The entries of a directory, without `.` and `..`, in listing order.
+    pub fn children(&self, ino: u64) -> Option<Vec<(u64, Node, String)>> {
+        let entries = match ino {
+            ROOT => vec![
+                (BY_HASH, Node::Dir, "by-hash".to_string()),
+                (BRANCHES, Node::Dir, "branches".to_string()),
+            ],
+            BY_HASH => self
+                .blobs
+                .iter()
+                .map(|(hash, length)| {
+                    (
+                        self.inodes[&Key::Blob(*hash)],
+                        Node::File {
+                            hash: *hash,
+                            length: *length,
+                        },
+                        hex::encode(hash),
+                    )
+                })
+                .collect(),
+            BRANCHES => self
+                .branches
+                .iter()
+                .map(|(name, b)| (self.inodes[&Key::BranchDir(b.id)], Node::Dir, name.clone()))
+                .collect(),
+            _ => match self.key(ino)? {
+                Key::BranchDir(id) => {
+                    let branch = self.branch(id)?;
+                    branch
+                        .content
+                        .map(|(hash, length)| {
+                            (
+                                self.inodes[&Key::BranchContent(id)],
+                                Node::File { hash, length },
+                                "content".to_string(),
+                            )
+                        })
+                        .into_iter()
+                        .collect()
+                }
+                _ => return None,
+            },
+        };
+        Some(entries)
+    }
+
+    /// The parent directory of a directory inode.
+    pub fn parent(&self, ino: u64) -> Option<u64> {
+        match ino {
+            ROOT | BY_HASH | BRANCHES => Some(ROOT),
+            _ => match self.key(ino)? {
+                Key::BranchDir(_) => Some(BRANCHES),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Directory names for branches: the branch name where it is unique and
+/// usable as a file name, the hex id otherwise.
+fn dir_names(branches: &[Branch]) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for name in branches.iter().filter_map(|b| b.name.as_deref()) {
+        *counts.entry(name).or_default() += 1;
+    }
+    branches
+        .iter()
+        .map(|b| match b.name.as_deref() {
+            Some(name) if counts[name] == 1 && usable(name) => name.to_string(),
+            _ => format!("{:X}", b.id),
+        })
+        .collect()
+}
+
+fn usable(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\0'])
+        // A name that is some other branch's id would shadow it.
+        && !(name.len() == 32 && name.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(byte: u8) -> Id {
+        Id::new([byte; 16]).unwrap()
+    }
+
+    fn branch(byte: u8, name: Option<&str>, content: Option<u8>) -> Branch {
+        Branch {
+            id: id(byte),
+            name: name.map(str::to_string),
+            content: content.map(|c| ([c; 32], c as u64)),
+        }
+    }
+
+    #[test]
+    fn fixed_directories_resolve() {
+        let tree = Tree::new();
+        assert_eq!(tree.lookup(ROOT, "by-hash"), Some(BY_HASH));
+        assert_eq!(tree.lookup(ROOT, "branches"), Some(BRANCHES));
+        assert_eq!(tree.lookup(ROOT, "other"), None);
+        for ino in [ROOT, BY_HASH, BRANCHES] {
+            assert_eq!(tree.node(ino), Some(Node::Dir));
+            assert_eq!(tree.parent(ino), Some(ROOT));
+        }
+        assert_eq!(tree.children(BY_HASH), Some(vec![]));
+        assert_eq!(tree.node(0), None);
+        assert_eq!(tree.node(FIRST_DYNAMIC), None);
+    }
+
+    #[test]
+    fn blobs_are_listed_and_looked_up_by_lower_case_hash() {
+        let mut tree = Tree::new();
+        tree.update([([2; 32], 20), ([1; 32], 10)], vec![]);
+        let names: Vec<String> = tree
+            .children(BY_HASH)
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, name)| name)
+            .collect();
+        assert_eq!(names, [hex::encode([1u8; 32]), hex::encode([2u8; 32])]);
+
+        let ino = tree.lookup(BY_HASH, &hex::encode([2u8; 32])).unwrap();
+        assert_eq!(
+            tree.node(ino),
+            Some(Node::File {
+                hash: [2; 32],
+                length: 20
+            })
+        );
+        assert_eq!(tree.lookup(BY_HASH, &hex::encode_upper([2u8; 32])), None);
+        assert_eq!(tree.lookup(BY_HASH, &hex::encode([3u8; 32])), None);
+        assert_eq!(tree.lookup(BY_HASH, "not-hex"), None);
+        assert_eq!(tree.children(ino), None);
+        assert_eq!(tree.parent(ino), None);
+    }
+
+    #[test]
+    fn branches_expose_their_content_blob() {
+        let mut tree = Tree::new();
+        tree.update(
+            [],
+            vec![
+                branch(1, Some("main"), Some(7)),
+                branch(2, Some("empty"), None),
+            ],
+        );
+        let names: Vec<String> = tree
+            .children(BRANCHES)
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, name)| name)
+            .collect();
+        assert_eq!(names, ["empty", "main"]);
+
+        let main = tree.lookup(BRANCHES, "main").unwrap();
+        assert_eq!(tree.node(main), Some(Node::Dir));
+        assert_eq!(tree.parent(main), Some(BRANCHES));
+        let content = tree.lookup(main, "content").unwrap();
+        assert_eq!(
+            tree.node(content),
+            Some(Node::File {
+                hash: [7; 32],
+                length: 7
+            })
+        );
+        assert_eq!(tree.children(main).unwrap().len(), 1);
+        assert_eq!(tree.lookup(main, "other"), None);
+
+        let empty = tree.lookup(BRANCHES, "empty").unwrap();
+        assert_eq!(tree.children(empty), Some(vec![]));
+        assert_eq!(tree.lookup(empty, "content"), None);
+    }
+
+    #[test]
+    fn ambiguous_or_unusable_names_fall_back_to_ids() {
+        let mut tree = Tree::new();
+        tree.update(
+            [],
+            vec![
+                branch(1, Some("twin"), None),
+                branch(2, Some("twin"), None),
+                branch(3, Some("a/b"), None),
+                branch(4, Some(".."), None),
+                branch(5, None, None),
+                branch(6, Some(&format!("{:X}", id(9))), None),
+                branch(7, Some("fine"), None),
+            ],
+        );
+        let names: Vec<String> = tree
+            .children(BRANCHES)
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, name)| name)
+            .collect();
+        let mut expected: Vec<String> = (1..=6).map(|b| format!("{:X}", id(b))).collect();
+        expected.push("fine".to_string());
+        expected.sort();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn inodes_survive_refreshes() {
+        let mut tree = Tree::new();
+        tree.update([([1; 32], 1)], vec![branch(1, Some("main"), Some(1))]);
+        let blob = tree.lookup(BY_HASH, &hex::encode([1u8; 32])).unwrap();
+        let main = tree.lookup(BRANCHES, "main").unwrap();
+        let content = tree.lookup(main, "content").unwrap();
+
+        // The branch moves to a new head and gets renamed; a blob appears.
+        tree.update(
+            [([1; 32], 1), ([2; 32], 2)],
+            vec![branch(1, Some("trunk"), Some(2))],
+        );
+        assert_eq!(tree.lookup(BY_HASH, &hex::encode([1u8; 32])), Some(blob));
+        assert_eq!(tree.lookup(BRANCHES, "main"), None);
+        assert_eq!(tree.lookup(BRANCHES, "trunk"), Some(main));
+        assert_eq!(tree.lookup(main, "content"), Some(content));
+        assert_eq!(
+            tree.node(content),
+            Some(Node::File {
+                hash: [2; 32],
+                length: 2
+            })
+        );
+
+        // Gone from the pile listing, gone from the tree; the inode is not reused.
+        tree.update([([2; 32], 2)], vec![]);
+        assert_eq!(tree.node(blob), None);
+        assert_eq!(tree.node(main), None);
+        assert_eq!(tree.node(content), None);
+        tree.update([([3; 32], 3)], vec![]);
+        let fresh = tree.lookup(BY_HASH, &hex::encode([3u8; 32])).unwrap();
+        assert!(![blob, main, content].contains(&fresh));
+    }
+}
@@ -0,0 +1,199 @@
+//! Record-oriented stdout writer behind the global `--output` flag.
+//!
+//! Commands describe each result row once as a list of named [`Field`]s and
+//! the writer serializes it: `plain` joins the fields with tabs (the
+//! traditional output), `json` emits one JSON object per line and
+//! `porcelain` is the scripting format: tab-separated like `plain`, but absent
+//! values are empty and tabs or newlines inside values become spaces, so every
+//! record is exactly one line with a fixed number of columns. In the machine
+//! formats nothing but records reaches stdout; prose goes through [`note!`],
+//! which writes to stdout in plain mode, to stderr otherwise, and is dropped
+//! entirely under `--quiet`.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt;
+use std::io::{self, StdoutLock, Write};
+use std::sync::OnceLock;
+
+use crate::cli::config;
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static QUIET: OnceLock<bool> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Tab-separated columns
+    #[default]
+    Plain,
+    /// One JSON object per line
+    Json,
+    /// Stable tab-separated columns for scripts
+    Porcelain,
+}
+
+/// Select the output format once at startup: the `--output` flag, then the
+/// `TRIBLES_OUTPUT` environment variable, then the `output` config key.
+/// `--porcelain` is shorthand for `--output porcelain`.
+pub fn init(flag: Option<OutputFormat>, porcelain: bool, quiet: bool) -> Result<()> {
+    let flag = flag.or(porcelain.then_some(OutputFormat::Porcelain));
+    let env = match std::env::var("TRIBLES_OUTPUT") {
+        Ok(v) if !v.is_empty() => Some(OutputFormat::from_str(&v, true).map_err(|_| {
+            anyhow::anyhow!("invalid TRIBLES_OUTPUT {v:?}: expected plain, json or porcelain")
+        })?),
+        _ => None,
+    };
+    let format = config::layered(flag, env, config::get().output).unwrap_or_default();
+    let _ = FORMAT.set(format);
+    let _ = QUIET.set(quiet);
+    Ok(())
+}
+
+pub fn format() -> OutputFormat {
+    *FORMAT.get_or_init(OutputFormat::default)
+}
+
+pub fn quiet() -> bool {
+    *QUIET.get_or_init(|| false)
+}
+
+/// Write a line of human-oriented narration; use through [`note!`].
+pub fn note(args: fmt::Arguments<'_>) {
+    if quiet() {
+        return;
+    }
+    match format() {
+        OutputFormat::Plain => println!("{args}"),
+        OutputFormat::Json | OutputFormat::Porcelain => eprintln!("{args}"),
+    }
+}
+
+/// `println!` for progress and summaries that are not a command's result.
+macro_rules! note {
+    ($($arg:tt)*) => {
+        $crate::cli::output::note(format_args!($($arg)*))
+    };
+}
+pub(crate) use note;
+
+/// A single value within a record.
+pub enum Field {
+    Text(String),
+    Number(u64),
+    /// Absent value: `null` in JSON, the given placeholder in plain output.
+    Missing(String),
+}
+
+impl Field {
+    pub fn missing(placeholder: impl Into<String>) -> Field {
+        Field::Missing(placeholder.into())
+    }
+
+    /// Rendering used by the plain format.
+    pub fn plain(&self) -> String {
+        match self {
+            Field::Text(s) | Field::Missing(s) => s.clone(),
+            Field::Number(n) => n.to_string(),
+        }
+    }
+
+    fn porcelain(&self) -> String {
+        match self {
+            Field::Text(s) => s.replace(['\t', '\n', '\r'], " "),
+            Field::Number(n) => n.to_string(),
+            Field::Missing(_) => String::new(),
+        }
+    }
+
+    fn json(&self) -> serde_json::Value {
+        match self {
+            Field::Text(s) => serde_json::Value::String(s.clone()),
+            Field::Number(n) => serde_json::Value::from(*n),
+            Field::Missing(_) => serde_json::Value::Null,
+        }
+    }
+}
+
+impl From<String> for Field {
+    fn from(s: String) -> Field {
+        Field::Text(s)
+    }
+}
+
+impl From<&str> for Field {
+    fn from(s: &str) -> Field {
+        Field::Text(s.to_string())
+    }
+}
+
+impl From<u64> for Field {
+    fn from(n: u64) -> Field {
+        Field::Number(n)
+    }
+}
+
+pub struct Output {
+    format: OutputFormat,
+    out: StdoutLock<'static>,
+}
+
+impl Output {
+    pub fn stdout() -> Output {
+        Output {
+            format: format(),
+            out: io::stdout().lock(),
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Write one record.
+    pub fn record(&mut self, fields: &[(&str, Field)]) -> Result<()> {
+        match self.format {
+            OutputFormat::Plain => {
+                let line: Vec<String> = fields.iter().map(|(_, f)| f.plain()).collect();
+                writeln!(self.out, "{}", line.join("\t"))?;
+            }
+            OutputFormat::Porcelain => {
+                let line: Vec<String> = fields.iter().map(|(_, f)| f.porcelain()).collect();
+                writeln!(self.out, "{}", line.join("\t"))?;
+            }
+            OutputFormat::Json => {
+                let object: serde_json::Map<String, serde_json::Value> = fields
+                    .iter()
+                    .map(|(k, f)| (k.to_string(), f.json()))
+                    .collect();
+                writeln!(self.out, "{}", serde_json::Value::Object(object))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_render_per_format() {
+        let fields = [
+            ("id", Field::from("AB")),
+            ("size", Field::from(3u64)),
+            ("head", Field::missing("-")),
+        ];
+        let plain: Vec<String> = fields.iter().map(|(_, f)| f.plain()).collect();
+        assert_eq!(plain.join("\t"), "AB\t3\t-");
+        let porcelain: Vec<String> = fields.iter().map(|(_, f)| f.porcelain()).collect();
+        assert_eq!(porcelain.join("\t"), "AB\t3\t");
+        assert_eq!(Field::from("a\tb\nc").porcelain(), "a b c");
+        let json: Vec<serde_json::Value> = fields.iter().map(|(_, f)| f.json()).collect();
+        assert_eq!(
+            json,
+            [serde_json::json!("AB"), 3.into(), serde_json::Value::Null]
+        );
+    }
+}
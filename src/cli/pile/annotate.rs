@@ -0,0 +1,170 @@
+//! `pile blob annotate` and `pile blob annotations`: free-form metadata on
+//! raw blobs.
+//!
+//! Annotations are ordinary tribles on a branch of their own (`_annotations`
+//! unless told otherwise). The entity they describe is derived from the blob
+//! handle, so every annotation of a blob lands on the same entity and later
+//! commits add to the earlier ones. Values are stored as LongString blobs.
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::View;
+use triblespace_core::blob::schemas::longstring::LongString;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::id::{ExclusiveId, Id};
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::{Trible, TribleSet};
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::error::{self, invalid_argument, ErrorKind};
+use crate::cli::output::{note, Field, Output};
+use crate::cli::util::parse_blob_handle;
+
+use super::branch::commit_content;
+use super::resolve::{resolve_branch, BranchRef};
+use super::signing::SigningArgs;
+use super::{ClosingPile, ClosingRepo};
+
+/// Branch annotations are committed to unless `--branch` names another.
+pub const DEFAULT_BRANCH: &str = "_annotations";
+
+/// Derivation context of the entity a blob's annotations are recorded on.
+const ENTITY_CONTEXT: &str = "trible pile blob annotate entity";
+
+pub fn annotate(
+    pile_path: PathBuf,
+    handle: String,
+    set: Vec<String>,
+    branch: String,
+    signing: SigningArgs,
+) -> Result<()> {
+    let assignments = set
+        .iter()
+        .map(|spec| parse_assignment(spec))
+        .collect::<Result<Vec<_>>>()?;
+    let hash = parse_blob_handle(&handle)?.raw;
+
+    let key = signing.load()?;
+    let pile: Pile<Blake3> = Pile::open(&pile_path)?;
+    let repo = Repository::new(pile, key, TribleSet::new())?;
+    ClosingRepo::new(repo).run(|repo| {
+        repo.storage_mut()
+            .refresh()
+            .map_err(|e| anyhow::anyhow!("refresh pile: {e:?}"))?;
+        let reader = repo
+            .storage_mut()
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let blob: Value<Handle<Blake3, UnknownBlob>> = Value::new(hash);
+        if reader.metadata(blob)?.is_none() {
+            return Err(error::not_found(format!("blob {handle} not found")));
+        }
+
+        let branch_id = match resolve_branch(repo.storage_mut(), &reader, BranchRef::Name(&branch))
+        {
+            Ok(info) => info.id,
+            Err(e) if error::kind_of(&e) == Some(ErrorKind::NotFound) => *repo
+                .create_branch(&branch, None)
+                .map_err(|e| anyhow::anyhow!("create branch: {e:?}"))?,
+            Err(e) => return Err(e),
+        };
+        let mut ws = repo
+            .pull(branch_id)
+            .map_err(|e| anyhow::anyhow!("pull branch: {e:?}"))?;
+        let entity = ExclusiveId::force(super::derived_entity(ENTITY_CONTEXT, &hash));
+        let mut content = TribleSet::new();
+        for (attribute, text) in &assignments {
+            let value = ws.put::<LongString, _>(text.clone());
+            content.insert(&Trible::new(&entity, attribute, &value));
+        }
+        ws.commit(content, &format!("annotate blake3:{}", hex::encode(hash)));
+        repo.push(&mut ws)
+            .map_err(|e| anyhow::anyhow!("push failed: {e:?}"))?;
+        note!(
+            "added {} annotation(s) to blake3:{} on {branch}",
+            assignments.len(),
+            hex::encode(hash)
+        );
+        Ok(())
+    })
+}
+
+pub fn annotations(pile: PathBuf, handle: String, branch: String) -> Result<()> {
+    let hash = parse_blob_handle(&handle)?.raw;
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        // Nothing was ever annotated while the branch does not exist.
+        let head = match resolve_branch(pile, &reader, BranchRef::Name(&branch)) {
+            Ok(info) => info.head,
+            Err(e) if error::kind_of(&e) == Some(ErrorKind::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+        let content = match head {
+            Some(head) => commit_content(&reader, head)?,
+            None => TribleSet::new(),
+        };
+
+        let entity = super::derived_entity(ENTITY_CONTEXT, &hash).raw();
+        let mut rows: Vec<(String, String)> = Vec::new();
+        for t in content.iter() {
+            if t.data[..16] != entity {
+                continue;
+            }
+            let attribute = Id::new(t.data[16..32].try_into()?)
+                .ok_or_else(|| anyhow::anyhow!("trible with nil attribute"))?;
+            let raw: [u8; 32] = t.data[32..].try_into()?;
+            let value: Value<Handle<Blake3, LongString>> = Value::new(raw);
+            let text = match reader.get::<View<str>, _>(value) {
+                Ok(text) => text.as_ref().to_string(),
+                Err(_) => hex::encode(raw),
+            };
+            let attribute =
+                crate::cli::attr::label(&attribute).unwrap_or_else(|| format!("{attribute:X}"));
+            rows.push((attribute, text));
+        }
+        rows.sort();
+
+        let mut out = Output::stdout();
+        for (attribute, value) in rows {
+            out.record(&[
+                ("attribute", Field::from(attribute)),
+                ("value", Field::from(value)),
+            ])?;
+        }
+        Ok(())
+    })
+}
+
+/// Parse `ATTR=VALUE`, with ATTR a hex id or an attribute name.
+fn parse_assignment(spec: &str) -> Result<(Id, String)> {
+    let (attribute, value) = spec
+        .split_once('=')
+        .ok_or_else(|| invalid_argument(format!("invalid --set {spec:?}: expected ATTR=VALUE")))?;
+    let attribute = Id::new(super::query::parse_attribute(attribute)?)
+        .ok_or_else(|| invalid_argument(format!("invalid --set {spec:?}: nil attribute id")))?;
+    Ok((attribute, value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments_split_at_the_first_equals_sign() {
+        let attr = "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F";
+        let (attribute, value) = parse_assignment(&format!("{attr}=a=b")).unwrap();
+        assert_eq!(format!("{attribute:X}"), attr);
+        assert_eq!(value, "a=b");
+        assert!(parse_assignment(attr).is_err());
+        assert!(parse_assignment(&format!("{}=x", "0".repeat(32))).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Reading tar and zip archives member by member, for
+//! `pile blob put --archive`.
+//!
+//! Members are read straight from the archive stream into memory; nothing is
+//! extracted to disk, so member paths are never interpreted as local paths.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveFormat {
+    /// A tarball, optionally gzip or zstd compressed
+    Tar,
+    /// A zip file
+    Zip,
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Call `each` with the path and contents of every regular file in the
+/// archive at `path`, in archive order. Compressed tarballs are recognised
+/// by their magic bytes and decompressed on the fly.
+pub(super) fn for_each_member(
+    path: &Path,
+    format: ArchiveFormat,
+    mut each: impl FnMut(String, Vec<u8>) -> Result<()>,
+) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    match format {
+        ArchiveFormat::Tar => {
+            let mut input = BufReader::new(file);
+            let head = input.fill_buf()?;
+            let stream: Box<dyn Read> = if head.starts_with(GZIP_MAGIC) {
+                Box::new(flate2::read::GzDecoder::new(input))
+            } else if head.starts_with(ZSTD_MAGIC) {
+                Box::new(zstd::stream::read::Decoder::with_buffer(input)?)
+            } else {
+                Box::new(input)
+            };
+            let mut archive = tar::Archive::new(stream);
+            for entry in archive.entries()? {
+                let mut entry = entry.context("read tar entry")?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let member = entry.path()?.to_string_lossy().into_owned();
+                let mut data = Vec::with_capacity(entry.size() as usize);
+                entry
+                    .read_to_end(&mut data)
+                    .with_context(|| format!("read tar member {member}"))?;
+                each(member, data)?;
+            }
+        }
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(file)
+                .map_err(|e| crate::cli::error::invalid_argument(format!("not a zip file: {e}")))?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i)?;
+                if !entry.is_file() {
+                    continue;
+                }
+                let member = entry.name().to_string();
+                let mut data = Vec::with_capacity(entry.size() as usize);
+                entry
+                    .read_to_end(&mut data)
+                    .with_context(|| format!("read zip member {member}"))?;
+                each(member, data)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Attribute usage across a branch's content.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use triblespace_core::id::Id;
+
+use crate::cli::attr::label;
+use crate::cli::output::{Field, Output};
+
+use super::branch::{head_content, select_branch};
+use super::ClosingPile;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AttrSort {
+    /// Most used attributes first
+    Count,
+    /// By attribute id
+    Id,
+}
+
+/// `pile branch attrs`: per attribute, how many tribles use it and on how
+/// many distinct entities.
+pub fn run(pile: PathBuf, id: Option<String>, name: Option<String>, sort: AttrSort) -> Result<()> {
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let branch_id = select_branch(pile, id.as_deref(), name.as_deref())?;
+        let content = head_content(pile, branch_id)?;
+
+        let mut usage: HashMap<Id, (u64, HashSet<Id>)> = HashMap::new();
+        for t in content.iter() {
+            let (tribles, entities) = usage.entry(*t.a()).or_default();
+            *tribles += 1;
+            entities.insert(*t.e());
+        }
+        let mut rows: Vec<(String, Id, u64, u64)> = usage
+            .into_iter()
+            .map(|(attribute, (tribles, entities))| {
+                (
+                    format!("{attribute:X}"),
+                    attribute,
+                    tribles,
+                    entities.len() as u64,
+                )
+            })
+            .collect();
+        rows.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        if sort == AttrSort::Count {
+            // Stable, so equal counts stay in id order.
+            rows.sort_by(|a, b| b.2.cmp(&a.2));
+        }
+
+        let mut out = Output::stdout();
+        for (hex, attribute, tribles, entities) in rows {
+            out.record(&[
+                ("attribute", Field::from(hex)),
+                ("tribles", Field::from(tribles)),
+                ("entities", Field::from(entities)),
+                (
+                    "label",
+                    label(&attribute)
+                        .map(Field::from)
+                        .unwrap_or_else(|| Field::missing("-")),
+                ),
+            ])?;
+        }
+        Ok(())
+    })
+}
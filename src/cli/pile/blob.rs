@@ -5,25 +5,70 @@ use std::path::PathBuf;
 
 // DEFAULT_MAX_PILE_SIZE removed; the new Pile API no longer uses a size const generic
 
+use super::archive::ArchiveFormat;
+use super::signing::SigningArgs;
+use super::ClosingPile;
+use crate::cli::config;
+use crate::cli::output::{note, Field, Output, OutputFormat};
+use crate::cli::stats;
 use crate::cli::util::parse_blob_handle;
+use crate::cli::verify;
 use triblespace_core::repo::BlobStoreMeta;
 
 #[derive(Parser)]
 pub enum Command {
     /// List all blob handles stored in a pile file.
+    #[command(visible_alias = "ls")]
     List {
         /// Path to the pile file to inspect
-        path: PathBuf,
+        path: Option<PathBuf>,
         /// Show creation time and size for each blob
         #[arg(long)]
         metadata: bool,
     },
-    /// Ingest a file into a pile, creating the pile if necessary.
+    /// Ingest files into a pile, creating the pile if necessary.
+    ///
+    /// Prints one handle per file, in the order the files were given, with
+    /// `new` or `existing` depending on whether the pile already held it.
+    /// Existing blobs are not appended again.
+    #[command(allow_missing_positional = true)]
     Put {
         /// Path to the pile file to modify
-        pile: PathBuf,
-        /// File whose contents should be stored in the pile
-        file: PathBuf,
+        pile: Option<PathBuf>,
+        /// Files whose contents should be stored in the pile; give the pile
+        /// path explicitly when storing more than one
+        #[arg(required_unless_present = "url")]
+        files: Vec<PathBuf>,
+        /// Download an HTTP(S) resource and store it instead of files
+        #[arg(long, conflicts_with_all = ["files", "archive"])]
+        url: Option<String>,
+        /// Fail without storing anything unless the download hashes to
+        /// this handle (e.g. "blake3:HEX...")
+        #[arg(long, value_name = "HANDLE", requires = "url")]
+        expected_handle: Option<String>,
+        /// Number of files read and hashed in parallel (defaults to the
+        /// `concurrency` config key, then the number of CPUs)
+        #[arg(long)]
+        threads: Option<usize>,
+        /// Fail if any file was already in the pile (new files are still
+        /// stored)
+        #[arg(long)]
+        fail_if_present: bool,
+        /// Treat the file as an archive and store each regular file in it as
+        /// its own blob, printing `handle<TAB>member-path` lines
+        #[arg(long)]
+        archive: bool,
+        /// Archive format; gzip and zstd compressed tarballs are detected
+        #[arg(long, value_enum, default_value_t = ArchiveFormat::Tar, requires = "archive")]
+        format: ArchiveFormat,
+        /// Also store a manifest mapping member paths to their blobs, as a
+        /// trible set, and print its handle
+        #[arg(long, requires = "archive")]
+        manifest_blob: bool,
+        /// Also write the handles, one per line in input order, to PATH
+        /// (replaced atomically)
+        #[arg(long, value_name = "PATH")]
+        handle_out: Option<PathBuf>,
     },
     /// Extract a blob from a pile by its handle.
     Get {
@@ -35,11 +80,59 @@ pub enum Command {
         output: PathBuf,
     },
     /// Inspect a blob and print basic metadata.
+    #[command(allow_missing_positional = true)]
     Inspect {
         /// Path to the pile file to read
-        pile: PathBuf,
+        pile: Option<PathBuf>,
         /// Handle of the blob to inspect (e.g. "blake3:HEX...")
         handle: String,
+        /// Decode the blob as a trible set and list its tribles, with
+        /// attribute names where known
+        #[arg(long)]
+        decode: bool,
+    },
+    /// Check that a trible set archive is stored in canonical form.
+    ///
+    /// Decodes the blob and re-encodes it; older tooling could write archives
+    /// whose bytes differ from the canonical encoding of the same set, which
+    /// defeats deduplication. Reports the handle the canonical encoding
+    /// would have. Nothing is rewritten.
+    #[command(allow_missing_positional = true)]
+    CanonicalCheck {
+        /// Path to the pile file to read
+        pile: Option<PathBuf>,
+        /// Handle of the blob to check (e.g. "blake3:HEX...")
+        handle: String,
+    },
+    /// Record metadata about a blob as tribles on an annotations branch.
+    ///
+    /// Each --set ATTR=VALUE adds a trible of ATTR (a hex id or a name
+    /// listed by `trible attr list`) with VALUE stored as a LongString, on
+    /// an entity derived from the handle. Annotating again adds to the
+    /// earlier annotations; the branch is created on first use.
+    Annotate {
+        /// Path to the pile file to modify
+        pile: PathBuf,
+        /// Handle of the blob to annotate (e.g. "blake3:HEX...")
+        handle: String,
+        /// Annotation to add, ATTR=VALUE (repeatable)
+        #[arg(long = "set", value_name = "ATTR=VALUE", required = true)]
+        set: Vec<String>,
+        /// Branch the annotations are committed to
+        #[arg(long, default_value = super::annotate::DEFAULT_BRANCH)]
+        branch: String,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+    /// List the annotations recorded for a blob.
+    Annotations {
+        /// Path to the pile file to read
+        pile: PathBuf,
+        /// Handle of the blob (e.g. "blake3:HEX...")
+        handle: String,
+        /// Branch the annotations were committed to
+        #[arg(long, default_value = super::annotate::DEFAULT_BRANCH)]
+        branch: String,
     },
 }
 
@@ -54,111 +147,247 @@ pub fn run(cmd: Command) -> Result<()> {
             use triblespace::prelude::BlobStore;
             use triblespace::prelude::BlobStoreList;
             use triblespace_core::blob::schemas::UnknownBlob;
-            use triblespace_core::repo::pile::Pile;
             use triblespace_core::value::schemas::hash::Blake3;
             use triblespace_core::value::schemas::hash::Handle;
             use triblespace_core::value::schemas::hash::Hash;
 
-            let mut pile: Pile<Blake3> = Pile::open(&path)?;
-            let res = (|| -> Result<(), anyhow::Error> {
+            let path = super::resolve_pile(path)?;
+            let pile = {
+                let _phase = stats::phase!("pile open");
+                ClosingPile::open(&path)?
+            };
+            pile.run(|pile| {
+                super::refresh_for_read(pile)?;
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                let mut out = Output::stdout();
                 for handle in reader.blobs() {
                     let handle: triblespace_core::value::Value<Handle<Blake3, UnknownBlob>> =
                         handle?;
                     let hash: triblespace_core::value::Value<Hash<Blake3>> =
                         Handle::to_hash(handle);
                     let string: String = hash.from_value();
-                    if metadata {
-                        let meta_opt = reader.metadata(handle)?;
-                        if let Some(meta) = meta_opt {
-                            let dt = UNIX_EPOCH + Duration::from_millis(meta.timestamp);
-                            let time: DateTime<Utc> = DateTime::<Utc>::from(dt);
-                            println!("{}\t{}\t{}", string, time.to_rfc3339(), meta.length);
-                        } else {
-                            println!("{string}");
-                        }
+                    let meta_opt = if metadata {
+                        reader.metadata(handle)?
+                    } else {
+                        None
+                    };
+                    if let Some(meta) = meta_opt {
+                        let dt = UNIX_EPOCH + Duration::from_millis(meta.timestamp);
+                        let time: DateTime<Utc> = DateTime::<Utc>::from(dt);
+                        out.record(&[
+                            ("handle", Field::from(string)),
+                            ("created", Field::from(time.to_rfc3339())),
+                            ("length", Field::from(meta.length)),
+                        ])?;
                     } else {
-                        println!("{string}");
+                        out.record(&[("handle", Field::from(string))])?;
                     }
                 }
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
-        Command::Put { pile, file } => {
+        Command::Put {
+            pile,
+            files,
+            threads,
+            fail_if_present,
+            archive,
+            format,
+            manifest_blob,
+            handle_out,
+            url,
+            expected_handle,
+        } => {
+            use std::collections::{BTreeMap, HashSet};
+            use std::sync::atomic::{AtomicUsize, Ordering};
+            use std::sync::mpsc;
+
             use triblespace::prelude::blobschemas::FileBytes;
-            use triblespace::prelude::BlobStorePut;
-            use triblespace_core::blob::Bytes;
-            use triblespace_core::repo::pile::Pile;
+            use triblespace::prelude::BlobStore;
             use triblespace_core::value::schemas::hash::Blake3;
             use triblespace_core::value::schemas::hash::Handle;
             use triblespace_core::value::schemas::hash::Hash;
+            use triblespace_core::value::Value;
 
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
-                let file_handle = File::open(&file)?;
-                let bytes = unsafe { Bytes::map_file(&file_handle)? };
-                let handle = pile.put::<FileBytes, _>(bytes)?;
-                let hash: triblespace_core::value::Value<Hash<Blake3>> = Handle::to_hash(handle);
-                let string: String = hash.from_value();
-                println!("{string}");
-                Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            if let Some(url) = url {
+                return put_url(
+                    &super::resolve_pile(pile)?,
+                    &url,
+                    expected_handle.as_deref(),
+                    fail_if_present,
+                    handle_out.as_deref(),
+                );
+            }
+            if archive {
+                let [file] = files.as_slice() else {
+                    return Err(crate::cli::error::invalid_argument(
+                        "--archive takes exactly one archive file",
+                    ));
+                };
+                return put_archive(
+                    &super::resolve_pile(pile)?,
+                    file,
+                    format,
+                    manifest_blob,
+                    fail_if_present,
+                    handle_out.as_deref(),
+                );
+            }
+            let threads = config::layered(threads, None, config::get().concurrency)
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+                .clamp(1, files.len());
+            let pile = super::resolve_pile(pile)?;
+            let pile = {
+                let _phase = stats::phase!("pile open");
+                ClosingPile::open(&pile)?
+            };
+            pile.run(|pile| {
+                pile.refresh()?;
+                let reader = pile
+                    .reader()
+                    .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+
+                // Workers map and hash the files; this thread is the only
+                // writer and appends them in the order they were given.
+                let next = AtomicUsize::new(0);
+                let (tx, rx) = mpsc::sync_channel(threads * 2);
+                std::thread::scope(|scope| -> Result<()> {
+                    for _ in 0..threads {
+                        let (tx, next, files) = (tx.clone(), &next, &files);
+                        scope.spawn(move || loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(path) = files.get(i) else { break };
+                            if tx.send((i, hash_file(path))).is_err() {
+                                break;
+                            }
+                        });
+                    }
+                    drop(tx);
+
+                    let mut out = Output::stdout();
+                    let mut handles = Vec::with_capacity(files.len());
+                    let mut pending = BTreeMap::new();
+                    let mut written: HashSet<[u8; 32]> = HashSet::new();
+                    let mut existing = 0usize;
+                    let mut emitted = 0usize;
+                    // Waiting for the workers is the hashing time this thread
+                    // sees; the appends count as the transfer.
+                    loop {
+                        let received = {
+                            let _phase = stats::phase!("hashing");
+                            rx.recv()
+                        };
+                        let Ok((i, hashed)) = received else { break };
+                        pending.insert(i, hashed);
+                        while let Some(hashed) = pending.remove(&emitted) {
+                            let (bytes, hash) = hashed?;
+                            let handle: Value<Handle<Blake3, FileBytes>> = Value::new(hash);
+                            // Blobs already in the pile (or earlier in this
+                            // batch) are not appended again. A concurrent
+                            // writer may still add the same blob after this
+                            // check, which only costs a duplicate record.
+                            let new = reader.metadata(handle)?.is_none() && written.insert(hash);
+                            if new {
+                                let length = bytes.len() as u64;
+                                let stored = {
+                                    let _phase = stats::phase!("transfer");
+                                    verify::put::<FileBytes, _>(pile, bytes)?
+                                };
+                                debug_assert_eq!(stored.raw, hash);
+                                stats::written(length);
+                            }
+                            let hash: Value<Hash<Blake3>> = Handle::to_hash(handle);
+                            let string: String = hash.from_value();
+                            if !new {
+                                existing += 1;
+                            }
+                            out.record(&[
+                                ("handle", Field::from(string.as_str())),
+                                ("status", Field::from(if new { "new" } else { "existing" })),
+                            ])?;
+                            handles.push(string);
+                            emitted += 1;
+                        }
+                    }
+                    if let Some(path) = &handle_out {
+                        crate::cli::util::write_handles(path, &handles)?;
+                    }
+                    if fail_if_present && existing > 0 {
+                        return Err(crate::cli::error::conflict(format!(
+                            "{existing} of {} file(s) already in the pile",
+                            files.len()
+                        )));
+                    }
+                    Ok(())
+                })
+            })?;
         }
         Command::Get {
             pile,
             handle,
             output,
         } => {
-            use std::io::Write;
+            use crate::cli::util::{copy_chunks, write_atomically, COPY_CHUNK};
 
             use triblespace::prelude::BlobStore;
             use triblespace::prelude::BlobStoreGet;
             use triblespace_core::blob::schemas::UnknownBlob;
             use triblespace_core::blob::Bytes;
-            use triblespace_core::repo::pile::Pile;
             use triblespace_core::value::schemas::hash::Blake3;
             use triblespace_core::value::schemas::hash::Handle;
 
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
+            let pile = {
+                let _phase = stats::phase!("pile open");
+                ClosingPile::open(&pile)?
+            };
+            pile.run(|pile| {
+                super::refresh_for_read(pile)?;
                 let hash_val = parse_blob_handle(&handle)?;
                 let handle_val: triblespace_core::value::Value<Handle<Blake3, UnknownBlob>> =
                     hash_val.into();
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                // The blob is a view into the mmapped pile; writing it in
+                // chunks keeps only a window of it resident.
                 let bytes: Bytes = reader.get(handle_val)?;
-                let mut file = File::create(&output)?;
-                file.write_all(&bytes)?;
-                Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+                stats::read(bytes.len() as u64);
+                write_atomically(&output, |file| {
+                    copy_chunks(bytes.chunks(COPY_CHUNK).map(Ok), file, |_| {})?;
+                    Ok(())
+                })
+            })?;
         }
-        Command::Inspect { pile, handle } => {
+        Command::Inspect {
+            pile,
+            handle,
+            decode,
+        } => {
             use chrono::DateTime;
             use chrono::Utc;
             use file_type::FileType;
             use std::time::Duration;
             use std::time::UNIX_EPOCH;
 
+            use triblespace::prelude::blobschemas::SimpleArchive;
             use triblespace::prelude::BlobStore;
             use triblespace::prelude::BlobStoreGet;
             use triblespace_core::blob::schemas::UnknownBlob;
             use triblespace_core::blob::Blob;
-            use triblespace_core::repo::pile::Pile;
             use triblespace_core::repo::BlobMetadata;
+            use triblespace_core::trible::TribleSet;
             use triblespace_core::value::schemas::hash::Blake3;
             use triblespace_core::value::schemas::hash::Handle;
 
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
+            let pile = super::resolve_pile(pile)?;
+            let pile = {
+                let _phase = stats::phase!("pile open");
+                ClosingPile::open(&pile)?
+            };
+            pile.run(|pile| {
+                super::refresh_for_read(pile)?;
                 let hash_val = parse_blob_handle(&handle)?;
                 let handle_val: triblespace_core::value::Value<Handle<Blake3, UnknownBlob>> =
                     hash_val.into();
@@ -168,7 +397,7 @@ pub fn run(cmd: Command) -> Result<()> {
                 let blob: Blob<UnknownBlob> = reader.get(handle_val)?;
                 let metadata: BlobMetadata = reader
                     .metadata(handle_val)?
-                    .ok_or_else(|| anyhow::anyhow!("blob not found"))?;
+                    .ok_or_else(|| crate::cli::error::not_found("blob not found"))?;
 
                 let dt = UNIX_EPOCH + Duration::from_millis(metadata.timestamp);
                 let time: DateTime<Utc> = DateTime::<Utc>::from(dt);
@@ -177,17 +406,292 @@ pub fn run(cmd: Command) -> Result<()> {
                 let name = ftype.name();
 
                 let handle_str: String = hash_val.from_value();
+                let mut out = Output::stdout();
+                let tribles = if decode {
+                    let archive: triblespace_core::value::Value<Handle<Blake3, SimpleArchive>> =
+                        triblespace_core::value::Value::new(handle_val.raw);
+                    let set: TribleSet = reader.get(archive).map_err(|e| {
+                        crate::cli::error::corrupt(format!(
+                            "blob is not a trible set archive: {e:?}"
+                        ))
+                    })?;
+                    let mut tribles: Vec<[u8; 64]> = set.iter().map(|t| t.data).collect();
+                    tribles.sort_unstable();
+                    Some(tribles)
+                } else {
+                    None
+                };
+                if let (Some(tribles), true) = (&tribles, out.format() != OutputFormat::Plain) {
+                    for t in tribles {
+                        let attribute = decoded_attribute(t)?;
+                        out.record(&[
+                            ("entity", Field::from(hex::encode_upper(&t[..16]))),
+                            ("attribute", Field::from(format!("{attribute:X}"))),
+                            (
+                                "label",
+                                crate::cli::attr::label(&attribute)
+                                    .map(Field::from)
+                                    .unwrap_or_else(|| Field::missing("-")),
+                            ),
+                            ("value", Field::from(hex::encode(&t[32..]))),
+                        ])?;
+                    }
+                    return Ok(());
+                }
+                if out.format() != OutputFormat::Plain {
+                    out.record(&[
+                        ("handle", Field::from(handle_str)),
+                        ("created", Field::from(time.to_rfc3339())),
+                        ("length", Field::from(metadata.length)),
+                        ("type", Field::from(name)),
+                    ])?;
+                    return Ok(());
+                }
                 println!(
                     "Hash: {handle_str}\nTime: {}\nLength: {} bytes\nType: {}",
                     time.to_rfc3339(),
                     metadata.length,
                     name
                 );
+                if let Some(tribles) = tribles {
+                    println!("Tribles: {}", tribles.len());
+                    for t in &tribles {
+                        println!(
+                            "  {}  {}  {}",
+                            hex::encode_upper(&t[..16]),
+                            crate::cli::attr::display(&decoded_attribute(t)?),
+                            hex::encode(&t[32..])
+                        );
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Command::CanonicalCheck { pile, handle } => {
+            use triblespace::prelude::blobschemas::SimpleArchive;
+            use triblespace_core::value::schemas::hash::{Blake3, Handle};
+            use triblespace_core::value::Value;
+
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile| {
+                super::refresh_for_read(pile)?;
+                let handle: Value<Handle<Blake3, SimpleArchive>> =
+                    parse_blob_handle(&handle)?.into();
+                let reader = pile
+                    .reader()
+                    .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                if reader.metadata(handle)?.is_none() {
+                    return Err(crate::cli::error::not_found("blob not found"));
+                }
+                let canonical = canonical_handle(&reader, handle).ok_or_else(|| {
+                    crate::cli::error::invalid_argument("blob is not a trible set archive")
+                })?;
+                let stored = format!("blake3:{}", hex::encode(handle.raw));
+                let canonical_str = format!("blake3:{}", hex::encode(canonical.raw));
+                let mut out = Output::stdout();
+                if out.format() != OutputFormat::Plain {
+                    out.record(&[
+                        ("handle", Field::from(stored)),
+                        (
+                            "canonical",
+                            Field::from(if canonical == handle { "yes" } else { "no" }),
+                        ),
+                        ("canonical_handle", Field::from(canonical_str)),
+                    ])?;
+                } else if canonical == handle {
+                    println!("{stored} is canonical");
+                } else {
+                    println!("{stored} is not canonical; canonical handle {canonical_str}");
+                }
+                if canonical != handle {
+                    return Err(crate::cli::error::corrupt(
+                        "archive is not in canonical form",
+                    ));
+                }
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
+        Command::Annotate {
+            pile,
+            handle,
+            set,
+            branch,
+            signing,
+        } => super::annotate::annotate(pile, handle, set, branch, signing)?,
+        Command::Annotations {
+            pile,
+            handle,
+            branch,
+        } => super::annotate::annotations(pile, handle, branch)?,
     }
     Ok(())
 }
+
+/// Store every regular file in `archive` as its own blob, optionally with a
+/// manifest of member paths in the shape of a snapshot's file entities.
+fn put_archive(
+    pile: &std::path::Path,
+    archive: &std::path::Path,
+    format: ArchiveFormat,
+    manifest_blob: bool,
+    fail_if_present: bool,
+    handle_out: Option<&std::path::Path>,
+) -> Result<()> {
+    use std::collections::HashSet;
+
+    use triblespace::prelude::blobschemas::{FileBytes, LongString, SimpleArchive};
+    use triblespace::prelude::BlobStore;
+    use triblespace_core::blob::schemas::UnknownBlob;
+    use triblespace_core::blob::Bytes;
+    use triblespace_core::id::ufoid;
+    use triblespace_core::trible::{Trible, TribleSet};
+    use triblespace_core::value::schemas::hash::{Blake3, Handle};
+    use triblespace_core::value::Value;
+
+    use super::snapshot::{schema, unsigned};
+
+    ClosingPile::open(pile)?.run(|pile| {
+        pile.refresh()?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let mut out = Output::stdout();
+        let mut manifest = TribleSet::new();
+        let mut written: HashSet<[u8; 32]> = HashSet::new();
+        let mut handles = Vec::new();
+        let (mut members, mut existing) = (0usize, 0usize);
+        super::archive::for_each_member(archive, format, |member, data| {
+            let hash = *blake3::hash(&data).as_bytes();
+            let handle: Value<Handle<Blake3, UnknownBlob>> = Value::new(hash);
+            let length = data.len() as u64;
+            if reader.metadata(handle)?.is_none() && written.insert(hash) {
+                verify::put::<FileBytes, _>(pile, Bytes::from_source(data))?;
+                stats::written(length);
+            } else {
+                existing += 1;
+            }
+            members += 1;
+            if manifest_blob {
+                let entity = ufoid();
+                let path = verify::put::<LongString, _>(pile, member.clone())?;
+                manifest.insert(&Trible::new(&entity, &schema::path.id(), &path));
+                manifest.insert(&Trible::new(&entity, &schema::content.id(), &handle));
+                manifest.insert(&Trible::new(&entity, &schema::size.id(), &unsigned(length)));
+            }
+            let string = format!("blake3:{}", hex::encode(hash));
+            out.record(&[
+                ("handle", Field::from(string.as_str())),
+                ("path", Field::from(member)),
+            ])?;
+            handles.push(string);
+            Ok(())
+        })?;
+        if let Some(path) = handle_out {
+            crate::cli::util::write_handles(path, &handles)?;
+        }
+        if manifest_blob {
+            let handle = verify::put::<SimpleArchive, _>(pile, manifest)?;
+            note!("manifest blake3:{}", hex::encode(handle.raw));
+        }
+        if fail_if_present && existing > 0 {
+            return Err(crate::cli::error::conflict(format!(
+                "{existing} of {members} member(s) already in the pile"
+            )));
+        }
+        Ok(())
+    })
+}
+
+/// Download `url` and store it as one blob. The pile is only opened once the
+/// download finished and matched `expected`.
+fn put_url(
+    pile: &std::path::Path,
+    url: &str,
+    expected: Option<&str>,
+    fail_if_present: bool,
+    handle_out: Option<&std::path::Path>,
+) -> Result<()> {
+    use triblespace::prelude::BlobStore;
+    use triblespace_core::blob::schemas::UnknownBlob;
+    use triblespace_core::blob::Blob;
+    use triblespace_core::value::schemas::hash::{Blake3, Handle};
+    use triblespace_core::value::Value;
+
+    let bytes = crate::cli::download::fetch(url, expected)?;
+    let hash = *blake3::hash(&bytes).as_bytes();
+    ClosingPile::open(pile)?.run(|pile| {
+        pile.refresh()?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let handle: Value<Handle<Blake3, UnknownBlob>> = Value::new(hash);
+        let new = reader.metadata(handle)?.is_none();
+        if new {
+            let length = bytes.len() as u64;
+            verify::put::<UnknownBlob, _>(pile, Blob::<UnknownBlob>::new(bytes))?;
+            stats::written(length);
+        }
+        let string = format!("blake3:{}", hex::encode(hash));
+        Output::stdout().record(&[
+            ("handle", Field::from(string.as_str())),
+            ("status", Field::from(if new { "new" } else { "existing" })),
+        ])?;
+        if let Some(path) = handle_out {
+            crate::cli::util::write_handles(path, &[string])?;
+        }
+        if fail_if_present && !new {
+            return Err(crate::cli::error::conflict(format!(
+                "{url} is already in the pile"
+            )));
+        }
+        Ok(())
+    })
+}
+
+/// Decode `handle` as a trible set archive and return the handle of its
+/// canonical encoding, which differs from `handle` when the stored bytes are
+/// not canonical. `None` when the blob is missing or not an archive.
+pub(super) fn canonical_handle(
+    reader: &triblespace_core::repo::pile::PileReader<
+        triblespace_core::value::schemas::hash::Blake3,
+    >,
+    handle: triblespace_core::value::Value<
+        triblespace_core::value::schemas::hash::Handle<
+            triblespace_core::value::schemas::hash::Blake3,
+            triblespace::prelude::blobschemas::SimpleArchive,
+        >,
+    >,
+) -> Option<
+    triblespace_core::value::Value<
+        triblespace_core::value::schemas::hash::Handle<
+            triblespace_core::value::schemas::hash::Blake3,
+            triblespace::prelude::blobschemas::SimpleArchive,
+        >,
+    >,
+> {
+    use triblespace::prelude::blobschemas::SimpleArchive;
+    use triblespace::prelude::BlobStoreGet;
+    use triblespace_core::blob::{Blob, ToBlob};
+    use triblespace_core::trible::TribleSet;
+    use triblespace_core::value::schemas::hash::{Blake3, Hash};
+
+    let set: TribleSet = reader.get::<TribleSet, SimpleArchive>(handle).ok()?;
+    let blob: Blob<SimpleArchive> = set.to_blob();
+    let hash: triblespace_core::value::Value<Hash<Blake3>> = Hash::<Blake3>::digest(&blob.bytes);
+    Some(hash.into())
+}
+
+fn decoded_attribute(trible: &[u8; 64]) -> Result<triblespace_core::id::Id> {
+    triblespace_core::id::Id::new(trible[16..32].try_into()?)
+        .ok_or_else(|| crate::cli::error::corrupt("trible with nil attribute"))
+}
+
+/// Map `path` and compute its blob hash, off the writer thread.
+fn hash_file(path: &std::path::Path) -> Result<(triblespace_core::blob::Bytes, [u8; 32])> {
+    use anyhow::Context;
+
+    let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let bytes = unsafe { triblespace_core::blob::Bytes::map_file(&file)? };
+    let hash = *blake3::hash(&bytes).as_bytes();
+    Ok((bytes, hash))
+}
@@ -1,8 +1,7 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::io::{Read, Seek, SeekFrom};
 use std::path::PathBuf;
 
 // DEFAULT_MAX_PILE_SIZE removed; the new Pile API no longer uses a size const generic
@@ -10,12 +9,10 @@ use std::path::PathBuf;
 use triblespace::prelude::blobschemas::SimpleArchive;
 use triblespace::prelude::BlobStore;
 use triblespace::prelude::BlobStoreGet;
-use triblespace::prelude::BlobStorePut;
 use triblespace::prelude::BranchStore;
 use triblespace::prelude::View;
 use triblespace_core::blob::schemas::longstring::LongString;
 use triblespace_core::blob::ToBlob;
-use triblespace_core::id::id_hex;
 use triblespace_core::id::Id;
 use triblespace_core::repo::pile::Pile;
 use triblespace_core::repo::Repository;
@@ -23,28 +20,29 @@ use triblespace_core::trible::TribleSet;
 use triblespace_core::value::schemas::hash::{Blake3, Handle, Hash};
 use triblespace_core::value::Value;
 
-use super::signing::load_signing_key;
+use super::resolve::{resolve_branch, BranchRef};
+use super::scan::{
+    collapse_branch_states, scan_pile_records, walk_journal, BranchMeta, BranchState, MetaCache,
+    RawBranchRecord, RecordKind,
+};
+use super::signing::{load_signing_key, SigningArgs};
+use super::walk::{walk_commits, DEFAULT_MAX_DEPTH};
+use super::{ClosingPile, ClosingRepo};
+use crate::cli::output::{note, Field, Output, OutputFormat};
+use crate::cli::style::{self, Color};
+use crate::cli::util::skip_present;
+use crate::cli::verify;
 use triblespace_core::repo::BlobStoreMeta;
 
 type BranchNameHandle = Value<Handle<Blake3, LongString>>;
 
-// These markers are part of the stable on-disk pile format (see
-// triblespace-rs/book/src/pile-format.md). Copy them exactly; do not invent.
-#[allow(non_upper_case_globals)]
-const MAGIC_MARKER_BLOB: Id = id_hex!("1E08B022FF2F47B6EBACF1D68EB35D96");
-#[allow(non_upper_case_globals)]
-const MAGIC_MARKER_BRANCH: Id = id_hex!("2BC991A7F5D5D2A3A468C53B0AA03504");
-#[allow(non_upper_case_globals)]
-const MAGIC_MARKER_BRANCH_TOMBSTONE: Id = id_hex!("E888CC787202D2AE4C654BFE9699C430");
-
-const RECORD_LEN: u64 = 64;
-
 #[derive(Parser)]
 pub enum Command {
     /// List branches in a pile file (id + head + name).
+    #[command(visible_alias = "ls")]
     List {
         /// Path to the pile file to inspect
-        path: PathBuf,
+        path: Option<PathBuf>,
         /// Include all branches ever seen (scans raw pile records, including deleted)
         #[arg(long)]
         all: bool,
@@ -53,28 +51,33 @@ pub enum Command {
         deleted: bool,
     },
     /// Create a new branch in a pile file.
+    #[command(allow_missing_positional = true)]
     Create {
         /// Path to the pile file to modify
-        pile: PathBuf,
+        pile: Option<PathBuf>,
         /// Name of the branch to create
         name: String,
-        /// Optional signing key path. The file should contain a 64-char hex seed.
-        #[arg(long)]
-        signing_key: Option<PathBuf>,
+        #[command(flatten)]
+        signing: SigningArgs,
     },
     /// Inspect a branch in a pile and print its id, name, and current head handle.
+    #[command(allow_missing_positional = true)]
     Inspect {
         /// Path to the pile file to inspect
-        pile: PathBuf,
-        /// Branch identifier to inspect (hex encoded)
+        pile: Option<PathBuf>,
+        /// Branch to inspect (hex id, unique id prefix or name)
         branch: String,
     },
     /// Delete a branch in a pile (writes a tombstone).
+    #[command(visible_alias = "rm", allow_missing_positional = true)]
     Delete {
         /// Path to the pile file to modify
-        pile: PathBuf,
-        /// Branch identifier to delete (hex encoded)
+        pile: Option<PathBuf>,
+        /// Branch to delete (hex id, unique id prefix or name)
         branch: String,
+        /// Delete without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
     },
     /// Set the branch metadata handle for a branch in a pile (CAS update).
     ///
@@ -96,15 +99,124 @@ pub enum Command {
     ///
     /// This scans the pile file for branch update and tombstone records and
     /// prints the most recent entries for a branch (latest first).
+    #[command(allow_missing_positional = true)]
     Reflog {
         /// Path to the pile file to inspect
-        pile: PathBuf,
+        pile: Option<PathBuf>,
         /// Branch identifier to inspect (hex encoded)
         branch: String,
         /// Maximum results to print
         #[arg(long, default_value_t = 50)]
         limit: usize,
     },
+    /// Point a branch back at metadata from its reflog.
+    ///
+    /// `--steps N` picks the metadata the branch had N updates ago; `--to`
+    /// picks a metadata handle directly, which must appear in the reflog
+    /// unless `--force` is given. The target's commit chain is verified
+    /// before the branch is compare-and-swapped to it.
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["id", "name"])))]
+    #[command(group(clap::ArgGroup::new("entry").required(true).args(["to", "steps"])))]
+    Restore {
+        /// Path to the pile file to modify
+        pile: Option<PathBuf>,
+        /// Branch to restore (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to restore (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Branch metadata handle to restore (64 hex chars, optionally prefixed with `blake3:`)
+        #[arg(long, value_name = "HANDLE")]
+        to: Option<String>,
+        /// Restore the metadata the branch had N updates ago
+        #[arg(long, value_name = "N")]
+        steps: Option<usize>,
+        /// Allow `--to` handles that never appear in the branch's reflog
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the branch's head each time it changes, until Ctrl-C.
+    ///
+    /// The pile is polled every `--interval` milliseconds. Each change
+    /// prints the branch id with the old and new head commit (`-` when there
+    /// is none) and, with `--exec`, runs CMD through `sh -c` with
+    /// `TRIBLE_BRANCH_ID`, `TRIBLE_OLD_HEAD` and `TRIBLE_NEW_HEAD` set. A
+    /// failing CMD is reported but does not stop the watch.
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["id", "name"])))]
+    Watch {
+        /// Path to the pile file to watch
+        pile: Option<PathBuf>,
+        /// Branch to watch (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to watch (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Shell command to run after each change
+        #[arg(long, value_name = "CMD")]
+        exec: Option<String>,
+        /// Exit after the first change
+        #[arg(long)]
+        once: bool,
+        /// Milliseconds between polls
+        #[arg(long, default_value_t = 500, value_name = "MS")]
+        interval: u64,
+    },
+    /// Drop old branch records from the pile, keeping recent history only.
+    ///
+    /// Keeps the most recent N metadata records of each branch (`--keep`) or
+    /// those written since a time (`--keep-since`), and always the current
+    /// one. The pile is rewritten next to itself and renamed into place;
+    /// metadata blobs only the dropped records point at are dropped with
+    /// them. `branch reflog` and `branch restore` can no longer reach the
+    /// dropped heads, so this asks for confirmation unless `--yes` is given.
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["id", "name", "all"])))]
+    #[command(group(clap::ArgGroup::new("retain").required(true).args(["keep", "keep_since"])))]
+    PruneHistory {
+        /// Path to the pile file to rewrite
+        pile: Option<PathBuf>,
+        /// Branch to prune (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to prune (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Prune every branch in the pile, deleted ones included
+        #[arg(long)]
+        all: bool,
+        /// Number of most recent records to keep per branch
+        #[arg(long, value_name = "N")]
+        keep: Option<usize>,
+        /// Keep records written at or after this time (RFC 3339, YYYY-MM-DD or Unix milliseconds)
+        #[arg(long, value_name = "TIME")]
+        keep_since: Option<String>,
+        /// Rewrite without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+        /// Report what would be dropped without rewriting the pile
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Stream every branch set and delete record in the pile, oldest first.
+    ///
+    /// Branch records carry no clock of their own; each is stamped with the
+    /// newest blob written before it. Use `--output json` for one object per
+    /// record.
+    #[command(allow_missing_positional = true)]
+    Journal {
+        /// Path to the pile file to inspect
+        pile: Option<PathBuf>,
+        /// Only show records for this branch (hex encoded)
+        #[arg(long)]
+        branch: Option<String>,
+        /// Only show records at or after this time (RFC 3339, YYYY-MM-DD or Unix milliseconds)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only show records before this time (RFC 3339, YYYY-MM-DD or Unix milliseconds)
+        #[arg(long)]
+        until: Option<String>,
+    },
     /// Export a branch from one pile into another, copying reachable blobs.
     ///
     /// This transfers all blobs reachable from the source branch metadata into
@@ -121,11 +233,109 @@ pub enum Command {
         #[arg(long)]
         to_pile: PathBuf,
     },
+    /// Export a branch's content as N-Quads or N-Triples.
+    ///
+    /// Entities and attributes become `urn:trible:id:<HEX>` IRIs and values
+    /// `xsd:hexBinary` literals. Lines are sorted, so exporting the same
+    /// content always gives the same output.
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["id", "name"])))]
+    ExportRdf {
+        /// Path to the pile file to read
+        pile: Option<PathBuf>,
+        /// Branch to export (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to export (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Output grammar
+        #[arg(long, value_enum, default_value_t = super::export::RdfFormat::Nquads)]
+        format: super::export::RdfFormat,
+        /// Write to FILE instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Emit values that are handles of UTF-8 blobs in the pile, such as
+        /// long strings, as string literals
+        #[arg(long)]
+        resolve_strings: bool,
+    },
+    /// Export a branch's content as one JSON object per entity.
+    ///
+    /// Keys are attribute names (see `trible attr list`) or ids,
+    /// each holding an array of values; long strings are resolved and other
+    /// values written as `{"schema", "hex"}` objects. Entities are sorted by
+    /// id.
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["id", "name"])))]
+    ExportJson {
+        /// Path to the pile file to read
+        pile: Option<PathBuf>,
+        /// Branch to export (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to export (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Write to FILE instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Indent each object over several lines
+        #[arg(long)]
+        pretty: bool,
+    },
+    /// Report how each attribute is used in a branch's content.
+    ///
+    /// For every attribute: the number of tribles, the number of distinct
+    /// entities, and its name from `trible attr list`, if it has one.
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["id", "name"])))]
+    Attrs {
+        /// Path to the pile file to inspect
+        pile: Option<PathBuf>,
+        /// Branch to inspect (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to inspect (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Row order
+        #[arg(long, value_enum, default_value_t = super::attrs::AttrSort::Count)]
+        sort: super::attrs::AttrSort,
+    },
+    /// Render the commit history of branches as a Graphviz DOT graph.
+    ///
+    /// Each commit reachable from the selected heads appears once, labelled
+    /// with its short handle, timestamp and message, with edges to its
+    /// parents; head commits are drawn as boxes. Render with `dot -Tsvg`.
+    #[command(group(
+        clap::ArgGroup::new("target")
+            .required(true)
+            .multiple(true)
+            .args(["id", "name", "all"])
+    ))]
+    Graph {
+        /// Path to the pile file to read
+        pile: PathBuf,
+        /// Branch to include (hex id, repeatable)
+        #[arg(long)]
+        id: Vec<String>,
+        /// Branch to include (unique name, repeatable)
+        #[arg(long)]
+        name: Vec<String>,
+        /// Include every branch in the pile
+        #[arg(long, conflicts_with_all = ["id", "name"])]
+        all: bool,
+        /// Leave out commits more than N parent steps below every head
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+        /// Write to FILE instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
     /// Show statistics for a branch.
+    #[command(allow_missing_positional = true)]
     Stats {
         /// Path to the pile file to inspect
-        pile: PathBuf,
-        /// Branch identifier to inspect (hex encoded)
+        pile: Option<PathBuf>,
+        /// Branch to inspect (hex id, unique id prefix or name)
         branch: String,
         /// Also compute unique triples/entities/attributes by materializing commit content.
         #[arg(long, default_value_t = false)]
@@ -133,6 +343,7 @@ pub enum Command {
     },
     /// Import reachable blobs from a source branch into a target pile and
     /// attach them to the target branch via a single merge commit.
+    #[command(visible_alias = "mi")]
     MergeImport {
         /// Path to the source pile file
         #[arg(long)]
@@ -147,16 +358,23 @@ pub enum Command {
         /// Destination branch identifier (hex)
         #[arg(long)]
         to_id: String,
-        /// Optional signing key path. The file should contain a 64-char hex seed.
-        #[arg(long)]
-        signing_key: Option<PathBuf>,
+        #[command(flatten)]
+        signing: SigningArgs,
+        /// Refuse the import unless every new commit is validly signed by
+        /// this public key (64 hex chars); repeat to allow several keys
+        #[arg(long, value_name = "PUBKEY_HEX")]
+        require_signed_by: Vec<String>,
+        /// Message recorded on the merge commit
+        #[arg(short, long)]
+        message: Option<String>,
     },
     /// Consolidate multiple branches into a single new branch.
     Consolidate {
         /// Path to the pile file to modify
         pile: PathBuf,
         /// Branch identifier(s) to consolidate (hex encoded).
-        /// Ignored when --include-deleted is set.
+        /// Ignored when --include-deleted is set; a branch name with
+        /// --include-orphans.
         #[arg(num_args = 0..)]
         branches: Vec<String>,
         /// Optional name for the newly created consolidated branch
@@ -176,15 +394,38 @@ pub enum Command {
         /// by scanning the raw pile file.
         #[arg(long, conflicts_with = "by_name")]
         by_name_include_deleted: bool,
-        /// Optional signing key path. The file should contain a 64-char hex seed.
-        #[arg(long)]
-        signing_key: Option<PathBuf>,
+        /// Treat the single BRANCHES argument as a branch name and merge the
+        /// heads of every `NAME--orphan-*` branch (left by `migrate
+        /// branch-names`) into the NAME branch itself instead of a new one.
+        #[arg(
+            long,
+            conflicts_with_all = ["by_name", "by_name_include_deleted", "out_name", "delete_sources"]
+        )]
+        include_orphans: bool,
+        /// With --include-orphans, delete (tombstone) the orphan branches
+        /// once their heads are part of the NAME branch
+        #[arg(long, requires = "include_orphans")]
+        retire: bool,
+        /// Message recorded on the merge commit(s)
+        #[arg(short, long)]
+        message: Option<String>,
+        #[command(flatten)]
+        signing: SigningArgs,
     },
     /// Walk the commit history of a branch (newest first).
+    ///
+    /// With -v every commit shows its full signer key and whether its
+    /// signature verifies.
+    ///
+    /// Commit times come from the commit's embedded timestamp, or else from
+    /// when its blob was written to the pile (marked "stored"). Commits with
+    /// neither are flagged, listed last and left out of `--since`/`--until`
+    /// windows.
+    #[command(allow_missing_positional = true)]
     Log {
         /// Path to the pile file to inspect
-        pile: PathBuf,
-        /// Branch identifier (hex encoded)
+        pile: Option<PathBuf>,
+        /// Branch to walk (hex id, unique id prefix or name)
         branch: String,
         /// Maximum commits to print
         #[arg(long, default_value_t = 50)]
@@ -192,21 +433,36 @@ pub enum Command {
         /// Compact one-line-per-commit format
         #[arg(long)]
         oneline: bool,
+        /// Fail unless every signed commit walked was signed by this public
+        /// key (64 hex chars); unsigned merge commits are allowed
+        #[arg(long, value_name = "PUBKEY")]
+        require_signer: Option<String>,
+        /// Only show commits at or after this time (RFC 3339, YYYY-MM-DD or Unix milliseconds)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only show commits before this time (RFC 3339, YYYY-MM-DD or Unix milliseconds)
+        #[arg(long)]
+        until: Option<String>,
+        /// Only show commits signed by this public key (64 hex chars)
+        #[arg(long, value_name = "PUBKEY")]
+        author: Option<String>,
     },
     /// Census attribute IDs across all commits in a branch.
+    #[command(allow_missing_positional = true)]
     Describe {
         /// Path to the pile file to inspect
-        pile: PathBuf,
-        /// Branch identifier (hex encoded)
+        pile: Option<PathBuf>,
+        /// Branch to describe (hex id, unique id prefix or name)
         branch: String,
         /// Also show per-entity breakdown
         #[arg(long)]
         entities: bool,
     },
     /// Display a single commit's structure.
+    #[command(allow_missing_positional = true)]
     Show {
         /// Path to the pile file to inspect
-        pile: PathBuf,
+        pile: Option<PathBuf>,
         /// Commit handle (blake3:... or raw 64-char hex)
         commit: String,
     },
@@ -215,11 +471,31 @@ pub enum Command {
     Rename {
         /// Path to the pile file to modify
         pile: PathBuf,
-        /// Branch to rename (name or hex id)
+        /// Branch to rename (hex id, unique id prefix or name)
         branch: String,
         /// New name for the branch
         new_name: String,
-        /// Optional signing key path. The file should contain a 64-char hex seed.
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+    /// Re-sign branch metadata with a new key, keeping name and head.
+    ///
+    /// The previous metadata blobs stay in the pile for auditing.
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["id", "name", "all"])))]
+    Resign {
+        /// Path to the pile file to modify
+        pile: PathBuf,
+        /// Branch to re-sign (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to re-sign (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Re-sign every branch in the pile
+        #[arg(long)]
+        all: bool,
+        /// New signing key file or keyring name (defaults to
+        /// TRIBLES_SIGNING_KEY, TRIBLES_SIGNING_KEY_HEX, then the config)
         #[arg(long)]
         signing_key: Option<PathBuf>,
     },
@@ -228,82 +504,90 @@ pub enum Command {
 pub fn run(cmd: Command) -> Result<()> {
     match cmd {
         Command::List { path, all, deleted } => {
-            use triblespace_core::repo::pile::Pile;
+            let path = super::resolve_pile(path)?;
 
             if all || deleted {
                 // Raw pile scan mode (absorbs former `journal` command).
-                let mut pile: Pile<Blake3> = Pile::open(&path)?;
-                let res = (|| -> Result<(), anyhow::Error> {
-                    pile.refresh()?;
+                ClosingPile::open(&path)?.run(|pile| {
+                    super::refresh_for_read(pile)?;
                     let reader = pile
                         .reader()
                         .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
 
                     let records = scan_pile_records(&path)?;
                     let states = collapse_branch_states(&records);
+                    let mut metas = MetaCache::new(&reader);
 
                     let mut rows: Vec<(Id, &BranchState)> = states.iter().map(|(id, s)| (*id, s)).collect();
                     rows.sort_by_key(|(id, _)| *id);
 
+                    let mut out = Output::stdout();
                     for (id, state) in rows {
                         if deleted && state.kind != RecordKind::Tombstone {
                             continue;
                         }
 
-                        let meta_handle = match state.kind {
-                            RecordKind::Set => state.meta,
-                            RecordKind::Tombstone => state.last_set,
-                        };
-
                         let kind = match state.kind {
                             RecordKind::Set => "set",
                             RecordKind::Tombstone => "delete",
                         };
 
-                        let mut name = "-".to_string();
-                        let mut head_str = "-".to_string();
+                        let mut name = Field::missing("-");
+                        let mut head_str = Field::missing("-");
 
-                        if let Some(mh) = meta_handle {
-                            if reader.metadata(mh)?.is_some() {
-                                if let Ok(meta_set) = reader.get::<TribleSet, _>(mh) {
-                                    if let Ok(Some(n)) = load_branch_name(&reader, &meta_set) {
-                                        name = n;
-                                    }
-                                    if let Some(h) = extract_repo_head(&meta_set) {
-                                        head_str = format!("blake3:{}", hex::encode(h.raw));
-                                    }
+                        if let Some(mh) = state.meta_handle() {
+                            if let BranchMeta::Decoded {
+                                name: n,
+                                head,
+                                remote_state,
+                            } = metas.get(mh)?
+                            {
+                                if *remote_state {
+                                    continue;
+                                }
+                                if let Some(n) = n {
+                                    name = Field::from(n.clone());
+                                }
+                                if let Some(h) = head {
+                                    head_str =
+                                        Field::from(format!("blake3:{}", hex::encode(h.raw)));
                                 }
                             }
                         }
 
-                        println!("{id:X}\t{kind}\t{head_str}\t{name}");
+                        out.record(&[
+                            ("id", Field::from(format!("{id:X}"))),
+                            ("kind", Field::from(kind)),
+                            ("head", head_str),
+                            ("name", name),
+                        ])?;
                     }
                     Ok(())
-                })();
-                let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-                res.and(close_res)?;
+                })?;
             } else {
                 // Default mode: list active branches via pile.branches().
-                let mut pile: Pile<Blake3> = Pile::open(&path)?;
-                let res = (|| -> Result<(), anyhow::Error> {
-                    pile.refresh()?;
+                ClosingPile::open(&path)?.run(|pile| {
+                    super::refresh_for_read(pile)?;
                     let reader = pile
                         .reader()
                         .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
                     let iter = pile.branches()?;
                     let head_attr = triblespace_core::repo::head.id();
-                    let mut rows: Vec<(String, Id, String)> = Vec::new();
+                    let mut rows: Vec<(Field, Id, Field)> = Vec::new();
                     for branch in iter {
                         let id = branch?;
                         let meta_handle = match pile.head(id)? {
                             Some(handle) => handle,
                             None => {
-                                rows.push(("<deleted>".to_string(), id, "-".to_string()));
+                                rows.push((Field::missing("<deleted>"), id, Field::missing("-")));
                                 continue;
                             }
                         };
 
                         let (name, head) = match reader.get::<TribleSet, _>(meta_handle) {
+                            Ok(meta) if crate::cli::remote_state::is_remote_state(&meta) => {
+                                continue;
+                            }
                             Ok(meta) => {
                                 let name_attr = triblespace_core::metadata::name.id();
                                 let mut name_handle: Option<BranchNameHandle> = None;
@@ -325,29 +609,31 @@ pub fn run(cmd: Command) -> Result<()> {
                                 }
 
                                 let name = match name_handle {
-                                    None => "<unnamed>".to_string(),
+                                    None => Field::missing("<unnamed>"),
                                     Some(handle) => match reader.get::<View<str>, _>(handle) {
-                                        Ok(view) => view.as_ref().to_string(),
-                                        Err(_) => format!(
+                                        Ok(view) => Field::from(view.as_ref()),
+                                        Err(_) => Field::missing(format!(
                                             "<name blob missing ({})>",
                                             hex::encode_upper(&handle.raw[..4])
-                                        ),
+                                        )),
                                     },
                                 };
 
                                 let head = match head_handle {
-                                    None => "-".to_string(),
-                                    Some(handle) => format!("blake3:{}", hex::encode(handle.raw)),
+                                    None => Field::missing("-"),
+                                    Some(handle) => {
+                                        Field::from(format!("blake3:{}", hex::encode(handle.raw)))
+                                    }
                                 };
 
                                 (name, head)
                             }
                             Err(_) => (
-                                format!(
+                                Field::missing(format!(
                                     "<metadata blob missing ({})>",
                                     hex::encode_upper(&meta_handle.raw[..4])
-                                ),
-                                "-".to_string(),
+                                )),
+                                Field::missing("-"),
                             ),
                         };
 
@@ -355,70 +641,77 @@ pub fn run(cmd: Command) -> Result<()> {
                     }
 
                     rows.sort_by(|(a_name, a_id, _), (b_name, b_id, _)| {
-                        a_name.cmp(b_name).then_with(|| a_id.cmp(b_id))
+                        a_name
+                            .plain()
+                            .cmp(&b_name.plain())
+                            .then_with(|| a_id.cmp(b_id))
                     });
 
+                    let mut out = Output::stdout();
                     for (name, id, head) in rows {
-                        println!("{id:X}\t{head}\t{name}");
+                        out.record(&[
+                            ("id", Field::from(format!("{id:X}"))),
+                            ("head", head),
+                            ("name", name),
+                        ])?;
                     }
                     Ok(())
-                })();
-                let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-                res.and(close_res)?;
+                })?;
             }
         }
         Command::Create {
             pile,
             name,
-            signing_key,
+            signing,
         } => {
-            use triblespace_core::repo::pile::Pile;
-            use triblespace_core::repo::Repository;
-            use triblespace_core::value::schemas::hash::Blake3;
-            let pile: Pile<Blake3> = Pile::open(&pile)?;
-            let key = load_signing_key(&signing_key)?;
-            let mut repo = Repository::new(pile, key, TribleSet::new())?;
-
-            let res = (|| -> Result<(), anyhow::Error> {
-                let branch_id = repo
-                    .create_branch(&name, None)
-                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+            let pile = super::resolve_pile(pile)?;
+            let key = signing.load()?;
+            ClosingPile::open(&pile)?.run(|pile| {
+                pile.refresh()?;
+                let branch_id = triblespace::prelude::ufoid();
+                let name_handle: BranchNameHandle =
+                    verify::put(pile, name.to_blob()).context("put name blob")?;
+                let meta = provenance::new_branch_metadata(&key, &branch_id, name_handle);
+                let meta = verify::put(pile, meta).context("put branch meta")?;
+                match pile.update(*branch_id, None, Some(meta))? {
+                    triblespace_core::repo::PushResult::Success() => {}
+                    triblespace_core::repo::PushResult::Conflict(_) => {
+                        return Err(crate::cli::error::conflict(format!(
+                            "branch id {:X} is already taken",
+                            *branch_id
+                        )));
+                    }
+                }
                 println!("{:#X}", *branch_id);
                 Ok(())
-            })();
-
-            // Ensure the underlying pile is closed whether the command succeeds or fails.
-            let close_res = repo
-                .into_storage()
-                .close()
-                .map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
         Command::Inspect { pile, branch } => {
             use triblespace::prelude::blobschemas::SimpleArchive;
             use triblespace::prelude::valueschemas::Handle;
 
-            use triblespace_core::repo::pile::Pile;
             use triblespace_core::trible::TribleSet;
             use triblespace_core::value::schemas::hash::Blake3;
             use triblespace_core::value::schemas::hash::Hash;
             use triblespace_core::value::Value;
 
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
-                let branch_id = parse_branch_id_hex(&branch)?;
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile| {
+                super::refresh_for_read(pile)?;
+                let branch_id = find_branch(pile, &branch)?;
 
                 let meta_handle = pile
                     .head(branch_id)?
-                    .ok_or_else(|| anyhow::anyhow!("branch not found"))?;
+                    .ok_or_else(|| crate::cli::error::not_found("branch not found"))?;
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
                 let meta_present = reader.metadata(meta_handle)?.is_some();
-                let (name_val, head_val, head_err): (
+                let (name_val, head_val, head_err, creator): (
                     Option<String>,
                     Option<Value<Handle<Blake3, SimpleArchive>>>,
                     Option<String>,
+                    Option<[u8; 32]>,
                 ) = if meta_present {
                     match reader.get::<TribleSet, SimpleArchive>(meta_handle) {
                         Ok(meta) => {
@@ -431,55 +724,86 @@ pub fn run(cmd: Command) -> Result<()> {
                                 }
                             }
                             let name_val = load_branch_name(&reader, &meta)?;
-                            (name_val, head_val, None)
+                            (name_val, head_val, None, provenance::creator(&meta))
                         }
-                        Err(e) => (None, None, Some(format!("decode failed: {e:?}"))),
+                        Err(e) => (None, None, Some(format!("decode failed: {e:?}")), None),
                     }
                 } else {
-                    (None, None, None)
+                    (None, None, None, None)
                 };
+                let creator = creator.as_ref().map(provenance::format);
 
                 let id_hex = format!("{branch_id:X}");
                 let meta_hash: Value<Hash<Blake3>> = Handle::to_hash(meta_handle);
                 let meta_hex: String = meta_hash.from_value();
+                let meta_state = if meta_present { "present" } else { "missing" };
+                let head = match head_val {
+                    Some(h) => {
+                        let head_hash: Value<Hash<Blake3>> = Handle::to_hash(h);
+                        let head_hex: String = head_hash.from_value();
+                        let present = reader.metadata(h)?.is_some();
+                        Some((head_hex, if present { "present" } else { "missing" }))
+                    }
+                    None => None,
+                };
+
+                let mut out = Output::stdout();
+                if out.format() != OutputFormat::Plain {
+                    let (head_hex, head_state) = match head {
+                        Some((hex, state)) => (Field::from(hex), Field::from(state)),
+                        None => (Field::missing("-"), Field::missing("-")),
+                    };
+                    out.record(&[
+                        ("id", Field::from(id_hex)),
+                        ("name", name_val.map_or(Field::missing("-"), Field::from)),
+                        ("meta", Field::from(meta_hex)),
+                        ("meta_state", Field::from(meta_state)),
+                        (
+                            "meta_error",
+                            head_err.map_or(Field::missing("-"), Field::from),
+                        ),
+                        ("head", head_hex),
+                        ("head_state", head_state),
+                        (
+                            "creator",
+                            creator.map_or(Field::missing("unknown"), Field::from),
+                        ),
+                    ])?;
+                    return Ok(());
+                }
 
                 println!("Id:        {id_hex}");
                 if let Some(nstr) = name_val.clone() {
                     println!("Name:      {nstr}");
                 }
                 println!(
-                    "Meta:      {meta_hex} [{}]{}",
-                    if meta_present { "present" } else { "missing" },
+                    "Meta:      {meta_hex} {}{}",
+                    style::presence(meta_present),
                     head_err
                         .as_deref()
                         .map(|e| format!(" ({e})"))
                         .unwrap_or_default()
                 );
-                if let Some(h) = head_val {
-                    let head_hash: Value<Hash<Blake3>> = Handle::to_hash(h);
-                    let head_hex: String = head_hash.from_value();
-                    let present = reader.metadata(h)?.is_some();
+                if let Some((head_hex, head_state)) = head {
                     println!(
-                        "Head:      {head_hex} [{}]",
-                        if present { "present" } else { "missing" }
+                        "Head:      {head_hex} {}",
+                        style::presence(head_state == "present")
                     );
                 }
+                println!("Creator:   {}", creator.as_deref().unwrap_or("unknown"));
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
-        Command::Delete { pile, branch } => {
-            use triblespace_core::repo::pile::Pile;
-            use triblespace_core::value::schemas::hash::Blake3;
-
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
-                let branch_id = parse_branch_id_hex(&branch)?;
+        Command::Delete { pile, branch, yes } => {
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile| {
+                pile.refresh()?;
+                let branch_id = find_branch(pile, &branch)?;
 
                 let old = pile
                     .head(branch_id)?
-                    .ok_or_else(|| anyhow::anyhow!("branch not found"))?;
+                    .ok_or_else(|| crate::cli::error::not_found("branch not found"))?;
+                crate::cli::util::confirm(&format!("This will delete branch {branch_id:X}"), yes)?;
 
                 match pile.update(branch_id, Some(old), None)? {
                     triblespace_core::repo::PushResult::Success() => {
@@ -490,9 +814,7 @@ pub fn run(cmd: Command) -> Result<()> {
                         anyhow::bail!("branch {branch_id:X} advanced concurrently; rerun delete")
                     }
                 }
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
         Command::Set {
             pile,
@@ -502,12 +824,10 @@ pub fn run(cmd: Command) -> Result<()> {
         } => {
             use triblespace::prelude::blobschemas::SimpleArchive;
             use triblespace::prelude::valueschemas::Handle;
-            use triblespace_core::repo::pile::Pile;
             use triblespace_core::value::schemas::hash::Blake3;
             use triblespace_core::value::Value;
 
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
+            ClosingPile::open(&pile)?.run(|pile| {
                 let branch_id = parse_branch_id_hex(&branch)?;
                 let new_meta: Value<Handle<Blake3, SimpleArchive>> = parse_blake3_handle(&meta)?;
 
@@ -532,27 +852,24 @@ pub fn run(cmd: Command) -> Result<()> {
                         anyhow::bail!("branch head changed concurrently; current={got}")
                     }
                 }
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
         Command::Reflog {
             pile,
             branch,
             limit,
         } => {
-            use triblespace_core::repo::pile::Pile;
-
             let branch_id = parse_branch_id_hex(&branch)?;
 
-            let mut pile_reader: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
-                pile_reader.refresh()?;
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile_reader| {
+                super::refresh_for_read(pile_reader)?;
                 let reader = pile_reader
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
 
                 let all_records = scan_pile_records(&pile)?;
+                let mut metas = MetaCache::new(&reader);
 
                 // Filter to this branch, keep last `limit` entries.
                 let branch_records: Vec<&RawBranchRecord> = all_records
@@ -581,19 +898,21 @@ pub fn run(cmd: Command) -> Result<()> {
                     let meta_state;
 
                     if let Some(mh) = rec.meta_handle {
-                        let present = reader.metadata(mh)?.is_some();
-                        meta_state = if present { "present" } else { "missing" };
-                        if present {
-                            if let Ok(meta_set) = reader.get::<TribleSet, _>(mh) {
-                                name = load_branch_name(&reader, &meta_set).ok().flatten();
-                                if let Some(h) = extract_repo_head(&meta_set) {
-                                    head_str = format!("blake3:{}", hex::encode(h.raw));
-                                    head_state = if reader.metadata(h)?.is_some() {
-                                        "present"
-                                    } else {
-                                        "missing"
-                                    };
-                                }
+                        let decoded = metas.get(mh)?;
+                        meta_state = if *decoded == BranchMeta::Missing {
+                            "missing"
+                        } else {
+                            "present"
+                        };
+                        if let BranchMeta::Decoded { name: n, head, .. } = decoded {
+                            name = n.clone();
+                            if let Some(h) = *head {
+                                head_str = format!("blake3:{}", hex::encode(h.raw));
+                                head_state = if reader.metadata(h)?.is_some() {
+                                    "present"
+                                } else {
+                                    "missing"
+                                };
                             }
                         }
                     } else {
@@ -606,134 +925,409 @@ pub fn run(cmd: Command) -> Result<()> {
                     );
                 }
                 Ok(())
-            })();
+            })?;
+        }
+        Command::Restore {
+            pile,
+            id,
+            name,
+            to,
+            steps,
+            force,
+        } => {
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile_handle| {
+                pile_handle.refresh()?;
+                // A deleted branch no longer resolves, but its reflog is
+                // still there to restore from.
+                let branch_id = match select_branch(pile_handle, id.as_deref(), name.as_deref()) {
+                    Ok(branch_id) => branch_id,
+                    Err(err) => match id.as_deref().map(parse_branch_id_hex) {
+                        Some(Ok(branch_id)) => branch_id,
+                        _ => return Err(err),
+                    },
+                };
+                let current = pile_handle.head(branch_id)?;
+
+                // Distinct metadata the branch pointed at, newest first.
+                let mut history: Vec<Value<Handle<Blake3, SimpleArchive>>> = Vec::new();
+                for rec in scan_pile_records(&pile)?
+                    .iter()
+                    .rev()
+                    .filter(|r| r.branch_id == branch_id)
+                {
+                    if let Some(meta) = rec.meta_handle {
+                        if history.last() != Some(&meta) {
+                            history.push(meta);
+                        }
+                    }
+                }
+                if history.first() != current.as_ref() {
+                    if let Some(current) = current {
+                        history.insert(0, current);
+                    }
+                }
+
+                let target = match (to.as_deref(), steps) {
+                    (Some(raw), _) => {
+                        let target: Value<Handle<Blake3, SimpleArchive>> =
+                            parse_blake3_handle(raw)?;
+                        if !force && !history.contains(&target) {
+                            return Err(crate::cli::error::invalid_argument(format!(
+                                "blake3:{} is not in the reflog of branch {branch_id:X}; pass --force to restore it anyway",
+                                hex::encode(target.raw)
+                            )));
+                        }
+                        target
+                    }
+                    (None, Some(steps)) => {
+                        // Without a current head, one step back is the last
+                        // metadata the branch had.
+                        let index = if current.is_some() { steps } else { steps.max(1) - 1 };
+                        *history.get(index).ok_or_else(|| {
+                            crate::cli::error::not_found(format!(
+                                "branch {branch_id:X} has only {} reflog entries",
+                                history.len()
+                            ))
+                        })?
+                    }
+                    (None, None) => unreachable!("clap requires --to or --steps"),
+                };
+                if current == Some(target) {
+                    println!("branch {branch_id:X} already at blake3:{}", hex::encode(target.raw));
+                    return Ok(());
+                }
+
+                let reader = pile_handle
+                    .reader()
+                    .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                let head_of = |meta: Value<Handle<Blake3, SimpleArchive>>| -> Result<Option<_>> {
+                    let meta: TribleSet = reader.get(meta).map_err(|e| {
+                        crate::cli::error::corrupt(format!(
+                            "read branch metadata blake3:{}: {e:?}",
+                            hex::encode(meta.raw)
+                        ))
+                    })?;
+                    Ok(extract_repo_head(&meta))
+                };
+                let old_head = match current {
+                    Some(meta) => head_of(meta).ok().flatten(),
+                    None => None,
+                };
+                let new_head = head_of(target)?;
+                if let Some(head) = new_head {
+                    let mut chains = super::diagnose::ChainVerifier::new(&reader, DEFAULT_MAX_DEPTH);
+                    if let (_, Some(problem)) = chains.verify_chain(head) {
+                        return Err(crate::cli::error::corrupt(format!(
+                            "commit chain of blake3:{} is broken: {problem}",
+                            hex::encode(head.raw)
+                        )));
+                    }
+                }
+
+                match pile_handle.update(branch_id, current, Some(target))? {
+                    triblespace_core::repo::PushResult::Success() => {
+                        let show = |h: Option<Value<Handle<Blake3, SimpleArchive>>>| {
+                            h.map(|h| format!("blake3:{}", hex::encode(h.raw)))
+                                .unwrap_or_else(|| "-".to_string())
+                        };
+                        println!("restored branch {branch_id:X}");
+                        println!("old head: {}", show(old_head));
+                        println!("new head: {}", show(new_head));
+                        Ok(())
+                    }
+                    triblespace_core::repo::PushResult::Conflict(_) => Err(crate::cli::error::conflict(
+                        "branch head changed concurrently; retry the restore",
+                    )),
+                }
+            })?;
+        }
+        Command::PruneHistory {
+            pile,
+            id,
+            name,
+            all,
+            keep,
+            keep_since,
+            yes,
+            dry_run,
+        } => {
+            let pile = super::resolve_pile(pile)?;
+            super::prune::run(pile, id, name, all, keep, keep_since, yes, dry_run)?
+        }
+        Command::Watch {
+            pile,
+            id,
+            name,
+            exec,
+            once,
+            interval,
+        } => {
+            use std::sync::mpsc::{self, RecvTimeoutError};
+            use std::time::Duration;
 
-            let close_res = pile_reader
-                .close()
-                .map_err(|e| anyhow::anyhow!("close pile: {e:?}"));
-            res.and(close_res)?;
+            let pile = super::resolve_pile(pile)?;
+            let head_field = |h: &str| {
+                if h == "-" {
+                    Field::missing("-")
+                } else {
+                    Field::from(h)
+                }
+            };
+            let (stop, stopped) = mpsc::channel();
+            ctrlc::set_handler(move || {
+                let _ = stop.send(());
+            })
+            .map_err(|e| anyhow::anyhow!("cannot install the SIGINT handler: {e}"))?;
+
+            ClosingPile::open(&pile)?.run(|pile_handle| {
+                pile_handle.refresh()?;
+                let branch_id = select_branch(pile_handle, id.as_deref(), name.as_deref())?;
+                let mut last = watched_head(pile_handle, branch_id)?;
+                note!(
+                    "watching branch {branch_id:X} at {}; press Ctrl-C to stop",
+                    show_head(last)
+                );
+                let mut out = Output::stdout();
+                loop {
+                    match stopped.recv_timeout(Duration::from_millis(interval)) {
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                    }
+                    pile_handle.refresh()?;
+                    let head = watched_head(pile_handle, branch_id)?;
+                    if head == last {
+                        continue;
+                    }
+                    let (old, new) = (show_head(last), show_head(head));
+                    last = head;
+                    out.record(&[
+                        ("id", Field::from(format!("{branch_id:X}"))),
+                        ("old", head_field(&old)),
+                        ("new", head_field(&new)),
+                    ])?;
+                    if let Some(cmd) = exec.as_deref() {
+                        let status = std::process::Command::new("sh")
+                            .arg("-c")
+                            .arg(cmd)
+                            .env("TRIBLE_BRANCH_ID", format!("{branch_id:X}"))
+                            .env("TRIBLE_OLD_HEAD", &old)
+                            .env("TRIBLE_NEW_HEAD", &new)
+                            .status()
+                            .with_context(|| format!("run --exec command {cmd:?}"))?;
+                        if !status.success() {
+                            eprintln!("warning: --exec command exited with {status}");
+                        }
+                    }
+                    if once {
+                        return Ok(());
+                    }
+                }
+            })?;
+        }
+        Command::Journal {
+            pile,
+            branch,
+            since,
+            until,
+        } => {
+            use crate::cli::util::{format_millis, parse_time};
+
+            let branch_id = branch.as_deref().map(parse_branch_id_hex).transpose()?;
+            let since = since.as_deref().map(parse_time).transpose()?;
+            let until = until.as_deref().map(parse_time).transpose()?;
+            let pile = super::resolve_pile(pile)?;
+            if !pile.exists() {
+                return Err(crate::cli::error::not_found(format!(
+                    "pile not found: {}",
+                    pile.display()
+                )));
+            }
+
+            let handle_field = |h: Option<Value<Handle<Blake3, SimpleArchive>>>| {
+                h.map_or(Field::missing("-"), |h| {
+                    Field::from(format!("blake3:{}", hex::encode(h.raw)))
+                })
+            };
+            let mut out = Output::stdout();
+            walk_journal(&pile, |entry| {
+                if branch_id.is_some_and(|id| id != entry.branch_id) {
+                    return Ok(());
+                }
+                if since.is_some() || until.is_some() {
+                    let Some(at) = entry.timestamp else {
+                        return Ok(());
+                    };
+                    if since.is_some_and(|s| at < s) || until.is_some_and(|u| at >= u) {
+                        return Ok(());
+                    }
+                }
+                let op = match entry.kind {
+                    RecordKind::Set => "set",
+                    RecordKind::Tombstone => "delete",
+                };
+                out.record(&[
+                    ("offset", Field::from(entry.offset)),
+                    ("id", Field::from(format!("{:X}", entry.branch_id))),
+                    ("op", Field::from(op)),
+                    ("old", handle_field(entry.old)),
+                    ("new", handle_field(entry.new)),
+                    (
+                        "timestamp_ms",
+                        entry.timestamp.map_or(Field::missing("-"), Field::from),
+                    ),
+                    (
+                        "time",
+                        entry
+                            .timestamp
+                            .map_or(Field::missing("-"), |t| Field::from(format_millis(t))),
+                    ),
+                ])
+            })?;
+        }
+        Command::ExportRdf {
+            pile,
+            id,
+            name,
+            format,
+            out,
+            resolve_strings,
+        } => {
+            let pile = super::resolve_pile(pile)?;
+            super::export::rdf(pile, id, name, format, out, resolve_strings)?
+        }
+        Command::ExportJson {
+            pile,
+            id,
+            name,
+            out,
+            pretty,
+        } => {
+            let pile = super::resolve_pile(pile)?;
+            super::export::json(pile, id, name, out, pretty)?
         }
+        Command::Attrs {
+            pile,
+            id,
+            name,
+            sort,
+        } => {
+            let pile = super::resolve_pile(pile)?;
+            super::attrs::run(pile, id, name, sort)?
+        }
+        Command::Graph {
+            pile,
+            id,
+            name,
+            all,
+            max_depth,
+            out,
+        } => super::export::graph(pile, id, name, all, max_depth, out)?,
         Command::Export {
             from_pile,
             branch,
             to_pile,
         } => {
             use triblespace_core::repo;
-            use triblespace_core::repo::pile::Pile;
             use triblespace_core::value::schemas::hash::Blake3;
             use triblespace_core::value::schemas::hash::Handle;
             use triblespace_core::value::Value;
 
             let bid = parse_branch_id_hex(&branch)?;
 
-            let mut src: Pile<Blake3> = Pile::open(&from_pile)?;
-            let mut dst: Pile<Blake3> = match Pile::open(&to_pile) {
-                Ok(pile) => pile,
-                Err(err) => {
-                    let _ = src.close();
-                    return Err(err.into());
-                }
-            };
+            let src = ClosingPile::open(&from_pile)?;
+            let dst = ClosingPile::open(&to_pile)?;
 
-            let res = (|| -> Result<(), anyhow::Error> {
-                // Obtain the source branch metadata handle (root) and ensure it exists.
-                let src_meta = src
-                    .head(bid)?
-                    .ok_or_else(|| anyhow::anyhow!("source branch head not found"))?;
+            src.run(|src| {
+                dst.run(|dst| {
+                    // Obtain the source branch metadata handle (root) and ensure it exists.
+                    let src_meta = src
+                        .head(bid)?
+                        .ok_or_else(|| crate::cli::error::not_found("source branch head not found"))?;
 
-                // Prepare a mapping from source handle raw -> destination handle for later lookup.
-                use std::collections::HashMap;
-                use triblespace_core::value::VALUE_LEN;
-                let mut mapping: HashMap<[u8; VALUE_LEN], Value<Handle<Blake3, _>>> =
-                    HashMap::new();
+                    // Prepare a mapping from source handle raw -> destination handle for later lookup.
+                    use std::collections::HashMap;
+                    use triblespace_core::value::VALUE_LEN;
+                    let mut mapping: HashMap<[u8; VALUE_LEN], Value<Handle<Blake3, _>>> =
+                        HashMap::new();
 
-                let src_reader = src
-                    .reader()
-                    .map_err(|e| anyhow::anyhow!("src pile reader error: {e:?}"))?;
-                let handles = repo::reachable(&src_reader, std::iter::once(src_meta.transmute()));
-
-                let mut visited: usize = 0;
-                let mut stored: usize = 0;
-                for r in repo::transfer(&src_reader, &mut dst, handles) {
-                    match r {
-                        Ok((src_h, dst_h)) => {
-                            visited += 1;
-                            stored += 1;
-                            mapping.insert(src_h.raw, dst_h);
-                        }
-                        Err(e) => return Err(anyhow::anyhow!("transfer failed: {e}")),
-                    }
-                }
+                    let src_reader = src
+                        .reader()
+                        .map_err(|e| anyhow::anyhow!("src pile reader error: {e:?}"))?;
+                    dst.refresh()?;
+                    let dst_reader = dst
+                        .reader()
+                        .map_err(|e| anyhow::anyhow!("dst pile reader error: {e:?}"))?;
+                    let skipped = std::cell::Cell::new(0usize);
+                    let handles = skip_present(
+                        repo::reachable(&src_reader, std::iter::once(src_meta.transmute())),
+                        &dst_reader,
+                        &skipped,
+                    );
 
-                // Find the destination handle corresponding to the source branch meta.
-                let dst_meta = mapping
-                    .get(&src_meta.raw)
-                    .ok_or_else(|| {
-                        anyhow::anyhow!("destination meta handle not found after transfer")
-                    })?
-                    .clone();
-
-                // Update the destination pile branch pointer to the copied meta handle.
-                let old = dst.head(bid)?;
-                let res = dst
-                    .update(bid, old, Some(dst_meta.transmute()))
-                    .map_err(|e| anyhow::anyhow!("destination branch update failed: {e:?}"))?;
-                match res {
-                    triblespace_core::repo::PushResult::Success() => {
-                        println!(
-                            "export: copied visited={} stored={} and set branch {:#X}",
-                            visited, stored, bid
-                        );
+                    let mut stored: usize = 0;
+                    for r in repo::transfer(&src_reader, dst, handles) {
+                        match r {
+                            Ok((src_h, dst_h)) => {
+                                stored += 1;
+                                mapping.insert(src_h.raw, dst_h);
+                            }
+                            Err(e) => return Err(anyhow::anyhow!("transfer failed: {e}")),
+                        }
                     }
-                    triblespace_core::repo::PushResult::Conflict(existing) => {
-                        println!("export: copied visited={} stored={} but branch update conflicted: existing={:?}", visited, stored, existing);
+                    let skipped = skipped.get();
+                    let visited = stored + skipped;
+
+                    // Find the destination handle corresponding to the source branch
+                    // meta; a skipped blob is already there under the same handle.
+                    let dst_meta = mapping
+                        .get(&src_meta.raw)
+                        .copied()
+                        .unwrap_or_else(|| src_meta.transmute());
+
+                    // Update the destination pile branch pointer to the copied meta handle.
+                    let old = dst.head(bid)?;
+                    let res = dst
+                        .update(bid, old, Some(dst_meta.transmute()))
+                        .map_err(|e| anyhow::anyhow!("destination branch update failed: {e:?}"))?;
+                    match res {
+                        triblespace_core::repo::PushResult::Success() => {
+                            note!(
+                                "export: copied visited={} stored={} skipped={} and set branch {:#X}",
+                                visited,
+                                stored,
+                                skipped,
+                                bid
+                            );
+                        }
+                        triblespace_core::repo::PushResult::Conflict(existing) => {
+                            note!("export: copied visited={} stored={} skipped={} but branch update conflicted: existing={:?}", visited, stored, skipped, existing);
+                        }
                     }
-                }
-                Ok(())
-            })();
-
-            let close_src = src.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            let close_dst = dst.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-
-            match res {
-                Ok(()) => {
-                    close_src?;
-                    close_dst?;
                     Ok(())
-                }
-                Err(err) => {
-                    if let Err(close_err) = close_src {
-                        eprintln!("warning: failed to close source pile cleanly: {close_err:#}");
-                    }
-                    if let Err(close_err) = close_dst {
-                        eprintln!(
-                            "warning: failed to close destination pile cleanly: {close_err:#}"
-                        );
-                    }
-                    Err(err)
-                }
-            }?;
+                })
+            })?;
         }
         Command::Stats { pile, branch, full } => {
+            use crate::cli::report::{self, FullStats, StatsReport};
             use std::collections::{BTreeSet, HashSet};
             use triblespace::prelude::blobschemas::SimpleArchive;
             use triblespace::prelude::valueschemas::Handle;
 
-            use triblespace_core::repo::pile::Pile;
             use triblespace_core::trible::TribleSet;
             use triblespace_core::value::schemas::hash::Blake3;
             use triblespace_core::value::schemas::hash::Hash;
             use triblespace_core::value::Value;
 
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile| {
                 // Ensure indices are loaded before scanning
-                pile.refresh()?;
+                super::refresh_for_read(pile)?;
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
 
-                let branch_id = parse_branch_id_hex(&branch)?;
+                let branch_id = resolve_branch(pile, &reader, BranchRef::Any(&branch))?.id;
 
                 // Traversal attributes
                 let repo_parent_attr = triblespace_core::repo::parent.id();
@@ -742,7 +1336,7 @@ pub fn run(cmd: Command) -> Result<()> {
                 // Resolve branch head
                 let meta_handle = pile
                     .head(branch_id)?
-                    .ok_or_else(|| anyhow::anyhow!("branch not found"))?;
+                    .ok_or_else(|| crate::cli::error::not_found("branch not found"))?;
 
                 let mut head_opt: Option<Value<Handle<Blake3, SimpleArchive>>> = None;
                 if reader.metadata(meta_handle)?.is_some() {
@@ -826,39 +1420,61 @@ pub fn run(cmd: Command) -> Result<()> {
                     }
                 }
 
-                println!("Branch: {branch_id:X}");
-                println!("Commits: {commit_count}");
-                println!("Content blobs (accum): {content_blob_count}");
-                println!("Content bytes (accum): {content_bytes_total}");
-                println!("Triples (accum): {total_triples_accum}");
-                if content_misaligned_count > 0 {
-                    println!("Warning: {content_misaligned_count} content blob(s) had non-64-byte-aligned length.");
-                }
-                if full {
+                let full = full.then(|| {
                     // Count unique triples, entities, and attributes only when explicitly requested.
-                    let unique_triples = unioned.len();
                     let mut entities: HashSet<Id> = HashSet::new();
                     let mut attributes: HashSet<Id> = HashSet::new();
                     for t in unioned.iter() {
                         entities.insert(*t.e());
                         attributes.insert(*t.a());
                     }
-                    println!("Triples (unique): {unique_triples}");
-                    println!("Entities: {}", entities.len());
-                    println!("Attributes: {}", attributes.len());
+                    FullStats {
+                        unique_triples: unioned.len() as u64,
+                        entities: entities.len() as u64,
+                        attributes: attributes.len() as u64,
+                    }
+                });
+                let report = StatsReport {
+                    branch: format!("{branch_id:X}"),
+                    commits: commit_count as u64,
+                    content_blobs: content_blob_count as u64,
+                    content_bytes: content_bytes_total,
+                    triples: total_triples_accum as u64,
+                    misaligned_content_blobs: content_misaligned_count as u64,
+                    full,
+                };
+                if crate::cli::output::format() == OutputFormat::Json {
+                    return report::emit(&report);
+                }
+
+                println!("Branch: {}", report.branch);
+                println!("Commits: {}", report.commits);
+                println!("Content blobs (accum): {}", report.content_blobs);
+                println!("Content bytes (accum): {}", report.content_bytes);
+                println!("Triples (accum): {}", report.triples);
+                if report.misaligned_content_blobs > 0 {
+                    println!(
+                        "Warning: {} content blob(s) had non-64-byte-aligned length.",
+                        report.misaligned_content_blobs
+                    );
+                }
+                if let Some(full) = &report.full {
+                    println!("Triples (unique): {}", full.unique_triples);
+                    println!("Entities: {}", full.entities);
+                    println!("Attributes: {}", full.attributes);
                 }
 
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
         Command::MergeImport {
             from_pile,
             from_id,
             to_pile,
             to_id,
-            signing_key,
+            signing,
+            require_signed_by,
+            message,
         } => {
             use triblespace::prelude::blobschemas::SimpleArchive;
             use triblespace_core::repo;
@@ -871,91 +1487,92 @@ pub fn run(cmd: Command) -> Result<()> {
             struct CopyStats {
                 visited: usize,
                 stored: usize,
+                skipped: usize,
             }
 
             let src_bid = parse_branch_id_hex(&from_id)?;
             let dst_bid = parse_branch_id_hex(&to_id)?;
-            let key = load_signing_key(&signing_key)?;
-
-            let mut src: Pile<Blake3> = Pile::open(&from_pile)?;
-            let dst_pile: Pile<Blake3> = match Pile::open(&to_pile) {
-                Ok(pile) => pile,
-                Err(err) => {
-                    let _ = src.close();
-                    return Err(err.into());
-                }
-            };
-
-            let mut repo = Repository::new(dst_pile, key, TribleSet::new())?;
-            let result = (|| -> Result<CopyStats, anyhow::Error> {
-                let src_head: Value<Handle<Blake3, SimpleArchive>> = src
-                    .head(src_bid)?
-                    .ok_or_else(|| anyhow::anyhow!("source branch head not found"))?;
+            let allowed = require_signed_by
+                .iter()
+                .map(|k| parse_public_key(k))
+                .collect::<Result<Vec<_>>>()?;
+            let key = signing.load()?;
+
+            let src = ClosingPile::open(&from_pile)?;
+            let dst_pile: Pile<Blake3> = Pile::open(&to_pile)?;
+            let repo = ClosingRepo::new(Repository::new(dst_pile, key.clone(), TribleSet::new())?);
+
+            let stats = src.run(|src| {
+                repo.run(|repo| {
+                    let src_head: Value<Handle<Blake3, SimpleArchive>> =
+                        src.head(src_bid)?.ok_or_else(|| {
+                            crate::cli::error::not_found("source branch head not found")
+                        })?;
+
+                    let src_reader = src
+                        .reader()
+                        .map_err(|e| anyhow::anyhow!("src pile reader error: {e:?}"))?;
 
-                let src_reader = src
-                    .reader()
-                    .map_err(|e| anyhow::anyhow!("src pile reader error: {e:?}"))?;
-
-                let handles = repo::reachable(&src_reader, std::iter::once(src_head.transmute()));
-                let mut visited: usize = 0;
-                let mut stored: usize = 0;
-                for r in repo::transfer(&src_reader, repo.storage_mut(), handles) {
-                    match r {
-                        Ok((_src_h, _dst_h)) => {
-                            visited += 1;
-                            stored += 1;
+                    repo.storage_mut().refresh()?;
+                    let dst_reader = repo
+                        .storage_mut()
+                        .reader()
+                        .map_err(|e| anyhow::anyhow!("dst pile reader error: {e:?}"))?;
+                    let skipped = std::cell::Cell::new(0usize);
+                    let handles = skip_present(
+                        repo::reachable(&src_reader, std::iter::once(src_head.transmute())),
+                        &dst_reader,
+                        &skipped,
+                    );
+                    let mut stored: usize = 0;
+                    for r in repo::transfer(&src_reader, repo.storage_mut(), handles) {
+                        match r {
+                            Ok((_src_h, _dst_h)) => stored += 1,
+                            Err(e) => return Err(anyhow::anyhow!("transfer failed: {e}")),
                         }
-                        Err(e) => return Err(anyhow::anyhow!("transfer failed: {e}")),
                     }
-                }
-
-                let mut ws = repo
-                    .pull(dst_bid)
-                    .map_err(|e| anyhow::anyhow!("failed to open destination branch: {e:?}"))?;
-                ws.merge_commit(src_head)
-                    .map_err(|e| anyhow::anyhow!("merge failed: {e:?}"))?;
-
-                while let Some(mut incoming) = repo
-                    .try_push(&mut ws)
-                    .map_err(|e| anyhow::anyhow!("push failed: {e:?}"))?
-                {
-                    incoming
-                        .merge(&mut ws)
-                        .map_err(|e| anyhow::anyhow!("merge conflict: {e:?}"))?;
-                    ws = incoming;
-                }
+                    let skipped = skipped.get();
+                    let visited = stored + skipped;
 
-                Ok(CopyStats { visited, stored })
-            })();
+                    if !allowed.is_empty() {
+                        let dst_meta = repo.storage_mut().head(dst_bid)?;
+                        let reader = repo
+                            .storage_mut()
+                            .reader()
+                            .map_err(|e| anyhow::anyhow!("dst pile reader error: {e:?}"))?;
+                        let commit_head = |meta| {
+                            reader
+                                .get::<TribleSet, SimpleArchive>(meta)
+                                .ok()
+                                .and_then(|meta| extract_repo_head(&meta))
+                        };
+                        if let Some(incoming) = commit_head(src_head) {
+                            let local = dst_meta.and_then(commit_head);
+                            enforce_signer_policy(&unapproved_commits(
+                                &reader, incoming, local, &allowed,
+                            ))?;
+                        }
+                    }
 
-            let close_src = src.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            let close_dst = repo
-                .into_storage()
-                .close()
-                .map_err(|e| anyhow::anyhow!("{e:?}"));
+                    super::merge::merge_into_branch(
+                        repo.storage_mut(),
+                        &key,
+                        dst_bid,
+                        &[src_head],
+                        message.as_deref(),
+                    )?;
 
-            match result {
-                Ok(stats) => {
-                    close_src?;
-                    close_dst?;
-                    println!(
-                        "merge-import: copied visited={} stored={} and attached source head to destination branch",
-                        stats.visited, stats.stored
-                    );
-                    Ok(())
-                }
-                Err(err) => {
-                    if let Err(close_err) = close_src {
-                        eprintln!("warning: failed to close source pile cleanly: {close_err:#}");
-                    }
-                    if let Err(close_err) = close_dst {
-                        eprintln!(
-                            "warning: failed to close destination pile cleanly: {close_err:#}"
-                        );
-                    }
-                    Err(err)
-                }
-            }?;
+                    Ok(CopyStats {
+                        visited,
+                        stored,
+                        skipped,
+                    })
+                })
+            })?;
+            note!(
+                "merge-import: copied visited={} stored={} skipped={} and attached source head to destination branch",
+                stats.visited, stats.stored, stats.skipped
+            );
         }
         Command::Consolidate {
             pile,
@@ -965,22 +1582,34 @@ pub fn run(cmd: Command) -> Result<()> {
             delete_sources,
             by_name_include_deleted,
             by_name,
-            signing_key,
+            include_orphans,
+            retire,
+            message,
+            signing,
         } => {
             use std::collections::{BTreeMap, HashSet};
 
-            let key = load_signing_key(&signing_key)?;
+            let key = signing.load()?;
 
-            if by_name_include_deleted {
+            if include_orphans {
+                let [name] = branches.as_slice() else {
+                    return Err(crate::cli::error::invalid_argument(
+                        "--include-orphans takes exactly one branch name",
+                    ));
+                };
+                let pile_store: Pile<Blake3> = Pile::open(&pile)?;
+                let repo = Repository::new(pile_store, key.clone(), TribleSet::new())?;
+                ClosingRepo::new(repo)
+                    .run(|repo| consolidate_orphans(repo, name, dry_run, retire))?;
+            } else if by_name_include_deleted {
                 if out_name.is_some() {
                     eprintln!("warning: --out-name is ignored when --by-name-include-deleted is set");
                 }
 
                 let pile_path = pile;
                 let pile_store: Pile<Blake3> = Pile::open(&pile_path)?;
-                let mut repo = Repository::new(pile_store, key.clone(), TribleSet::new())?;
-
-                let res = (|| -> Result<(), anyhow::Error> {
+                let repo = Repository::new(pile_store, key.clone(), TribleSet::new())?;
+                ClosingRepo::new(repo).run(|repo| {
                     repo.storage_mut().refresh()?;
                     let reader = repo
                         .storage_mut()
@@ -993,7 +1622,7 @@ pub fn run(cmd: Command) -> Result<()> {
 
                     let n_active = states.values().filter(|s| s.kind == RecordKind::Set).count();
                     let n_deleted = states.values().filter(|s| s.kind == RecordKind::Tombstone).count();
-                    println!(
+                    note!(
                         "scanning pile: found {} unique branch IDs ({} active, {} tombstoned)",
                         states.len(), n_active, n_deleted
                     );
@@ -1001,39 +1630,27 @@ pub fn run(cmd: Command) -> Result<()> {
                     // --- Phase 2: Name resolution & grouping ---
                     let mut groups: BTreeMap<String, Vec<(Id, Option<Value<Handle<Blake3, SimpleArchive>>>)>> = BTreeMap::new();
 
+                    let mut metas = MetaCache::new(&reader);
                     for (bid, state) in &states {
-                        let meta_handle = match state.kind {
-                            RecordKind::Set => state.meta,
-                            RecordKind::Tombstone => state.last_set,
-                        };
-
-                        let Some(mh) = meta_handle else {
+                        let Some(mh) = state.meta_handle() else {
                             groups.entry("<unnamed>".to_string()).or_default().push((*bid, None));
                             continue;
                         };
 
-                        if reader.metadata(mh)?.is_none() {
-                            eprintln!("warning: metadata blob missing for branch {bid:X}");
-                            groups.entry("<unnamed>".to_string()).or_default().push((*bid, None));
-                            continue;
-                        }
-
-                        let meta_set = match reader.get::<TribleSet, SimpleArchive>(mh) {
-                            Ok(ms) => ms,
-                            Err(_) => {
+                        match metas.get(mh)? {
+                            BranchMeta::Missing => {
+                                eprintln!("warning: metadata blob missing for branch {bid:X}");
+                                groups.entry("<unnamed>".to_string()).or_default().push((*bid, None));
+                            }
+                            BranchMeta::Undecodable => {
                                 eprintln!("warning: failed to read metadata for branch {bid:X}");
                                 groups.entry("<unnamed>".to_string()).or_default().push((*bid, None));
-                                continue;
                             }
-                        };
-
-                        let name = load_branch_name(&reader, &meta_set)
-                            .ok()
-                            .flatten()
-                            .unwrap_or_else(|| "<unnamed>".to_string());
-
-                        let head = extract_repo_head(&meta_set);
-                        groups.entry(name).or_default().push((*bid, head));
+                            BranchMeta::Decoded { name, head, .. } => {
+                                let name = name.clone().unwrap_or_else(|| "<unnamed>".to_string());
+                                groups.entry(name).or_default().push((*bid, *head));
+                            }
+                        }
                     }
 
                     // --- Phase 3: Subsumption + merge per name group ---
@@ -1045,33 +1662,32 @@ pub fn run(cmd: Command) -> Result<()> {
                         (*id, label)
                     }).collect();
                     let created_count = consolidate_groups(
-                        &groups, &statuses, &reader, &mut repo, &key,
-                        dry_run, delete_sources,
+                        &groups,
+                        &statuses,
+                        &reader,
+                        repo,
+                        &key,
+                        message.as_deref(),
+                        dry_run,
+                        delete_sources,
                     )?;
 
                     if dry_run {
-                        println!("\ndry-run: no changes were made");
+                        note!("\ndry-run: no changes were made");
                     } else {
-                        println!("\ncreated {created_count} consolidated branch(es)");
+                        note!("\ncreated {created_count} consolidated branch(es)");
                     }
 
                     Ok(())
-                })();
-
-                let close_res = repo
-                    .into_storage()
-                    .close()
-                    .map_err(|e| anyhow::anyhow!("{e:?}"));
-                res.and(close_res)?;
+                })?;
             } else if by_name {
                 if out_name.is_some() {
                     eprintln!("warning: --out-name is ignored when --by-name is set");
                 }
 
                 let pile_store: Pile<Blake3> = Pile::open(&pile)?;
-                let mut repo = Repository::new(pile_store, key.clone(), TribleSet::new())?;
-
-                let res = (|| -> Result<(), anyhow::Error> {
+                let repo = Repository::new(pile_store, key.clone(), TribleSet::new())?;
+                ClosingRepo::new(repo).run(|repo| {
                     repo.storage_mut().refresh()?;
                     let reader = repo
                         .storage_mut()
@@ -1081,62 +1697,55 @@ pub fn run(cmd: Command) -> Result<()> {
                     // Iterate active branches, resolve names, group.
                     let mut groups: std::collections::BTreeMap<String, Vec<(Id, Option<Value<Handle<Blake3, SimpleArchive>>>)>> = std::collections::BTreeMap::new();
 
-                    let branch_ids: Vec<Id> = repo.storage_mut().branches()?
-                        .collect::<Result<Vec<_>, _>>()?;
+                    let branch_ids: Vec<Id> =
+                        crate::cli::remote_state::branch_ids(repo.storage_mut(), &reader)?;
 
-                    println!("found {} active branch(es)", branch_ids.len());
+                    note!("found {} active branch(es)", branch_ids.len());
 
+                    let mut metas = MetaCache::new(&reader);
                     for bid in &branch_ids {
                         let Some(mh) = repo.storage_mut().head(*bid)? else {
                             continue;
                         };
 
-                        if reader.metadata(mh)?.is_none() {
-                            eprintln!("warning: metadata blob missing for branch {bid:X}");
-                            groups.entry("<unnamed>".to_string()).or_default().push((*bid, None));
-                            continue;
-                        }
-
-                        let meta_set = match reader.get::<TribleSet, SimpleArchive>(mh) {
-                            Ok(ms) => ms,
-                            Err(_) => {
+                        match metas.get(mh)? {
+                            BranchMeta::Missing => {
+                                eprintln!("warning: metadata blob missing for branch {bid:X}");
+                                groups.entry("<unnamed>".to_string()).or_default().push((*bid, None));
+                            }
+                            BranchMeta::Undecodable => {
                                 eprintln!("warning: failed to read metadata for branch {bid:X}");
                                 groups.entry("<unnamed>".to_string()).or_default().push((*bid, None));
-                                continue;
                             }
-                        };
-
-                        let name = load_branch_name(&reader, &meta_set)
-                            .ok()
-                            .flatten()
-                            .unwrap_or_else(|| "<unnamed>".to_string());
-
-                        let head = extract_repo_head(&meta_set);
-                        groups.entry(name).or_default().push((*bid, head));
+                            BranchMeta::Decoded { name, head, .. } => {
+                                let name = name.clone().unwrap_or_else(|| "<unnamed>".to_string());
+                                groups.entry(name).or_default().push((*bid, *head));
+                            }
+                        }
                     }
 
                     let statuses: HashMap<Id, &str> = branch_ids.iter()
                         .map(|bid| (*bid, "active"))
                         .collect();
                     let created_count = consolidate_groups(
-                        &groups, &statuses, &reader, &mut repo, &key,
-                        dry_run, delete_sources,
+                        &groups,
+                        &statuses,
+                        &reader,
+                        repo,
+                        &key,
+                        message.as_deref(),
+                        dry_run,
+                        delete_sources,
                     )?;
 
                     if dry_run {
-                        println!("\ndry-run: no changes were made");
+                        note!("\ndry-run: no changes were made");
                     } else {
-                        println!("\ncreated {created_count} consolidated branch(es)");
+                        note!("\ncreated {created_count} consolidated branch(es)");
                     }
 
                     Ok(())
-                })();
-
-                let close_res = repo
-                    .into_storage()
-                    .close()
-                    .map_err(|e| anyhow::anyhow!("{e:?}"));
-                res.and(close_res)?;
+                })?;
             } else {
                 // Original explicit-branch-IDs path.
                 // Parse branch ids before opening the pile so CLI errors don't leave files open.
@@ -1150,9 +1759,8 @@ pub fn run(cmd: Command) -> Result<()> {
                 }
 
                 let pile: Pile<Blake3> = Pile::open(&pile)?;
-                let mut repo = Repository::new(pile, key.clone(), TribleSet::new())?;
-
-                let res = (|| -> Result<(), anyhow::Error> {
+                let repo = Repository::new(pile, key.clone(), TribleSet::new())?;
+                ClosingRepo::new(repo).run(|repo| {
                     // Ensure in-memory indices are populated.
                     repo.storage_mut().refresh()?;
                     let reader = repo
@@ -1167,10 +1775,9 @@ pub fn run(cmd: Command) -> Result<()> {
                     let mut candidates: Vec<(Id, Option<Value<Handle<Blake3, SimpleArchive>>>)> =
                         Vec::new();
                     for bid in branch_ids {
-                        let meta_handle = repo
-                            .storage_mut()
-                            .head(bid)?
-                            .ok_or_else(|| anyhow::anyhow!("branch not found: {bid:X}"))?;
+                        let meta_handle = repo.storage_mut().head(bid)?.ok_or_else(|| {
+                            crate::cli::error::not_found(format!("branch not found: {bid:X}"))
+                        })?;
 
                         let mut head_val: Option<Value<Handle<Blake3, SimpleArchive>>> = None;
                         if reader.metadata(meta_handle)?.is_some() {
@@ -1187,47 +1794,84 @@ pub fn run(cmd: Command) -> Result<()> {
                         candidates.push((bid, head_val));
                     }
 
-                    println!("found {} branch(es)", candidates.len());
+                    note!("found {} branch(es)", candidates.len());
                     for (bid, head) in &candidates {
                         let id_hex = format!("{bid:X}");
                         if let Some(h) = head {
                             let hh: Value<Hash<Blake3>> = Handle::to_hash(*h);
                             let hex: String = hh.from_value();
-                            println!("- {id_hex} -> commit {hex}");
+                            note!("- {id_hex} -> commit {hex}");
                         } else {
-                            println!("- {id_hex} -> <no head>");
+                            note!("- {id_hex} -> <no head>");
                         }
                     }
 
-                    if dry_run {
-                        println!("dry-run: no changes will be made");
+                    if candidates.len() == 1 {
+                        note!("only one branch present; nothing to consolidate");
                         return Ok(());
                     }
 
-                    if candidates.len() == 1 {
-                        println!("only one branch present; nothing to consolidate");
-                        return Ok(());
+                    // Skip branches without a head, then drop repeated heads
+                    // and heads another candidate already contains.
+                    let heads: Vec<Value<Handle<Blake3, SimpleArchive>>> =
+                        candidates.iter().filter_map(|(_, h)| *h).collect();
+                    if heads.is_empty() {
+                        anyhow::bail!("no branch heads available to attach");
+                    }
+                    let (parents, dropped) = independent_heads(&heads, &reader);
+                    let commit_hex = |h: Value<Handle<Blake3, SimpleArchive>>| -> String {
+                        let hh: Value<Hash<Blake3>> = Handle::to_hash(h);
+                        hh.from_value()
+                    };
+                    for (head, why) in &dropped {
+                        match why {
+                            DroppedHead::Duplicate => note!(
+                                "dropping commit {}: another branch has the same head",
+                                commit_hex(*head)
+                            ),
+                            DroppedHead::AncestorOf(other) => note!(
+                                "dropping commit {}: already contained in commit {}",
+                                commit_hex(*head),
+                                commit_hex(*other)
+                            ),
+                        }
                     }
 
-                    // Collect parent commit handles (skip branches without a head).
-                    let parents: Vec<Value<Handle<Blake3, SimpleArchive>>> =
-                        candidates.iter().filter_map(|(_, h)| *h).collect();
-                    if parents.is_empty() {
-                        anyhow::bail!("no branch heads available to attach");
+                    if dry_run {
+                        match parents.as_slice() {
+                            [head] => note!(
+                                "would point the new branch at commit {} (no merge needed)",
+                                commit_hex(*head)
+                            ),
+                            _ => note!("would merge {} head(s)", parents.len()),
+                        }
+                        note!("dry-run: no changes will be made");
+                        return Ok(());
                     }
 
-                    // Create a single merge commit that has all branch heads as parents.
-                    let commit_set = triblespace_core::repo::commit::commit_metadata(
-                        &key,
-                        parents.clone(),
-                        None,
-                        None,
-                        None,
-                    );
-                    let commit_handle = repo
-                        .storage_mut()
-                        .put(commit_set.to_blob())
-                        .map_err(|e| anyhow::anyhow!("failed to put commit blob: {e:?}"))?;
+                    // A single remaining head needs no merge commit; otherwise
+                    // create one that has every remaining head as a parent.
+                    let commit_handle = match parents.as_slice() {
+                        [head] => {
+                            note!("single independent head; no merge commit needed");
+                            *head
+                        }
+                        _ => {
+                            let message = message
+                                .map(|m| verify::put::<LongString, _>(repo.storage_mut(), m))
+                                .transpose()
+                                .context("put message")?;
+                            let commit_set = triblespace_core::repo::commit::commit_metadata(
+                                &key,
+                                parents.clone(),
+                                message,
+                                None,
+                                None,
+                            );
+                            verify::put(repo.storage_mut(), commit_set.to_blob())
+                                .context("failed to put commit blob")?
+                        }
+                    };
 
                     // Decide output branch name.
                     let out = out_name.unwrap_or_else(|| "consolidated".to_string());
@@ -1242,7 +1886,7 @@ pub fn run(cmd: Command) -> Result<()> {
                             if let Some(old) = repo.storage_mut().head(*bid)? {
                                 match repo.storage_mut().update(*bid, Some(old), None)? {
                                     triblespace_core::repo::PushResult::Success() => {
-                                        println!("deleted source branch {bid:X}");
+                                        note!("deleted source branch {bid:X}");
                                     }
                                     triblespace_core::repo::PushResult::Conflict(_) => {
                                         eprintln!("warning: branch {bid:X} advanced concurrently; skipping delete");
@@ -1252,13 +1896,7 @@ pub fn run(cmd: Command) -> Result<()> {
                         }
                     }
                     Ok(())
-                })();
-
-                let close_res = repo
-                    .into_storage()
-                    .close()
-                    .map_err(|e| anyhow::anyhow!("{e:?}"));
-                res.and(close_res)?;
+                })?;
             }
         }
         Command::Log {
@@ -1266,23 +1904,36 @@ pub fn run(cmd: Command) -> Result<()> {
             branch,
             limit,
             oneline,
+            require_signer,
+            since,
+            until,
+            author,
         } => {
+            use crate::cli::util::{format_millis, parse_time};
             use std::collections::HashSet;
-            use triblespace_core::repo::pile::Pile;
 
-            let branch_id = parse_branch_id_hex(&branch)?;
-
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
-                pile.refresh()?;
+            let required_signer = require_signer
+                .as_deref()
+                .map(parse_public_key)
+                .transpose()?;
+            let verbose = crate::cli::logging::verbose() > 0;
+            let author = author.as_deref().map(parse_public_key).transpose()?;
+            let since = since.as_deref().map(parse_time).transpose()?;
+            let until = until.as_deref().map(parse_time).transpose()?;
+            let windowed = since.is_some() || until.is_some();
+
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile| {
+                super::refresh_for_read(pile)?;
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                let branch_id = resolve_branch(pile, &reader, BranchRef::Any(&branch))?.id;
 
                 // Resolve branch head commit.
                 let branch_meta = pile
                     .head(branch_id)?
-                    .ok_or_else(|| anyhow::anyhow!("branch not found"))?;
+                    .ok_or_else(|| crate::cli::error::not_found("branch not found"))?;
                 let branch_meta_set: TribleSet = reader
                     .get(branch_meta)
                     .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
@@ -1294,47 +1945,73 @@ pub fn run(cmd: Command) -> Result<()> {
                     std::collections::VecDeque::new();
                 let mut visited: HashSet<[u8; 32]> = HashSet::new();
                 queue.push_back(commit_head);
-                let mut printed = 0usize;
+                let mut shown: Vec<(Option<u64>, String)> = Vec::new();
+                let mut foreign = 0usize;
 
                 while let Some(current) = queue.pop_front() {
                     if !visited.insert(current.raw) {
                         continue;
                     }
-                    if printed >= limit {
+                    if shown.len() >= limit {
                         break;
                     }
 
+                    let hash: Value<Hash<Blake3>> = Handle::to_hash(current);
+                    let hex: String = hash.from_value();
                     let commit_set: TribleSet = match reader.get(current) {
                         Ok(c) => c,
                         Err(_) => {
-                            let hash: Value<Hash<Blake3>> = Handle::to_hash(current);
-                            let hex: String = hash.from_value();
-                            println!("{hex}  <missing blob>");
-                            printed += 1;
+                            if !windowed && author.is_none() {
+                                shown.push((None, format!("{hex}  <missing blob>")));
+                            }
                             continue;
                         }
                     };
 
                     let info = read_commit_fields(&commit_set);
-                    let hash: Value<Hash<Blake3>> = Handle::to_hash(current);
-                    let hex: String = hash.from_value();
+                    let stored = reader.metadata(current)?.map(|m| m.timestamp);
+                    let millis = commit_millis(&info).or(stored);
+                    let signature = (verbose || required_signer.is_some())
+                        .then(|| verify_commit_signature(&reader, &info));
+                    if let Some(required) = &required_signer {
+                        let trusted = match info.signed_by {
+                            None => signature == Some(SignatureState::Absent),
+                            Some(pk) => pk == *required && signature == Some(SignatureState::Valid),
+                        };
+                        if !trusted {
+                            foreign += 1;
+                            eprintln!("commit {hex} is not signed by the required key");
+                        }
+                    }
+                    let linear = queue.is_empty() && info.parents.len() <= 1;
+                    for p in &info.parents {
+                        queue.push_back(*p);
+                    }
 
-                    let msg = if let Some(sm) = &info.short_message {
-                        sm.clone()
-                    } else if let Some(mh) = info.message {
-                        match reader.get::<View<str>, _>(mh) {
-                            Ok(v) => {
-                                let s = v.as_ref();
-                                if s.len() > 72 {
-                                    format!("{}...", &s[..72])
-                                } else {
-                                    s.to_string()
-                                }
-                            }
-                            Err(_) => "<message blob missing>".to_string(),
+                    if millis.is_some_and(|at| since.is_some_and(|since| at < since)) {
+                        // On a linear stretch every remaining ancestor is
+                        // older still.
+                        if linear {
+                            break;
                         }
-                    } else {
-                        "<no message>".to_string()
+                        continue;
+                    }
+                    let in_window = match millis {
+                        Some(at) => until.is_none_or(|until| at < until),
+                        None => !windowed,
+                    };
+                    let by_author = author.is_none_or(|pk| info.signed_by == Some(pk));
+                    if !in_window || !by_author {
+                        continue;
+                    }
+
+                    let msg = match (&info.short_message, commit_message(&reader, &info)) {
+                        (Some(sm), _) => sm.clone(),
+                        (None, Some(m)) => match m.char_indices().nth(72) {
+                            Some((cut, _)) => format!("{}...", &m[..cut]),
+                            None => m,
+                        },
+                        (None, None) => "<no message>".to_string(),
                     };
 
                     let content_count = if let Some(ch) = info.content {
@@ -1346,30 +2023,34 @@ pub fn run(cmd: Command) -> Result<()> {
                         "0".to_string()
                     };
 
-                    let ts_str = if let Some(ts_val) = info.timestamp {
-                        use triblespace_core::value::schemas::time::Lower;
-                        let lower: Lower = ts_val.try_from_value().unwrap_or(Lower(0));
-                        let epoch = hifitime::Epoch::from_tai_duration(
-                            hifitime::Duration::from_total_nanoseconds(lower.0));
-                        hifitime::efmt::Formatter::new(
-                            epoch,
-                            hifitime::efmt::consts::ISO8601,
-                        ).to_string()
-                    } else {
-                        "?".to_string()
+                    let ts_str = match (info.timestamp, stored) {
+                        (Some(_), _) => format_commit_time(&info),
+                        (None, Some(at)) => format!("{} (stored)", format_millis(at)),
+                        (None, None) => "? (no timestamp)".to_string(),
                     };
 
+                    let mut text = String::new();
                     if oneline {
-                        println!(
-                            "\x1b[33m{short}\x1b[0m  {ts_str}  {msg}",
-                            short = &hex[..16],
+                        text += &format!(
+                            "{short}  {ts_str}  {msg}",
+                            short = style::paint(Color::Yellow, &hex[..16]),
                         );
                     } else {
-                        println!("\x1b[33mcommit {hex}\x1b[0m");
-                        if let Some(pk) = &info.signed_by {
-                            println!("Signed: {}", hex::encode(&pk[..8]));
+                        text +=
+                            &format!("{}\n", style::paint(Color::Yellow, format!("commit {hex}")));
+                        match (&info.signed_by, signature.filter(|_| verbose)) {
+                            (Some(pk), Some(_)) => {
+                                text += &format!("Signer: {}\n", hex::encode(pk))
+                            }
+                            (Some(pk), None) => {
+                                text += &format!("Signed: {}\n", hex::encode(&pk[..8]))
+                            }
+                            (None, _) => {}
+                        }
+                        if let Some(state) = signature.filter(|_| verbose) {
+                            text += &format!("Signature: {}\n", state.label());
                         }
-                        println!("Date:   {ts_str}");
+                        text += &format!("Date:   {ts_str}\n");
                         if !info.parents.is_empty() {
                             let parent_strs: Vec<String> = info.parents.iter().map(|p| {
                                 let ph: Value<Hash<Blake3>> = Handle::to_hash(*p);
@@ -1377,34 +2058,30 @@ pub fn run(cmd: Command) -> Result<()> {
                                 phex[..16].to_string()
                             }).collect();
                             let label = if info.parents.len() > 1 { "Merge: " } else { "Parent:" };
-                            println!("{label} {}", parent_strs.join(" "));
+                            text += &format!("{label} {}\n", parent_strs.join(" "));
                         }
-                        println!();
-                        println!("    {msg}");
-                        println!();
-                        println!("    {content_count} tribles");
-                        println!();
+                        text += &format!("\n    {msg}\n\n    {content_count} tribles\n");
                     }
-                    printed += 1;
+                    shown.push((millis, text));
+                }
 
-                    for p in &info.parents {
-                        queue.push_back(*p);
-                    }
+                // Walk order, except that commits without a time go last.
+                shown.sort_by_key(|(millis, _)| millis.is_none());
+                for (_, text) in shown {
+                    println!("{text}");
+                }
+                if foreign > 0 {
+                    anyhow::bail!("{foreign} commit(s) not signed by the required key");
                 }
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
         Command::Show { pile, commit } => {
-            use triblespace_core::repo::pile::Pile;
+            let commit_handle: Value<Handle<Blake3, SimpleArchive>> = parse_blake3_handle(&commit)?;
 
-            let commit_handle: Value<Handle<Blake3, SimpleArchive>> =
-                parse_blake3_handle(&commit)?;
-
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
-                pile.refresh()?;
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile| {
+                super::refresh_for_read(pile)?;
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
@@ -1433,6 +2110,10 @@ pub fn run(cmd: Command) -> Result<()> {
                 if let Some(pk) = &info.signed_by {
                     println!("Signed by: {}", hex::encode(pk));
                 }
+                println!(
+                    "Signature: {}",
+                    verify_commit_signature(&reader, &info).label()
+                );
 
                 // Parents
                 if info.parents.is_empty() {
@@ -1495,9 +2176,7 @@ pub fn run(cmd: Command) -> Result<()> {
                 println!("Commit tribles: {}", commit_set.len());
 
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
         Command::Describe {
             pile,
@@ -1505,21 +2184,19 @@ pub fn run(cmd: Command) -> Result<()> {
             entities,
         } => {
             use std::collections::HashSet;
-            use triblespace_core::repo::pile::Pile;
-
-            let branch_id = parse_branch_id_hex(&branch)?;
 
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
-                pile.refresh()?;
+            let pile = super::resolve_pile(pile)?;
+            ClosingPile::open(&pile)?.run(|pile| {
+                super::refresh_for_read(pile)?;
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                let branch_id = resolve_branch(pile, &reader, BranchRef::Any(&branch))?.id;
 
                 // Resolve branch head commit.
                 let branch_meta = pile
                     .head(branch_id)?
-                    .ok_or_else(|| anyhow::anyhow!("branch not found"))?;
+                    .ok_or_else(|| crate::cli::error::not_found("branch not found"))?;
                 let branch_meta_set: TribleSet = reader
                     .get(branch_meta)
                     .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
@@ -1638,8 +2315,9 @@ pub fn run(cmd: Command) -> Result<()> {
                 for (attr_id, tally) in &sorted {
                     let name = attr_names
                         .get(attr_id)
-                        .map(|s| s.as_str())
-                        .unwrap_or("-");
+                        .cloned()
+                        .or_else(|| crate::cli::attr::label(attr_id))
+                        .unwrap_or_else(|| "-".to_string());
                     if entities {
                         println!(
                             "{attr_id:X}  tribles={tc}  entities={ec}  {name}",
@@ -1655,31 +2333,27 @@ pub fn run(cmd: Command) -> Result<()> {
                 }
 
                 Ok(())
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
         Command::Rename {
             pile,
             branch,
             new_name,
-            signing_key,
+            signing,
         } => {
-            use triblespace_core::repo::pile::Pile;
             use triblespace_core::repo::branch as branch_mod;
             use triblespace_core::query::find;
             use triblespace_core::macros::pattern;
 
-            let branch_id = parse_branch_id_hex(&branch)?;
-            let key = load_signing_key(&signing_key)?;
+            let key = signing.load()?;
 
-            let mut pile: Pile<Blake3> = Pile::open(&pile)?;
-            let res = (|| -> Result<(), anyhow::Error> {
+            ClosingPile::open(&pile)?.run(|pile| {
                 pile.refresh()?;
+                let branch_id = find_branch(pile, &branch)?;
 
-                let mut current_meta_handle = pile
-                    .head(branch_id)?
-                    .ok_or_else(|| anyhow::anyhow!("branch {branch} not found"))?;
+                let mut current_meta_handle = pile.head(branch_id)?.ok_or_else(|| {
+                    crate::cli::error::not_found(format!("branch {branch} not found"))
+                })?;
 
                 loop {
                     // Load current branch metadata.
@@ -1704,21 +2378,15 @@ pub fn run(cmd: Command) -> Result<()> {
                     };
 
                     // Store the new name as a LongString blob.
-                    let name_handle: BranchNameHandle = pile
-                        .put(new_name.clone().to_blob())
-                        .map_err(|e| anyhow::anyhow!("put name blob: {e:?}"))?;
+                    let name_handle: BranchNameHandle = verify::put(pile, new_name.clone().to_blob())
+                        .context("put name blob")?;
 
                     // Build new branch metadata with the new name.
-                    let new_meta = branch_mod::branch_metadata(
-                        &key,
-                        branch_id,
-                        name_handle,
-                        commit_blob,
-                    );
+                    let mut new_meta =
+                        branch_mod::branch_metadata(&key, branch_id, name_handle, commit_blob);
+                    provenance::keep_creator(&meta, &mut new_meta);
 
-                    let new_meta_handle = pile
-                        .put(new_meta)
-                        .map_err(|e| anyhow::anyhow!("put branch meta: {e:?}"))?;
+                    let new_meta_handle = verify::put(pile, new_meta).context("put branch meta")?;
 
                     // CAS: swap old metadata for new.
                     match pile.update(branch_id, Some(current_meta_handle), Some(new_meta_handle))? {
@@ -1735,9 +2403,55 @@ pub fn run(cmd: Command) -> Result<()> {
                         }
                     }
                 }
-            })();
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
+        }
+        Command::Resign {
+            pile,
+            id,
+            name,
+            all: _,
+            signing_key,
+        } => {
+            let key = load_signing_key(&signing_key, false)?;
+            ClosingPile::open(&pile)?.run(|pile| {
+                pile.refresh()?;
+                let targets = if id.is_some() || name.is_some() {
+                    vec![select_branch(pile, id.as_deref(), name.as_deref())?]
+                } else {
+                    let reader = pile
+                        .reader()
+                        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+                    let ids: Vec<Id> = pile.branches()?.collect::<Result<Vec<_>, _>>()?;
+                    let mut targets = Vec::new();
+                    for bid in ids {
+                        let Some(meta) = pile.head(bid)? else {
+                            continue;
+                        };
+                        let Ok(meta) = reader.get::<TribleSet, SimpleArchive>(meta) else {
+                            continue;
+                        };
+                        if !crate::cli::remote_state::is_remote_state(&meta) {
+                            targets.push(bid);
+                        }
+                    }
+                    targets
+                };
+
+                let mut out = Output::stdout();
+                for branch_id in targets {
+                    let (old, new) = resign_branch(pile, &key, branch_id)?;
+                    let old: Value<Hash<Blake3>> = Handle::to_hash(old);
+                    let new: Value<Hash<Blake3>> = Handle::to_hash(new);
+                    let old: String = old.from_value();
+                    let new: String = new.from_value();
+                    out.record(&[
+                        ("branch", Field::from(format!("{branch_id:X}"))),
+                        ("old_meta", Field::from(old)),
+                        ("new_meta", Field::from(new)),
+                    ])?;
+                }
+                Ok(())
+            })?;
         }
     }
     Ok(())
@@ -1745,134 +2459,131 @@ pub fn run(cmd: Command) -> Result<()> {
 
 // ───────────── Shared helpers ─────────────
 
-/// Kind of raw branch record in a pile file.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum RecordKind {
-    Set,
-    Tombstone,
-}
-
-/// A single branch record read from the raw pile file.
-#[derive(Clone, Debug)]
-struct RawBranchRecord {
-    offset: u64,
-    branch_id: Id,
-    kind: RecordKind,
-    /// Branch metadata handle (only when kind == Set).
-    meta_handle: Option<Value<Handle<Blake3, SimpleArchive>>>,
-}
-
-/// Collapsed final state per branch from a raw pile scan.
-#[derive(Clone, Debug)]
-struct BranchState {
-    kind: RecordKind,
-    /// Current metadata handle (only when kind == Set).
-    meta: Option<Value<Handle<Blake3, SimpleArchive>>>,
-    /// Most recent Set metadata handle (kept even after tombstone).
-    last_set: Option<Value<Handle<Blake3, SimpleArchive>>>,
-}
-
-/// Scan the raw pile file for all branch update/tombstone records.
-fn scan_pile_records(path: &std::path::Path) -> Result<Vec<RawBranchRecord>> {
-    let mut file = std::fs::File::open(path)?;
-    let file_len = file.metadata()?.len();
-    let mut records = Vec::new();
-    let mut offset: u64 = 0;
-    let mut buf = [0u8; RECORD_LEN as usize];
-
-    while offset + RECORD_LEN <= file_len {
-        file.seek(SeekFrom::Start(offset))?;
-        if file.read_exact(&mut buf).is_err() {
-            break;
-        }
-
-        let magic: [u8; 16] = buf[0..16].try_into().unwrap();
-        if magic == MAGIC_MARKER_BLOB.raw() {
-            let len = u64::from_ne_bytes(buf[24..32].try_into().unwrap());
-            let pad = blob_padding(len);
-            offset = offset
-                .checked_add(RECORD_LEN)
-                .and_then(|o| o.checked_add(len))
-                .and_then(|o| o.checked_add(pad))
-                .ok_or_else(|| anyhow::anyhow!("pile too large"))?;
-            continue;
-        }
+/// Who created a branch.
+///
+/// `branch_metadata` only names a signer once a branch has a head, so
+/// `pile branch create` records the creating key under its own attribute.
+/// Metadata rewritten by other tools (e.g. a library push) may drop it.
+pub(crate) mod provenance {
+    use ed25519_dalek::SigningKey;
+    use triblespace::prelude::*;
+    use triblespace_core::repo::branch::branch_metadata;
+    use triblespace_core::value::schemas::ed25519::ED25519PublicKey;
+
+    use super::BranchNameHandle;
+
+    attributes! {
+        "A6CFAE6EE9AA0582B3212172B6C4801A" as created_by: ED25519PublicKey;
+    }
 
-        if magic == MAGIC_MARKER_BRANCH.raw() {
-            let raw_id: [u8; 16] = buf[16..32].try_into().unwrap();
-            let Some(id) = Id::new(raw_id) else { break };
-            let raw_handle: [u8; 32] = buf[32..64].try_into().unwrap();
-            let meta: Value<Handle<Blake3, SimpleArchive>> = Value::new(raw_handle);
-            records.push(RawBranchRecord {
-                offset,
-                branch_id: id,
-                kind: RecordKind::Set,
-                meta_handle: Some(meta),
-            });
-            offset += RECORD_LEN;
-            continue;
-        }
+    /// Metadata for a new branch without a head, created by `key`.
+    pub fn new_branch_metadata(
+        key: &SigningKey,
+        branch_id: &ExclusiveId,
+        name: BranchNameHandle,
+    ) -> TribleSet {
+        let mut meta = branch_metadata(key, **branch_id, name, None);
+        meta += entity! { branch_id @ created_by: key.verifying_key() };
+        meta
+    }
 
-        if magic == MAGIC_MARKER_BRANCH_TOMBSTONE.raw() {
-            let raw_id: [u8; 16] = buf[16..32].try_into().unwrap();
-            let Some(id) = Id::new(raw_id) else { break };
-            records.push(RawBranchRecord {
-                offset,
-                branch_id: id,
-                kind: RecordKind::Tombstone,
-                meta_handle: None,
-            });
-            offset += RECORD_LEN;
-            continue;
+    /// Carry the creator over from `old` into rebuilt metadata.
+    pub fn keep_creator(old: &TribleSet, new: &mut TribleSet) {
+        let attr = created_by.id();
+        for t in old.iter().filter(|t| t.a() == &attr) {
+            new.insert(t);
         }
+    }
 
-        break;
+    /// The creator's public key, if the metadata records one.
+    pub fn creator(meta: &TribleSet) -> Option<[u8; 32]> {
+        let attr = created_by.id();
+        meta.iter()
+            .find(|t| t.a() == &attr)
+            .map(|t| t.v::<ED25519PublicKey>().raw)
     }
 
-    Ok(records)
+    /// `ed25519:<hex>` for display.
+    pub fn format(key: &[u8; 32]) -> String {
+        format!("ed25519:{}", hex::encode(key))
+    }
 }
 
-/// Collapse raw records into final state per branch.
-fn collapse_branch_states(records: &[RawBranchRecord]) -> HashMap<Id, BranchState> {
-    let mut states: HashMap<Id, BranchState> = HashMap::new();
-    for rec in records {
-        let entry = states.entry(rec.branch_id).or_insert(BranchState {
-            kind: rec.kind,
-            meta: rec.meta_handle,
-            last_set: if rec.kind == RecordKind::Set {
-                rec.meta_handle
-            } else {
-                None
-            },
-        });
-        entry.kind = rec.kind;
-        match rec.kind {
-            RecordKind::Set => {
-                entry.meta = rec.meta_handle;
-                entry.last_set = rec.meta_handle;
+/// Replace a branch's metadata with a copy signed by `key` (same name, same
+/// head), retrying the CAS update on concurrent writes. Returns the old and
+/// new metadata handles.
+fn resign_branch(
+    pile: &mut Pile<Blake3>,
+    key: &ed25519_dalek::SigningKey,
+    branch_id: Id,
+) -> Result<(
+    Value<Handle<Blake3, SimpleArchive>>,
+    Value<Handle<Blake3, SimpleArchive>>,
+)> {
+    use triblespace_core::repo::branch as branch_mod;
+
+    let mut current = pile
+        .head(branch_id)?
+        .ok_or_else(|| crate::cli::error::not_found(format!("branch {branch_id:X} not found")))?;
+    loop {
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let meta: TribleSet = reader
+            .get(current)
+            .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
+        let name_attr = triblespace_core::metadata::name.id();
+        let name_handle: BranchNameHandle = meta
+            .iter()
+            .find(|t| t.a() == &name_attr)
+            .map(|t| *t.v())
+            .ok_or_else(|| anyhow::anyhow!("branch {branch_id:X} has no name"))?;
+        let commit_blob = match extract_repo_head(&meta) {
+            Some(head) => {
+                let commit: TribleSet = reader
+                    .get(head)
+                    .map_err(|e| anyhow::anyhow!("read commit: {e:?}"))?;
+                Some(commit.to_blob())
             }
-            RecordKind::Tombstone => {
-                entry.meta = None;
+            None => None,
+        };
+
+        let mut new_meta = branch_mod::branch_metadata(key, branch_id, name_handle, commit_blob);
+        provenance::keep_creator(&meta, &mut new_meta);
+        let new = verify::put(pile, new_meta).context("put branch meta")?;
+        if new.raw == current.raw {
+            // Already signed by this key.
+            return Ok((current, new));
+        }
+        match pile.update(branch_id, Some(current), Some(new))? {
+            triblespace_core::repo::PushResult::Success() => return Ok((current, new)),
+            triblespace_core::repo::PushResult::Conflict(conflict) => {
+                current = conflict.ok_or_else(|| {
+                    crate::cli::error::conflict(format!(
+                        "branch {branch_id:X} was deleted concurrently"
+                    ))
+                })?;
             }
         }
     }
-    states
 }
 
 /// Parsed commit fields from a commit TribleSet.
 #[derive(Clone, Debug)]
-struct CommitInfo {
-    parents: Vec<Value<Handle<Blake3, SimpleArchive>>>,
-    content: Option<Value<Handle<Blake3, SimpleArchive>>>,
-    metadata: Option<Value<Handle<Blake3, SimpleArchive>>>,
-    message: Option<Value<Handle<Blake3, LongString>>>,
-    short_message: Option<String>,
-    timestamp: Option<Value<triblespace_core::value::schemas::time::NsTAIInterval>>,
-    signed_by: Option<[u8; 32]>,
+pub(crate) struct CommitInfo {
+    pub(crate) parents: Vec<Value<Handle<Blake3, SimpleArchive>>>,
+    pub(crate) content: Option<Value<Handle<Blake3, SimpleArchive>>>,
+    pub(crate) metadata: Option<Value<Handle<Blake3, SimpleArchive>>>,
+    pub(crate) message: Option<Value<Handle<Blake3, LongString>>>,
+    pub(crate) short_message: Option<String>,
+    pub(crate) timestamp: Option<Value<triblespace_core::value::schemas::time::NsTAIInterval>>,
+    pub(crate) signed_by: Option<[u8; 32]>,
+    pub(crate) signature_r: Option<[u8; 32]>,
+    pub(crate) signature_s: Option<[u8; 32]>,
 }
 
 /// Parse a commit TribleSet into structured fields.
-fn read_commit_fields(commit: &TribleSet) -> CommitInfo {
+pub(crate) fn read_commit_fields(commit: &TribleSet) -> CommitInfo {
     use triblespace_core::repo;
     use triblespace_core::value::schemas::ed25519 as ed;
     use triblespace_core::value::schemas::shortstring::ShortString;
@@ -1886,6 +2597,8 @@ fn read_commit_fields(commit: &TribleSet) -> CommitInfo {
     let timestamp_attr = repo::timestamp.id();
     let created_at_attr = triblespace_core::metadata::created_at.id();
     let signed_by_attr = repo::signed_by.id();
+    let signature_r_attr = repo::signature_r.id();
+    let signature_s_attr = repo::signature_s.id();
 
     let mut info = CommitInfo {
         parents: Vec::new(),
@@ -1895,6 +2608,8 @@ fn read_commit_fields(commit: &TribleSet) -> CommitInfo {
         short_message: None,
         timestamp: None,
         signed_by: None,
+        signature_r: None,
+        signature_s: None,
     };
 
     for t in commit.iter() {
@@ -1916,23 +2631,190 @@ fn read_commit_fields(commit: &TribleSet) -> CommitInfo {
         } else if a == signed_by_attr {
             let v: Value<ed::ED25519PublicKey> = *t.v();
             info.signed_by = Some(v.raw);
+        } else if a == signature_r_attr {
+            let v: Value<ed::ED25519RComponent> = *t.v();
+            info.signature_r = Some(v.raw);
+        } else if a == signature_s_attr {
+            let v: Value<ed::ED25519SComponent> = *t.v();
+            info.signature_s = Some(v.raw);
         }
     }
 
     info
 }
 
-fn blob_padding(len: u64) -> u64 {
-    // The pile stores blobs padded so the next record begins on a 64-byte boundary.
-    let rem = len % RECORD_LEN;
-    if rem == 0 {
-        0
-    } else {
-        RECORD_LEN - rem
+/// A commit's embedded timestamp (the lower bound) as Unix milliseconds.
+/// The commit's message: the LongString message blob when present, else the
+/// inline short message.
+pub(crate) fn commit_message(
+    reader: &impl BlobStoreGet<Blake3>,
+    info: &CommitInfo,
+) -> Option<String> {
+    match info.message {
+        Some(handle) => Some(match reader.get::<View<str>, _>(handle) {
+            Ok(text) => text.as_ref().to_string(),
+            Err(_) => "<message blob missing>".to_string(),
+        }),
+        None => info.short_message.clone(),
+    }
+}
+
+fn commit_millis(info: &CommitInfo) -> Option<u64> {
+    use triblespace_core::value::schemas::time::Lower;
+
+    let lower: Lower = info.timestamp?.try_from_value().ok()?;
+    let epoch =
+        hifitime::Epoch::from_tai_duration(hifitime::Duration::from_total_nanoseconds(lower.0));
+    let millis = epoch.to_unix_milliseconds();
+    (millis >= 0.0).then_some(millis as u64)
+}
+
+/// ISO 8601 rendering of a commit's timestamp, or `?` without one.
+pub(crate) fn format_commit_time(info: &CommitInfo) -> String {
+    use triblespace_core::value::schemas::time::Lower;
+
+    let Some(ts_val) = info.timestamp else {
+        return "?".to_string();
+    };
+    let lower: Lower = ts_val.try_from_value().unwrap_or(Lower(0));
+    let epoch =
+        hifitime::Epoch::from_tai_duration(hifitime::Duration::from_total_nanoseconds(lower.0));
+    hifitime::efmt::Formatter::new(epoch, hifitime::efmt::consts::ISO8601).to_string()
+}
+
+/// Result of checking a commit's signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SignatureState {
+    Valid,
+    Invalid,
+    /// No signature, as for merge commits without content.
+    Absent,
+    /// Signed, but the content blob is not in the pile.
+    Unverifiable,
+}
+
+impl SignatureState {
+    fn label(self) -> &'static str {
+        match self {
+            SignatureState::Valid => "valid",
+            SignatureState::Invalid => "invalid",
+            SignatureState::Absent => "absent",
+            SignatureState::Unverifiable => "unverifiable (content blob missing)",
+        }
+    }
+}
+
+/// Verify the ed25519 signature a commit carries over its content blob.
+fn verify_commit_signature(
+    reader: &triblespace_core::repo::pile::PileReader<Blake3>,
+    info: &CommitInfo,
+) -> SignatureState {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+    use triblespace_core::blob::schemas::UnknownBlob;
+    use triblespace_core::blob::Bytes;
+
+    let (signer, r, s) = match (info.signed_by, info.signature_r, info.signature_s) {
+        (None, None, None) => return SignatureState::Absent,
+        (Some(signer), Some(r), Some(s)) => (signer, r, s),
+        _ => return SignatureState::Invalid,
+    };
+    let (Some(content), Ok(key)) = (info.content, VerifyingKey::from_bytes(&signer)) else {
+        return SignatureState::Invalid;
+    };
+    let content: Value<Handle<Blake3, UnknownBlob>> = content.transmute();
+    let Ok(bytes) = reader.get::<Bytes, _>(content) else {
+        return SignatureState::Unverifiable;
+    };
+    match key.verify(&bytes, &Signature::from_components(r, s)) {
+        Ok(()) => SignatureState::Valid,
+        Err(_) => SignatureState::Invalid,
+    }
+}
+
+/// Parse a 64-char hex ed25519 public key.
+pub(crate) fn parse_public_key(hex_key: &str) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    hex::decode_to_slice(hex_key.trim(), &mut key).map_err(|_| {
+        crate::cli::error::invalid_argument(format!(
+            "invalid public key {hex_key:?}: expected 64 hex chars"
+        ))
+    })?;
+    Ok(key)
+}
+
+/// Commits reachable from `incoming` but not from `local` that are not
+/// validly signed by one of `allowed`, each with the reason. Merge commits
+/// without content carry no signature and are accepted.
+pub(crate) fn unapproved_commits(
+    reader: &triblespace_core::repo::pile::PileReader<Blake3>,
+    incoming: Value<Handle<Blake3, SimpleArchive>>,
+    local: Option<Value<Handle<Blake3, SimpleArchive>>>,
+    allowed: &[[u8; 32]],
+) -> Vec<(Value<Handle<Blake3, SimpleArchive>>, String)> {
+    use std::collections::HashSet;
+
+    let read = |handle| {
+        reader
+            .get::<TribleSet, SimpleArchive>(handle)
+            .ok()
+            .map(|commit| read_commit_fields(&commit))
+    };
+
+    let mut seen: HashSet<[u8; 32]> = HashSet::new();
+    let mut stack: Vec<_> = local.into_iter().collect();
+    while let Some(current) = stack.pop() {
+        if seen.insert(current.raw) {
+            stack.extend(read(current).into_iter().flat_map(|info| info.parents));
+        }
+    }
+
+    let mut offending = Vec::new();
+    let mut stack = vec![incoming];
+    while let Some(current) = stack.pop() {
+        if !seen.insert(current.raw) {
+            continue;
+        }
+        let Some(info) = read(current) else {
+            offending.push((current, "commit blob missing".to_string()));
+            continue;
+        };
+        let reason = match (verify_commit_signature(reader, &info), info.signed_by) {
+            (SignatureState::Valid, Some(signer)) if allowed.contains(&signer) => None,
+            (SignatureState::Valid, Some(signer)) => Some(format!(
+                "signed by ed25519:{}, which is not allowed",
+                hex::encode(signer)
+            )),
+            (SignatureState::Absent, _) if info.content.is_none() => None,
+            (SignatureState::Absent, _) => Some("unsigned".to_string()),
+            (state, _) => Some(format!("signature {}", state.label())),
+        };
+        if let Some(reason) = reason {
+            offending.push((current, reason));
+        }
+        stack.extend(info.parents);
     }
+    offending
 }
 
-fn extract_repo_head(meta: &TribleSet) -> Option<Value<Handle<Blake3, SimpleArchive>>> {
+/// Fail with the list of `offending` commits, if there are any.
+pub(crate) fn enforce_signer_policy(
+    offending: &[(Value<Handle<Blake3, SimpleArchive>>, String)],
+) -> Result<()> {
+    if offending.is_empty() {
+        return Ok(());
+    }
+    for (commit, reason) in offending {
+        let hash: Value<Hash<Blake3>> = Handle::to_hash(*commit);
+        let hex: String = hash.from_value();
+        eprintln!("commit {hex}: {reason}");
+    }
+    Err(anyhow::anyhow!(
+        "{} commit(s) not signed by an allowed key; no branch heads were updated",
+        offending.len()
+    ))
+}
+
+pub(crate) fn extract_repo_head(meta: &TribleSet) -> Option<Value<Handle<Blake3, SimpleArchive>>> {
     use triblespace::prelude::blobschemas::SimpleArchive;
     use triblespace::prelude::valueschemas::Handle;
     use triblespace_core::repo;
@@ -1953,7 +2835,7 @@ fn extract_repo_head(meta: &TribleSet) -> Option<Value<Handle<Blake3, SimpleArch
     head_handle
 }
 
-fn parse_branch_id_hex(s: &str) -> Result<Id> {
+pub(crate) fn parse_branch_id_hex(s: &str) -> Result<Id> {
     let raw = hex::decode(s).map_err(|e| anyhow::anyhow!("branch id hex decode failed: {e}"))?;
     let raw: [u8; 16] = raw
         .as_slice()
@@ -1962,7 +2844,7 @@ fn parse_branch_id_hex(s: &str) -> Result<Id> {
     Id::new(raw).ok_or_else(|| anyhow::anyhow!("branch id cannot be nil"))
 }
 
-fn parse_blake3_handle(s: &str) -> Result<Value<Handle<Blake3, SimpleArchive>>> {
+pub(crate) fn parse_blake3_handle(s: &str) -> Result<Value<Handle<Blake3, SimpleArchive>>> {
     let s = s.trim();
     let hex = match s.split_once(':') {
         Some((proto, rest)) => {
@@ -1983,7 +2865,9 @@ fn parse_blake3_handle(s: &str) -> Result<Value<Handle<Blake3, SimpleArchive>>>
     Ok(Value::new(raw))
 }
 
-fn parse_blake3_handle_opt(s: &str) -> Result<Option<Value<Handle<Blake3, SimpleArchive>>>> {
+pub(crate) fn parse_blake3_handle_opt(
+    s: &str,
+) -> Result<Option<Value<Handle<Blake3, SimpleArchive>>>> {
     let s = s.trim();
     if s == "-" || s.eq_ignore_ascii_case("none") {
         return Ok(None);
@@ -1997,18 +2881,124 @@ fn parse_blake3_handle_opt(s: &str) -> Result<Option<Value<Handle<Blake3, Simple
 /// create new branches. Returns the number of branches created.
 ///
 /// `statuses` maps branch IDs to display labels (e.g. "active"/"deleted").
+/// Merge the heads of every `NAME--orphan-*` branch into the canonical NAME
+/// branch with a single merge commit, retrying against concurrent writers
+/// the way `merge-import` does. With `retire` the orphan
+/// branches are tombstoned once the canonical branch contains their heads.
+fn consolidate_orphans(
+    repo: &mut Repository<Pile<Blake3>>,
+    key: &ed25519_dalek::SigningKey,
+    name: &str,
+    message: Option<&str>,
+    dry_run: bool,
+    retire: bool,
+) -> Result<()> {
+    repo.storage_mut().refresh()?;
+    let reader = repo
+        .storage_mut()
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let canonical = resolve_branch(repo.storage_mut(), &reader, BranchRef::Name(name))?;
+    let prefix = format!("{name}--orphan-");
+    let orphans: Vec<_> = super::resolve::all_branches(repo.storage_mut(), &reader)?
+        .into_iter()
+        .filter(|b| b.name.as_deref().is_some_and(|n| n.starts_with(&prefix)))
+        .collect();
+    if orphans.is_empty() {
+        note!("no orphan branches named {prefix}*; nothing to consolidate");
+        return Ok(());
+    }
+    note!(
+        "found {} orphan branch(es) of {}",
+        orphans.len(),
+        canonical.label()
+    );
+
+    let commit_hex = |h: Value<Handle<Blake3, SimpleArchive>>| -> String {
+        let hh: Value<Hash<Blake3>> = Handle::to_hash(h);
+        hh.from_value()
+    };
+    // The canonical head goes first so that orphan heads it already contains
+    // are dropped rather than merged again.
+    let heads: Vec<Value<Handle<Blake3, SimpleArchive>>> = canonical
+        .head
+        .into_iter()
+        .chain(orphans.iter().filter_map(|b| b.head))
+        .collect();
+    let (independent, dropped) = independent_heads(&heads, &reader);
+    for (head, why) in &dropped {
+        match why {
+            DroppedHead::Duplicate => note!(
+                "dropping commit {}: another branch has the same head",
+                commit_hex(*head)
+            ),
+            DroppedHead::AncestorOf(other) => note!(
+                "dropping commit {}: already contained in commit {}",
+                commit_hex(*head),
+                commit_hex(*other)
+            ),
+        }
+    }
+    let incoming: Vec<_> = independent
+        .into_iter()
+        .filter(|h| Some(*h) != canonical.head)
+        .collect();
+
+    if dry_run {
+        note!(
+            "would merge {} orphan head(s) into {}",
+            incoming.len(),
+            canonical.label()
+        );
+        if retire {
+            for orphan in &orphans {
+                note!("would retire orphan branch {}", orphan.label());
+            }
+        }
+        note!("dry-run: no changes will be made");
+        return Ok(());
+    }
+
+    if !incoming.is_empty() {
+        super::merge::merge_into_branch(repo.storage_mut(), key, canonical.id, &incoming, message)?;
+    }
+    println!(
+        "merged {} orphan head(s) into {}",
+        incoming.len(),
+        canonical.label()
+    );
+
+    if retire {
+        for orphan in &orphans {
+            match repo
+                .storage_mut()
+                .update(orphan.id, Some(orphan.meta), None)?
+            {
+                triblespace_core::repo::PushResult::Success() => {
+                    note!("retired orphan branch {}", orphan.label());
+                }
+                triblespace_core::repo::PushResult::Conflict(_) => {
+                    eprintln!(
+                        "warning: branch {} advanced concurrently; skipping retire",
+                        orphan.label()
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 fn consolidate_groups(
     groups: &std::collections::BTreeMap<String, Vec<(Id, Option<Value<Handle<Blake3, SimpleArchive>>>)>>,
     statuses: &HashMap<Id, &str>,
     reader: &triblespace_core::repo::pile::PileReader<Blake3>,
     repo: &mut Repository<Pile<Blake3>>,
     key: &ed25519_dalek::SigningKey,
+    message: Option<&str>,
     dry_run: bool,
     delete_sources: bool,
 ) -> Result<usize> {
-    use std::collections::HashSet;
-
-    let parent_attr = triblespace_core::repo::parent.id();
     let mut created_count: usize = 0;
 
     for (name, members) in groups {
@@ -2019,68 +3009,49 @@ fn consolidate_groups(
             if !dry_run && delete_sources {
                 let cleaned = tombstone_branches(repo, members, None)?;
                 if cleaned > 0 {
-                    println!("\nname group \"{name}\" ({} branches): all empty, cleaned up {cleaned} branch(es)", members.len());
+                    note!("\nname group \"{name}\" ({} branches): all empty, cleaned up {cleaned} branch(es)", members.len());
                 } else {
-                    println!("\nname group \"{name}\" ({} branches): all empty, skipping", members.len());
+                    note!(
+                        "\nname group \"{name}\" ({} branches): all empty, skipping",
+                        members.len()
+                    );
                 }
             } else {
-                println!("\nname group \"{name}\" ({} branches): all empty, skipping", members.len());
+                note!(
+                    "\nname group \"{name}\" ({} branches): all empty, skipping",
+                    members.len()
+                );
             }
             continue;
         }
 
-        println!("\nname group \"{name}\" ({} branches, {} with heads):", members.len(), heads.len());
+        note!(
+            "\nname group \"{name}\" ({} branches, {} with heads):",
+            members.len(),
+            heads.len()
+        );
         for (bid, head) in members {
             let status = statuses.get(bid).copied().unwrap_or("?");
             if let Some(h) = head {
                 let hh: Value<Hash<Blake3>> = Handle::to_hash(*h);
                 let hex: String = hh.from_value();
-                println!("  - {bid:X} [{status}] head={}", &hex[..23]);
+                note!("  - {bid:X} [{status}] head={}", &hex[..23]);
             } else {
-                println!("  - {bid:X} [{status}] <no head>");
+                note!("  - {bid:X} [{status}] <no head>");
             }
         }
 
-        // Deduplicate heads (same commit on multiple branch IDs).
-        let unique_heads: Vec<Value<Handle<Blake3, SimpleArchive>>> = {
-            let mut seen: HashSet<[u8; 32]> = HashSet::new();
-            heads.iter().copied().filter(|h| seen.insert(h.raw)).collect()
-        };
-
-        // Compute subsumption: a head is subsumed if another head
-        // has it as an ancestor.
-        let mut subsumed: HashSet<[u8; 32]> = HashSet::new();
-        if unique_heads.len() > 1 {
-            for i in 0..unique_heads.len() {
-                if subsumed.contains(&unique_heads[i].raw) { continue; }
-                for j in 0..unique_heads.len() {
-                    if i == j { continue; }
-                    if subsumed.contains(&unique_heads[j].raw) { continue; }
-                    match is_ancestor_of(unique_heads[i], unique_heads[j], reader, &parent_attr) {
-                        Ok(true) => {
-                            subsumed.insert(unique_heads[i].raw);
-                            let hh: Value<Hash<Blake3>> = Handle::to_hash(unique_heads[i]);
-                            let hex: String = hh.from_value();
-                            println!("  ({}... subsumed)", &hex[..23]);
-                            break;
-                        }
-                        Ok(false) => {}
-                        Err(e) => {
-                            eprintln!("  warning: ancestry check failed: {e:#}");
-                        }
-                    }
-                }
+        let (non_subsumed, dropped) = independent_heads(&heads, reader);
+        for (head, why) in &dropped {
+            if let DroppedHead::AncestorOf(_) = why {
+                let hh: Value<Hash<Blake3>> = Handle::to_hash(*head);
+                let hex: String = hh.from_value();
+                note!("  ({}... subsumed)", &hex[..23]);
             }
         }
 
-        let non_subsumed: Vec<Value<Handle<Blake3, SimpleArchive>>> = unique_heads
-            .iter()
-            .copied()
-            .filter(|h| !subsumed.contains(&h.raw))
-            .collect();
-
         if non_subsumed.is_empty() {
-            println!("  -> all heads subsumed, skipping");
+            note!("  -> all heads subsumed, skipping");
             continue;
         }
 
@@ -2093,7 +3064,9 @@ fn consolidate_groups(
             });
             if already_active {
                 if dry_run {
-                    println!("  -> already consolidated (active branch has the sole non-subsumed head)");
+                    note!(
+                        "  -> already consolidated (active branch has the sole non-subsumed head)"
+                    );
                 } else if delete_sources {
                     let keeper = members.iter().find(|(bid, head)| {
                         head.as_ref() == Some(&dominated_head)
@@ -2101,37 +3074,45 @@ fn consolidate_groups(
                     }).map(|(b, _)| *b);
                     let cleaned = tombstone_branches(repo, members, keeper)?;
                     if cleaned > 0 {
-                        println!("  -> already consolidated, cleaned up {cleaned} redundant branch(es)");
+                        note!(
+                            "  -> already consolidated, cleaned up {cleaned} redundant branch(es)"
+                        );
                     } else {
-                        println!("  -> already consolidated, skipping");
+                        note!("  -> already consolidated, skipping");
                     }
                 } else {
-                    println!("  -> already consolidated, skipping");
+                    note!("  -> already consolidated, skipping");
                 }
                 continue;
             }
         }
 
         if dry_run {
-            println!("  -> would merge {} non-subsumed head(s) into \"{name}\"", non_subsumed.len());
+            note!(
+                "  -> would merge {} non-subsumed head(s) into \"{name}\"",
+                non_subsumed.len()
+            );
             continue;
         }
 
         let commit_handle = if non_subsumed.len() == 1 {
-            println!("  -> single non-subsumed head, creating branch directly");
+            note!("  -> single non-subsumed head, creating branch directly");
             non_subsumed[0]
         } else {
-            println!("  -> merging {} non-subsumed heads", non_subsumed.len());
+            note!("  -> merging {} non-subsumed heads", non_subsumed.len());
+            let message = message
+                .map(|m| verify::put::<LongString, _>(repo.storage_mut(), m.to_string()))
+                .transpose()
+                .context("put message")?;
             let commit_set = triblespace_core::repo::commit::commit_metadata(
                 key,
                 non_subsumed.clone(),
-                None,
+                message,
                 None,
                 None,
             );
-            repo.storage_mut()
-                .put(commit_set.to_blob())
-                .map_err(|e| anyhow::anyhow!("failed to put commit blob: {e:?}"))?
+            verify::put(repo.storage_mut(), commit_set.to_blob())
+                .context("failed to put commit blob")?
         };
 
         let new_id = *repo
@@ -2142,13 +3123,66 @@ fn consolidate_groups(
 
         if delete_sources {
             let cleaned = tombstone_branches(repo, members, Some(new_id))?;
-            println!("  deleted {cleaned} source branch(es)");
+            note!("  deleted {cleaned} source branch(es)");
         }
     }
 
     Ok(created_count)
 }
 
+/// Why [`independent_heads`] left a candidate head out.
+enum DroppedHead {
+    /// An earlier candidate has the same head.
+    Duplicate,
+    /// The head is reachable from this other candidate head.
+    AncestorOf(Value<Handle<Blake3, SimpleArchive>>),
+}
+
+/// Deduplicate `heads` and drop every head reachable from another one, so a
+/// merge of what remains has no redundant parents. Returns the remaining
+/// heads in input order together with each dropped head and the reason.
+fn independent_heads(
+    heads: &[Value<Handle<Blake3, SimpleArchive>>],
+    reader: &impl BlobStoreGet<Blake3>,
+) -> (
+    Vec<Value<Handle<Blake3, SimpleArchive>>>,
+    Vec<(Value<Handle<Blake3, SimpleArchive>>, DroppedHead)>,
+) {
+    use std::collections::HashSet;
+
+    let parent_attr = triblespace_core::repo::parent.id();
+    let mut dropped = Vec::new();
+    let mut seen: HashSet<[u8; 32]> = HashSet::new();
+    let mut unique_heads = Vec::new();
+    for head in heads {
+        if seen.insert(head.raw) {
+            unique_heads.push(*head);
+        } else {
+            dropped.push((*head, DroppedHead::Duplicate));
+        }
+    }
+
+    let mut subsumed: HashSet<[u8; 32]> = HashSet::new();
+    for (i, head) in unique_heads.iter().enumerate() {
+        for (j, other) in unique_heads.iter().enumerate() {
+            if i == j || subsumed.contains(&other.raw) {
+                continue;
+            }
+            match is_ancestor_of(*head, *other, reader, &parent_attr) {
+                Ok(true) => {
+                    subsumed.insert(head.raw);
+                    dropped.push((*head, DroppedHead::AncestorOf(*other)));
+                    break;
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("  warning: ancestry check failed: {e:#}"),
+            }
+        }
+    }
+    unique_heads.retain(|h| !subsumed.contains(&h.raw));
+    (unique_heads, dropped)
+}
+
 /// Tombstone all branches in `members` except `keeper`. Returns the number tombstoned.
 fn tombstone_branches(
     repo: &mut Repository<Pile<Blake3>>,
@@ -2169,38 +3203,35 @@ fn tombstone_branches(
     Ok(count)
 }
 
-fn is_ancestor_of(
+pub(crate) fn is_ancestor_of(
     ancestor: Value<Handle<Blake3, SimpleArchive>>,
     descendant: Value<Handle<Blake3, SimpleArchive>>,
     reader: &impl BlobStoreGet<Blake3>,
     parent_attr: &Id,
 ) -> Result<bool> {
-    use std::collections::HashSet;
+    use std::ops::ControlFlow;
 
-    let mut visited: HashSet<[u8; 32]> = HashSet::new();
-    let mut stack: Vec<Value<Handle<Blake3, SimpleArchive>>> = vec![descendant];
-
-    while let Some(current) = stack.pop() {
+    let found = walk_commits(descendant, DEFAULT_MAX_DEPTH, |current| {
         if current.raw == ancestor.raw {
-            return Ok(true);
-        }
-        if !visited.insert(current.raw) {
-            continue;
+            return ControlFlow::Break(());
         }
         let commit: TribleSet = match reader.get(current) {
             Ok(c) => c,
-            Err(_) => continue, // Missing blob — stop traversal on this branch.
+            // Missing blob — stop traversal on this branch.
+            Err(_) => return ControlFlow::Continue(Vec::new()),
         };
-        for t in commit.iter() {
-            if t.a() == parent_attr {
-                stack.push(*t.v::<Handle<Blake3, SimpleArchive>>());
-            }
-        }
-    }
-    Ok(false)
+        ControlFlow::Continue(
+            commit
+                .iter()
+                .filter(|t| t.a() == parent_attr)
+                .map(|t| *t.v::<Handle<Blake3, SimpleArchive>>())
+                .collect(),
+        )
+    })?;
+    Ok(found.is_some())
 }
 
-fn load_branch_name(
+pub(crate) fn load_branch_name(
     reader: &impl BlobStoreGet<Blake3>,
     meta: &TribleSet,
 ) -> Result<Option<String>> {
@@ -2225,6 +3256,107 @@ fn load_branch_name(
     Ok(Some(view.as_ref().to_string()))
 }
 
+/// Resolve a `--id`/`--name` pair (exactly one given) to a branch id.
+/// The head commit `branch watch` compares between polls; `None` once the
+/// branch is deleted or while its metadata carries no head.
+fn watched_head(
+    pile: &mut Pile<Blake3>,
+    branch_id: Id,
+) -> Result<Option<Value<Handle<Blake3, SimpleArchive>>>> {
+    let Some(meta) = pile.head(branch_id)? else {
+        return Ok(None);
+    };
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let meta: TribleSet = reader.get(meta).map_err(|e| {
+        crate::cli::error::corrupt(format!(
+            "read branch metadata blake3:{}: {e:?}",
+            hex::encode(meta.raw)
+        ))
+    })?;
+    Ok(extract_repo_head(&meta))
+}
+
+fn show_head(head: Option<Value<Handle<Blake3, SimpleArchive>>>) -> String {
+    head.map(|h| format!("blake3:{}", hex::encode(h.raw)))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub(crate) fn select_branch(
+    pile: &mut Pile<Blake3>,
+    id: Option<&str>,
+    name: Option<&str>,
+) -> Result<Id> {
+    let branch = match (id, name) {
+        (Some(id), _) => BranchRef::Id(id),
+        (None, Some(name)) => BranchRef::Name(name),
+        (None, None) => {
+            return Err(crate::cli::error::invalid_argument(
+                "pass either --id or --name to select a branch",
+            ))
+        }
+    };
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    Ok(resolve_branch(pile, &reader, branch)?.id)
+}
+
+/// Resolve a positional branch argument (id, unique id prefix or name).
+pub(crate) fn find_branch(pile: &mut Pile<Blake3>, raw: &str) -> Result<Id> {
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    Ok(resolve_branch(pile, &reader, BranchRef::Any(raw))?.id)
+}
+
+/// The union of the content of every commit reachable from a branch head.
+pub(crate) fn head_content(pile: &mut Pile<Blake3>, branch_id: Id) -> Result<TribleSet> {
+    let meta = pile
+        .head(branch_id)?
+        .ok_or_else(|| crate::cli::error::not_found(format!("branch {branch_id:X} not found")))?;
+    let reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let meta: TribleSet = reader
+        .get(meta)
+        .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
+    match extract_repo_head(&meta) {
+        Some(head) => commit_content(&reader, head),
+        None => Ok(TribleSet::new()),
+    }
+}
+
+/// The union of the content of `commit` and all of its ancestors: the
+/// state checked out at that commit.
+pub(crate) fn commit_content(
+    reader: &triblespace_core::repo::pile::PileReader<Blake3>,
+    commit: Value<Handle<Blake3, SimpleArchive>>,
+) -> Result<TribleSet> {
+    use std::ops::ControlFlow;
+
+    let mut content = TribleSet::new();
+    let failed = walk_commits(commit, DEFAULT_MAX_DEPTH, |current| {
+        let commit: TribleSet = match reader.get(current) {
+            Ok(commit) => commit,
+            Err(e) => return ControlFlow::Break(anyhow::anyhow!("read commit: {e:?}")),
+        };
+        let info = read_commit_fields(&commit);
+        if let Some(handle) = info.content {
+            match reader.get::<TribleSet, _>(handle) {
+                Ok(part) => content += part,
+                Err(e) => return ControlFlow::Break(anyhow::anyhow!("read commit content: {e:?}")),
+            }
+        }
+        ControlFlow::Continue(info.parents)
+    })?;
+    match failed {
+        Some(e) => Err(e),
+        None => Ok(content),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2242,7 +3374,7 @@ mod tests {
         let mut f = NamedTempFile::new().expect("tmpfile");
         writeln!(f, "{}", hex).expect("write");
         let path = f.path().to_path_buf();
-        let key = load_signing_key(&Some(path)).expect("parse file");
+        let key = load_signing_key(&Some(path), false).expect("parse file");
         let expected = ed25519_dalek::SigningKey::from_bytes(&seed);
         assert_eq!(key.to_bytes(), expected.to_bytes());
     }
@@ -0,0 +1,130 @@
+//! `trible pile commit`: inspect individual commits.
+
+use anyhow::Result;
+use clap::Parser;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace_core::repo::pile::PileReader;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::output::{note, Field, Output};
+
+use super::branch::{commit_content, commit_message, parse_blake3_handle, read_commit_fields};
+use super::entity::{attribute_fields, display_value};
+use super::ClosingPile;
+
+type CommitHandle = Value<Handle<Blake3, SimpleArchive>>;
+
+#[derive(Parser)]
+pub enum Command {
+    /// Show the tribles added and removed between two commits.
+    ///
+    /// Compares the content checked out at COMMIT_B, the base, with the
+    /// content checked out at COMMIT_A. COMMIT_B defaults to COMMIT_A's first
+    /// parent (in handle order for merges); root commits are compared with
+    /// the empty set. Long strings are resolved and known attributes
+    /// labelled.
+    Diff {
+        /// Path to the pile file to inspect
+        pile: PathBuf,
+        /// Commit handle (blake3:... or raw 64-char hex)
+        commit_a: String,
+        /// Base commit handle
+        commit_b: Option<String>,
+    },
+}
+
+pub fn run(cmd: Command) -> Result<()> {
+    match cmd {
+        Command::Diff {
+            pile,
+            commit_a,
+            commit_b,
+        } => diff(pile, commit_a, commit_b),
+    }
+}
+
+fn diff(pile: PathBuf, commit_a: String, commit_b: Option<String>) -> Result<()> {
+    let target = parse_blake3_handle(&commit_a)?;
+    let base = commit_b.as_deref().map(parse_blake3_handle).transpose()?;
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let info = read_commit(&reader, target)?;
+        if let Some(message) = commit_message(&reader, &info) {
+            note!("message: {message}");
+        }
+        let base = match base {
+            Some(base) => Some(base),
+            None => info.parents.first().copied(),
+        };
+        let (added, removed) = content_diff(&reader, target, base)?;
+
+        let entities: HashSet<&[u8]> = added.iter().chain(&removed).map(|t| &t[..16]).collect();
+        let mut out = Output::stdout();
+        for (change, tribles) in [("added", &added), ("removed", &removed)] {
+            for t in tribles {
+                let (attribute, label) = attribute_fields(t)?;
+                out.record(&[
+                    ("change", Field::from(change)),
+                    ("entity", Field::from(hex::encode_upper(&t[..16]))),
+                    ("attribute", attribute),
+                    ("label", label),
+                    (
+                        "value",
+                        Field::from(display_value(&reader, t[32..].try_into()?, &entities)),
+                    ),
+                ])?;
+            }
+        }
+        note!("{} added, {} removed", added.len(), removed.len());
+        Ok(())
+    })
+}
+
+fn read_commit(
+    reader: &PileReader<Blake3>,
+    commit: CommitHandle,
+) -> Result<super::branch::CommitInfo> {
+    let set: TribleSet = reader.get(commit).map_err(|_| {
+        crate::cli::error::not_found(format!("commit {} not found", hex::encode(commit.raw)))
+    })?;
+    Ok(read_commit_fields(&set))
+}
+
+/// Tribles checked out at `target` but not at `base`, and the reverse, each
+/// sorted. Without a base everything at `target` counts as added.
+pub(crate) fn content_diff(
+    reader: &PileReader<Blake3>,
+    target: CommitHandle,
+    base: Option<CommitHandle>,
+) -> Result<(Vec<[u8; 64]>, Vec<[u8; 64]>)> {
+    read_commit(reader, target)?;
+    let new: HashSet<[u8; 64]> = commit_content(reader, target)?
+        .iter()
+        .map(|t| t.data)
+        .collect();
+    let old: HashSet<[u8; 64]> = match base {
+        Some(base) => {
+            read_commit(reader, base)?;
+            commit_content(reader, base)?
+                .iter()
+                .map(|t| t.data)
+                .collect()
+        }
+        None => HashSet::new(),
+    };
+    let mut added: Vec<[u8; 64]> = new.difference(&old).copied().collect();
+    let mut removed: Vec<[u8; 64]> = old.difference(&new).copied().collect();
+    added.sort_unstable();
+    removed.sort_unstable();
+    Ok((added, removed))
+}
@@ -3,15 +3,35 @@ use clap::Parser;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 
+use crate::cli::output::{note, Output, OutputFormat};
+use crate::cli::report::{
+    self, BlobCheck, BranchCheck, CanonicalCheck, DiagnoseReport, NonCanonical, SpaceReport,
+};
+use crate::cli::stats;
+use crate::cli::style::{self, Color};
+
 #[derive(Parser)]
 pub enum Command {
     /// Verify pile integrity (blob hash validation + branch commit-chain checks).
     Check {
         /// Path to the pile file to inspect
-        pile: PathBuf,
+        pile: Option<PathBuf>,
         /// Exit non-zero at the first detected issue
         #[arg(long)]
         fail_fast: bool,
+        /// Report a commit chain error instead of following parent links
+        /// more than this many commits deep
+        #[arg(long, default_value_t = super::walk::DEFAULT_MAX_DEPTH)]
+        max_depth: usize,
+        /// Also report the bytes held by invalid blobs, by blobs no branch
+        /// reaches and by duplicate blob records (extra passes over the pile);
+        /// blobs only `pile pin` keeps are counted separately
+        #[arg(long)]
+        space: bool,
+        /// Also report trible set archives whose stored bytes differ from
+        /// their canonical encoding (decodes every archive blob)
+        #[arg(long)]
+        canonical: bool,
     },
     /// Locate occurrences of a blob handle in raw pile bytes.
     ///
@@ -19,9 +39,10 @@ pub enum Command {
     /// points at a missing blob) and you want to distinguish:
     /// - a missing blob record (0 header matches), vs
     /// - a blob referenced inside other blob payloads (payload refs)
+    #[command(allow_missing_positional = true)]
     LocateHash {
         /// Path to the pile file to inspect
-        pile: PathBuf,
+        pile: Option<PathBuf>,
         /// Handle to locate (e.g. "blake3:HEX..." or bare 64 hex)
         handle: String,
     },
@@ -29,12 +50,32 @@ pub enum Command {
 
 pub fn run(cmd: Command) -> Result<()> {
     match cmd {
-        Command::Check { pile, fail_fast } => check(&pile, fail_fast),
-        Command::LocateHash { pile, handle } => locate_hash_in_pile(&pile, &handle),
+        Command::Check {
+            pile,
+            fail_fast,
+            max_depth,
+            space,
+            canonical,
+        } => check(
+            &super::resolve_pile(pile)?,
+            fail_fast,
+            max_depth,
+            space,
+            canonical,
+        ),
+        Command::LocateHash { pile, handle } => {
+            locate_hash_in_pile(&super::resolve_pile(pile)?, &handle)
+        }
     }
 }
 
-fn check(pile_path: &Path, fail_fast: bool) -> Result<()> {
+fn check(
+    pile_path: &Path,
+    fail_fast: bool,
+    max_depth: usize,
+    space: bool,
+    canonical: bool,
+) -> Result<()> {
     use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
     use triblespace::prelude::{BlobStore, BlobStoreGet, BranchStore};
 
@@ -46,16 +87,34 @@ fn check(pile_path: &Path, fail_fast: bool) -> Result<()> {
     use triblespace_core::value::Value;
 
     match Pile::<Blake3>::open(pile_path) {
-        Ok(mut pile) => {
-            let res = (|| -> Result<(), anyhow::Error> {
+        Ok(pile) => {
+            super::ClosingPile::new(pile).run(|pile| {
+                // Under `--output json` stdout carries only the report.
+                let json = crate::cli::output::format() == OutputFormat::Json;
+                macro_rules! say {
+                    ($($arg:tt)*) => {
+                        if json {
+                            esay!($($arg)*)
+                        } else {
+                            say!($($arg)*)
+                        }
+                    };
+                }
+                let mut report = DiagnoseReport::default();
                 let mut any_error = false;
+                // Records past this offset may have been written while the
+                // checks ran and are reported as possibly unchecked.
+                let scanned_to = super::scan::appended_since(pile_path, 0)?.end;
+                super::refresh_for_read(pile)?;
                 let reader = pile
                     .reader()
                     .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
 
                 // Blob hash validation.
                 let mut invalid = 0usize;
+                let mut invalid_bytes = 0u64;
                 let mut total = 0usize;
+                let mut valid = Vec::new();
                 for item in reader.iter() {
                     match item {
                         Ok((handle, blob)) => {
@@ -65,6 +124,9 @@ fn check(pile_path: &Path, fail_fast: bool) -> Result<()> {
                             let computed = Hash::<Blake3>::digest(&blob.bytes);
                             if expected != computed {
                                 invalid += 1;
+                                invalid_bytes += blob.bytes.len() as u64;
+                            } else if canonical {
+                                valid.push(Value::<Handle<Blake3, SimpleArchive>>::new(handle.raw));
                             }
                         }
                         Err(_) => {
@@ -75,110 +137,92 @@ fn check(pile_path: &Path, fail_fast: bool) -> Result<()> {
                     }
                 }
 
+                report.blobs = BlobCheck {
+                    total: total as u64,
+                    invalid: invalid as u64,
+                };
                 if invalid == 0 {
-                    println!("Pile appears healthy");
+                    say!("{}", style::paint(Color::Green, "Pile appears healthy"));
                 } else {
-                    println!("Pile corrupt: {invalid} of {total} blobs have incorrect hashes");
+                    say!(
+                        "{}",
+                        style::paint(
+                            Color::Red,
+                            format!(
+                                "Pile corrupt: {invalid} of {total} blobs have incorrect hashes"
+                            )
+                        )
+                    );
                     if fail_fast {
-                        anyhow::bail!("invalid blob hashes detected");
+                        return Err(crate::cli::error::corrupt("invalid blob hashes detected"));
                     }
                     any_error = true;
                 }
 
-                // Branch integrity diagnostics.
-                println!("\nBranches:");
-                let _repo_branch_attr: triblespace_core::id::Id =
-                    id_hex!("8694CC73AF96A5E1C7635C677D1B928A");
-                let repo_head_attr: triblespace_core::id::Id =
-                    id_hex!("272FBC56108F336C4D2E17289468C35F");
-                let repo_parent_attr: triblespace_core::id::Id =
-                    id_hex!("317044B612C690000D798CA660ECFD2A");
-                let repo_content_attr: triblespace_core::id::Id =
-                    id_hex!("4DD4DDD05CC31734B03ABB4E43188B1F");
-
-                fn verify_chain(
-                    reader: &triblespace_core::repo::pile::PileReader<Blake3>,
-                    start: Value<Handle<Blake3, SimpleArchive>>,
-                    repo_parent_attr: triblespace_core::id::Id,
-                    repo_content_attr: triblespace_core::id::Id,
-                ) -> (usize, Option<String>) {
-                    use std::collections::BTreeSet;
-                    let mut visited: BTreeSet<String> = BTreeSet::new();
-                    let mut stack: Vec<Value<Handle<Blake3, SimpleArchive>>> = vec![start];
-                    let mut count = 0usize;
-                    while let Some(h) = stack.pop() {
-                        let hh: Value<Hash<Blake3>> = Handle::to_hash(h);
-                        let hex: String = hh.from_value();
-                        if !visited.insert(hex.clone()) {
+                if canonical {
+                    let mut archives = 0usize;
+                    let mut differing = 0usize;
+                    let mut not_canonical = Vec::new();
+                    say!("\nCanonical encoding:");
+                    for handle in valid {
+                        let Some(canonical) = super::blob::canonical_handle(&reader, handle) else {
                             continue;
-                        }
-                        match reader.metadata(h) {
-                            Ok(None) => {
-                                return (count, Some(format!("commit blake3:{hex} missing")));
-                            }
-                            Ok(Some(_)) => {}
-                            Err(e) => {
-                                return (
-                                    count,
-                                    Some(format!("commit blake3:{hex} metadata error: {e:?}")),
-                                );
-                            }
-                        }
-                        let meta: TribleSet = match reader.get::<TribleSet, SimpleArchive>(h) {
-                            Ok(m) => m,
-                            Err(e) => {
-                                return (
-                                    count,
-                                    Some(format!("commit blake3:{hex} decode failed: {e:?}")),
-                                )
-                            }
                         };
-                        let mut content_handle: Option<Value<Handle<Blake3, SimpleArchive>>> = None;
-                        let mut parents: Vec<Value<Handle<Blake3, SimpleArchive>>> = Vec::new();
-                        for t in meta.iter() {
-                            if t.a() == &repo_content_attr {
-                                content_handle = Some(*t.v::<Handle<Blake3, SimpleArchive>>());
-                            } else if t.a() == &repo_parent_attr {
-                                parents.push(*t.v::<Handle<Blake3, SimpleArchive>>());
-                            }
-                        }
-                        // Some commits (for example merge-only commits) intentionally do not carry
-                        // a content blob. Only verify content existence when present.
-                        if let Some(c) = content_handle {
-                            match reader.metadata(c) {
-                                Ok(Some(_)) => {}
-                                Ok(None) => {
-                                    return (
-                                        count,
-                                        Some(format!("commit blake3:{hex} content blob missing")),
-                                    );
-                                }
-                                Err(e) => {
-                                    return (
-                                        count,
-                                        Some(format!("commit blake3:{hex} metadata error: {e:?}")),
-                                    );
-                                }
-                            }
+                        archives += 1;
+                        if canonical != handle {
+                            differing += 1;
+                            not_canonical.push(NonCanonical {
+                                handle: format!("blake3:{}", hex::encode(handle.raw)),
+                                canonical: format!("blake3:{}", hex::encode(canonical.raw)),
+                            });
+                            say!(
+                                "  {}",
+                                style::paint(
+                                    Color::Red,
+                                    format!(
+                                        "blake3:{} is not canonical; canonical handle blake3:{}",
+                                        hex::encode(handle.raw),
+                                        hex::encode(canonical.raw)
+                                    )
+                                )
+                            );
                         }
-                        for p in parents {
-                            stack.push(p);
+                    }
+                    say!("  {archives} archive(s) checked, {differing} not canonical");
+                    report.canonical = Some(CanonicalCheck {
+                        archives: archives as u64,
+                        not_canonical,
+                    });
+                    if differing > 0 {
+                        if fail_fast {
+                            return Err(crate::cli::error::corrupt(
+                                "non-canonical archives detected",
+                            ));
                         }
-                        count += 1;
+                        any_error = true;
                     }
-                    (count, None)
                 }
 
-                // Ensure in-memory indices are loaded before enumerating branches.
-                pile.refresh()?;
-                let iter = pile.branches()?;
-                for r in iter {
-                    let bid = r?;
+                // Branch integrity diagnostics.
+                say!("\nBranches:");
+                let _repo_branch_attr: triblespace_core::id::Id =
+                    id_hex!("8694CC73AF96A5E1C7635C677D1B928A");
+                let repo_head_attr: triblespace_core::id::Id =
+                    id_hex!("272FBC56108F336C4D2E17289468C35F");
+
+                let mut chains = ChainVerifier::new(&reader, max_depth);
+
+                for bid in crate::cli::remote_state::branch_ids(pile, &reader)? {
                     let meta_handle_opt = pile.head(bid)?;
                     let id_hex = format!("{bid:X}");
+                    let mut entry = BranchCheck {
+                        id: id_hex.clone(),
+                        ..Default::default()
+                    };
                     match meta_handle_opt {
                         None => {
-                            println!("- {id_hex}: <no branch metadata head set>");
+                            say!("- {id_hex}: <no branch metadata head set>");
+                            report.branches.push(entry);
                         }
                         Some(meta_handle) => {
                             let meta_present = reader.metadata(meta_handle)?.is_some();
@@ -210,76 +254,396 @@ fn check(pile_path: &Path, fail_fast: bool) -> Result<()> {
                             }
                             let meta_hash: Value<Hash<Blake3>> = Handle::to_hash(meta_handle);
                             let meta_hex: String = meta_hash.from_value();
+                            entry.name = name_val.clone();
+                            entry.meta = Some(format!("blake3:{meta_hex}"));
+                            entry.meta_present = meta_present;
+                            entry.meta_error = meta_err.clone();
+                            entry.head = head_val.map(|h| format!("blake3:{}", hex::encode(h.raw)));
                             if let Some(n) = name_val.as_ref() {
-                                println!(
-                                    "- {id_hex} ({n}): meta blake3:{meta_hex} [{}]{}",
-                                    if meta_present { "present" } else { "missing" },
+                                say!(
+                                    "- {id_hex} ({n}): meta blake3:{meta_hex} {}{}",
+                                    style::presence(meta_present),
                                     meta_err
                                         .as_deref()
                                         .map(|e| format!(" ({e})"))
                                         .unwrap_or_default()
                                 );
                             } else {
-                                println!(
-                                    "- {id_hex}: meta blake3:{meta_hex} [{}]{}",
-                                    if meta_present { "present" } else { "missing" },
+                                say!(
+                                    "- {id_hex}: meta blake3:{meta_hex} {}{}",
+                                    style::presence(meta_present),
                                     meta_err
                                         .as_deref()
                                         .map(|e| format!(" ({e})"))
                                         .unwrap_or_default()
                                 );
                             }
+                            if !meta_present || meta_err.is_some() {
+                                report.branches.push(entry);
+                            }
                             if !meta_present {
                                 if fail_fast {
-                                    anyhow::bail!("branch metadata blob missing for {id_hex}");
+                                    return Err(crate::cli::error::corrupt(format!(
+                                        "branch metadata blob missing for {id_hex}"
+                                    )));
                                 }
                                 any_error = true;
                                 continue;
                             }
                             if meta_err.is_some() {
                                 if fail_fast {
-                                    anyhow::bail!("branch metadata decode failed for {id_hex}");
+                                    return Err(crate::cli::error::corrupt(format!(
+                                        "branch metadata decode failed for {id_hex}"
+                                    )));
                                 }
                                 any_error = true;
                                 continue;
                             }
                             if let Some(head) = head_val {
-                                let (count, err) =
-                                    verify_chain(&reader, head, repo_parent_attr, repo_content_attr);
+                                let shallow =
+                                    crate::cli::remote_state::shallow_boundary(pile, &reader, bid)?;
+                                let (count, err) = chains.verify_shallow_chain(head, &shallow);
+                                entry.shallow = !shallow.is_empty();
+                                entry.chain_error = err.clone();
+                                if err.is_none() {
+                                    entry.commits = Some(count as u64);
+                                }
                                 if let Some(e) = err {
-                                    println!("  commit chain error: {e}");
+                                    say!(
+                                        "  {}",
+                                        style::paint(
+                                            Color::Red,
+                                            format!("commit chain error: {e}")
+                                        )
+                                    );
                                     if fail_fast {
-                                        anyhow::bail!(e);
+                                        return Err(crate::cli::error::corrupt(e));
                                     }
                                     any_error = true;
                                 } else {
-                                    println!("  commit chain: {count} commits");
+                                    say!(
+                                        "  {}",
+                                        style::paint(
+                                            Color::Green,
+                                            if shallow.is_empty() {
+                                                format!("commit chain: {count} commits")
+                                            } else {
+                                                format!("commit chain: {count} commits (shallow)")
+                                            }
+                                        )
+                                    );
                                 }
                             } else {
-                                println!("  no head set");
+                                say!("  no head set");
                             }
+                            report.branches.push(entry);
                         }
                     }
                 }
 
+                if space {
+                    let space = space_report(pile_path, pile, &reader, invalid_bytes)?;
+                    if !json {
+                        print_space(&space)?;
+                    }
+                    report.space = Some(space);
+                }
+
+                let late = super::scan::appended_since(pile_path, scanned_to)?;
+                report.late_blobs = late.blobs as u64;
+                if late.blobs > 0 {
+                    note!(
+                        "{} blob(s) were appended while diagnose ran and may not have been checked",
+                        late.blobs
+                    );
+                }
+
+                report.healthy = !any_error;
+                if json {
+                    report::emit(&report)?;
+                }
                 if any_error {
-                    anyhow::bail!("diagnostics reported issues");
+                    return Err(crate::cli::error::corrupt("diagnostics reported issues"));
                 }
 
                 Ok(())
-            })();
-
-            let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-            res.and(close_res)?;
+            })?;
         }
         Err(ReadError::IoError(err)) if err.kind() == std::io::ErrorKind::NotFound => {
-            anyhow::bail!("pile not found");
+            return Err(crate::cli::error::not_found("pile not found"));
         }
         Err(e) => return Err(e.into()),
     }
     Ok(())
 }
 
+type CommitHandle = triblespace_core::value::Value<
+    triblespace_core::value::schemas::hash::Handle<
+        triblespace_core::value::schemas::hash::Blake3,
+        triblespace::prelude::blobschemas::SimpleArchive,
+    >,
+>;
+
+/// What checking a single commit found.
+enum CommitCheck {
+    Ok { parents: Vec<CommitHandle> },
+    Failed(String),
+}
+
+/// Verifies branch commit chains, checking every commit at most once.
+///
+/// Branches usually share most of their history; the per-commit results are
+/// kept across [`ChainVerifier::verify_chain`] calls so each shared commit is
+/// decoded and its content looked up only for the first branch reaching it.
+pub(super) struct ChainVerifier<'a> {
+    reader: &'a triblespace_core::repo::pile::PileReader<
+        triblespace_core::value::schemas::hash::Blake3,
+    >,
+    max_depth: usize,
+    checked: std::collections::HashMap<[u8; 32], CommitCheck>,
+}
+
+impl<'a> ChainVerifier<'a> {
+    pub(super) fn new(
+        reader: &'a triblespace_core::repo::pile::PileReader<
+            triblespace_core::value::schemas::hash::Blake3,
+        >,
+        max_depth: usize,
+    ) -> Self {
+        Self {
+            reader,
+            max_depth,
+            checked: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Count the commits reachable from `start`, stopping at the first
+    /// broken one. Cycles and chains deeper than the limit are broken too.
+    pub(super) fn verify_chain(&mut self, start: CommitHandle) -> (usize, Option<String>) {
+        self.verify_shallow_chain(start, &std::collections::HashSet::new())
+    }
+
+    /// Like [`ChainVerifier::verify_chain`], but commits in `shallow` (the
+    /// boundary of a shallow pull) may be absent; the walk stops there.
+    pub(super) fn verify_shallow_chain(
+        &mut self,
+        start: CommitHandle,
+        shallow: &std::collections::HashSet<[u8; 32]>,
+    ) -> (usize, Option<String>) {
+        use std::ops::ControlFlow;
+        use triblespace_core::repo::BlobStoreMeta;
+
+        let mut count = 0usize;
+        let max_depth = self.max_depth;
+        let reader = self.reader;
+        let walked = super::walk::walk_commits(start, max_depth, |h| {
+            if shallow.contains(&h.raw) && matches!(reader.metadata(h), Ok(None)) {
+                return ControlFlow::Continue(Vec::new());
+            }
+            match self.check(h) {
+                CommitCheck::Failed(e) => ControlFlow::Break(e.clone()),
+                CommitCheck::Ok { parents } => {
+                    count += 1;
+                    ControlFlow::Continue(parents.clone())
+                }
+            }
+        });
+        match walked {
+            Ok(failed) => (count, failed),
+            Err(e) => (count, Some(e.to_string())),
+        }
+    }
+
+    /// Number of distinct commits checked so far.
+    #[cfg(test)]
+    fn checked_count(&self) -> usize {
+        self.checked.len()
+    }
+
+    fn check(&mut self, h: CommitHandle) -> &CommitCheck {
+        let reader = self.reader;
+        self.checked
+            .entry(h.raw)
+            .or_insert_with(|| check_commit(reader, h))
+    }
+}
+
+fn check_commit(
+    reader: &triblespace_core::repo::pile::PileReader<
+        triblespace_core::value::schemas::hash::Blake3,
+    >,
+    h: CommitHandle,
+) -> CommitCheck {
+    use triblespace::prelude::blobschemas::SimpleArchive;
+    use triblespace::prelude::BlobStoreGet;
+    use triblespace_core::repo::BlobStoreMeta;
+    use triblespace_core::trible::TribleSet;
+    use triblespace_core::value::schemas::hash::{Blake3, Handle, Hash};
+    use triblespace_core::value::Value;
+
+    // Only failures are printed; healthy commits never format their hash.
+    let hex = || -> String {
+        let hh: Value<Hash<Blake3>> = Handle::to_hash(h);
+        hh.from_value()
+    };
+    match reader.metadata(h) {
+        Ok(None) => return CommitCheck::Failed(format!("commit blake3:{} missing", hex())),
+        Ok(Some(_)) => {}
+        Err(e) => {
+            return CommitCheck::Failed(format!("commit blake3:{} metadata error: {e:?}", hex()))
+        }
+    }
+    let meta: TribleSet = match reader.get::<TribleSet, SimpleArchive>(h) {
+        Ok(m) => m,
+        Err(e) => {
+            return CommitCheck::Failed(format!("commit blake3:{} decode failed: {e:?}", hex()))
+        }
+    };
+    let repo_parent_attr = triblespace_core::repo::parent.id();
+    let repo_content_attr = triblespace_core::repo::content.id();
+    let mut content_handle: Option<CommitHandle> = None;
+    let mut parents: Vec<CommitHandle> = Vec::new();
+    for t in meta.iter() {
+        if t.a() == &repo_content_attr {
+            content_handle = Some(*t.v::<Handle<Blake3, SimpleArchive>>());
+        } else if t.a() == &repo_parent_attr {
+            parents.push(*t.v::<Handle<Blake3, SimpleArchive>>());
+        }
+    }
+    // Some commits (for example merge-only commits) intentionally do not carry
+    // a content blob. Only verify content existence when present.
+    if let Some(c) = content_handle {
+        match reader.metadata(c) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return CommitCheck::Failed(format!(
+                    "commit blake3:{} content blob missing",
+                    hex()
+                ));
+            }
+            Err(e) => {
+                return CommitCheck::Failed(format!(
+                    "commit blake3:{} metadata error: {e:?}",
+                    hex()
+                ));
+            }
+        }
+    }
+    CommitCheck::Ok { parents }
+}
+
+fn print_space(space: &SpaceReport) -> Result<()> {
+    let mut out = Output::stdout();
+    if out.format() != OutputFormat::Plain {
+        return out.record(&[
+            ("invalid_bytes", space.invalid_bytes.into()),
+            ("unreachable_blobs", space.unreachable_blobs.into()),
+            ("unreachable_bytes", space.unreachable_bytes.into()),
+            ("pinned_blobs", space.pinned_blobs.into()),
+            ("pinned_bytes", space.pinned_bytes.into()),
+            ("duplicate_records", space.duplicate_records.into()),
+            ("duplicate_bytes", space.duplicate_bytes.into()),
+        ]);
+    }
+    println!("\nSpace:");
+    println!("  invalid blobs:     {} bytes", space.invalid_bytes);
+    println!(
+        "  unreachable blobs: {} bytes in {} blob(s)",
+        space.unreachable_bytes, space.unreachable_blobs
+    );
+    println!(
+        "  pinned blobs:      {} bytes in {} blob(s)",
+        space.pinned_bytes, space.pinned_blobs
+    );
+    println!(
+        "  duplicate records: {} bytes in {} record(s)",
+        space.duplicate_bytes, space.duplicate_records
+    );
+    Ok(())
+}
+
+fn space_report(
+    pile_path: &Path,
+    pile: &mut triblespace_core::repo::pile::Pile<triblespace_core::value::schemas::hash::Blake3>,
+    reader: &triblespace_core::repo::pile::PileReader<
+        triblespace_core::value::schemas::hash::Blake3,
+    >,
+    invalid_bytes: u64,
+) -> Result<SpaceReport> {
+    use std::collections::HashSet;
+
+    use triblespace::prelude::{BlobStoreList, BranchStore};
+    use triblespace_core::blob::schemas::UnknownBlob;
+    use triblespace_core::repo;
+    use triblespace_core::repo::BlobStoreMeta;
+    use triblespace_core::value::schemas::hash::{Blake3, Handle};
+    use triblespace_core::value::Value;
+
+    // The pins branch is not a root like the others: what only it reaches
+    // is either pinned or its own bookkeeping.
+    let pins = super::pin::load(pile, reader)?;
+    let pins_branch = pins.as_ref().map(|(info, _)| info.id);
+    // Push records and shallow boundaries are no roots either; they only
+    // keep their own records.
+    let ids = crate::cli::remote_state::branch_ids(pile, reader)?;
+    let mut roots: Vec<Value<Handle<Blake3, UnknownBlob>>> = Vec::new();
+    for id in ids {
+        if Some(id) == pins_branch {
+            continue;
+        }
+        if let Some(meta) = pile.head(id)? {
+            roots.push(meta.transmute());
+        }
+    }
+    let (reachable, pinned, protected) = {
+        let _phase = stats::phase!("reachability");
+        let reachable: HashSet<[u8; 32]> = repo::reachable(reader, roots).map(|h| h.raw).collect();
+        let (pinned, mut protected): (HashSet<[u8; 32]>, HashSet<[u8; 32]>) = match &pins {
+            Some((info, pins)) => (
+                repo::reachable(reader, pins.iter().map(|hash| Value::new(*hash)))
+                    .map(|h| h.raw)
+                    .collect(),
+                super::pin::bookkeeping(pile_path, reader, info)?,
+            ),
+            None => Default::default(),
+        };
+        protected.extend(crate::cli::remote_state::bookkeeping(pile, reader)?);
+        (reachable, pinned, protected)
+    };
+
+    let mut unreachable_blobs = 0u64;
+    let mut unreachable_bytes = 0u64;
+    let mut pinned_blobs = 0u64;
+    let mut pinned_bytes = 0u64;
+    for handle in reader.blobs() {
+        let handle: Value<Handle<Blake3, UnknownBlob>> = handle?;
+        if reachable.contains(&handle.raw) {
+            continue;
+        }
+        let Some(meta) = reader.metadata(handle)? else {
+            continue;
+        };
+        if pinned.contains(&handle.raw) {
+            pinned_blobs += 1;
+            pinned_bytes += meta.length;
+        } else if !protected.contains(&handle.raw) {
+            unreachable_blobs += 1;
+            unreachable_bytes += meta.length;
+        }
+    }
+
+    let (duplicate_records, duplicate_bytes) = super::scan::duplicate_blob_records(pile_path)?;
+    Ok(SpaceReport {
+        invalid_bytes,
+        unreachable_blobs,
+        unreachable_bytes,
+        pinned_blobs,
+        pinned_bytes,
+        duplicate_records,
+        duplicate_bytes,
+    })
+}
+
 fn padding_for_blob(blob_size: usize) -> usize {
     // Match `triblespace_core::repo::pile::padding_for_blob` without depending on it.
     (64 - ((64 + blob_size) % 64)) % 64
@@ -408,3 +772,52 @@ fn locate_hash_in_pile(pile_path: &Path, handle: &str) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use tempfile::tempdir;
+    use triblespace::prelude::*;
+    use triblespace_core::repo::pile::Pile;
+    use triblespace_core::repo::Repository;
+    use triblespace_core::value::schemas::hash::Blake3;
+
+    #[test]
+    fn shared_history_is_checked_once_across_branches() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("chains.pile");
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo =
+            Repository::new(pile, SigningKey::from_bytes(&[7; 32]), TribleSet::new()).unwrap();
+        let main = *repo.create_branch("main", None).unwrap();
+        let mut ws = repo.pull(main).unwrap();
+        for i in 0..20 {
+            let label = ws.put::<blobschemas::LongString, _>(format!("commit {i}"));
+            let mut change = TribleSet::new();
+            change += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+            ws.commit(change, "main");
+        }
+        assert!(repo.try_push(&mut ws).unwrap().is_none());
+        let main_head = ws.head().unwrap();
+
+        let feature = *repo.create_branch("feature", Some(main_head)).unwrap();
+        let mut ws = repo.pull(feature).unwrap();
+        ws.commit(TribleSet::new(), "feature");
+        assert!(repo.try_push(&mut ws).unwrap().is_none());
+        let feature_head = ws.head().unwrap();
+
+        let mut pile = repo.into_storage();
+        pile.refresh().unwrap();
+        let reader = pile.reader().unwrap();
+        let mut chains = ChainVerifier::new(&reader, super::super::walk::DEFAULT_MAX_DEPTH);
+        assert_eq!(chains.verify_chain(main_head), (20, None));
+        assert_eq!(chains.checked_count(), 20);
+        assert_eq!(chains.verify_chain(feature_head), (21, None));
+        assert_eq!(chains.checked_count(), 21);
+        assert_eq!(chains.verify_chain(main_head), (20, None));
+        assert_eq!(chains.checked_count(), 21);
+        drop(chains);
+        pile.close().unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+//! `trible pile entity`: look at single entities in a branch's content.
+
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::View;
+use triblespace_core::blob::schemas::longstring::LongString;
+use triblespace_core::id::Id;
+use triblespace_core::repo::pile::PileReader;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::attr::label;
+use crate::cli::output::{note, Field, Output};
+
+use super::branch::{head_content, select_branch};
+use super::query::parse_id;
+use super::ClosingPile;
+
+#[derive(Parser)]
+pub enum Command {
+    /// Print every trible of an entity, then the entities referencing it.
+    ///
+    /// Values that are handles of UTF-8 blobs are printed as text and
+    /// references to other entities as their ids; other values as 64-char
+    /// hex. Each row carries a `section` of `attribute` or `referenced_by`;
+    /// for references the value is the referencing entity.
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "name"])))]
+    Show {
+        /// Path to the pile file to inspect
+        pile: PathBuf,
+        /// Branch to search (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to search (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Entity id (hex)
+        entity: String,
+    },
+}
+
+pub fn run(cmd: Command) -> Result<()> {
+    match cmd {
+        Command::Show {
+            pile,
+            id,
+            name,
+            entity,
+        } => show(pile, id, name, entity),
+    }
+}
+
+fn show(pile: PathBuf, id: Option<String>, name: Option<String>, entity: String) -> Result<()> {
+    let entity = parse_id(&entity)?;
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let branch_id = select_branch(pile, id.as_deref(), name.as_deref())?;
+        let content = head_content(pile, branch_id)?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+
+        let mut reference = [0u8; 32];
+        reference[16..].copy_from_slice(&entity);
+        let mut own: Vec<[u8; 64]> = Vec::new();
+        let mut inbound: Vec<[u8; 64]> = Vec::new();
+        for t in content.iter() {
+            if t.data[..16] == entity {
+                own.push(t.data);
+            } else if t.data[32..] == reference {
+                inbound.push(t.data);
+            }
+        }
+        if own.is_empty() && inbound.is_empty() {
+            return Err(crate::cli::error::not_found(format!(
+                "entity {} not found on branch {branch_id:X}",
+                hex::encode_upper(entity)
+            )));
+        }
+        own.sort_unstable();
+        inbound.sort_unstable_by(|a, b| (&a[16..32], &a[..16]).cmp(&(&b[16..32], &b[..16])));
+        let entities: HashSet<&[u8]> = content.iter().map(|t| &t.data[..16]).collect();
+
+        let mut out = Output::stdout();
+        for t in &own {
+            let value = display_value(&reader, t[32..].try_into()?, &entities);
+            let (attribute, label) = attribute_fields(t)?;
+            out.record(&[
+                ("section", Field::from("attribute")),
+                ("attribute", attribute),
+                ("label", label),
+                ("value", Field::from(value)),
+            ])?;
+        }
+        if !inbound.is_empty() {
+            note!("referenced by:");
+        }
+        for t in &inbound {
+            let (attribute, label) = attribute_fields(t)?;
+            out.record(&[
+                ("section", Field::from("referenced_by")),
+                ("attribute", attribute),
+                ("label", label),
+                ("value", Field::from(hex::encode_upper(&t[..16]))),
+            ])?;
+        }
+        Ok(())
+    })
+}
+
+/// A value as text: the contents of a UTF-8 blob it is a handle of, the id
+/// of one of `entities` it references, or 64-char hex.
+pub(super) fn display_value(
+    reader: &PileReader<Blake3>,
+    raw: [u8; 32],
+    entities: &HashSet<&[u8]>,
+) -> String {
+    let handle: Value<Handle<Blake3, LongString>> = Value::new(raw);
+    match reader.get::<View<str>, _>(handle) {
+        Ok(text) => text.as_ref().to_string(),
+        Err(_) if raw[..16] == [0u8; 16] && entities.contains(&raw[16..]) => {
+            hex::encode_upper(&raw[16..])
+        }
+        Err(_) => hex::encode(raw),
+    }
+}
+
+/// The attribute id and label columns for a trible.
+pub(super) fn attribute_fields(trible: &[u8; 64]) -> Result<(Field, Field)> {
+    let attribute = Id::new(trible[16..32].try_into()?)
+        .ok_or_else(|| anyhow::anyhow!("trible with nil attribute"))?;
+    let label = label(&attribute)
+        .map(Field::from)
+        .unwrap_or_else(|| Field::missing("-"));
+    Ok((Field::from(format!("{attribute:X}")), label))
+}
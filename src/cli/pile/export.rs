@@ -0,0 +1,386 @@
+//! Branch content exports for tools outside triblespace.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::View;
+use triblespace_core::blob::schemas::longstring::LongString;
+use triblespace_core::id::Id;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use super::branch::{head_content, select_branch};
+use super::ClosingPile;
+
+const HEX_BINARY: &str = "http://www.w3.org/2001/XMLSchema#hexBinary";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RdfFormat {
+    /// One quad per line, with the branch id as graph label
+    Nquads,
+    /// One triple per line
+    Ntriples,
+}
+
+/// `pile branch export-rdf`: one statement per trible, sorted by entity,
+/// attribute and value bytes.
+pub fn rdf(
+    pile: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    format: RdfFormat,
+    out: Option<PathBuf>,
+    resolve_strings: bool,
+) -> Result<()> {
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let branch_id = select_branch(pile, id.as_deref(), name.as_deref())?;
+        let content = head_content(pile, branch_id)?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+
+        let mut tribles: Vec<[u8; 64]> = content.iter().map(|t| t.data).collect();
+        tribles.sort_unstable();
+
+        let graph = match format {
+            RdfFormat::Nquads => format!(" {}", iri(&branch_id)),
+            RdfFormat::Ntriples => String::new(),
+        };
+        let mut writer = open_output(out.as_deref())?;
+        for t in &tribles {
+            let entity = Id::new(t[..16].try_into()?);
+            let attribute = Id::new(t[16..32].try_into()?);
+            let (Some(entity), Some(attribute)) = (entity, attribute) else {
+                continue;
+            };
+            let raw: [u8; 32] = t[32..].try_into()?;
+            let text = if resolve_strings {
+                let handle: Value<Handle<Blake3, LongString>> = Value::new(raw);
+                reader.get::<View<str>, _>(handle).ok()
+            } else {
+                None
+            };
+            let object = match text {
+                Some(text) => string_literal(text.as_ref()),
+                None => format!("\"{}\"^^<{HEX_BINARY}>", hex::encode_upper(raw)),
+            };
+            writeln!(
+                writer,
+                "{} {} {object}{graph} .",
+                iri(&entity),
+                iri(&attribute)
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    })
+}
+
+/// `pile branch export-json`: one JSON object per entity, in id order.
+///
+/// Attribute keys are attribute names where known (see [`crate::cli::attr`]),
+/// otherwise hex ids. Values that are handles of UTF-8 blobs in the pile
+/// become strings; the rest become `{"schema", "hex"}` objects, with `GenId`
+/// for references to other exported entities and `unknown` otherwise, since
+/// tribles do not carry their value schema.
+pub fn json(
+    pile: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    out: Option<PathBuf>,
+    pretty: bool,
+) -> Result<()> {
+    use serde_json::{json, Map, Value as Json};
+    use std::collections::{HashMap, HashSet};
+
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let branch_id = select_branch(pile, id.as_deref(), name.as_deref())?;
+        let content = head_content(pile, branch_id)?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+
+        let mut tribles: Vec<[u8; 64]> = content.iter().map(|t| t.data).collect();
+        tribles.sort_unstable();
+        let entities: HashSet<&[u8]> = tribles.iter().map(|t| &t[..16]).collect();
+        let mut names: HashMap<[u8; 16], String> = HashMap::new();
+
+        let mut writer = open_output(out.as_deref())?;
+        let mut emit = |object: Map<String, Json>| -> Result<()> {
+            let object = Json::Object(object);
+            if pretty {
+                serde_json::to_writer_pretty(&mut writer, &object)?;
+            } else {
+                serde_json::to_writer(&mut writer, &object)?;
+            }
+            writeln!(writer)?;
+            Ok(())
+        };
+
+        let mut current: Option<(&[u8], Map<String, Json>)> = None;
+        for t in &tribles {
+            let entity = &t[..16];
+            if current.as_ref().is_some_and(|(e, _)| *e != entity) {
+                let (_, object) = current.take().expect("checked");
+                emit(object)?;
+            }
+            let (_, object) = current.get_or_insert_with(|| {
+                let mut object = Map::new();
+                object.insert("id".to_string(), json!(hex::encode_upper(entity)));
+                (entity, object)
+            });
+
+            let attribute: [u8; 16] = t[16..32].try_into()?;
+            let key = names
+                .entry(attribute)
+                .or_insert_with(|| {
+                    Id::new(attribute)
+                        .and_then(|id| crate::cli::attr::label(&id))
+                        .filter(|name| name != "id")
+                        .unwrap_or_else(|| hex::encode_upper(attribute))
+                })
+                .clone();
+            let raw: [u8; 32] = t[32..].try_into()?;
+            let handle: Value<Handle<Blake3, LongString>> = Value::new(raw);
+            let value = match reader.get::<View<str>, _>(handle) {
+                Ok(text) => json!(text.as_ref()),
+                Err(_) => {
+                    let is_ref = raw[..16] == [0u8; 16] && entities.contains(&raw[16..]);
+                    json!({
+                        "schema": if is_ref { "GenId" } else { "unknown" },
+                        "hex": hex::encode_upper(raw),
+                    })
+                }
+            };
+            if let Json::Array(values) = object.entry(key).or_insert_with(|| json!([])) {
+                values.push(value);
+            }
+        }
+        if let Some((_, object)) = current {
+            emit(object)?;
+        }
+        drop(emit);
+        writer.flush()?;
+        Ok(())
+    })
+}
+
+/// `pile branch graph`: the commit DAG behind the selected branch heads as
+/// Graphviz DOT.
+///
+/// Commits are walked breadth-first from all heads at once, so a commit
+/// shared by several branches is one node. Edges point from a commit to its
+/// parents; head commits are boxes labelled with their branch names. With
+/// `max_depth` the parents of commits that many steps below a head are left
+/// out.
+pub fn graph(
+    pile: PathBuf,
+    ids: Vec<String>,
+    names: Vec<String>,
+    all: bool,
+    max_depth: Option<usize>,
+    out: Option<PathBuf>,
+) -> Result<()> {
+    use std::collections::{BTreeMap, HashSet, VecDeque};
+    use triblespace::prelude::blobschemas::SimpleArchive;
+    use triblespace::prelude::BranchStore;
+    use triblespace_core::trible::TribleSet;
+    use triblespace_core::value::schemas::hash::Hash;
+
+    use super::branch::{
+        extract_repo_head, format_commit_time, load_branch_name, read_commit_fields,
+    };
+
+    type CommitHandle = Value<Handle<Blake3, SimpleArchive>>;
+
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let mut branch_ids: Vec<Id> = Vec::new();
+        if all {
+            branch_ids = pile.branches()?.collect::<Result<Vec<_>, _>>()?;
+        }
+        for id in &ids {
+            branch_ids.push(select_branch(pile, Some(id), None)?);
+        }
+        for name in &names {
+            branch_ids.push(select_branch(pile, None, Some(name))?);
+        }
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+
+        // Head commit -> names of the branches pointing at it.
+        let mut heads: BTreeMap<[u8; 32], (CommitHandle, Vec<String>)> = BTreeMap::new();
+        let mut seen_branches: HashSet<Id> = HashSet::new();
+        for branch_id in branch_ids {
+            if !seen_branches.insert(branch_id) {
+                continue;
+            }
+            let meta = pile.head(branch_id)?.ok_or_else(|| {
+                crate::cli::error::not_found(format!("branch {branch_id:X} not found"))
+            })?;
+            let meta: TribleSet = reader
+                .get(meta)
+                .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
+            if crate::cli::remote_state::is_remote_state(&meta) {
+                continue;
+            }
+            let Some(head) = extract_repo_head(&meta) else {
+                continue;
+            };
+            let label = load_branch_name(&reader, &meta)
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| format!("{branch_id:X}"));
+            heads
+                .entry(head.raw)
+                .or_insert_with(|| (head, Vec::new()))
+                .1
+                .push(label);
+        }
+
+        let mut writer = open_output(out.as_deref())?;
+        writeln!(writer, "digraph commits {{")?;
+        writeln!(writer, "  node [shape=ellipse, fontname=\"monospace\"];")?;
+        let mut visited: HashSet<[u8; 32]> = HashSet::new();
+        let mut queue: VecDeque<(CommitHandle, usize)> =
+            heads.values().map(|(head, _)| (*head, 0)).collect();
+        let mut truncated = 0usize;
+        while let Some((current, depth)) = queue.pop_front() {
+            if !visited.insert(current.raw) {
+                continue;
+            }
+            let hash: Value<Hash<Blake3>> = Handle::to_hash(current);
+            let hex: String = hash.from_value();
+            let node = dot_string(&hex);
+            let mut lines = Vec::new();
+            let branches = heads.get(&current.raw).map(|(_, names)| names);
+            if let Some(names) = branches {
+                lines.push(format!("[{}]", names.join(", ")));
+            }
+            lines.push(hex[..12].to_string());
+            let commit = reader.get::<TribleSet, SimpleArchive>(current);
+            let info = commit.as_ref().ok().map(read_commit_fields);
+            match &info {
+                Some(info) => {
+                    lines.push(format_commit_time(info));
+                    let message = info.short_message.clone().or_else(|| {
+                        let text = reader.get::<View<str>, _>(info.message?).ok()?;
+                        Some(text.as_ref().lines().next().unwrap_or_default().to_string())
+                    });
+                    lines.extend(message);
+                }
+                None => lines.push("<missing blob>".to_string()),
+            }
+            let shape = if branches.is_some() {
+                ", shape=box, style=bold"
+            } else {
+                ""
+            };
+            writeln!(
+                writer,
+                "  {node} [label={}{shape}];",
+                dot_string(&lines.join("\n"))
+            )?;
+
+            let parents = info.map(|info| info.parents).unwrap_or_default();
+            if max_depth.is_some_and(|max| depth >= max) {
+                truncated += usize::from(!parents.is_empty());
+                continue;
+            }
+            for parent in parents {
+                let hash: Value<Hash<Blake3>> = Handle::to_hash(parent);
+                let parent_hex: String = hash.from_value();
+                writeln!(writer, "  {node} -> {};", dot_string(&parent_hex))?;
+                queue.push_back((parent, depth + 1));
+            }
+        }
+        writeln!(writer, "}}")?;
+        writer.flush()?;
+        if truncated > 0 {
+            eprintln!(
+                "stopped at depth {}: parents of {truncated} commit(s) omitted",
+                max_depth.unwrap_or_default()
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Buffered writer for `--out FILE`, or stdout.
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).map_err(|e| {
+                anyhow::anyhow!("failed to create {}: {e}", path.display())
+            })?))
+        }
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn iri(id: &Id) -> String {
+    format!("<urn:trible:id:{id:X}>")
+}
+
+/// An N-Triples string literal, escaping quotes, backslashes and control
+/// characters.
+fn string_literal(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{:04X}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// A quoted DOT string; newlines become DOT's centered line breaks.
+fn dot_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_literals_are_escaped() {
+        assert_eq!(string_literal("plain"), "\"plain\"");
+        assert_eq!(
+            string_literal("a \"q\"\\\n\u{7}"),
+            "\"a \\\"q\\\"\\\\\\n\\u0007\""
+        );
+    }
+
+    #[test]
+    fn dot_strings_escape_quotes_and_break_lines() {
+        assert_eq!(dot_string("a\"b\\c\nd"), "\"a\\\"b\\\\c\\nd\"");
+    }
+}
@@ -0,0 +1,320 @@
+//! `trible pile import-csv`: turn CSV rows into tribles on a branch.
+//!
+//! Every row becomes one entity, identified by its entity column; rows that
+//! share a value there describe the same entity. Each `--attr
+//! COLUMN=ATTRIBUTE:SCHEMA` mapping turns one column into tribles of that
+//! attribute. Empty cells produce no trible.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use triblespace::prelude::blobschemas::LongString;
+use triblespace::prelude::valueschemas::{GenId, ShortString, I256BE, U256BE};
+use triblespace_core::id::{ufoid, ExclusiveId, Id};
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::{Trible, TribleSet};
+use triblespace_core::value::schemas::hash::Blake3;
+use triblespace_core::value::Value;
+
+use crate::cli::error::invalid_argument;
+use crate::cli::output::note;
+
+use super::branch::select_branch;
+use super::signing::SigningArgs;
+use super::ClosingRepo;
+
+/// How the cells of a mapped column are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColumnSchema {
+    /// Text of any length, stored as a blob
+    Longstring,
+    /// Text of at most 32 bytes, stored inline
+    Shortstring,
+    /// Signed 64-bit integer
+    I64,
+    /// Unsigned 64-bit integer
+    U64,
+    /// Reference to another entity (32-char hex id)
+    Genid,
+}
+
+/// One `--attr COLUMN=ATTRIBUTE:SCHEMA` mapping.
+#[derive(Debug, PartialEq, Eq)]
+struct Mapping {
+    column: String,
+    attribute: Id,
+    schema: ColumnSchema,
+}
+
+/// An encoded cell: inline value bytes, or text still to be stored as a
+/// LongString blob.
+enum Cell {
+    Inline([u8; 32]),
+    Blob(String),
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn csv(
+    pile_path: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    file: PathBuf,
+    entity_column: String,
+    attrs: Vec<String>,
+    stable_ids: bool,
+    strict: bool,
+    signing: SigningArgs,
+) -> Result<()> {
+    let mappings = attrs
+        .iter()
+        .map(|spec| parse_mapping(spec))
+        .collect::<Result<Vec<_>>>()?;
+    let mut reader = csv::Reader::from_path(&file)
+        .map_err(|e| anyhow::anyhow!("failed to open {}: {e}", file.display()))?;
+    let headers = reader
+        .headers()
+        .map_err(|e| anyhow::anyhow!("failed to read the header of {}: {e}", file.display()))?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| invalid_argument(format!("{} has no column {name:?}", file.display())))
+    };
+    let entity_index = column(&entity_column)?;
+    let columns = mappings
+        .iter()
+        .map(|m| column(&m.column))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Convert every row before touching the pile, so --strict fails without
+    // leaving anything behind.
+    let mut rows: Vec<(String, Vec<(usize, Cell)>)> = Vec::new();
+    let mut skipped = 0usize;
+    for record in reader.records() {
+        let converted = match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |p| p.line());
+                convert_row(&record, entity_index, &columns, &mappings)
+                    .map_err(|e| format!("line {line}: {e}"))
+            }
+            Err(e) => Err(format!("{}: {e}", file.display())),
+        };
+        match converted {
+            Ok(row) => rows.push(row),
+            Err(e) if strict => return Err(invalid_argument(e)),
+            Err(e) => {
+                eprintln!("{e}");
+                skipped += 1;
+            }
+        }
+    }
+
+    let key = signing.load()?;
+    let pile: Pile<Blake3> = Pile::open(&pile_path)?;
+    let repo = Repository::new(pile, key, TribleSet::new())?;
+    ClosingRepo::new(repo).run(|repo| {
+        repo.storage_mut()
+            .refresh()
+            .map_err(|e| anyhow::anyhow!("refresh pile: {e:?}"))?;
+        let branch_id = select_branch(repo.storage_mut(), id.as_deref(), name.as_deref())?;
+        let mut ws = repo
+            .pull(branch_id)
+            .map_err(|e| anyhow::anyhow!("pull branch: {e:?}"))?;
+
+        let mut entities: HashMap<String, ExclusiveId> = HashMap::new();
+        let mut content = TribleSet::new();
+        for (key, cells) in &rows {
+            let entity = entities.entry(key.clone()).or_insert_with(|| {
+                if stable_ids {
+                    ExclusiveId::force(stable_id(key))
+                } else {
+                    ufoid()
+                }
+            });
+            for (mapping, cell) in cells {
+                let mapping = &mappings[*mapping];
+                let attribute = &mapping.attribute;
+                let trible = match (cell, mapping.schema) {
+                    (Cell::Blob(text), _) => {
+                        let handle = ws.put::<LongString, _>(text.clone());
+                        Trible::new(entity, attribute, &handle)
+                    }
+                    (Cell::Inline(raw), ColumnSchema::Shortstring) => {
+                        Trible::new(entity, attribute, &Value::<ShortString>::new(*raw))
+                    }
+                    (Cell::Inline(raw), ColumnSchema::I64) => {
+                        Trible::new(entity, attribute, &Value::<I256BE>::new(*raw))
+                    }
+                    (Cell::Inline(raw), ColumnSchema::U64) => {
+                        Trible::new(entity, attribute, &Value::<U256BE>::new(*raw))
+                    }
+                    (Cell::Inline(raw), _) => {
+                        Trible::new(entity, attribute, &Value::<GenId>::new(*raw))
+                    }
+                };
+                content.insert(&trible);
+            }
+        }
+
+        let tribles = content.len();
+        if tribles == 0 {
+            note!("nothing to import from {}", file.display());
+            return Ok(());
+        }
+        ws.commit(content, &format!("import {}", file.display()));
+        repo.push(&mut ws)
+            .map_err(|e| anyhow::anyhow!("push failed: {e:?}"))?;
+        note!(
+            "imported {} row(s) as {tribles} trible(s) on {branch_id:X}",
+            rows.len()
+        );
+        if skipped > 0 {
+            note!("skipped {skipped} row(s)");
+        }
+        Ok(())
+    })
+}
+
+/// Parse `COLUMN=ATTRIBUTE:SCHEMA`, with ATTRIBUTE a hex id or an attribute
+/// name.
+fn parse_mapping(spec: &str) -> Result<Mapping> {
+    let (column, rest) = spec.split_once('=').ok_or_else(|| {
+        invalid_argument(format!(
+            "invalid --attr {spec:?}: expected COLUMN=ATTRIBUTE:SCHEMA"
+        ))
+    })?;
+    let (attribute, schema) = rest.rsplit_once(':').ok_or_else(|| {
+        invalid_argument(format!(
+            "invalid --attr {spec:?}: missing :SCHEMA after the attribute"
+        ))
+    })?;
+    let schema = ColumnSchema::from_str(schema, true).map_err(|_| {
+        invalid_argument(format!(
+            "invalid --attr {spec:?}: unknown schema {schema:?} \
+             (expected longstring, shortstring, i64, u64 or genid)"
+        ))
+    })?;
+    let attribute = Id::new(super::query::parse_attribute(attribute)?)
+        .ok_or_else(|| invalid_argument(format!("invalid --attr {spec:?}: nil attribute id")))?;
+    Ok(Mapping {
+        column: column.to_string(),
+        attribute,
+        schema,
+    })
+}
+
+/// The entity key and encoded cells of one record, or why it cannot be
+/// imported.
+fn convert_row(
+    record: &csv::StringRecord,
+    entity_index: usize,
+    columns: &[usize],
+    mappings: &[Mapping],
+) -> Result<(String, Vec<(usize, Cell)>), String> {
+    let key = record.get(entity_index).unwrap_or_default();
+    if key.is_empty() {
+        return Err("empty entity column".to_string());
+    }
+    let mut cells = Vec::new();
+    for (i, (mapping, &index)) in mappings.iter().zip(columns).enumerate() {
+        let text = record.get(index).unwrap_or_default();
+        if text.is_empty() {
+            continue;
+        }
+        let cell = encode(mapping.schema, text)
+            .map_err(|e| format!("column {:?}: {e}", mapping.column))?;
+        cells.push((i, cell));
+    }
+    Ok((key.to_string(), cells))
+}
+
+fn encode(schema: ColumnSchema, text: &str) -> Result<Cell, String> {
+    let mut raw = [0u8; 32];
+    match schema {
+        ColumnSchema::Longstring => return Ok(Cell::Blob(text.to_string())),
+        ColumnSchema::Shortstring => {
+            if text.len() > 32 {
+                return Err(format!("{text:?} is longer than 32 bytes"));
+            }
+            raw[..text.len()].copy_from_slice(text.as_bytes());
+        }
+        ColumnSchema::I64 => {
+            let n: i64 = text
+                .trim()
+                .parse()
+                .map_err(|_| format!("{text:?} is not a signed 64-bit integer"))?;
+            if n < 0 {
+                raw = [0xFF; 32];
+            }
+            raw[24..].copy_from_slice(&n.to_be_bytes());
+        }
+        ColumnSchema::U64 => {
+            let n: u64 = text
+                .trim()
+                .parse()
+                .map_err(|_| format!("{text:?} is not an unsigned 64-bit integer"))?;
+            raw[24..].copy_from_slice(&n.to_be_bytes());
+        }
+        ColumnSchema::Genid => {
+            hex::decode_to_slice(text.trim(), &mut raw[16..])
+                .map_err(|_| format!("{text:?} is not a 32-char hex id"))?;
+            if raw == [0u8; 32] {
+                return Err("nil entity id".to_string());
+            }
+        }
+    }
+    Ok(Cell::Inline(raw))
+}
+
+/// Entity id derived from the entity column, so re-importing the same file
+/// (or an updated one) with `--stable-ids` extends the same entities.
+fn stable_id(key: &str) -> Id {
+    let hash = blake3::derive_key("trible pile import-csv entity id", key.as_bytes());
+    let mut raw: [u8; 16] = hash[..16].try_into().expect("16 bytes");
+    if raw == [0u8; 16] {
+        raw[15] = 1;
+    }
+    Id::new(raw).expect("non-nil")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_encoded_per_schema() {
+        let raw = |schema, text| match encode(schema, text) {
+            Ok(Cell::Inline(raw)) => raw,
+            _ => panic!("{text:?} did not encode inline"),
+        };
+        let minus_two = raw(ColumnSchema::I64, "-2");
+        assert_eq!(minus_two[..31], [0xFF; 31]);
+        assert_eq!(minus_two[31], 0xFE);
+        assert_eq!(raw(ColumnSchema::U64, "258")[30..], [1, 2]);
+        assert_eq!(raw(ColumnSchema::Shortstring, "hi")[..3], *b"hi\0");
+        assert_eq!(raw(ColumnSchema::Genid, &"AB".repeat(16))[..16], [0; 16]);
+        assert!(encode(ColumnSchema::I64, "x").is_err());
+        assert!(encode(ColumnSchema::U64, "-1").is_err());
+        assert!(encode(ColumnSchema::Shortstring, &"a".repeat(33)).is_err());
+        assert!(matches!(
+            encode(ColumnSchema::Longstring, "text"),
+            Ok(Cell::Blob(_))
+        ));
+    }
+
+    #[test]
+    fn mappings_split_column_attribute_and_schema() {
+        let attr = "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F";
+        let mapping = parse_mapping(&format!("age={attr}:I64")).unwrap();
+        assert_eq!(mapping.column, "age");
+        assert_eq!(format!("{:X}", mapping.attribute), attr);
+        assert_eq!(mapping.schema, ColumnSchema::I64);
+        assert!(parse_mapping(&format!("age={attr}")).is_err());
+        assert!(parse_mapping(&format!("age={attr}:float")).is_err());
+        assert!(parse_mapping("age").is_err());
+    }
+}
@@ -1,31 +1,28 @@
-use anyhow::{bail, Result};
+use anyhow::{Context, Result};
 use std::collections::HashSet;
-use std::convert::TryInto;
 use std::path::PathBuf;
 
-use triblespace::prelude::blobschemas::LongString;
+use ed25519_dalek::SigningKey;
+use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
 use triblespace::prelude::BlobStore;
 use triblespace::prelude::BlobStoreGet;
 use triblespace::prelude::BranchStore;
-use triblespace::prelude::View;
+use triblespace_core::blob::{Blob, ToBlob};
 use triblespace_core::id::Id;
 use triblespace_core::repo::pile::Pile;
-use triblespace_core::repo::Repository;
 use triblespace_core::trible::TribleSet;
 use triblespace_core::value::schemas::hash::Blake3;
 use triblespace_core::value::schemas::hash::Handle;
 use triblespace_core::value::schemas::hash::Hash;
 use triblespace_core::value::Value;
 
-use super::signing::load_signing_key;
+use super::branch::{extract_repo_head, is_ancestor_of, provenance};
+use super::resolve::{resolve_branch, BranchRef};
+use super::signing::SigningArgs;
+use super::ClosingPile;
+use crate::cli::verify;
 
-type CommitHandle = Value<Handle<Blake3, triblespace::prelude::blobschemas::SimpleArchive>>;
-
-#[derive(Debug, Clone)]
-struct BranchInfo {
-    name: Option<String>,
-    head: Option<CommitHandle>,
-}
+type CommitHandle = Value<Handle<Blake3, SimpleArchive>>;
 
 #[derive(Debug, Clone)]
 struct ResolvedSource {
@@ -33,110 +30,70 @@ struct ResolvedSource {
     head: Option<CommitHandle>,
 }
 
-fn parse_branch_id_hex(raw: &str) -> Result<Id> {
-    let raw = raw.trim();
-    let bytes = hex::decode(raw)?;
-    let arr: [u8; 16] = bytes
-        .as_slice()
-        .try_into()
-        .map_err(|_| anyhow::anyhow!("branch id must be 16 bytes (32 hex chars)"))?;
-    Id::new(arr).ok_or_else(|| anyhow::anyhow!("branch id cannot be nil"))
-}
-
-fn read_branch_info(pile: &mut Pile<Blake3>, branch_id: Id) -> Result<BranchInfo> {
-    use triblespace::prelude::blobschemas::SimpleArchive;
-
-    let reader = pile
-        .reader()
-        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
-
-    let Some(meta_handle) = pile
-        .head(branch_id)
-        .map_err(|e| anyhow::anyhow!("branch head: {e:?}"))?
-    else {
-        bail!("branch not found: {branch_id:X}");
-    };
-
-    let meta: TribleSet = reader
-        .get::<TribleSet, SimpleArchive>(meta_handle)
-        .map_err(|e| anyhow::anyhow!("branch metadata: {e:?}"))?;
-
-    let name_attr = triblespace_core::metadata::name.id();
-    let head_attr = triblespace_core::repo::head.id();
-
-    let mut name: Option<String> = None;
-    let mut head: Option<CommitHandle> = None;
-
-    for t in meta.iter() {
-        if t.a() == &name_attr {
-            if name.is_some() {
-                bail!("branch {branch_id:X} has multiple name values");
-            }
-            let handle: Value<Handle<Blake3, LongString>> = *t.v();
-            let view: View<str> = reader
-                .get(handle)
-                .map_err(|e| anyhow::anyhow!("branch name blob: {e:?}"))?;
-            name = Some(view.to_string());
-        } else if t.a() == &head_attr {
-            if head.is_some() {
-                bail!("branch {branch_id:X} has multiple heads");
-            }
-            head = Some(*t.v::<Handle<Blake3, SimpleArchive>>());
-        }
-    }
-
-    Ok(BranchInfo { name, head })
-}
-
 fn commit_hex(handle: CommitHandle) -> String {
     let hash: Value<Hash<Blake3>> = Handle::to_hash(handle);
     hash.from_value()
 }
 
+/// The LongString name handle branch metadata needs to be re-signed.
+pub(super) fn name_handle(
+    meta: &TribleSet,
+    branch_id: Id,
+) -> Result<Value<Handle<Blake3, LongString>>> {
+    let name_attr = triblespace_core::metadata::name.id();
+    meta.iter()
+        .find(|t| t.a() == &name_attr)
+        .map(|t| *t.v())
+        .ok_or_else(|| anyhow::anyhow!("branch {branch_id:X} has no name"))
+}
+
 pub fn run(
     pile_path: PathBuf,
     target: String,
     sources: Vec<String>,
-    signing_key: Option<PathBuf>,
+    message: Option<String>,
+    dry_run: bool,
+    signing: SigningArgs,
 ) -> Result<()> {
-    let key = load_signing_key(&signing_key)?;
-    let pile: Pile<Blake3> = Pile::open(&pile_path)?;
-    let mut repo = Repository::new(pile, key, TribleSet::new())?;
+    use triblespace_core::repo::branch::branch_metadata;
+    use triblespace_core::repo::commit::commit_metadata;
+    use triblespace_core::repo::PushResult;
+
+    let key = signing.load()?;
 
-    let res = (|| -> Result<(), anyhow::Error> {
-        repo.storage_mut()
-            .refresh()
+    ClosingPile::open(&pile_path)?.run(|pile| {
+        pile.refresh()
             .map_err(|e| anyhow::anyhow!("refresh pile: {e:?}"))?;
 
-        let target_id = parse_branch_id_hex(&target)?;
-        let target_info = read_branch_info(repo.storage_mut(), target_id)?;
-        let target_head = target_info.head;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let target = resolve_branch(pile, &reader, BranchRef::Any(&target))?;
+        let target_id = target.id;
+        let target_head = target.head;
 
         let mut resolved_sources: Vec<ResolvedSource> = Vec::new();
         let mut seen: HashSet<Id> = HashSet::new();
         for raw in sources {
-            let id = parse_branch_id_hex(&raw)?;
-            if id == target_id {
-                bail!("source branch matches target branch");
+            let info = resolve_branch(pile, &reader, BranchRef::Any(&raw))?;
+            if info.id == target_id {
+                return Err(crate::cli::error::invalid_argument(
+                    "source branch matches target branch",
+                ));
             }
-            if !seen.insert(id) {
+            if !seen.insert(info.id) {
                 continue;
             }
 
-            let info = read_branch_info(repo.storage_mut(), id)?;
-            let label = info
-                .name
-                .clone()
-                .map(|name| format!("{name} ({id:X})"))
-                .unwrap_or_else(|| format!("{id:X}"));
-
             resolved_sources.push(ResolvedSource {
-                label,
+                label: info.label(),
                 head: info.head,
             });
         }
 
+        let parent_attr = triblespace_core::repo::parent.id();
         let mut merged_branches = Vec::new();
+        let mut already_merged = Vec::new();
         let mut empty_branches = Vec::new();
         let mut unique_heads = Vec::new();
         let mut seen_heads = HashSet::new();
@@ -147,7 +104,15 @@ pub fn run(
                 continue;
             };
 
-            if Some(head) == target_head {
+            // Heads the target already contains would only add an empty merge.
+            let merged = match target_head {
+                Some(target_head) => {
+                    head == target_head || is_ancestor_of(head, target_head, &reader, &parent_attr)?
+                }
+                None => false,
+            };
+            if merged {
+                already_merged.push(source.label);
                 continue;
             }
 
@@ -157,23 +122,71 @@ pub fn run(
             }
         }
 
+        for label in &already_merged {
+            println!("{label}: already merged");
+        }
+
+        if !empty_branches.is_empty() {
+            empty_branches.sort();
+            println!(
+                "Skipped {} branch(es) with no head: {}",
+                empty_branches.len(),
+                empty_branches.join(", ")
+            );
+        }
+
         if unique_heads.is_empty() {
-            println!("No source heads to merge (all selected branches are empty).");
+            if already_merged.is_empty() {
+                println!("No source heads to merge (all selected branches are empty).");
+            } else {
+                println!("Already up to date");
+            }
             return Ok(());
         }
 
         let unique_count = unique_heads.len();
-        let mut ws = repo
-            .pull(target_id)
-            .map_err(|e| anyhow::anyhow!("pull target branch: {e:?}"))?;
-
-        for head in unique_heads {
-            ws.merge_commit(head)
-                .map_err(|e| anyhow::anyhow!("merge failed: {e:?}"))?;
+        if dry_run {
+            println!(
+                "Would merge {unique_count} head(s) from {} branch(es) into {}",
+                merged_branches.len(),
+                target.label()
+            );
+            for (label, head) in merged_branches {
+                println!("- {label} head=blake3:{}", commit_hex(head));
+            }
+            return Ok(());
         }
 
-        repo.push(&mut ws)
-            .map_err(|e| anyhow::anyhow!("push failed: {e:?}"))?;
+        // One merge commit whose parents are the target head followed by
+        // every source head it does not already contain.
+        let message = message
+            .map(|m| verify::put::<LongString, _>(pile, m))
+            .transpose()
+            .context("put message")?;
+        let parents: Vec<CommitHandle> = target_head.into_iter().chain(unique_heads).collect();
+        let commit: Blob<SimpleArchive> =
+            commit_metadata(&key, parents, message, None, None).to_blob();
+        verify::put(pile, commit.clone()).context("put merge commit")?;
+
+        let old_meta: TribleSet = reader
+            .get(target.meta)
+            .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
+        let mut new_meta = branch_metadata(
+            &key,
+            target_id,
+            name_handle(&old_meta, target_id)?,
+            Some(commit),
+        );
+        provenance::keep_creator(&old_meta, &mut new_meta);
+        let new_meta = verify::put(pile, new_meta).context("put branch metadata")?;
+        match pile.update(target_id, Some(target.meta), Some(new_meta))? {
+            PushResult::Success() => {}
+            PushResult::Conflict(_) => {
+                return Err(crate::cli::error::conflict(format!(
+                    "branch {target_id:X} advanced concurrently; rerun merge"
+                )))
+            }
+        }
 
         println!(
             "Updated {}:{:X} with {} merged head(s) from {} branch(es)",
@@ -187,23 +200,89 @@ pub fn run(
             println!("- {label} head=blake3:{}", commit_hex(head));
         }
 
-        if !empty_branches.is_empty() {
-            empty_branches.sort();
-            println!(
-                "Skipped {} branch(es) with no head: {}",
-                empty_branches.len(),
-                empty_branches.join(", ")
-            );
-        }
-
         Ok(())
-    })();
+    })
+}
 
-    let close_res = repo
-        .into_storage()
-        .close()
-        .map_err(|e| anyhow::anyhow!("{e:?}"));
+/// Point `target_id` at a merge commit of its current head and every head in
+/// `heads` it does not already contain, signed with `key` and carrying
+/// `message`. When the branch moves concurrently the merge is rebuilt on its
+/// new head and the update retried. A headless branch given a single head
+/// takes it directly. Returns the new head, or `None` when the branch already
+/// contains every head.
+pub(super) fn merge_into_branch(
+    pile: &mut Pile<Blake3>,
+    key: &SigningKey,
+    target_id: Id,
+    heads: &[CommitHandle],
+    message: Option<&str>,
+) -> Result<Option<CommitHandle>> {
+    use triblespace_core::repo::branch::branch_metadata;
+    use triblespace_core::repo::commit::commit_metadata;
+    use triblespace_core::repo::PushResult;
+
+    let parent_attr = triblespace_core::repo::parent.id();
+    let mut message_handle = None;
+    loop {
+        pile.refresh()
+            .map_err(|e| anyhow::anyhow!("refresh pile: {e:?}"))?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let meta_handle = pile.head(target_id)?.ok_or_else(|| {
+            crate::cli::error::not_found(format!("branch not found: {target_id:X}"))
+        })?;
+        let old_meta: TribleSet = reader
+            .get(meta_handle)
+            .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
+        let target_head = extract_repo_head(&old_meta);
+
+        let mut parents: Vec<CommitHandle> = target_head.into_iter().collect();
+        for &head in heads {
+            let contained = match target_head {
+                Some(target_head) => {
+                    head == target_head || is_ancestor_of(head, target_head, &reader, &parent_attr)?
+                }
+                None => false,
+            };
+            if !contained && !parents.contains(&head) {
+                parents.push(head);
+            }
+        }
+        if parents.len() == usize::from(target_head.is_some()) {
+            return Ok(None);
+        }
 
-    res.and(close_res)?;
-    Ok(())
+        let (new_head, commit): (CommitHandle, Blob<SimpleArchive>) = match parents.as_slice() {
+            [head] if target_head.is_none() => (
+                *head,
+                reader
+                    .get(*head)
+                    .map_err(|e| anyhow::anyhow!("read commit: {e:?}"))?,
+            ),
+            _ => {
+                if message_handle.is_none() {
+                    message_handle = message
+                        .map(|m| verify::put::<LongString, _>(pile, m.to_string()))
+                        .transpose()
+                        .context("put message")?;
+                }
+                let commit = commit_metadata(key, parents, message_handle, None, None).to_blob();
+                let handle = verify::put(pile, commit.clone()).context("put merge commit")?;
+                (handle, commit)
+            }
+        };
+        let mut new_meta = branch_metadata(
+            key,
+            target_id,
+            name_handle(&old_meta, target_id)?,
+            Some(commit),
+        );
+        provenance::keep_creator(&old_meta, &mut new_meta);
+        let new_meta = verify::put(pile, new_meta).context("put branch metadata")?;
+        match pile.update(target_id, Some(meta_handle), Some(new_meta))? {
+            PushResult::Success() => return Ok(Some(new_head)),
+            PushResult::Conflict(_) => continue,
+        }
+    }
 }
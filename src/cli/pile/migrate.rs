@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Context, Result};
@@ -7,9 +7,17 @@ use triblespace::prelude::*;
 use triblespace_core::repo::pile::Pile;
 use triblespace_core::repo::BlobStoreMeta;
 use triblespace_core::repo::PushResult;
-use triblespace_core::trible::TribleSet;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::{Trible, TribleSet};
 use triblespace_core::value::schemas::hash::{Blake3, Handle};
 
+use super::resolve::{all_branches, resolve_branch, BranchRef};
+use super::signing::SigningArgs;
+use super::{ClosingPile, ClosingRepo};
+use crate::cli::output::{Field, Output, OutputFormat};
+use crate::cli::remote_state;
+use crate::cli::verify;
+
 type NameHandle = Value<Handle<Blake3, blobschemas::LongString>>;
 type BranchMetaHandle = Value<Handle<Blake3, blobschemas::SimpleArchive>>;
 
@@ -26,11 +34,20 @@ mod legacy_branch_metadata {
 pub enum Migration {
     #[value(name = "branch-metadata-name")]
     BranchMetadataName,
+    /// Re-store ShortString values of `--attr` as LongString blobs under
+    /// `--to-attr`, committed on top of each branch.
+    #[value(name = "content-short-to-long-string")]
+    ContentShortToLongString,
 }
 
 #[derive(Parser, Debug)]
 pub enum Command {
     /// List known migrations and whether they are needed for this pile.
+    ///
+    /// With -v every branch still needing a migration and every group of
+    /// branches sharing a name is listed; `--output json` and
+    /// `--output porcelain` always emit those details as records.
+    #[command(visible_alias = "ls")]
     List,
     /// Run migrations (all by default, or a single named migration).
     Run {
@@ -43,6 +60,19 @@ pub enum Command {
         /// Do not rename duplicate branches (useful for forensic inspection).
         #[arg(long, default_value_t = false)]
         no_rename_duplicates: bool,
+        /// ShortString attribute to rewrite (content-short-to-long-string;
+        /// hex id or registered name)
+        #[arg(long, requires = "to_attr")]
+        attr: Option<String>,
+        /// LongString attribute the rewritten values are stored under
+        #[arg(long, requires = "attr")]
+        to_attr: Option<String>,
+        /// Branch to rewrite (hex id, unique id prefix or name); repeat for
+        /// several. Defaults to every branch.
+        #[arg(long)]
+        branch: Vec<String>,
+        #[command(flatten)]
+        signing: SigningArgs,
     },
 }
 
@@ -53,77 +83,178 @@ pub fn run(pile_path: PathBuf, cmd: Command) -> Result<()> {
             migration,
             dry_run,
             no_rename_duplicates,
+            attr,
+            to_attr,
+            branch,
+            signing,
         } => {
             let rename_duplicates = !no_rename_duplicates;
+            let content = match (attr, to_attr) {
+                (Some(attr), Some(to_attr)) => Some(ContentMigration {
+                    attr: parse_attribute_id(&attr)?,
+                    to_attr: parse_attribute_id(&to_attr)?,
+                    branches: branch,
+                    signing,
+                }),
+                _ => None,
+            };
             match migration {
                 None => {
                     migrate_branch_metadata_name(&pile_path, dry_run, rename_duplicates)?;
+                    // Only runs when told which attribute to rewrite.
+                    if let Some(content) = &content {
+                        migrate_content_short_to_long(&pile_path, content, dry_run)?;
+                    }
                 }
                 Some(Migration::BranchMetadataName) => {
                     migrate_branch_metadata_name(&pile_path, dry_run, rename_duplicates)?;
                 }
+                Some(Migration::ContentShortToLongString) => {
+                    let content = content.ok_or_else(|| {
+                        crate::cli::error::invalid_argument(
+                            "content-short-to-long-string needs --attr and --to-attr",
+                        )
+                    })?;
+                    migrate_content_short_to_long(&pile_path, &content, dry_run)?;
+                }
             }
             Ok(())
         }
     }
 }
 
+/// A branch that still carries only the legacy ShortString name.
+struct LegacyNamed {
+    id: Id,
+    legacy_name: String,
+    has_modern_name: bool,
+}
+
 fn list_migrations(pile_path: &PathBuf) -> Result<()> {
-    let mut pile: Pile<Blake3> = Pile::open(pile_path).context("open pile")?;
-    let res = (|| -> Result<(), anyhow::Error> {
-        pile.refresh().context("refresh pile")?;
-        let reader = pile.reader().context("pile reader")?;
+    let verbose = crate::cli::logging::verbose() > 0;
+    let plain = crate::cli::output::format() == OutputFormat::Plain;
+    ClosingPile::open(pile_path)
+        .context("open pile")?
+        .run(|pile| {
+            pile.refresh().context("refresh pile")?;
+            let mut out = Output::stdout();
+
+            let reader = pile.reader().context("pile reader")?;
+            let branch_ids = remote_state::branch_ids(pile, &reader).context("list branches")?;
+            // Without branches there is no branch metadata to decode.
+            if branch_ids.is_empty() {
+                if plain {
+                    println!("Known migrations:");
+                    println!("- branch-metadata-name: ok (nothing to migrate)");
+                } else {
+                    migration_record(&mut out, "ok", 0)?;
+                }
+                return Ok(());
+            }
 
-        let mut missing_name = 0usize;
-        let mut duplicate_names: HashMap<String, usize> = HashMap::new();
+            let mut legacy = Vec::new();
+            let mut by_name: BTreeMap<String, Vec<Id>> = BTreeMap::new();
 
-        for bid in pile.branches().context("list branches")? {
-            let bid = bid.context("branch id")?;
-            let Some(meta_handle) = pile.head(bid).context("branch head")? else {
-                continue;
-            };
-
-            let meta: TribleSet =
-                match reader.get::<TribleSet, blobschemas::SimpleArchive>(meta_handle) {
-                    Ok(meta) => meta,
-                    Err(_) => continue,
+            for bid in branch_ids {
+                let Some(meta_handle) = pile.head(bid).context("branch head")? else {
+                    continue;
                 };
 
-            if !has_unique_name(&meta) {
-                // Count only if the legacy name exists and is unambiguous; otherwise
-                // we don't know how to migrate it.
-                if legacy_branch_name(&meta)
-                    .context("read legacy branch name")?
-                    .is_some()
+                let meta: TribleSet =
+                    match reader.get::<TribleSet, blobschemas::SimpleArchive>(meta_handle) {
+                        Ok(meta) => meta,
+                        Err(_) => continue,
+                    };
+
+                if !has_unique_name(&meta) {
+                    // Count only if the legacy name exists and is unambiguous; otherwise
+                    // we don't know how to migrate it.
+                    if let Some(legacy_name) =
+                        legacy_branch_name(&meta).context("read legacy branch name")?
+                    {
+                        legacy.push(LegacyNamed {
+                            id: bid,
+                            legacy_name,
+                            has_modern_name: has_modern_name(&meta),
+                        });
+                    }
+                }
+
+                if let Some(name) =
+                    load_branch_name(&reader, &meta).context("decode branch name")?
                 {
-                    missing_name += 1;
+                    by_name.entry(name).or_default().push(bid);
                 }
             }
-
-            if let Some(name) = load_branch_name(&reader, &meta).context("decode branch name")? {
-                *duplicate_names.entry(name).or_insert(0) += 1;
+            by_name.retain(|_, ids| ids.len() > 1);
+
+            if !plain {
+                let status = if legacy.is_empty() { "ok" } else { "needed" };
+                migration_record(&mut out, status, legacy.len() as u64)?;
+                for branch in &legacy {
+                    out.record(&[
+                        ("kind", Field::from("legacy-name")),
+                        ("branch", Field::from(format!("{:X}", branch.id))),
+                        ("legacy_name", Field::from(branch.legacy_name.as_str())),
+                        (
+                            "modern_name",
+                            Field::from(if branch.has_modern_name { "yes" } else { "no" }),
+                        ),
+                    ])?;
+                }
+                for (name, ids) in &by_name {
+                    let ids: Vec<String> = ids.iter().map(|id| format!("{id:X}")).collect();
+                    out.record(&[
+                        ("kind", Field::from("duplicate-name")),
+                        ("name", Field::from(name.as_str())),
+                        ("branches", Field::from(ids.join(","))),
+                    ])?;
+                }
+                return Ok(());
             }
-        }
 
-        let duplicates = duplicate_names.values().filter(|v| **v > 1).count();
-
-        println!("Known migrations:");
-        if missing_name == 0 {
-            println!("- branch-metadata-name: ok");
-        } else {
-            println!("- branch-metadata-name: needed ({missing_name} branch(es))");
-        }
-        if duplicates > 0 {
-            println!(
-                "  note: {duplicates} duplicate branch name(s) detected (run migration to auto-rename)"
-            );
-        }
-        Ok(())
-    })();
+            println!("Known migrations:");
+            if legacy.is_empty() {
+                println!("- branch-metadata-name: ok");
+            } else {
+                println!(
+                    "- branch-metadata-name: needed ({} branch(es))",
+                    legacy.len()
+                );
+            }
+            if verbose {
+                for branch in &legacy {
+                    println!(
+                        "    {:X} legacy name {:?} (modern name: {})",
+                        branch.id,
+                        branch.legacy_name,
+                        if branch.has_modern_name { "yes" } else { "no" }
+                    );
+                }
+            }
+            if !by_name.is_empty() {
+                println!(
+                    "  note: {} duplicate branch name(s) detected (run migration to auto-rename)",
+                    by_name.len()
+                );
+            }
+            if verbose {
+                for (name, ids) in &by_name {
+                    let ids: Vec<String> = ids.iter().map(|id| format!("{id:X}")).collect();
+                    println!("    {name:?}: {}", ids.join(", "));
+                }
+            }
+            Ok(())
+        })
+}
 
-    let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-    res.and(close_res)?;
-    Ok(())
+fn migration_record(out: &mut Output, status: &str, branches: u64) -> Result<()> {
+    out.record(&[
+        ("kind", Field::from("migration")),
+        ("migration", Field::from("branch-metadata-name")),
+        ("status", Field::from(status)),
+        ("branches", Field::from(branches)),
+    ])
 }
 
 #[derive(Debug, Clone)]
@@ -141,16 +272,14 @@ fn migrate_branch_metadata_name(
     dry_run: bool,
     rename_duplicates: bool,
 ) -> Result<()> {
-    let mut pile: Pile<Blake3> = Pile::open(pile_path).context("open pile")?;
-    pile.restore().context("restore pile")?;
-
-    let res = (|| -> Result<(), anyhow::Error> {
+    let pile = ClosingPile::open(pile_path).context("open pile")?;
+    pile.run(|pile| {
+        pile.restore().context("restore pile")?;
         let reader = pile.reader().context("pile reader")?;
-        let iter = pile.branches().context("list branches")?;
+        let ids = remote_state::branch_ids(pile, &reader).context("list branches")?;
 
         let mut branches: Vec<BranchInfo> = Vec::new();
-        for bid in iter {
-            let bid = bid.context("branch id")?;
+        for bid in ids {
             let Some(meta_handle) = pile.head(bid).context("branch head")? else {
                 continue;
             };
@@ -206,14 +335,13 @@ fn migrate_branch_metadata_name(
                 continue;
             }
 
-            let name_handle: NameHandle = pile
-                .put::<blobschemas::LongString, _>(legacy_name.clone())
-                .context("store branch name blob")?;
+            let name_handle: NameHandle =
+                verify::put::<blobschemas::LongString, _>(pile, legacy_name.clone())
+                    .context("store branch name blob")?;
 
             let new_meta = rewrite_branch_meta(&info.meta, info.meta_entity, name_handle);
-            let new_meta_handle: BranchMetaHandle = pile
-                .put(new_meta.clone())
-                .context("store updated branch metadata")?;
+            let new_meta_handle: BranchMetaHandle =
+                verify::put(pile, new_meta.clone()).context("store updated branch metadata")?;
 
             match pile
                 .update(
@@ -240,8 +368,7 @@ fn migrate_branch_metadata_name(
 
         let mut renamed = 0usize;
         if rename_duplicates {
-            renamed =
-                rename_duplicate_branch_names(&mut pile, &branches, dry_run).context("dedupe")?;
+            renamed = rename_duplicate_branch_names(pile, &branches, dry_run).context("dedupe")?;
         }
 
         if dry_run {
@@ -253,11 +380,121 @@ fn migrate_branch_metadata_name(
             }
         }
         Ok(())
-    })();
+    })
+}
+
+/// Parameters of the content-short-to-long-string migration.
+struct ContentMigration {
+    attr: Id,
+    to_attr: Id,
+    branches: Vec<String>,
+    signing: SigningArgs,
+}
+
+fn parse_attribute_id(token: &str) -> Result<Id> {
+    Id::new(super::query::parse_attribute(token)?)
+        .ok_or_else(|| crate::cli::error::invalid_argument("nil attribute id"))
+}
 
-    let close_res = pile.close().map_err(|e| anyhow::anyhow!("{e:?}"));
-    res.and(close_res)?;
-    Ok(())
+/// Rewrite every `attr` trible in the checked-out content of each selected
+/// branch as a `to_attr` trible pointing at a LongString blob of the same
+/// text, and commit those tribles on top of the branch. Earlier commits are
+/// left untouched, so the ShortString tribles stay in the history.
+fn migrate_content_short_to_long(
+    pile_path: &PathBuf,
+    migration: &ContentMigration,
+    dry_run: bool,
+) -> Result<()> {
+    let ContentMigration { attr, to_attr, .. } = *migration;
+    let key = migration.signing.load()?;
+    let pile: Pile<Blake3> = Pile::open(pile_path).context("open pile")?;
+    let repo = Repository::new(pile, key, TribleSet::new())
+        .map_err(|e| anyhow!("open repository: {e:?}"))?;
+    ClosingRepo::new(repo).run(|repo| {
+        repo.storage_mut().refresh().context("refresh pile")?;
+        let reader = repo.storage_mut().reader().context("pile reader")?;
+        let targets = if migration.branches.is_empty() {
+            all_branches(repo.storage_mut(), &reader)?
+        } else {
+            migration
+                .branches
+                .iter()
+                .map(|raw| resolve_branch(repo.storage_mut(), &reader, BranchRef::Any(raw)))
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let mut rewritten = 0usize;
+        for branch in targets {
+            if branch.head.is_none() {
+                continue;
+            }
+            let mut ws = repo
+                .pull(branch.id)
+                .map_err(|e| anyhow!("pull branch {}: {e:?}", branch.label()))?;
+            let data = ws
+                .checkout(..)
+                .map_err(|e| anyhow!("checkout {}: {e:?}", branch.label()))?;
+            let legacy: Vec<(Id, [u8; 32])> = data
+                .iter()
+                .filter(|t| t.a() == &attr)
+                .map(|t| (*t.e(), t.data[32..64].try_into().unwrap()))
+                .collect();
+            if legacy.is_empty() {
+                continue;
+            }
+            if dry_run {
+                println!(
+                    "Would rewrite {} trible(s) of {attr:X} on branch {}",
+                    legacy.len(),
+                    branch.label()
+                );
+                continue;
+            }
+
+            let mut content = TribleSet::new();
+            for (entity, raw) in &legacy {
+                let text: String = Value::<valueschemas::ShortString>::new(*raw)
+                    .try_from_value()
+                    .map_err(|_| {
+                        crate::cli::error::corrupt(format!(
+                            "entity {entity:X} has a {attr:X} value that is not a ShortString"
+                        ))
+                    })?;
+                let handle = ws.put::<blobschemas::LongString, _>(text);
+                content.insert(&Trible::new(
+                    ExclusiveId::force_ref(entity),
+                    &to_attr,
+                    &handle,
+                ));
+            }
+            ws.commit(
+                content,
+                &format!("migrate {attr:X} ShortString values to LongString {to_attr:X}"),
+            );
+            repo.push(&mut ws)
+                .map_err(|e| anyhow!("push branch {}: {e:?}", branch.label()))?;
+            println!(
+                "Rewrote {} trible(s) of {attr:X} on branch {}",
+                legacy.len(),
+                branch.label()
+            );
+            rewritten += 1;
+        }
+        if !dry_run {
+            println!("content-short-to-long-string: {rewritten} branch(es) rewritten");
+        }
+        Ok(())
+    })
+}
+
+fn has_modern_name(meta: &TribleSet) -> bool {
+    find!(
+        (handle: NameHandle),
+        pattern!(meta, [{ triblespace_core::metadata::name: ?handle }])
+    )
+    .into_iter()
+    .next()
+    .is_some()
 }
 
 fn has_unique_name(meta: &TribleSet) -> bool {
@@ -269,7 +506,7 @@ fn has_unique_name(meta: &TribleSet) -> bool {
     names.next().is_some() && names.next().is_none()
 }
 
-fn legacy_branch_name(meta: &TribleSet) -> Result<Option<String>> {
+pub(super) fn legacy_branch_name(meta: &TribleSet) -> Result<Option<String>> {
     let mut names = find!(
         (name: String),
         pattern!(meta, [{ legacy_branch_metadata::legacy_name: ?name }])
@@ -387,18 +624,17 @@ fn rename_duplicate_branch_names(
                 continue;
             }
 
-            let name_handle: NameHandle = pile
-                .put::<blobschemas::LongString, _>(new_name.clone())
-                .context("store renamed branch name blob")?;
+            let name_handle: NameHandle =
+                verify::put::<blobschemas::LongString, _>(pile, new_name.clone())
+                    .context("store renamed branch name blob")?;
 
             let meta: TribleSet = reader
                 .get::<TribleSet, blobschemas::SimpleArchive>(orphan.meta_handle)
                 .context("read duplicate branch metadata")?;
 
             let new_meta = rewrite_branch_meta(&meta, orphan.meta_entity, name_handle);
-            let new_meta_handle: BranchMetaHandle = pile
-                .put(new_meta.clone())
-                .context("store renamed branch metadata")?;
+            let new_meta_handle: BranchMetaHandle =
+                verify::put(pile, new_meta.clone()).context("store renamed branch metadata")?;
 
             match pile
                 .update(
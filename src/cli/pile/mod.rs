@@ -1,20 +1,47 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{ArgGroup, Parser};
 use std::fs;
-use std::path::PathBuf;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
+use triblespace::prelude::BlobStore;
+use triblespace_core::repo::pile::{Pile, PileReader};
+use triblespace_core::repo::Repository;
+use triblespace_core::value::schemas::hash::Blake3;
+
+use crate::cli::config;
+use signing::SigningArgs;
+
+mod annotate;
+mod archive;
+mod attrs;
 pub mod blob;
 pub mod branch;
+mod commit;
 mod diagnose;
+mod entity;
+mod export;
+mod import;
 mod merge;
 mod migrate;
 pub mod net;
-mod signing;
+mod pin;
+mod prune;
+mod query;
+pub(crate) mod resolve;
+mod scan;
+mod search;
+pub(crate) mod signing;
+pub(crate) mod snapshot;
 mod squash;
+mod walk;
 
 #[derive(Parser)]
 pub enum PileCommand {
     /// Operations on branches stored in a pile file.
+    #[command(visible_alias = "br")]
     Branch {
         #[command(subcommand)]
         cmd: branch::Command,
@@ -24,18 +51,68 @@ pub enum PileCommand {
         #[command(subcommand)]
         cmd: blob::Command,
     },
+    /// Inspect single entities in a branch's content.
+    Entity {
+        #[command(subcommand)]
+        cmd: entity::Command,
+    },
+    /// Import CSV rows as tribles and commit them onto a branch.
+    ///
+    /// Each row describes the entity named by --entity-column; each --attr
+    /// COLUMN=ATTRIBUTE:SCHEMA turns a column into tribles of ATTRIBUTE (a hex
+    /// id or a name listed by `trible attr list`). SCHEMA is one of
+    /// longstring, shortstring, i64, u64 or genid. Rows that fail to convert
+    /// are reported with their line number and skipped.
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "name"])))]
+    ImportCsv {
+        /// Path to the pile file to modify
+        pile: PathBuf,
+        /// Branch to commit onto (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to commit onto (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// CSV file with a header row
+        file: PathBuf,
+        /// Column whose value identifies the row's entity
+        #[arg(long, value_name = "COLUMN")]
+        entity_column: String,
+        /// Column mapping, COLUMN=ATTRIBUTE:SCHEMA (repeatable)
+        #[arg(long = "attr", value_name = "MAP", required = true)]
+        attrs: Vec<String>,
+        /// Derive entity ids from the entity column instead of minting new
+        /// ones, so re-imports describe the same entities
+        #[arg(long)]
+        stable_ids: bool,
+        /// Fail on the first bad row instead of skipping it
+        #[arg(long)]
+        strict: bool,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
     /// Merge source branch heads into a target branch.
     Merge {
         /// Path to the pile file to modify
         pile: PathBuf,
-        /// Target branch id (hex)
+        /// Target branch (hex id, unique id prefix or name)
         target: String,
-        /// Source branch id(s) (hex)
+        /// Source branch(es) (hex id, unique id prefix or name)
         #[arg(num_args = 1..)]
         sources: Vec<String>,
-        /// Optional signing key path. The file should contain a 64-char hex seed.
+        /// Message recorded on the merge commit
+        #[arg(short, long)]
+        message: Option<String>,
+        /// Show what would be merged without creating a commit
         #[arg(long)]
-        signing_key: Option<PathBuf>,
+        dry_run: bool,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+    /// Inspect individual commits.
+    Commit {
+        #[command(subcommand)]
+        cmd: commit::Command,
     },
     /// Create a new empty pile file.
     ///
@@ -43,7 +120,7 @@ pub enum PileCommand {
     /// Unix-like systems achieves the same result.
     Create {
         /// Path to the pile file to create
-        path: PathBuf,
+        path: Option<PathBuf>,
     },
     /// Diagnostic helpers for inspecting and repairing piles.
     Diagnose {
@@ -62,6 +139,61 @@ pub enum PileCommand {
         #[command(subcommand)]
         cmd: net::Command,
     },
+    /// Pin blobs so they are not reported as unreachable.
+    Pin {
+        #[command(subcommand)]
+        cmd: pin::Command,
+    },
+    /// Query a branch's content with entity/attribute/value patterns.
+    ///
+    /// EXPR is a `;`-separated list of `ENTITY ATTRIBUTE VALUE` clauses in
+    /// which `?name` marks a variable, e.g. `?e title ?t; ?e author ?a`.
+    /// Attributes are hex ids or names listed by `trible attr list`.
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "name"])))]
+    Query {
+        /// Path to the pile file to query
+        pile: PathBuf,
+        /// Branch to query (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to query (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Query expression
+        expr: String,
+        /// Print at most this many results
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Find string values in a branch's content.
+    ///
+    /// Matches TEXT against inline short strings and the long string blobs
+    /// values point to, printing the entity, attribute and a snippet of each
+    /// match.
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "name"])))]
+    Search {
+        /// Path to the pile file to search
+        pile: PathBuf,
+        /// Branch to search (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to search (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Text to look for
+        text: String,
+        /// Match regardless of case
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+        /// Treat TEXT as a regular expression
+        #[arg(long)]
+        regex: bool,
+    },
+    /// Record directory trees as tribles on a branch.
+    Snapshot {
+        #[command(subcommand)]
+        cmd: snapshot::Command,
+    },
     /// Squash all branch histories into single commits in a new pile.
     ///
     /// For each branch, the full accumulated content and metadata are
@@ -79,25 +211,235 @@ pub enum PileCommand {
         /// Exclude these branches (by name or hex ID).
         #[arg(long)]
         exclude: Vec<String>,
-        /// Optional signing key path
-        #[arg(long)]
-        signing_key: Option<PathBuf>,
+        #[command(flatten)]
+        signing: SigningArgs,
     },
 }
 
-pub fn run(cmd: PileCommand) -> Result<()> {
+/// Pile path given via the group-level `--pile` option, if any.
+static DEFAULT_PILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Resolve the pile a subcommand operates on.
+///
+/// An explicit positional path wins, followed by `--pile`, the
+/// `TRIBLES_PILE` environment variable and finally the `pile` config key.
+pub(crate) fn resolve_pile(explicit: Option<PathBuf>) -> Result<PathBuf> {
+    let flag = explicit.or_else(|| DEFAULT_PILE.get().cloned());
+    let path = config::layered(
+        flag,
+        config::env_path("TRIBLES_PILE"),
+        config::get().pile.clone(),
+    )
+    .ok_or_else(|| {
+        crate::cli::error::invalid_argument("no pile specified: pass a path or set TRIBLES_PILE")
+    })?;
+    tracing::debug!(path = %path.display(), "resolved pile path");
+    Ok(path)
+}
+
+/// Set by the group-level `--no-refresh` flag.
+static NO_REFRESH: AtomicBool = AtomicBool::new(false);
+
+/// Bring a pile opened by a read-only command up to date with the file.
+///
+/// Read commands call this right after opening so they see blobs and branch
+/// updates appended by other processes in the meantime. `--no-refresh` skips
+/// the index rebuild when the caller knows the file is quiescent; commands
+/// that write always refresh, since their compare-and-swap needs the
+/// current heads.
+pub(crate) fn refresh_for_read(pile: &mut Pile<Blake3>) -> Result<()> {
+    if !NO_REFRESH.load(Ordering::Relaxed) {
+        pile.refresh()?;
+    }
+    Ok(())
+}
+
+/// An entity id derived from the blob `hash`, so everything a command
+/// records about one blob lands on the same entity. `context` keeps the
+/// entities of different commands apart.
+pub(crate) fn derived_entity(context: &str, hash: &[u8; 32]) -> triblespace_core::id::Id {
+    let derived = blake3::derive_key(context, hash);
+    let mut raw: [u8; 16] = derived[..16].try_into().expect("16 bytes");
+    if raw == [0u8; 16] {
+        raw[15] = 1;
+    }
+    triblespace_core::id::Id::new(raw).expect("non-nil")
+}
+
+pub(crate) use scan::Appended;
+
+/// A pile reader for long-running commands (`serve`, `mount`) that can be
+/// moved forward to see what other handles appended since it was taken.
+pub(crate) struct LiveReader {
+    path: PathBuf,
+    /// Offset just past the last record the reader is known to cover.
+    end: u64,
+    reader: PileReader<Blake3>,
+}
+
+impl LiveReader {
+    /// Refresh `pile`, opened from `path`, and read everything in it.
+    pub(crate) fn new(pile: &mut Pile<Blake3>, path: &Path) -> Result<Self> {
+        // Measure before refreshing: every record counted is then covered.
+        let end = scan::appended_since(path, 0)?.end;
+        pile.refresh()?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        Ok(LiveReader {
+            path: path.to_path_buf(),
+            end,
+            reader,
+        })
+    }
+
+    /// Refresh `pile` and replace the reader, reporting the records that were
+    /// appended since the previous one.
+    pub(crate) fn refresh(&mut self, pile: &mut Pile<Blake3>) -> Result<Appended> {
+        let appended = scan::appended_since(&self.path, self.end)?;
+        pile.refresh()?;
+        self.reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        self.end = appended.end;
+        Ok(appended)
+    }
+
+    pub(crate) fn reader(&self) -> &PileReader<Blake3> {
+        &self.reader
+    }
+}
+
+/// Storage a command has to close before it exits.
+pub(crate) trait Closeable {
+    fn close_storage(self) -> Result<()>;
+}
+
+impl Closeable for Pile<Blake3> {
+    fn close_storage(self) -> Result<()> {
+        self.close().map_err(|e| anyhow::anyhow!("{e:?}"))
+    }
+}
+
+impl Closeable for Repository<Pile<Blake3>> {
+    fn close_storage(self) -> Result<()> {
+        self.into_storage()
+            .close()
+            .map_err(|e| anyhow::anyhow!("{e:?}"))
+    }
+}
+
+/// Owns an open pile (or a repository over one) and closes it when dropped.
+///
+/// [`Closing::run`] is the usual way in: it hands the body the storage,
+/// closes it afterwards and reports a close failure unless the body already
+/// failed. Early returns and panics that skip it still close on drop, where a
+/// failure can only be logged.
+pub(crate) struct Closing<T: Closeable>(Option<T>);
+
+pub(crate) type ClosingPile = Closing<Pile<Blake3>>;
+pub(crate) type ClosingRepo = Closing<Repository<Pile<Blake3>>>;
+
+impl ClosingPile {
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        Ok(Closing(Some(Pile::open(path)?)))
+    }
+}
+
+impl<T: Closeable> Closing<T> {
+    pub(crate) fn new(storage: T) -> Self {
+        Closing(Some(storage))
+    }
+
+    /// Run `body` on the storage, then close it.
+    pub(crate) fn run<R>(mut self, body: impl FnOnce(&mut T) -> Result<R>) -> Result<R> {
+        let res = body(&mut self);
+        let closed = self.close();
+        match (res, closed) {
+            (Ok(value), closed) => closed.map(|()| value),
+            (Err(e), Ok(())) => Err(e),
+            (Err(e), Err(close)) => {
+                tracing::warn!("closing the pile failed: {close:#}");
+                Err(e)
+            }
+        }
+    }
+
+    pub(crate) fn close(mut self) -> Result<()> {
+        self.0.take().map_or(Ok(()), Closeable::close_storage)
+    }
+
+    /// Take the storage back, e.g. to build a repository on top of it.
+    pub(crate) fn into_inner(mut self) -> T {
+        self.0.take().expect("storage is open until closed")
+    }
+}
+
+impl<T: Closeable> Deref for Closing<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0.as_ref().expect("storage is open until closed")
+    }
+}
+
+impl<T: Closeable> DerefMut for Closing<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.0.as_mut().expect("storage is open until closed")
+    }
+}
+
+impl<T: Closeable> Drop for Closing<T> {
+    fn drop(&mut self) {
+        if let Some(storage) = self.0.take() {
+            if let Err(e) = storage.close_storage() {
+                tracing::warn!("closing the pile failed: {e:#}");
+            }
+        }
+    }
+}
+
+pub fn run(cmd: PileCommand, default_pile: Option<PathBuf>, no_refresh: bool) -> Result<()> {
+    if let Some(path) = default_pile {
+        let _ = DEFAULT_PILE.set(path);
+    }
+    NO_REFRESH.store(no_refresh, Ordering::Relaxed);
     match cmd {
         PileCommand::Branch { cmd } => branch::run(cmd),
         PileCommand::Blob { cmd } => blob::run(cmd),
+        PileCommand::Entity { cmd } => entity::run(cmd),
+        PileCommand::ImportCsv {
+            pile,
+            id,
+            name,
+            file,
+            entity_column,
+            attrs,
+            stable_ids,
+            strict,
+            signing,
+        } => import::csv(
+            pile,
+            id,
+            name,
+            file,
+            entity_column,
+            attrs,
+            stable_ids,
+            strict,
+            signing,
+        ),
         PileCommand::Merge {
             pile,
             target,
             sources,
-            signing_key,
-        } => merge::run(pile, target, sources, signing_key),
+            message,
+            dry_run,
+            signing,
+        } => merge::run(pile, target, sources, message, dry_run, signing),
+        PileCommand::Commit { cmd } => commit::run(cmd),
         PileCommand::Create { path } => {
-            use triblespace_core::repo::pile::Pile;
-            use triblespace_core::value::schemas::hash::Blake3;
+            let path = resolve_pile(path)?;
 
             if let Some(parent) = path.parent() {
                 fs::create_dir_all(parent)?;
@@ -111,12 +453,127 @@ pub fn run(cmd: PileCommand) -> Result<()> {
         PileCommand::Net { cmd } => net::run(cmd),
         PileCommand::Diagnose { cmd } => diagnose::run(cmd),
         PileCommand::Migrate { pile, cmd } => migrate::run(pile, cmd),
+        PileCommand::Pin { cmd } => pin::run(cmd),
+        PileCommand::Query {
+            pile,
+            id,
+            name,
+            expr,
+            limit,
+        } => query::run(pile, id, name, expr, limit),
+        PileCommand::Search {
+            pile,
+            id,
+            name,
+            text,
+            ignore_case,
+            regex,
+        } => search::run(pile, id, name, text, ignore_case, regex),
+        PileCommand::Snapshot { cmd } => snapshot::run(cmd),
         PileCommand::Squash {
             source,
             dest,
             include,
             exclude,
-            signing_key,
-        } => squash::run(source, dest, signing_key, include, exclude),
+            signing,
+        } => squash::run(source, dest, signing, include, exclude),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Store {
+        closes: Rc<Cell<u32>>,
+        fail: bool,
+    }
+
+    impl Closeable for Store {
+        fn close_storage(self) -> Result<()> {
+            self.closes.set(self.closes.get() + 1);
+            if self.fail {
+                anyhow::bail!("close failed");
+            }
+            Ok(())
+        }
+    }
+
+    fn store(fail: bool) -> (Closing<Store>, Rc<Cell<u32>>) {
+        let closes = Rc::new(Cell::new(0));
+        let store = Store {
+            closes: closes.clone(),
+            fail,
+        };
+        (Closing::new(store), closes)
+    }
+
+    #[test]
+    fn derived_entities_depend_on_context_and_handle() {
+        assert_eq!(derived_entity("a", &[1; 32]), derived_entity("a", &[1; 32]));
+        assert_ne!(derived_entity("a", &[1; 32]), derived_entity("a", &[2; 32]));
+        assert_ne!(derived_entity("a", &[1; 32]), derived_entity("b", &[1; 32]));
+    }
+
+    #[test]
+    fn run_closes_and_reports_close_failures() {
+        let (closing, closes) = store(false);
+        assert_eq!(closing.run(|_| Ok(7)).unwrap(), 7);
+        assert_eq!(closes.get(), 1);
+
+        let (closing, closes) = store(true);
+        let err = closing.run(|_| Ok(())).unwrap_err();
+        assert_eq!(err.to_string(), "close failed");
+        assert_eq!(closes.get(), 1);
+    }
+
+    #[test]
+    fn run_prefers_the_body_error_and_still_closes() {
+        let (closing, closes) = store(true);
+        let err = closing
+            .run(|_| -> Result<()> { anyhow::bail!("body failed") })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "body failed");
+        assert_eq!(closes.get(), 1);
+    }
+
+    #[test]
+    fn dropping_closes_exactly_once() {
+        let (closing, closes) = store(false);
+        drop(closing);
+        assert_eq!(closes.get(), 1);
+
+        let (closing, closes) = store(false);
+        closing.close().unwrap();
+        assert_eq!(closes.get(), 1);
+    }
+
+    #[test]
+    fn live_reader_reports_records_appended_by_another_handle() {
+        use triblespace::prelude::blobschemas::LongString;
+        use triblespace::prelude::*;
+        use triblespace_core::repo::BlobStoreMeta;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("live.pile");
+        let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut live = LiveReader::new(&mut pile, &path).unwrap();
+
+        let mut writer: Pile<Blake3> = Pile::open(&path).unwrap();
+        let handle = writer.put::<LongString, _>("late".to_string()).unwrap();
+        let meta = writer.put::<SimpleArchive, _>(TribleSet::new()).unwrap();
+        writer.update(*ufoid(), None, Some(meta)).unwrap();
+        writer.close().unwrap();
+
+        assert!(live.reader().metadata(handle).unwrap().is_none());
+        let appended = live.refresh(&mut pile).unwrap();
+        assert_eq!((appended.blobs, appended.branch_updates), (2, 1));
+        assert!(live.reader().metadata(handle).unwrap().is_some());
+
+        let appended = live.refresh(&mut pile).unwrap();
+        assert_eq!((appended.blobs, appended.branch_updates), (0, 0));
+        pile.close().unwrap();
     }
 }
@@ -36,7 +36,7 @@ pub enum Command {
     },
     /// Sync with peers. No topic = serve only. With topic = live bidirectional sync.
     Sync {
-        pile: PathBuf,
+        pile: Option<PathBuf>,
         #[arg(long, value_delimiter = ',')]
         peers: Vec<String>,
         #[arg(long)]
@@ -45,8 +45,9 @@ pub enum Command {
         key: Option<PathBuf>,
     },
     /// One-shot pull a branch from a remote peer.
+    #[command(allow_missing_positional = true)]
     Pull {
-        pile: PathBuf,
+        pile: Option<PathBuf>,
         remote: String,
         #[arg(long)]
         branch: String,
@@ -58,12 +59,18 @@ pub enum Command {
 pub fn run(cmd: Command) -> Result<()> {
     match cmd {
         Command::Identity { key } => run_identity(key),
-        Command::Sync { pile, peers, topic, key } => {
-            run_sync(pile, peers, topic, key)
-        }
-        Command::Pull { pile, remote, branch, key } => {
-            run_pull(pile, remote, branch, key)
-        }
+        Command::Sync {
+            pile,
+            peers,
+            topic,
+            key,
+        } => run_sync(super::resolve_pile(pile)?, peers, topic, key),
+        Command::Pull {
+            pile,
+            remote,
+            branch,
+            key,
+        } => run_pull(super::resolve_pile(pile)?, remote, branch, key),
     }
 }
 
@@ -168,7 +175,10 @@ fn run_pull(pile_path: PathBuf, remote: String, branch: String, key_path: Option
     let outcome = triblespace_net::tracking::merge_tracking_into_local(
         &mut repo, tracking_id, &branch,
     )?;
-    let _ = repo.into_storage().into_store().close();
+    repo.into_storage()
+        .into_store()
+        .close()
+        .map_err(|e| anyhow!("close pile: {e:?}"))?;
 
     match outcome {
         MergeOutcome::Empty => return Err(anyhow!("remote has no commit")),
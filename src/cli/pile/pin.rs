@@ -0,0 +1,314 @@
+//! `trible pile pin`: keep blobs that no branch reaches.
+//!
+//! Pins are tribles on the reserved `_pins` branch, one entity per pinned
+//! handle. Every change commits the complete pin set on top of the previous
+//! one, so only the head commit's content counts. `pile diagnose check
+//! --space` treats the pinned handles as extra roots, keeps the branch's own
+//! commits, contents and messages out of the unreachable count, and reports
+//! what only pins keep alive on a line of its own.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::collections::{BTreeSet, HashSet};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::BranchStore;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::blob::{Blob, ToBlob};
+use triblespace_core::id::ExclusiveId;
+use triblespace_core::repo::pile::{Pile, PileReader};
+use triblespace_core::repo::{BlobStoreMeta, PushResult};
+use triblespace_core::trible::{Trible, TribleSet};
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::error::{self, ErrorKind};
+use crate::cli::output::{note, Field, Output};
+use crate::cli::util::parse_blob_handle;
+use crate::cli::verify;
+
+use super::branch::{extract_repo_head, provenance, read_commit_fields};
+use super::resolve::{resolve_branch, BranchInfo, BranchRef};
+use super::signing::SigningArgs;
+use super::walk::{walk_commits, DEFAULT_MAX_DEPTH};
+use super::ClosingPile;
+
+/// The branch pins are recorded on.
+pub const BRANCH: &str = "_pins";
+
+/// Derivation context of the entity a blob's pin is recorded on.
+const ENTITY_CONTEXT: &str = "trible pile pin entity";
+
+pub(crate) mod schema {
+    use triblespace::prelude::*;
+
+    attributes! {
+        "9D9003573D3E5C272717CC3F6B677681" as pinned: valueschemas::Handle<valueschemas::Blake3, blobschemas::UnknownBlob>;
+    }
+}
+
+#[derive(Parser)]
+pub enum Command {
+    /// Pin blobs so the unreachable report no longer counts them.
+    ///
+    /// Handles the pile does not hold are pinned anyway, with a warning,
+    /// so a blob can be pinned before it arrives.
+    Add {
+        /// Path to the pile file to modify
+        pile: PathBuf,
+        /// Handles of the blobs to pin (e.g. "blake3:HEX...")
+        #[arg(required = true)]
+        handles: Vec<String>,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+    /// List pinned handles and whether the pile holds them.
+    List {
+        /// Path to the pile file to read
+        pile: Option<PathBuf>,
+    },
+    /// Unpin blobs.
+    Remove {
+        /// Path to the pile file to modify
+        pile: PathBuf,
+        /// Handles of the blobs to unpin (e.g. "blake3:HEX...")
+        #[arg(required = true)]
+        handles: Vec<String>,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+}
+
+pub fn run(cmd: Command) -> Result<()> {
+    match cmd {
+        Command::Add {
+            pile,
+            handles,
+            signing,
+        } => change(pile, handles, true, signing),
+        Command::List { pile } => list(super::resolve_pile(pile)?),
+        Command::Remove {
+            pile,
+            handles,
+            signing,
+        } => change(pile, handles, false, signing),
+    }
+}
+
+fn change(pile_path: PathBuf, handles: Vec<String>, add: bool, signing: SigningArgs) -> Result<()> {
+    let hashes = handles
+        .iter()
+        .map(|handle| Ok(parse_blob_handle(handle)?.raw))
+        .collect::<Result<Vec<[u8; 32]>>>()?;
+    let key = signing.load()?;
+    ClosingPile::open(&pile_path)?.run(|pile| {
+        pile.refresh()?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let (branch, mut pins) = match load(pile, &reader)? {
+            Some((info, pins)) => (Some(info), pins),
+            None => (None, BTreeSet::new()),
+        };
+
+        let mut changed = 0usize;
+        for hash in &hashes {
+            let label = format!("blake3:{}", hex::encode(hash));
+            if add {
+                let blob: Value<Handle<Blake3, UnknownBlob>> = Value::new(*hash);
+                if reader.metadata(blob)?.is_none() {
+                    eprintln!("warning: {label} is not in the pile; pinned anyway");
+                }
+                if pins.insert(*hash) {
+                    changed += 1;
+                }
+            } else if pins.remove(hash) {
+                changed += 1;
+            } else {
+                eprintln!("warning: {label} is not pinned");
+            }
+        }
+        let verb = if add { "pinned" } else { "unpinned" };
+        if changed == 0 {
+            note!("{verb} 0 blob(s); {} pin(s) in total", pins.len());
+            return Ok(());
+        }
+
+        let (branch_id, old) = match branch {
+            Some(info) => (info.id, info.meta),
+            None => {
+                let branch_id = triblespace::prelude::ufoid();
+                let name: Value<Handle<Blake3, LongString>> =
+                    verify::put(pile, BRANCH.to_string().to_blob()).context("put name blob")?;
+                let meta = provenance::new_branch_metadata(&key, &branch_id, name);
+                let meta = verify::put(pile, meta).context("put branch meta")?;
+                match pile.update(*branch_id, None, Some(meta))? {
+                    PushResult::Success() => {}
+                    PushResult::Conflict(_) => {
+                        return Err(error::conflict(format!(
+                            "branch id {:X} is already taken",
+                            *branch_id
+                        )))
+                    }
+                }
+                (*branch_id, meta)
+            }
+        };
+        let old_meta: TribleSet = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?
+            .get(old)
+            .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
+
+        let mut content = TribleSet::new();
+        for hash in &pins {
+            let entity = ExclusiveId::force(super::derived_entity(ENTITY_CONTEXT, hash));
+            let value: Value<Handle<Blake3, UnknownBlob>> = Value::new(*hash);
+            content.insert(&Trible::new(&entity, &schema::pinned.id(), &value));
+        }
+        let content: Blob<SimpleArchive> = content.to_blob();
+        verify::put(pile, content.clone()).context("put pin set")?;
+        let message = format!("{verb} {changed} blob(s)");
+        let message = verify::put::<LongString, _>(pile, message).context("put message")?;
+        let commit: Blob<SimpleArchive> = triblespace_core::repo::commit::commit_metadata(
+            &key,
+            extract_repo_head(&old_meta).into_iter().collect::<Vec<_>>(),
+            Some(message),
+            Some(content),
+            None,
+        )
+        .to_blob();
+        verify::put(pile, commit.clone()).context("put commit")?;
+        let mut new_meta = triblespace_core::repo::branch::branch_metadata(
+            &key,
+            branch_id,
+            super::merge::name_handle(&old_meta, branch_id)?,
+            Some(commit),
+        );
+        provenance::keep_creator(&old_meta, &mut new_meta);
+        let new_meta = verify::put(pile, new_meta).context("put branch metadata")?;
+        match pile.update(branch_id, Some(old), Some(new_meta))? {
+            PushResult::Success() => {}
+            PushResult::Conflict(_) => {
+                return Err(error::conflict(format!(
+                    "{BRANCH} changed concurrently; run the pin command again"
+                )))
+            }
+        }
+        note!("{verb} {changed} blob(s); {} pin(s) in total", pins.len());
+        Ok(())
+    })
+}
+
+fn list(pile: PathBuf) -> Result<()> {
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let pins = load(pile, &reader)?
+            .map(|(_, pins)| pins)
+            .unwrap_or_default();
+        let mut out = Output::stdout();
+        for hash in pins {
+            let blob: Value<Handle<Blake3, UnknownBlob>> = Value::new(hash);
+            let present = reader.metadata(blob)?.is_some();
+            out.record(&[
+                (
+                    "handle",
+                    Field::from(format!("blake3:{}", hex::encode(hash))),
+                ),
+                ("present", Field::from(if present { "yes" } else { "no" })),
+            ])?;
+        }
+        Ok(())
+    })
+}
+
+/// The pins branch and the handles pinned by its head commit, or `None`
+/// while nothing was ever pinned.
+pub(crate) fn load(
+    pile: &mut Pile<Blake3>,
+    reader: &PileReader<Blake3>,
+) -> Result<Option<(BranchInfo, BTreeSet<[u8; 32]>)>> {
+    let info = match resolve_branch(pile, reader, BranchRef::Name(BRANCH)) {
+        Ok(info) => info,
+        Err(e) if error::kind_of(&e) == Some(ErrorKind::NotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let meta: TribleSet = reader
+        .get(info.meta)
+        .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
+    // Each commit holds the whole pin set, so older ones do not count.
+    let content = match extract_repo_head(&meta) {
+        Some(head) => {
+            let commit: TribleSet = reader
+                .get(head)
+                .map_err(|e| anyhow::anyhow!("read commit: {e:?}"))?;
+            match read_commit_fields(&commit).content {
+                Some(content) => reader
+                    .get(content)
+                    .map_err(|e| anyhow::anyhow!("read commit content: {e:?}"))?,
+                None => TribleSet::new(),
+            }
+        }
+        None => TribleSet::new(),
+    };
+    let attr = schema::pinned.id();
+    let pins = content
+        .iter()
+        .filter(|t| t.a() == &attr)
+        .map(|t| t.data[32..].try_into().expect("32 byte value"))
+        .collect();
+    Ok(Some((info, pins)))
+}
+
+/// The blobs the pins branch keeps for itself: every metadata blob it ever
+/// had and what the current one points at, and every pin commit with its
+/// content and message. Unlike `repo::reachable` this does not follow the
+/// handles inside the contents, so a blob unpinned since is not kept alive
+/// by an older commit.
+pub(crate) fn bookkeeping(
+    pile_path: &Path,
+    reader: &PileReader<Blake3>,
+    info: &BranchInfo,
+) -> Result<HashSet<[u8; 32]>> {
+    let meta: TribleSet = reader
+        .get(info.meta)
+        .map_err(|e| anyhow::anyhow!("read branch metadata: {e:?}"))?;
+    let mut kept = HashSet::from([info.meta.raw]);
+    super::scan::walk_journal(pile_path, |entry| {
+        if entry.branch_id == info.id {
+            kept.extend(entry.new.map(|meta| meta.raw));
+        }
+        Ok(())
+    })?;
+    // Values that are not handles never match a blob, so taking them all
+    // is harmless.
+    kept.extend(
+        meta.iter()
+            .map(|t| -> [u8; 32] { t.data[32..].try_into().expect("32 byte value") }),
+    );
+    let Some(head) = extract_repo_head(&meta) else {
+        return Ok(kept);
+    };
+    let failed = walk_commits(head, DEFAULT_MAX_DEPTH, |current| {
+        let commit: TribleSet = match reader.get(current) {
+            Ok(commit) => commit,
+            Err(e) => return ControlFlow::Break(anyhow::anyhow!("read commit: {e:?}")),
+        };
+        kept.insert(current.raw);
+        let info = read_commit_fields(&commit);
+        kept.extend(info.content.map(|h| h.raw));
+        kept.extend(info.message.map(|h| h.raw));
+        ControlFlow::Continue(info.parents)
+    })?;
+    match failed {
+        Some(e) => Err(e),
+        None => Ok(kept),
+    }
+}
@@ -0,0 +1,313 @@
+//! `pile branch prune-history`: drop old branch records from a pile.
+//!
+//! Every branch update appends a record pointing at a new metadata blob, and
+//! `branch reflog` and `branch restore` can go back to any of them. Pruning
+//! rewrites the pile into a temporary file next to it, copying every record
+//! byte for byte except the dropped branch records and the metadata blobs
+//! only they point at, and renames the result over the original. The pile
+//! stays exclusively locked from the scan to the rename. Commits and
+//! content stay, as do the times blobs were written at; only the ways back
+//! to older heads go.
+
+use anyhow::Result;
+use fs2::FileExt;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace::prelude::BranchStore;
+use triblespace_core::id::Id;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use super::branch::{parse_branch_id_hex, select_branch};
+use super::scan::{self, PileRecord, RawBranchRecord, RecordKind, RECORD_LEN};
+use super::ClosingPile;
+use crate::cli::output::{note, Field, Output};
+use crate::cli::util::{confirm, parse_time};
+
+type MetaHandle = Value<Handle<Blake3, SimpleArchive>>;
+
+/// Which records of a branch survive.
+#[derive(Clone, Copy, Debug)]
+enum Retain {
+    /// The most recent N set records.
+    Last(usize),
+    /// Set records written at or after this Unix millisecond time.
+    Since(u64),
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run(
+    path: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    all: bool,
+    keep: Option<usize>,
+    keep_since: Option<String>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let retain = match (keep, keep_since.as_deref()) {
+        (Some(n), _) => Retain::Last(n),
+        (None, Some(raw)) => Retain::Since(parse_time(raw)?),
+        (None, None) => unreachable!("clap requires --keep or --keep-since"),
+    };
+
+    // Branch names are resolved through the pile; everything after works on
+    // the raw file under the lock.
+    let selected: Option<Id> = if all {
+        None
+    } else {
+        ClosingPile::open(&path)?.run(|pile| {
+            pile.refresh()?;
+            // A deleted branch no longer resolves, but its records are
+            // still there to prune.
+            match select_branch(pile, id.as_deref(), name.as_deref()) {
+                Ok(branch_id) => Ok(Some(branch_id)),
+                Err(err) => match id.as_deref().map(parse_branch_id_hex) {
+                    Some(Ok(branch_id)) => Ok(Some(branch_id)),
+                    _ => Err(err),
+                },
+            }
+        })?
+    };
+
+    // Held from the scan until the copy has replaced the pile, so no other
+    // handle appends to a file that is about to be unlinked.
+    let lock = lock_pile(&path)?;
+    let (records, end) = scan::pile_records(&path)?;
+    let file_len = lock.metadata()?.len();
+    if end < file_len {
+        return Err(crate::cli::error::corrupt(format!(
+            "{} has {} unreadable byte(s) after offset {end}; the pile was not changed, \
+             run `trible pile diagnose check` on it first",
+            path.display(),
+            file_len - end
+        )));
+    }
+    let selected: HashSet<Id> = match selected {
+        Some(branch_id) => HashSet::from([branch_id]),
+        None => records
+            .iter()
+            .filter_map(|r| match r {
+                PileRecord::Branch(rec) => Some(rec.branch_id),
+                PileRecord::Blob { .. } => None,
+            })
+            .collect(),
+    };
+
+    // Branch records per selected branch with the newest blob time
+    // before each, as `branch journal` stamps them.
+    let mut per_branch: HashMap<Id, Vec<(usize, RecordKind, Option<u64>)>> = HashMap::new();
+    let mut newest_blob: Option<u64> = None;
+    for (index, record) in records.iter().enumerate() {
+        match record {
+            PileRecord::Blob { timestamp, .. } => {
+                newest_blob = Some(newest_blob.map_or(*timestamp, |t| t.max(*timestamp)));
+            }
+            PileRecord::Branch(rec) if selected.contains(&rec.branch_id) => per_branch
+                .entry(rec.branch_id)
+                .or_default()
+                .push((index, rec.kind, newest_blob)),
+            PileRecord::Branch(_) => {}
+        }
+    }
+
+    let mut dropped: HashSet<usize> = HashSet::new();
+    let mut branches: Vec<(Id, usize, usize)> = Vec::new();
+    for (branch_id, entries) in &per_branch {
+        let kinds: Vec<(RecordKind, Option<u64>)> = entries
+            .iter()
+            .map(|&(_, kind, time)| (kind, time))
+            .collect();
+        let first = first_kept(&kinds, retain);
+        dropped.extend(entries[..first].iter().map(|&(index, ..)| index));
+        branches.push((*branch_id, entries.len() - first, first));
+    }
+    branches.sort();
+
+    // Metadata blobs only dropped records point at go with them.
+    let meta_of = |index: usize| match &records[index] {
+        PileRecord::Branch(rec) => rec.meta_handle,
+        PileRecord::Blob { .. } => None,
+    };
+    let retained_metas: HashSet<MetaHandle> = (0..records.len())
+        .filter(|index| !dropped.contains(index))
+        .filter_map(meta_of)
+        .collect();
+    let dropped_metas: HashSet<[u8; 32]> = dropped
+        .iter()
+        .filter_map(|&index| meta_of(index))
+        .filter(|meta| !retained_metas.contains(meta))
+        .map(|meta| meta.raw)
+        .collect();
+    let mut dropped_bytes = dropped.len() as u64 * RECORD_LEN;
+    for record in &records {
+        if let PileRecord::Blob { hash, len, .. } = record {
+            if dropped_metas.contains(hash) {
+                dropped_bytes += len;
+            }
+        }
+    }
+
+    let mut out = Output::stdout();
+    for (branch_id, kept, removed) in &branches {
+        out.record(&[
+            ("branch", Field::from(format!("{branch_id:X}"))),
+            ("kept", Field::from(*kept)),
+            ("dropped", Field::from(*removed)),
+        ])?;
+    }
+    let verb = if dry_run { "would drop" } else { "dropped" };
+    let summary = format!(
+        "{verb} {} record(s) and {dropped_bytes} byte(s)",
+        dropped.len()
+    );
+    if dry_run || dropped.is_empty() {
+        note!("{summary}");
+        return Ok(());
+    }
+    confirm(
+        &format!(
+            "This will drop {} branch record(s) from {} for good",
+            dropped.len(),
+            path.display()
+        ),
+        yes,
+    )?;
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut partial = tempfile::Builder::new()
+        .prefix(".trible-prune-")
+        .tempfile_in(dir)?;
+    // Records are copied byte for byte, so blobs keep the times they
+    // were written at and the journal times stay as they were.
+    let mut src = File::open(&path)?;
+    {
+        let mut dst = BufWriter::new(partial.as_file_mut());
+        let mut buf = Vec::new();
+        for (index, record) in records.iter().enumerate() {
+            let (offset, len) = match record {
+                PileRecord::Blob { hash, .. } if dropped_metas.contains(hash) => continue,
+                PileRecord::Blob { offset, len, .. } => (*offset, *len),
+                PileRecord::Branch(_) if dropped.contains(&index) => continue,
+                PileRecord::Branch(rec) => (rec.offset, RECORD_LEN),
+            };
+            buf.resize(len as usize, 0);
+            src.seek(SeekFrom::Start(offset))?;
+            src.read_exact(&mut buf)?;
+            dst.write_all(&buf)?;
+        }
+        dst.flush()?;
+    }
+    partial.as_file().sync_all()?;
+
+    // The copy must read back with every branch where the journal left it.
+    let branch_records: Vec<RawBranchRecord> = records
+        .iter()
+        .filter_map(|record| match record {
+            PileRecord::Branch(rec) => Some(rec.clone()),
+            PileRecord::Blob { .. } => None,
+        })
+        .collect();
+    let heads = scan::collapse_branch_states(&branch_records);
+    ClosingPile::open(partial.path())?.run(|copy| {
+        copy.refresh()?;
+        for (branch_id, state) in &heads {
+            if copy.head(*branch_id)? != state.meta {
+                return Err(crate::cli::error::corrupt(format!(
+                    "branch {branch_id:X} moved in the rewritten copy; the pile was not changed"
+                )));
+            }
+        }
+        Ok(())
+    })?;
+
+    // Writers that ignore the lock would still be lost to the rename.
+    let late = scan::appended_since(&path, end)?;
+    if late.blobs + late.branch_updates > 0 {
+        return Err(crate::cli::error::conflict(
+            "the pile was written to while pruning; nothing was replaced, run the prune again",
+        ));
+    }
+    std::fs::set_permissions(partial.path(), lock.metadata()?.permissions())?;
+    partial.persist(&path).map_err(|e| e.error)?;
+    drop(lock);
+    note!("{summary}");
+    Ok(())
+}
+
+/// Open the pile file and take an exclusive lock on it, waiting for other
+/// handles to let go.
+fn lock_pile(path: &Path) -> Result<File> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    match file.try_lock_exclusive() {
+        Ok(()) => {}
+        Err(e) if e.kind() == fs2::lock_contended_error().kind() => {
+            note!("waiting for other processes to release {}", path.display());
+            file.lock_exclusive()?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(file)
+}
+
+/// Index of the first record of a branch to keep, given its records oldest
+/// first. Everything from the first retained set record on survives, so a
+/// later delete stays in place, and the last set record is always kept.
+fn first_kept(records: &[(RecordKind, Option<u64>)], retain: Retain) -> usize {
+    let sets: Vec<usize> = records
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, _))| *kind == RecordKind::Set)
+        .map(|(index, _)| index)
+        .collect();
+    let Some(&last) = sets.last() else {
+        return 0;
+    };
+    match retain {
+        Retain::Last(n) => sets[sets.len().saturating_sub(n.max(1))],
+        Retain::Since(since) => sets
+            .iter()
+            .copied()
+            .find(|&index| records[index].1.is_some_and(|time| time >= since))
+            .unwrap_or(last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SET: RecordKind = RecordKind::Set;
+    const DELETE: RecordKind = RecordKind::Tombstone;
+
+    #[test]
+    fn keeps_the_last_sets_and_any_later_delete() {
+        let records = [
+            (SET, Some(1)),
+            (SET, Some(2)),
+            (SET, Some(3)),
+            (DELETE, Some(3)),
+        ];
+        assert_eq!(first_kept(&records, Retain::Last(2)), 1);
+        assert_eq!(first_kept(&records, Retain::Last(10)), 0);
+        // The current metadata survives even when asked to keep nothing.
+        assert_eq!(first_kept(&records, Retain::Last(0)), 2);
+        assert_eq!(first_kept(&[(DELETE, None)], Retain::Last(1)), 0);
+    }
+
+    #[test]
+    fn keep_since_falls_back_to_the_last_set() {
+        let records = [(SET, Some(10)), (SET, Some(20)), (SET, Some(30))];
+        assert_eq!(first_kept(&records, Retain::Since(15)), 1);
+        assert_eq!(first_kept(&records, Retain::Since(5)), 0);
+        assert_eq!(first_kept(&records, Retain::Since(99)), 2);
+    }
+}
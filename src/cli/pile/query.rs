@@ -0,0 +1,385 @@
+//! `trible pile query`: conjunctive pattern queries over a branch's content.
+//!
+//! An expression is a list of clauses separated by `;`, each an
+//! `ENTITY ATTRIBUTE VALUE` pattern:
+//!
+//! ```text
+//! ?book 5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F ?title; ?book author ?who
+//! ```
+//!
+//! `?name` is a variable and binds the same value in every clause it
+//! appears in. Entities are otherwise 32-char hex ids; attributes are hex ids
+//! or names known to [`crate::cli::attr`]. Values are 64-char hex raw
+//! values, 32-char hex ids (for references to other entities) or `"text"`
+//! for a short string. Each result prints the variables in order of first
+//! appearance.
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::cli::error::invalid_argument;
+use crate::cli::output::{Field, Output};
+
+use super::branch::{head_content, select_branch};
+use super::ClosingPile;
+
+/// A position in a clause: a variable (by index) or a constant, with ids
+/// widened to 32 bytes the way `GenId` values store them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Term {
+    Var(usize),
+    Const([u8; 32]),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Clause {
+    entity: Term,
+    attribute: Term,
+    value: Term,
+}
+
+impl Clause {
+    fn terms(&self) -> [Term; 3] {
+        [self.entity, self.attribute, self.value]
+    }
+}
+
+#[derive(Debug)]
+struct Variable {
+    name: String,
+    /// First seen as an entity or attribute, so printed as an id.
+    is_id: bool,
+}
+
+#[derive(Debug)]
+struct Query {
+    variables: Vec<Variable>,
+    clauses: Vec<Clause>,
+}
+
+pub fn run(
+    pile: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    expr: String,
+    limit: Option<usize>,
+) -> Result<()> {
+    let query = parse(&expr)?;
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let branch_id = select_branch(pile, id.as_deref(), name.as_deref())?;
+        let content = head_content(pile, branch_id)?;
+        let tribles: Vec<[u8; 64]> = content.iter().map(|t| t.data).collect();
+
+        let mut out = Output::stdout();
+        for row in query.solve(&tribles, limit) {
+            let fields: Vec<(&str, Field)> = query
+                .variables
+                .iter()
+                .zip(row)
+                .map(|(var, value)| {
+                    let text = if var.is_id {
+                        hex::encode_upper(&value[16..])
+                    } else {
+                        hex::encode(value)
+                    };
+                    (var.name.as_str(), Field::from(text))
+                })
+                .collect();
+            out.record(&fields)?;
+        }
+        Ok(())
+    })
+}
+
+fn parse(expr: &str) -> Result<Query> {
+    let mut query = Query {
+        variables: Vec::new(),
+        clauses: Vec::new(),
+    };
+    for clause in split_clauses(expr)? {
+        let [entity, attribute, value] = clause.as_slice() else {
+            return Err(invalid_argument(format!(
+                "expected ENTITY ATTRIBUTE VALUE, got {:?}",
+                clause.join(" ")
+            )));
+        };
+        let entity = query.term(entity, true, |t| parse_id(t).map(widen))?;
+        let attribute = query.term(attribute, true, |t| parse_attribute(t).map(widen))?;
+        let value = query.term(value, false, parse_value)?;
+        query.clauses.push(Clause {
+            entity,
+            attribute,
+            value,
+        });
+    }
+    if query.clauses.is_empty() {
+        return Err(invalid_argument("empty query"));
+    }
+    if query.variables.is_empty() {
+        return Err(invalid_argument("query has no ?variables to print"));
+    }
+    Ok(query)
+}
+
+/// Split on `;` and whitespace outside of double quotes.
+fn split_clauses(expr: &str) -> Result<Vec<Vec<String>>> {
+    let mut clauses = Vec::new();
+    let mut clause: Vec<String> = Vec::new();
+    let mut token: Option<String> = None;
+    let mut chars = expr.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut quoted = String::from('"');
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => quoted.push(c),
+                        None => return Err(invalid_argument("unterminated string in query")),
+                    }
+                }
+                quoted.push('"');
+                token.get_or_insert_with(String::new).push_str(&quoted);
+            }
+            ';' => {
+                clause.extend(token.take());
+                if !clause.is_empty() {
+                    clauses.push(std::mem::take(&mut clause));
+                }
+            }
+            c if c.is_whitespace() => clause.extend(token.take()),
+            c => token.get_or_insert_with(String::new).push(c),
+        }
+    }
+    clause.extend(token);
+    if !clause.is_empty() {
+        clauses.push(clause);
+    }
+    Ok(clauses)
+}
+
+impl Query {
+    fn term(
+        &mut self,
+        token: &str,
+        is_id: bool,
+        constant: impl FnOnce(&str) -> Result<[u8; 32]>,
+    ) -> Result<Term> {
+        let Some(name) = token.strip_prefix('?') else {
+            return constant(token).map(Term::Const);
+        };
+        if name.is_empty() {
+            return Err(invalid_argument("variable names may not be empty"));
+        }
+        if let Some(index) = self.variables.iter().position(|v| v.name == name) {
+            return Ok(Term::Var(index));
+        }
+        self.variables.push(Variable {
+            name: name.to_string(),
+            is_id,
+        });
+        Ok(Term::Var(self.variables.len() - 1))
+    }
+
+    /// Every binding of the variables that satisfies all clauses, up to
+    /// `limit` rows.
+    fn solve(&self, tribles: &[[u8; 64]], limit: Option<usize>) -> Vec<Vec<[u8; 32]>> {
+        let candidates: Vec<Vec<&[u8; 64]>> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                tribles
+                    .iter()
+                    .filter(|t| {
+                        clause
+                            .terms()
+                            .iter()
+                            .zip(parts(t))
+                            .all(|(term, part)| !matches!(term, Term::Const(c) if *c != part))
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut rows = Vec::new();
+        let mut binding = vec![None; self.variables.len()];
+        let order = self.plan();
+        self.search(&order, &candidates, &mut binding, &mut rows, limit);
+        rows
+    }
+
+    /// Clause order: always continue with the clause that has the most
+    /// constant or already bound positions.
+    fn plan(&self) -> Vec<usize> {
+        let mut bound = vec![false; self.variables.len()];
+        let mut remaining: Vec<usize> = (0..self.clauses.len()).collect();
+        let mut order = Vec::new();
+        while !remaining.is_empty() {
+            let known = |i: usize| {
+                self.clauses[i]
+                    .terms()
+                    .iter()
+                    .filter(|t| match t {
+                        Term::Var(v) => bound[*v],
+                        Term::Const(_) => true,
+                    })
+                    .count()
+            };
+            let (pos, _) = remaining
+                .iter()
+                .enumerate()
+                .max_by_key(|&(pos, &i)| (known(i), std::cmp::Reverse(pos)))
+                .expect("non-empty");
+            let next = remaining.remove(pos);
+            for term in self.clauses[next].terms() {
+                if let Term::Var(v) = term {
+                    bound[v] = true;
+                }
+            }
+            order.push(next);
+        }
+        order
+    }
+
+    fn search(
+        &self,
+        order: &[usize],
+        candidates: &[Vec<&[u8; 64]>],
+        binding: &mut [Option<[u8; 32]>],
+        rows: &mut Vec<Vec<[u8; 32]>>,
+        limit: Option<usize>,
+    ) {
+        let Some((&next, rest)) = order.split_first() else {
+            rows.push(binding.iter().map(|v| v.expect("all bound")).collect());
+            return;
+        };
+        for trible in &candidates[next] {
+            if limit.is_some_and(|limit| rows.len() >= limit) {
+                return;
+            }
+            let mut newly_bound = Vec::new();
+            let matched = self.clauses[next].terms().iter().zip(parts(trible)).all(
+                |(term, part)| match *term {
+                    Term::Const(c) => c == part,
+                    Term::Var(v) => match binding[v] {
+                        Some(bound) => bound == part,
+                        None => {
+                            binding[v] = Some(part);
+                            newly_bound.push(v);
+                            true
+                        }
+                    },
+                },
+            );
+            if matched {
+                self.search(rest, candidates, binding, rows, limit);
+            }
+            for v in newly_bound {
+                binding[v] = None;
+            }
+        }
+    }
+}
+
+/// Entity, attribute and value of a trible, ids widened to 32 bytes.
+fn parts(trible: &[u8; 64]) -> [[u8; 32]; 3] {
+    let mut entity = [0u8; 16];
+    let mut attribute = [0u8; 16];
+    let mut value = [0u8; 32];
+    entity.copy_from_slice(&trible[..16]);
+    attribute.copy_from_slice(&trible[16..32]);
+    value.copy_from_slice(&trible[32..]);
+    [widen(entity), widen(attribute), value]
+}
+
+fn widen(id: [u8; 16]) -> [u8; 32] {
+    let mut value = [0u8; 32];
+    value[16..].copy_from_slice(&id);
+    value
+}
+
+pub(super) fn parse_id(token: &str) -> Result<[u8; 16]> {
+    let mut id = [0u8; 16];
+    hex::decode_to_slice(token, &mut id)
+        .map_err(|_| invalid_argument(format!("expected a 32-char hex id, got {token:?}")))?;
+    Ok(id)
+}
+
+pub(super) fn parse_attribute(token: &str) -> Result<[u8; 16]> {
+    if let Some(id) = crate::cli::attr::resolve(token) {
+        return parse_id(&id);
+    }
+    parse_id(token).map_err(|_| {
+        invalid_argument(format!(
+            "unknown attribute {token:?}: expected a 32-char hex id or a registered name"
+        ))
+    })
+}
+
+fn parse_value(token: &str) -> Result<[u8; 32]> {
+    if let Some(text) = token
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        if text.len() > 32 {
+            return Err(invalid_argument(format!(
+                "string {text:?} is longer than 32 bytes"
+            )));
+        }
+        let mut value = [0u8; 32];
+        value[..text.len()].copy_from_slice(text.as_bytes());
+        return Ok(value);
+    }
+    if token.len() == 32 {
+        return parse_id(token).map(widen);
+    }
+    let mut value = [0u8; 32];
+    hex::decode_to_slice(token, &mut value).map_err(|_| {
+        invalid_argument(format!(
+            "expected ?var, \"text\", or a 32- or 64-char hex value, got {token:?}"
+        ))
+    })?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trible(entity: u8, attribute: u8, value: [u8; 32]) -> [u8; 64] {
+        let mut t = [0u8; 64];
+        t[..16].copy_from_slice(&[entity; 16]);
+        t[16..32].copy_from_slice(&[attribute; 16]);
+        t[32..].copy_from_slice(&value);
+        t
+    }
+
+    #[test]
+    fn clauses_split_on_semicolons_outside_strings() {
+        let clauses = split_clauses(r#"?e AA "a; b" ;; ?e BB ?v"#).unwrap();
+        assert_eq!(
+            clauses,
+            [vec!["?e", "AA", "\"a; b\""], vec!["?e", "BB", "?v"]]
+        );
+        assert!(split_clauses("?e AA \"open").is_err());
+    }
+
+    #[test]
+    fn joins_share_variables_across_clauses() {
+        let kind = "01".repeat(16);
+        let owner = "02".repeat(16);
+        let query = parse(&format!(r#"?pet {kind} "cat"; ?pet {owner} ?who"#)).unwrap();
+        let cat = parse_value("\"cat\"").unwrap();
+        let dog = parse_value("\"dog\"").unwrap();
+        let tribles = [
+            trible(7, 1, cat),
+            trible(7, 2, widen([9; 16])),
+            trible(8, 1, dog),
+            trible(8, 2, widen([10; 16])),
+        ];
+        let rows = query.solve(&tribles, None);
+        assert_eq!(rows, [vec![widen([7; 16]), widen([9; 16])]]);
+        assert!(query.variables.iter().all(|v| v.is_id == (v.name == "pet")));
+        assert!(query.solve(&tribles, Some(0)).is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+//! Resolving a branch argument to a branch, shared by every command that
+//! takes one, whether it names a branch in a pile or on a remote.
+//!
+//! An argument may be a full 32-character hex id, a unique hex prefix of at
+//! least [`MIN_PREFIX`] characters, or a branch name. Names are read from the
+//! `metadata::name` LongString and from the legacy ShortString attribute
+//! older piles still carry. An argument that fits more than one branch is an
+//! error listing the candidates, never a silent pick.
+
+use anyhow::Result;
+use std::convert::TryInto;
+
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::BranchStore;
+use triblespace_core::id::Id;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use super::branch::{extract_repo_head, load_branch_name};
+use super::migrate::legacy_branch_name;
+
+type MetaHandle = Value<Handle<Blake3, SimpleArchive>>;
+type CommitHandle = Value<Handle<Blake3, SimpleArchive>>;
+
+/// Shortest hex prefix accepted as an abbreviated branch id.
+pub(crate) const MIN_PREFIX: usize = 4;
+
+/// How a command was told which branch to use.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum BranchRef<'a> {
+    /// A full id or unique id prefix (`--id`).
+    Id(&'a str),
+    /// A branch name (`--name`).
+    Name(&'a str),
+    /// A positional argument: any of the above.
+    Any(&'a str),
+}
+
+impl BranchRef<'_> {
+    fn raw(&self) -> &str {
+        match self {
+            BranchRef::Id(raw) | BranchRef::Name(raw) | BranchRef::Any(raw) => raw.trim(),
+        }
+    }
+}
+
+/// A resolved branch and what its current metadata says about it.
+#[derive(Debug, Clone)]
+pub(crate) struct BranchInfo {
+    pub(crate) id: Id,
+    /// The LongString name, falling back to the legacy ShortString one.
+    pub(crate) name: Option<String>,
+    pub(crate) meta: MetaHandle,
+    pub(crate) head: Option<CommitHandle>,
+    /// Every name the metadata carries, for matching.
+    names: Vec<String>,
+    remote_state: bool,
+}
+
+impl BranchInfo {
+    /// `name (ID)`, or just the id for unnamed branches.
+    pub(crate) fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{name} ({:X})", self.id),
+            None => format!("{:X}", self.id),
+        }
+    }
+}
+
+/// Resolve `branch` against `store`, reading branch metadata through `reader`.
+pub(crate) fn resolve_branch<S: BranchStore<Blake3>>(
+    store: &mut S,
+    reader: &impl BlobStoreGet<Blake3>,
+    branch: BranchRef<'_>,
+) -> Result<BranchInfo> {
+    let raw = branch.raw();
+    // A 32-char hex string is still a legal name, so `--name` skips this.
+    if !matches!(branch, BranchRef::Name(_)) {
+        if let Some(id) = parse_full_id(raw) {
+            let meta = store
+                .head(id)?
+                .ok_or_else(|| crate::cli::error::not_found(format!("branch not found: {raw}")))?;
+            return Ok(branch_info(reader, id, meta));
+        }
+        if matches!(branch, BranchRef::Id(_)) && !is_prefix(raw) {
+            return Err(crate::cli::error::invalid_argument(format!(
+                "branch id {raw:?} is neither a 32-char hex id nor a hex prefix of at least {MIN_PREFIX} chars"
+            )));
+        }
+    }
+    pick(branch, all_branches(store, reader)?)
+}
+
+/// Every branch in `store` that has metadata, leaving out the bookkeeping
+/// entries of [`crate::cli::remote_state`].
+pub(crate) fn all_branches<S: BranchStore<Blake3>>(
+    store: &mut S,
+    reader: &impl BlobStoreGet<Blake3>,
+) -> Result<Vec<BranchInfo>> {
+    let ids: Vec<Id> = store.branches()?.collect::<Result<Vec<_>, _>>()?;
+    let mut branches = Vec::new();
+    for id in ids {
+        if let Some(meta) = store.head(id)? {
+            let info = branch_info(reader, id, meta);
+            if !info.remote_state {
+                branches.push(info);
+            }
+        }
+    }
+    Ok(branches)
+}
+
+/// Choose the single branch `branch` refers to among `branches`.
+fn pick(branch: BranchRef<'_>, branches: Vec<BranchInfo>) -> Result<BranchInfo> {
+    let raw = branch.raw();
+    let by_id = !matches!(branch, BranchRef::Name(_)) && is_prefix(raw);
+    let by_name = !matches!(branch, BranchRef::Id(_));
+    let prefix = raw.to_ascii_uppercase();
+    let mut candidates: Vec<BranchInfo> = branches
+        .into_iter()
+        .filter(|b| !b.remote_state)
+        .filter(|b| {
+            (by_name && b.names.iter().any(|n| n == raw))
+                || (by_id && format!("{:X}", b.id).starts_with(&prefix))
+        })
+        .collect();
+    match candidates.len() {
+        0 => Err(crate::cli::error::not_found(format!(
+            "branch not found: {raw}"
+        ))),
+        1 => Ok(candidates.remove(0)),
+        n => {
+            candidates.sort_by_key(|b| b.id);
+            let list: Vec<String> = candidates.iter().map(BranchInfo::label).collect();
+            Err(crate::cli::error::invalid_argument(format!(
+                "branch {raw:?} is ambiguous ({n} branches: {}); use the full hex id",
+                list.join(", ")
+            )))
+        }
+    }
+}
+
+fn branch_info(reader: &impl BlobStoreGet<Blake3>, id: Id, meta: MetaHandle) -> BranchInfo {
+    let Ok(set) = reader.get::<TribleSet, SimpleArchive>(meta) else {
+        return BranchInfo {
+            id,
+            name: None,
+            meta,
+            head: None,
+            names: Vec::new(),
+            remote_state: false,
+        };
+    };
+    let names: Vec<String> = [
+        load_branch_name(reader, &set).ok().flatten(),
+        legacy_branch_name(&set).ok().flatten(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    BranchInfo {
+        id,
+        name: names.first().cloned(),
+        meta,
+        head: extract_repo_head(&set),
+        names,
+        remote_state: crate::cli::remote_state::is_remote_state(&set),
+    }
+}
+
+fn parse_full_id(raw: &str) -> Option<Id> {
+    let bytes = hex::decode(raw).ok()?;
+    let bytes: [u8; 16] = bytes.as_slice().try_into().ok()?;
+    Id::new(bytes)
+}
+
+fn is_prefix(raw: &str) -> bool {
+    (MIN_PREFIX..32).contains(&raw.len()) && raw.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+    use triblespace_core::repo::pile::Pile;
+
+    mod legacy {
+        use triblespace::prelude::*;
+
+        attributes! {
+            "2E26F8BA886495A8DF04ACF0ED3ACBD4" as name: valueschemas::ShortString;
+        }
+    }
+
+    fn id(hex: &str) -> Id {
+        parse_full_id(hex).unwrap()
+    }
+
+    fn branch(id_hex: &str, name: Option<&str>) -> BranchInfo {
+        BranchInfo {
+            id: id(id_hex),
+            name: name.map(str::to_string),
+            meta: Value::new([0; 32]),
+            head: None,
+            names: name.into_iter().map(str::to_string).collect(),
+            remote_state: false,
+        }
+    }
+
+    fn fixture() -> Vec<BranchInfo> {
+        vec![
+            branch("AAAA0000000000000000000000000001", Some("main")),
+            branch("AAAA0000000000000000000000000002", Some("dup")),
+            branch("BBBB0000000000000000000000000003", Some("dup")),
+        ]
+    }
+
+    #[test]
+    fn unique_prefixes_resolve_case_insensitively() {
+        let found = pick(BranchRef::Any("bbbb"), fixture()).unwrap();
+        assert_eq!(found.id, id("BBBB0000000000000000000000000003"));
+        assert!(pick(BranchRef::Id("bbb"), fixture()).is_err());
+        // `--name` never matches ids.
+        assert!(pick(BranchRef::Name("bbbb"), fixture()).is_err());
+    }
+
+    #[test]
+    fn ambiguity_lists_every_candidate() {
+        let err = pick(BranchRef::Any("dup"), fixture()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "branch \"dup\" is ambiguous (2 branches: dup (AAAA0000000000000000000000000002), \
+             dup (BBBB0000000000000000000000000003)); use the full hex id"
+        );
+        let err = pick(BranchRef::Any("aaaa"), fixture()).unwrap_err();
+        assert!(err.to_string().contains("(2 branches: main (AAAA"));
+    }
+
+    #[test]
+    fn legacy_short_string_names_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pile: Pile<Blake3> = Pile::open(&dir.path().join("legacy.pile")).unwrap();
+
+        let legacy = ufoid();
+        let mut meta = TribleSet::new();
+        meta += entity! { &legacy @ legacy::name: "old-style" };
+        let meta = pile.put::<SimpleArchive, _>(meta).unwrap();
+        pile.update(*legacy, None, Some(meta)).unwrap();
+
+        let current = ufoid();
+        let name = pile.put::<LongString, _>("new-style".to_string()).unwrap();
+        let mut meta = TribleSet::new();
+        meta += entity! { &current @ triblespace_core::metadata::name: name };
+        let meta = pile.put::<SimpleArchive, _>(meta).unwrap();
+        pile.update(*current, None, Some(meta)).unwrap();
+
+        let reader = pile.reader().unwrap();
+        let found = resolve_branch(&mut pile, &reader, BranchRef::Name("old-style")).unwrap();
+        assert_eq!(found.id, *legacy);
+        assert_eq!(found.name.as_deref(), Some("old-style"));
+        let found = resolve_branch(&mut pile, &reader, BranchRef::Any("new-style")).unwrap();
+        assert_eq!(found.id, *current);
+        pile.close().unwrap();
+    }
+}
@@ -0,0 +1,469 @@
+//! One pass over a pile's branch journal, shared by the commands that look
+//! past the current heads (`branch list --all`, `branch reflog`,
+//! `branch consolidate --by-name-include-deleted`), plus the raw blob record
+//! pass behind `diagnose check --space` and the full record walk
+//! `branch prune-history` rewrites the pile from.
+//!
+//! The raw scan reads record headers only. Branch metadata blobs are decoded
+//! lazily through [`MetaCache`], at most once per handle, so branches and
+//! journal entries that share a metadata blob do not pay for the archive
+//! validation again.
+
+use anyhow::Result;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace::prelude::BlobStoreGet;
+use triblespace_core::id::id_hex;
+use triblespace_core::id::Id;
+use triblespace_core::repo::pile::PileReader;
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use super::branch::{extract_repo_head, load_branch_name};
+
+type MetaHandle = Value<Handle<Blake3, SimpleArchive>>;
+
+// These markers are part of the stable on-disk pile format (see
+// triblespace-rs/book/src/pile-format.md). Copy them exactly; do not invent.
+#[allow(non_upper_case_globals)]
+const MAGIC_MARKER_BLOB: Id = id_hex!("1E08B022FF2F47B6EBACF1D68EB35D96");
+#[allow(non_upper_case_globals)]
+const MAGIC_MARKER_BRANCH: Id = id_hex!("2BC991A7F5D5D2A3A468C53B0AA03504");
+#[allow(non_upper_case_globals)]
+const MAGIC_MARKER_BRANCH_TOMBSTONE: Id = id_hex!("E888CC787202D2AE4C654BFE9699C430");
+
+pub(crate) const RECORD_LEN: u64 = 64;
+
+/// Kind of raw branch record in a pile file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RecordKind {
+    Set,
+    Tombstone,
+}
+
+/// A single branch record read from the raw pile file.
+#[derive(Clone, Debug)]
+pub(crate) struct RawBranchRecord {
+    pub(crate) offset: u64,
+    pub(crate) branch_id: Id,
+    pub(crate) kind: RecordKind,
+    /// Branch metadata handle (only when kind == Set).
+    pub(crate) meta_handle: Option<MetaHandle>,
+}
+
+/// Collapsed final state per branch from a raw pile scan.
+#[derive(Clone, Debug)]
+pub(crate) struct BranchState {
+    pub(crate) kind: RecordKind,
+    /// Current metadata handle (only when kind == Set).
+    pub(crate) meta: Option<MetaHandle>,
+    /// Most recent Set metadata handle (kept even after tombstone).
+    pub(crate) last_set: Option<MetaHandle>,
+}
+
+impl BranchState {
+    /// The metadata describing the branch, falling back to the last set
+    /// record for tombstoned branches.
+    pub(crate) fn meta_handle(&self) -> Option<MetaHandle> {
+        match self.kind {
+            RecordKind::Set => self.meta,
+            RecordKind::Tombstone => self.last_set,
+        }
+    }
+}
+
+/// A record header read from the raw pile file.
+enum Record {
+    /// `timestamp` is the Unix millisecond time the blob was written.
+    Blob {
+        hash: [u8; 32],
+        timestamp: u64,
+    },
+    Branch {
+        id: Id,
+        meta: MetaHandle,
+    },
+    Tombstone {
+        id: Id,
+    },
+}
+
+/// Walk the complete records of the pile file from `offset`, calling `visit`
+/// with each record's offset, on-disk length and header. Stops at the first
+/// unknown or truncated record and returns the offset just past the last
+/// complete one.
+fn walk_records(
+    path: &Path,
+    mut offset: u64,
+    mut visit: impl FnMut(u64, u64, Record),
+) -> Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut buf = [0u8; RECORD_LEN as usize];
+
+    while offset + RECORD_LEN <= file_len {
+        file.seek(SeekFrom::Start(offset))?;
+        if file.read_exact(&mut buf).is_err() {
+            break;
+        }
+
+        let magic: [u8; 16] = buf[0..16].try_into().unwrap();
+        let (record_len, record) = if magic == MAGIC_MARKER_BLOB.raw() {
+            let len = u64::from_ne_bytes(buf[24..32].try_into().unwrap());
+            let record_len = RECORD_LEN
+                .checked_add(len)
+                .and_then(|l| l.checked_add(blob_padding(len)))
+                .ok_or_else(|| anyhow::anyhow!("pile too large"))?;
+            let timestamp = u64::from_ne_bytes(buf[16..24].try_into().unwrap());
+            let hash = buf[32..64].try_into().unwrap();
+            (record_len, Record::Blob { hash, timestamp })
+        } else if magic == MAGIC_MARKER_BRANCH.raw() {
+            let raw_id: [u8; 16] = buf[16..32].try_into().unwrap();
+            let Some(id) = Id::new(raw_id) else { break };
+            let raw_handle: [u8; 32] = buf[32..64].try_into().unwrap();
+            let meta = Value::new(raw_handle);
+            (RECORD_LEN, Record::Branch { id, meta })
+        } else if magic == MAGIC_MARKER_BRANCH_TOMBSTONE.raw() {
+            let raw_id: [u8; 16] = buf[16..32].try_into().unwrap();
+            let Some(id) = Id::new(raw_id) else { break };
+            (RECORD_LEN, Record::Tombstone { id })
+        } else {
+            break;
+        };
+
+        let end = offset
+            .checked_add(record_len)
+            .ok_or_else(|| anyhow::anyhow!("pile too large"))?;
+        if end > file_len {
+            break;
+        }
+        visit(offset, record_len, record);
+        offset = end;
+    }
+
+    Ok(offset)
+}
+
+/// Scan the raw pile file for all branch update/tombstone records.
+pub(crate) fn scan_pile_records(path: &Path) -> Result<Vec<RawBranchRecord>> {
+    let mut records = Vec::new();
+    walk_records(path, 0, |offset, _, record| match record {
+        Record::Blob { .. } => {}
+        Record::Branch { id, meta } => records.push(RawBranchRecord {
+            offset,
+            branch_id: id,
+            kind: RecordKind::Set,
+            meta_handle: Some(meta),
+        }),
+        Record::Tombstone { id } => records.push(RawBranchRecord {
+            offset,
+            branch_id: id,
+            kind: RecordKind::Tombstone,
+            meta_handle: None,
+        }),
+    })?;
+    Ok(records)
+}
+
+/// A record of the pile file, for commands that rewrite the whole file.
+#[derive(Clone, Debug)]
+pub(crate) enum PileRecord {
+    /// `len` is the on-disk length from `offset`, header and padding
+    /// included, and `timestamp` the Unix millisecond time the blob was
+    /// written.
+    Blob {
+        offset: u64,
+        hash: [u8; 32],
+        timestamp: u64,
+        len: u64,
+    },
+    Branch(RawBranchRecord),
+}
+
+/// Read every record header of the pile file in write order, returning them
+/// with the offset just past the last complete record.
+pub(crate) fn pile_records(path: &Path) -> Result<(Vec<PileRecord>, u64)> {
+    let mut records = Vec::new();
+    let end = walk_records(path, 0, |offset, len, record| {
+        records.push(match record {
+            Record::Blob { hash, timestamp } => PileRecord::Blob {
+                offset,
+                hash,
+                timestamp,
+                len,
+            },
+            Record::Branch { id, meta } => PileRecord::Branch(RawBranchRecord {
+                offset,
+                branch_id: id,
+                kind: RecordKind::Set,
+                meta_handle: Some(meta),
+            }),
+            Record::Tombstone { id } => PileRecord::Branch(RawBranchRecord {
+                offset,
+                branch_id: id,
+                kind: RecordKind::Tombstone,
+                meta_handle: None,
+            }),
+        })
+    })?;
+    Ok((records, end))
+}
+
+/// One branch record of the journal, as `branch journal` reports it.
+#[derive(Clone, Debug)]
+pub(crate) struct JournalEntry {
+    pub(crate) offset: u64,
+    pub(crate) branch_id: Id,
+    pub(crate) kind: RecordKind,
+    /// The branch's metadata handle before this record, if it had one.
+    pub(crate) old: Option<MetaHandle>,
+    /// The metadata handle this record sets (only when kind == Set).
+    pub(crate) new: Option<MetaHandle>,
+    /// Unix milliseconds of the newest blob written before the record.
+    pub(crate) timestamp: Option<u64>,
+}
+
+/// Stream the branch journal of the pile file in write order.
+///
+/// Branch records carry no clock of their own, so each entry is stamped with
+/// the newest blob record written before it; for a set that is normally its
+/// metadata blob. Only the latest handle per branch is kept in memory, never
+/// the records themselves, and the first error from `visit` ends the walk.
+pub(crate) fn walk_journal(
+    path: &Path,
+    mut visit: impl FnMut(JournalEntry) -> Result<()>,
+) -> Result<()> {
+    let mut current: HashMap<Id, MetaHandle> = HashMap::new();
+    let mut newest_blob: Option<u64> = None;
+    let mut result = Ok(());
+    walk_records(path, 0, |offset, _, record| {
+        if result.is_err() {
+            return;
+        }
+        let (branch_id, kind, new) = match record {
+            Record::Blob { timestamp, .. } => {
+                newest_blob = Some(newest_blob.map_or(timestamp, |t| t.max(timestamp)));
+                return;
+            }
+            Record::Branch { id, meta } => (id, RecordKind::Set, Some(meta)),
+            Record::Tombstone { id } => (id, RecordKind::Tombstone, None),
+        };
+        let old = match new {
+            Some(meta) => current.insert(branch_id, meta),
+            None => current.remove(&branch_id),
+        };
+        result = visit(JournalEntry {
+            offset,
+            branch_id,
+            kind,
+            old,
+            new,
+            timestamp: newest_blob,
+        });
+    })?;
+    result
+}
+
+/// Records appended to a pile file past a known offset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Appended {
+    pub(crate) blobs: usize,
+    pub(crate) branch_updates: usize,
+    /// Offset just past the last complete record.
+    pub(crate) end: u64,
+}
+
+/// Count the complete records appended to the pile file since `offset`.
+pub(crate) fn appended_since(path: &Path, offset: u64) -> Result<Appended> {
+    let (mut blobs, mut branch_updates) = (0, 0);
+    let end = walk_records(path, offset, |_, _, record| match record {
+        Record::Blob { .. } => blobs += 1,
+        Record::Branch { .. } | Record::Tombstone { .. } => branch_updates += 1,
+    })?;
+    Ok(Appended {
+        blobs,
+        branch_updates,
+        end,
+    })
+}
+
+/// Collapse raw records into final state per branch.
+pub(crate) fn collapse_branch_states(records: &[RawBranchRecord]) -> HashMap<Id, BranchState> {
+    let mut states: HashMap<Id, BranchState> = HashMap::new();
+    for rec in records {
+        let entry = states.entry(rec.branch_id).or_insert(BranchState {
+            kind: rec.kind,
+            meta: rec.meta_handle,
+            last_set: if rec.kind == RecordKind::Set {
+                rec.meta_handle
+            } else {
+                None
+            },
+        });
+        entry.kind = rec.kind;
+        match rec.kind {
+            RecordKind::Set => {
+                entry.meta = rec.meta_handle;
+                entry.last_set = rec.meta_handle;
+            }
+            RecordKind::Tombstone => {
+                entry.meta = None;
+            }
+        }
+    }
+    states
+}
+
+/// Blob records repeating a hash stored earlier in the pile file: how many
+/// there are and how many bytes they take up, headers and padding included.
+pub(crate) fn duplicate_blob_records(path: &Path) -> Result<(u64, u64)> {
+    let mut seen: HashSet<[u8; 32]> = HashSet::new();
+    let (mut records, mut bytes) = (0u64, 0u64);
+    walk_records(path, 0, |_, record_len, record| {
+        if let Record::Blob { hash, .. } = record {
+            if !seen.insert(hash) {
+                records += 1;
+                bytes += record_len;
+            }
+        }
+    })?;
+    Ok((records, bytes))
+}
+
+fn blob_padding(len: u64) -> u64 {
+    // The pile stores blobs padded so the next record begins on a 64-byte boundary.
+    let rem = len % RECORD_LEN;
+    if rem == 0 {
+        0
+    } else {
+        RECORD_LEN - rem
+    }
+}
+
+/// What a branch metadata blob says about its branch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum BranchMeta {
+    /// The metadata blob is not in the pile.
+    Missing,
+    /// The blob is present but is not a readable SimpleArchive.
+    Undecodable,
+    Decoded {
+        name: Option<String>,
+        head: Option<MetaHandle>,
+        /// The entry is a `trible fetch`/`push` record, not a branch.
+        remote_state: bool,
+    },
+}
+
+/// Decodes branch metadata blobs at most once per handle.
+pub(crate) struct MetaCache<'a> {
+    reader: &'a PileReader<Blake3>,
+    decoded: HashMap<[u8; 32], BranchMeta>,
+}
+
+impl<'a> MetaCache<'a> {
+    pub(crate) fn new(reader: &'a PileReader<Blake3>) -> Self {
+        Self {
+            reader,
+            decoded: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, handle: MetaHandle) -> Result<&BranchMeta> {
+        let reader = self.reader;
+        Ok(match self.decoded.entry(handle.raw) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(decode_meta(reader, handle)?),
+        })
+    }
+
+    /// Number of distinct metadata blobs looked at so far.
+    pub(crate) fn decoded_count(&self) -> usize {
+        self.decoded.len()
+    }
+}
+
+fn decode_meta(reader: &PileReader<Blake3>, handle: MetaHandle) -> Result<BranchMeta> {
+    if reader.metadata(handle)?.is_none() {
+        return Ok(BranchMeta::Missing);
+    }
+    let Ok(meta) = reader.get::<TribleSet, SimpleArchive>(handle) else {
+        return Ok(BranchMeta::Undecodable);
+    };
+    Ok(BranchMeta::Decoded {
+        name: load_branch_name(reader, &meta).ok().flatten(),
+        head: extract_repo_head(&meta),
+        remote_state: crate::cli::remote_state::is_remote_state(&meta),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use triblespace::prelude::*;
+    use triblespace_core::repo::pile::Pile;
+
+    #[test]
+    fn scan_collapses_journal_and_decodes_shared_metadata_once() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("scan.pile");
+        let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+
+        let name = pile
+            .put::<blobschemas::LongString, _>("shared".to_string())
+            .unwrap();
+        let mut meta = TribleSet::new();
+        meta += entity! { &ufoid() @ triblespace_core::metadata::name: name };
+        let meta = pile.put::<SimpleArchive, _>(meta).unwrap();
+
+        let (a, b, c) = (*ufoid(), *ufoid(), *ufoid());
+        for id in [a, b, c] {
+            pile.update(id, None, Some(meta)).unwrap();
+        }
+        pile.update(c, Some(meta), None).unwrap();
+        pile.close().unwrap();
+
+        let records = scan_pile_records(&path).unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[3].branch_id, c);
+        assert_eq!(records[3].kind, RecordKind::Tombstone);
+        assert!(records.windows(2).all(|w| w[0].offset < w[1].offset));
+
+        let states = collapse_branch_states(&records);
+        assert_eq!(states.len(), 3);
+        assert_eq!(states[&a].kind, RecordKind::Set);
+        assert_eq!(states[&c].kind, RecordKind::Tombstone);
+        assert_eq!(states[&c].meta, None);
+        assert_eq!(states[&c].meta_handle(), Some(meta));
+
+        let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        pile.refresh().unwrap();
+        let reader = pile.reader().unwrap();
+        let mut cache = MetaCache::new(&reader);
+        for state in states.values() {
+            let decoded = cache.get(state.meta_handle().unwrap()).unwrap();
+            assert_eq!(
+                decoded,
+                &BranchMeta::Decoded {
+                    name: Some("shared".to_string()),
+                    head: None,
+                    remote_state: false,
+                }
+            );
+        }
+        assert_eq!(cache.decoded_count(), 1);
+        assert_eq!(
+            cache.get(Value::new([7; 32])).unwrap(),
+            &BranchMeta::Missing
+        );
+        drop(cache);
+        pile.close().unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+//! `trible pile search`: find string values in a branch's content.
+
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::View;
+use triblespace_core::blob::schemas::longstring::LongString;
+use triblespace_core::id::Id;
+use triblespace_core::repo::pile::PileReader;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::attr::label;
+use crate::cli::error::invalid_argument;
+use crate::cli::output::{Field, Output};
+
+use super::branch::{head_content, select_branch};
+use super::ClosingPile;
+
+/// Characters of context kept on each side of a match in the snippet.
+const CONTEXT: usize = 30;
+
+pub fn run(
+    pile: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    text: String,
+    ignore_case: bool,
+    regex: bool,
+) -> Result<()> {
+    let pattern = if regex { text } else { regex::escape(&text) };
+    let matcher = RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| invalid_argument(format!("invalid pattern: {e}")))?;
+
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let branch_id = select_branch(pile, id.as_deref(), name.as_deref())?;
+        let content = head_content(pile, branch_id)?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+
+        let mut tribles: Vec<[u8; 64]> = content.iter().map(|t| t.data).collect();
+        tribles.sort_unstable();
+        // Values repeat across tribles; each is decoded (and each long
+        // string blob read) at most once.
+        let mut strings: HashMap<[u8; 32], Option<String>> = HashMap::new();
+        let mut out = Output::stdout();
+        for t in &tribles {
+            let raw: [u8; 32] = t[32..].try_into()?;
+            let text = strings
+                .entry(raw)
+                .or_insert_with(|| value_text(&reader, raw));
+            let Some(text) = text else {
+                continue;
+            };
+            let Some(snippet) = snippet(&matcher, text) else {
+                continue;
+            };
+            let attribute = Id::new(t[16..32].try_into()?)
+                .ok_or_else(|| anyhow::anyhow!("trible with nil attribute"))?;
+            out.record(&[
+                ("entity", Field::from(hex::encode_upper(&t[..16]))),
+                ("attribute", Field::from(format!("{attribute:X}"))),
+                (
+                    "label",
+                    label(&attribute)
+                        .map(Field::from)
+                        .unwrap_or_else(|| Field::missing("-")),
+                ),
+                ("snippet", Field::from(snippet)),
+            ])?;
+        }
+        Ok(())
+    })
+}
+
+/// The text of a value: a short string stored inline, or the contents of a
+/// UTF-8 blob it is a handle of.
+fn value_text(reader: &PileReader<Blake3>, raw: [u8; 32]) -> Option<String> {
+    if let Some(text) = short_string(&raw) {
+        return Some(text.to_string());
+    }
+    let handle: Value<Handle<Blake3, LongString>> = Value::new(raw);
+    reader
+        .get::<View<str>, _>(handle)
+        .ok()
+        .map(|text| text.as_ref().to_string())
+}
+
+/// Inline UTF-8 text padded with zero bytes, as `ShortString` stores it.
+fn short_string(raw: &[u8; 32]) -> Option<&str> {
+    let len = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+    if len == 0 || raw[len..].iter().any(|&b| b != 0) {
+        return None;
+    }
+    let text = std::str::from_utf8(&raw[..len]).ok()?;
+    (!text.chars().any(char::is_control)).then_some(text)
+}
+
+/// The first match in `text` with some context, on a single line.
+fn snippet(matcher: &Regex, text: &str) -> Option<String> {
+    let found = matcher.find(text)?;
+    let before: String = {
+        let mut chars: Vec<char> = text[..found.start()].chars().rev().take(CONTEXT).collect();
+        chars.reverse();
+        chars.into_iter().collect()
+    };
+    let after: String = text[found.end()..].chars().take(CONTEXT).collect();
+    let mut snippet = String::new();
+    if before.len() < found.start() {
+        snippet.push('…');
+    }
+    snippet.push_str(&before);
+    snippet.push_str(found.as_str());
+    snippet.push_str(&after);
+    if found.end() + after.len() < text.len() {
+        snippet.push('…');
+    }
+    Some(snippet.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_strings_are_zero_padded_text() {
+        let mut raw = [0u8; 32];
+        raw[..5].copy_from_slice(b"hello");
+        assert_eq!(short_string(&raw), Some("hello"));
+        raw[20] = 1;
+        assert_eq!(short_string(&raw), None);
+        assert_eq!(short_string(&[0u8; 32]), None);
+        assert_eq!(short_string(&[0xFF; 32]), None);
+    }
+
+    #[test]
+    fn snippets_keep_context_around_the_match() {
+        let matcher = Regex::new("needle").unwrap();
+        assert_eq!(
+            snippet(&matcher, "a needle\nhere").unwrap(),
+            "a needle here"
+        );
+        let long = format!("{}needle{}", "x".repeat(40), "y".repeat(40));
+        let expected = format!("…{}needle{}…", "x".repeat(30), "y".repeat(30));
+        assert_eq!(snippet(&matcher, &long).unwrap(), expected);
+        assert!(snippet(&matcher, "haystack").is_none());
+    }
+}
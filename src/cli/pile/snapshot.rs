@@ -0,0 +1,865 @@
+//! `trible pile snapshot`: record directory trees as tribles.
+//!
+//! A snapshot is one entity carrying the snapshotted directory and the time
+//! it was taken, plus one entity per file or symlink below it. Files point to
+//! their contents as blobs; since blobs are content addressed, unchanged files
+//! reference the blobs stored by earlier snapshots and add no data.
+//! `checkout` writes the most recent snapshot back to disk and `diff`
+//! compares snapshots with each other or with a directory.
+
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use triblespace::prelude::blobschemas::{FileBytes, LongString, SimpleArchive};
+use triblespace::prelude::valueschemas::{GenId, I256BE, U256BE};
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::View;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::blob::Bytes;
+use triblespace_core::id::{ufoid, ExclusiveId, Id};
+use triblespace_core::repo::pile::{Pile, PileReader};
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::{Trible, TribleSet};
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::error::invalid_argument;
+use crate::cli::output::{note, Field, Output};
+
+use super::branch::{
+    commit_content, head_content, parse_blake3_handle, read_commit_fields, select_branch,
+};
+use super::signing::SigningArgs;
+use super::{ClosingPile, ClosingRepo};
+
+pub(crate) mod schema {
+    use triblespace::prelude::*;
+
+    attributes! {
+        "3C0D7E5A1F9B4E2C8A6D0F1B3E5C7A92" as root: valueschemas::Handle<valueschemas::Blake3, blobschemas::LongString>;
+        "8E4A2C6F0B1D3F5E7A9C1E3B5D7F9A04" as taken_at: valueschemas::I256BE;
+        "B17F3D5C9E1A2B4D6F8A0C2E4B6D8F15" as snapshot: valueschemas::GenId;
+        "D2A6E8C0F4B2D6A8C0E2F4A6B8D0E226" as path: valueschemas::Handle<valueschemas::Blake3, blobschemas::LongString>;
+        "5F9B1D3E7A0C2E4F6B8D0A2C4E6F8B37" as size: valueschemas::U256BE;
+        "7A3E5C1B9D2F4A6C8E0B2D4F6A8C0E48" as modified_at: valueschemas::I256BE;
+        "9C5B7D3F1E0A2C4B6D8F0E2A4C6B8D59" as content: valueschemas::Handle<valueschemas::Blake3, blobschemas::UnknownBlob>;
+        "E4C8A2F6B0D4E8A2C6F0B4D8E2A6C06A" as symlink_target: valueschemas::Handle<valueschemas::Blake3, blobschemas::LongString>;
+    }
+}
+
+#[derive(Parser)]
+pub enum Command {
+    /// Record every file and symlink below DIR and commit them to a branch.
+    ///
+    /// Each file becomes an entity with its path relative to DIR, size,
+    /// modification time and a handle of its contents; symlinks carry their
+    /// target instead. Directories are implied by the paths. Entries whose
+    /// paths are not valid UTF-8 and special files are reported and skipped.
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "name"])))]
+    Create {
+        /// Path to the pile file to modify
+        pile: PathBuf,
+        /// Branch to commit onto (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to commit onto (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Directory to snapshot
+        dir: PathBuf,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+    /// Write the most recent snapshot on a branch back to disk.
+    ///
+    /// Recreates the recorded files and symlinks below DEST and restores
+    /// file modification times. Files whose contents already match are
+    /// skipped. Stored paths that would leave DEST are rejected.
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "name"])))]
+    Checkout {
+        /// Path to the pile file to read
+        pile: PathBuf,
+        /// Branch to read (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to read (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Directory to write the tree into (created if missing)
+        dest: PathBuf,
+        /// Read the content checked out at this commit instead of the head
+        #[arg(long, value_name = "HANDLE")]
+        commit: Option<String>,
+        /// Rewrite files even when their contents already match
+        #[arg(long)]
+        force: bool,
+    },
+    /// List the paths added, removed and modified between two snapshots.
+    ///
+    /// With two --commit options, compares the snapshot at the first with
+    /// the one at the second. With --against, compares the snapshot at the
+    /// branch head (or the single --commit) with the files in DIR. Without
+    /// either, compares the two most recent snapshots on the branch. Each
+    /// row is a change (`A`, `D` or `M`) and a path; files count as modified
+    /// when their content hashes differ.
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "name"])))]
+    Diff {
+        /// Path to the pile file to read
+        pile: PathBuf,
+        /// Branch to read (hex id or unique id prefix)
+        #[arg(long)]
+        id: Option<String>,
+        /// Branch to read (unique name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Commit whose snapshot to compare (at most twice)
+        #[arg(long = "commit", value_name = "HANDLE")]
+        commits: Vec<String>,
+        /// Directory to compare the snapshot with
+        #[arg(long, value_name = "DIR")]
+        against: Option<PathBuf>,
+    },
+}
+
+/// A file or symlink found below the snapshotted directory.
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    /// Path relative to the root, `/`-separated.
+    path: String,
+    full: PathBuf,
+    kind: EntryKind,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum EntryKind {
+    File { size: u64 },
+    Symlink { target: String },
+}
+
+pub fn run(cmd: Command) -> Result<()> {
+    match cmd {
+        Command::Create {
+            pile,
+            id,
+            name,
+            dir,
+            signing,
+        } => create(pile, id, name, dir, signing),
+        Command::Checkout {
+            pile,
+            id,
+            name,
+            dest,
+            commit,
+            force,
+        } => checkout(pile, id, name, dest, commit, force),
+        Command::Diff {
+            pile,
+            id,
+            name,
+            commits,
+            against,
+        } => diff(pile, id, name, commits, against),
+    }
+}
+
+fn create(
+    pile_path: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    dir: PathBuf,
+    signing: SigningArgs,
+) -> Result<()> {
+    let scan = scan(&dir)?;
+    let key = signing.load()?;
+    let pile: Pile<Blake3> = Pile::open(&pile_path)?;
+    let repo = Repository::new(pile, key, TribleSet::new())?;
+    ClosingRepo::new(repo).run(|repo| {
+        repo.storage_mut()
+            .refresh()
+            .map_err(|e| anyhow::anyhow!("refresh pile: {e:?}"))?;
+        let branch_id = select_branch(repo.storage_mut(), id.as_deref(), name.as_deref())?;
+        let recorded = record(repo, branch_id, &dir, &scan)?;
+        note!(
+            "snapshot {:X} of {}: {} file(s), {} symlink(s), {} byte(s) on {branch_id:X}",
+            recorded.snapshot,
+            scan.root.display(),
+            recorded.files,
+            recorded.symlinks,
+            recorded.bytes
+        );
+        Ok(())
+    })
+}
+
+/// A directory tree ready to be recorded.
+pub(crate) struct Scan {
+    pub(crate) root: PathBuf,
+    entries: Vec<Entry>,
+}
+
+/// Walk `dir`, which must be a directory with a UTF-8 path.
+pub(crate) fn scan(dir: &Path) -> Result<Scan> {
+    if !dir.is_dir() {
+        return Err(invalid_argument(format!(
+            "{} is not a directory",
+            dir.display()
+        )));
+    }
+    let root = fs::canonicalize(dir)
+        .map_err(|e| anyhow::anyhow!("failed to resolve {}: {e}", dir.display()))?;
+    if root.to_str().is_none() {
+        return Err(invalid_argument(format!(
+            "{} is not a valid UTF-8 path",
+            root.display()
+        )));
+    }
+    let mut entries = Vec::new();
+    walk(&root, "", &mut entries)?;
+    Ok(Scan { root, entries })
+}
+
+/// What [`record`] committed.
+pub(crate) struct Recorded {
+    pub(crate) snapshot: Id,
+    pub(crate) files: u64,
+    pub(crate) symlinks: u64,
+    pub(crate) bytes: u64,
+    /// Content, path and target blobs the pile did not hold before.
+    pub(crate) new_blobs: u64,
+    pub(crate) commit: Option<Value<Handle<Blake3, SimpleArchive>>>,
+}
+
+/// Commit a snapshot of `scan` onto `branch_id`; `dir` is the directory as
+/// given, for the commit message.
+pub(crate) fn record(
+    repo: &mut Repository<Pile<Blake3>>,
+    branch_id: Id,
+    dir: &Path,
+    scan: &Scan,
+) -> Result<Recorded> {
+    let before = repo
+        .storage_mut()
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let mut seen: HashSet<[u8; 32]> = HashSet::new();
+    let mut new_blobs = 0u64;
+    let mut count_new = |handle: Value<Handle<Blake3, UnknownBlob>>| -> Result<()> {
+        if before.metadata(handle)?.is_none() && seen.insert(handle.raw) {
+            new_blobs += 1;
+        }
+        Ok(())
+    };
+
+    let mut ws = repo
+        .pull(branch_id)
+        .map_err(|e| anyhow::anyhow!("pull branch: {e:?}"))?;
+    let snapshot = ufoid();
+    let mut content = TribleSet::new();
+    let root_text = scan.root.to_str().expect("checked by scan");
+    let handle = ws.put::<LongString, _>(root_text.to_string());
+    count_new(handle.transmute())?;
+    content.insert(&Trible::new(&snapshot, &schema::root.id(), &handle));
+    content.insert(&Trible::new(
+        &snapshot,
+        &schema::taken_at.id(),
+        &nanos(SystemTime::now()),
+    ));
+
+    let (mut files, mut symlinks, mut bytes) = (0u64, 0u64, 0u64);
+    for entry in &scan.entries {
+        let entity = ufoid();
+        let reference: Value<GenId> = Value::new(genid(&snapshot));
+        content.insert(&Trible::new(&entity, &schema::snapshot.id(), &reference));
+        let handle = ws.put::<LongString, _>(entry.path.clone());
+        count_new(handle.transmute())?;
+        content.insert(&Trible::new(&entity, &schema::path.id(), &handle));
+        if let Some(modified) = entry.modified {
+            content.insert(&Trible::new(
+                &entity,
+                &schema::modified_at.id(),
+                &nanos(modified),
+            ));
+        }
+        match &entry.kind {
+            EntryKind::File { size } => {
+                let file = File::open(&entry.full)
+                    .map_err(|e| anyhow::anyhow!("failed to open {}: {e}", entry.full.display()))?;
+                // Empty files cannot be mapped.
+                let data = if *size == 0 {
+                    Bytes::from(Vec::new())
+                } else {
+                    unsafe { Bytes::map_file(&file)? }
+                };
+                let handle: Value<Handle<Blake3, UnknownBlob>> =
+                    ws.put::<FileBytes, _>(data).transmute();
+                count_new(handle)?;
+                content.insert(&Trible::new(&entity, &schema::content.id(), &handle));
+                content.insert(&Trible::new(&entity, &schema::size.id(), &unsigned(*size)));
+                files += 1;
+                bytes += size;
+            }
+            EntryKind::Symlink { target } => {
+                let handle = ws.put::<LongString, _>(target.clone());
+                count_new(handle.transmute())?;
+                content.insert(&Trible::new(&entity, &schema::symlink_target.id(), &handle));
+                symlinks += 1;
+            }
+        }
+    }
+
+    ws.commit(content, &format!("snapshot {}", dir.display()));
+    repo.push(&mut ws)
+        .map_err(|e| anyhow::anyhow!("push failed: {e:?}"))?;
+    Ok(Recorded {
+        snapshot: *snapshot,
+        files,
+        symlinks,
+        bytes,
+        new_blobs,
+        commit: ws.head(),
+    })
+}
+
+/// Whether the content `commit` itself added records a snapshot.
+pub(crate) fn is_snapshot_commit(
+    reader: &PileReader<Blake3>,
+    commit: Value<Handle<Blake3, SimpleArchive>>,
+) -> Result<bool> {
+    let commit: TribleSet = reader
+        .get(commit)
+        .map_err(|e| anyhow::anyhow!("read commit: {e:?}"))?;
+    let Some(content) = read_commit_fields(&commit).content else {
+        return Ok(false);
+    };
+    let content: TribleSet = reader
+        .get(content)
+        .map_err(|e| anyhow::anyhow!("read commit content: {e:?}"))?;
+    Ok(!snapshots(&content).is_empty())
+}
+
+/// A file or symlink as recorded in a snapshot.
+#[derive(Debug, Default)]
+struct Stored {
+    path: Option<String>,
+    content: Option<[u8; 32]>,
+    modified: Option<SystemTime>,
+    target: Option<String>,
+}
+
+fn checkout(
+    pile: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    dest: PathBuf,
+    commit: Option<String>,
+    force: bool,
+) -> Result<()> {
+    let commit = commit.as_deref().map(parse_blake3_handle).transpose()?;
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let branch_id = select_branch(pile, id.as_deref(), name.as_deref())?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let content = match commit {
+            Some(commit) => commit_content(&reader, commit)?,
+            None => head_content(pile, branch_id)?,
+        };
+        let snapshot = *snapshots(&content).last().ok_or_else(|| {
+            crate::cli::error::not_found(format!("no snapshot on branch {branch_id:X}"))
+        })?;
+        let stored = snapshot_entries(&reader, &content, snapshot);
+        fs::create_dir_all(&dest)
+            .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", dest.display()))?;
+
+        let (mut written, mut skipped, mut failed) = (0u64, 0u64, 0u64);
+        // Files first: symlinks created later cannot redirect file writes.
+        let (links, files): (Vec<_>, Vec<_>) = stored.iter().partition(|s| s.target.is_some());
+        for entry in files.into_iter().chain(links) {
+            let path = entry.path.as_deref().unwrap_or_default();
+            match restore(&reader, &dest, entry, force) {
+                Ok(true) => written += 1,
+                Ok(false) => skipped += 1,
+                Err(e) => {
+                    eprintln!("{path}: {e:#}");
+                    failed += 1;
+                }
+            }
+        }
+        note!("snapshot {snapshot:X}: {written} written, {skipped} skipped, {failed} failed");
+        if failed > 0 {
+            anyhow::bail!("{failed} of {} entries failed", stored.len());
+        }
+        Ok(())
+    })
+}
+
+/// The snapshot entities in `content`, oldest first.
+fn snapshots(content: &TribleSet) -> Vec<Id> {
+    let taken_at = schema::taken_at.id();
+    let mut snapshots: Vec<(Option<SystemTime>, Id)> = content
+        .iter()
+        .filter(|t| t.a() == &taken_at)
+        .map(|t| (t.data[32..].try_into().ok().and_then(time_value), *t.e()))
+        .collect();
+    snapshots.sort();
+    snapshots.into_iter().map(|(_, id)| id).collect()
+}
+
+/// The entries recorded for `snapshot`, in path order.
+fn snapshot_entries(reader: &PileReader<Blake3>, content: &TribleSet, snapshot: Id) -> Vec<Stored> {
+    let mut reference = [0u8; 32];
+    reference[16..].copy_from_slice(&snapshot.raw());
+    let members: Vec<Id> = content
+        .iter()
+        .filter(|t| t.a() == &schema::snapshot.id() && t.data[32..] == reference)
+        .map(|t| *t.e())
+        .collect();
+    let mut entries: BTreeMap<Id, Stored> = members
+        .into_iter()
+        .map(|e| (e, Stored::default()))
+        .collect();
+    let text = |raw: [u8; 32]| -> Option<String> {
+        let handle: Value<Handle<Blake3, LongString>> = Value::new(raw);
+        let view: View<str> = reader.get(handle).ok()?;
+        Some(view.as_ref().to_string())
+    };
+    for t in content.iter() {
+        let Some(entry) = entries.get_mut(t.e()) else {
+            continue;
+        };
+        let raw: [u8; 32] = t.data[32..].try_into().expect("32-byte value");
+        let attribute = *t.a();
+        if attribute == schema::path.id() {
+            entry.path = text(raw);
+        } else if attribute == schema::content.id() {
+            entry.content = Some(raw);
+        } else if attribute == schema::modified_at.id() {
+            entry.modified = time_value(raw);
+        } else if attribute == schema::symlink_target.id() {
+            entry.target = text(raw);
+        }
+    }
+    let mut entries: Vec<Stored> = entries.into_values().collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// Write one entry below `dest`; `Ok(false)` when it was already in place.
+fn restore(reader: &PileReader<Blake3>, dest: &Path, entry: &Stored, force: bool) -> Result<bool> {
+    let relative = entry
+        .path
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("entry without a readable path"))?;
+    let relative = safe_relative(relative)?;
+    let target = dest.join(relative);
+    let parent = target.parent().expect("joined path has a parent");
+    ensure_no_symlinks(dest, relative)?;
+    fs::create_dir_all(parent)
+        .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", parent.display()))?;
+
+    if let Some(link) = &entry.target {
+        if !force && fs::read_link(&target).is_ok_and(|existing| existing == Path::new(link)) {
+            return Ok(false);
+        }
+        remove_existing(&target)?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(link, &target)?;
+        #[cfg(not(unix))]
+        anyhow::bail!("symlinks are only restored on Unix");
+        return Ok(true);
+    }
+
+    let hash = entry
+        .content
+        .ok_or_else(|| anyhow::anyhow!("entry has neither content nor a link target"))?;
+    let unchanged = !force
+        && fs::symlink_metadata(&target).is_ok_and(|m| m.is_file())
+        && fs::read(&target).is_ok_and(|data| *blake3::hash(&data).as_bytes() == hash);
+    if !unchanged {
+        let handle: Value<Handle<Blake3, UnknownBlob>> = Value::new(hash);
+        let bytes: Bytes = reader
+            .get(handle)
+            .map_err(|e| anyhow::anyhow!("content blob {}: {e:?}", hex::encode(hash)))?;
+        remove_existing(&target)?;
+        fs::write(&target, &bytes[..])?;
+    }
+    if let Some(modified) = entry.modified {
+        File::options()
+            .write(true)
+            .open(&target)
+            .and_then(|file| file.set_modified(modified))
+            .map_err(|e| anyhow::anyhow!("failed to set the modification time: {e}"))?;
+    }
+    Ok(!unchanged)
+}
+
+/// A stored path as a relative path that stays below the destination.
+fn safe_relative(path: &str) -> Result<&Path> {
+    let relative = Path::new(path);
+    let normal = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if path.is_empty() || !normal || path.contains('\\') {
+        return Err(crate::cli::error::corrupt(format!(
+            "refusing unsafe path {path:?}"
+        )));
+    }
+    Ok(relative)
+}
+
+/// Refuse to write through a symlinked directory below `dest`.
+fn ensure_no_symlinks(dest: &Path, relative: &Path) -> Result<()> {
+    let mut current = dest.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        if components.peek().is_none() {
+            break;
+        }
+        current.push(component);
+        if fs::symlink_metadata(&current).is_ok_and(|m| m.file_type().is_symlink()) {
+            return Err(crate::cli::error::corrupt(format!(
+                "refusing to write through symlink {}",
+                current.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Make room for a file or symlink; directories are left alone.
+fn remove_existing(target: &Path) -> Result<()> {
+    match fs::symlink_metadata(target) {
+        Ok(meta) if meta.is_dir() => Err(anyhow::anyhow!("{} is a directory", target.display())),
+        Ok(_) => fs::remove_file(target)
+            .map_err(|e| anyhow::anyhow!("failed to replace {}: {e}", target.display())),
+        Err(_) => Ok(()),
+    }
+}
+
+/// The time stored by [`nanos`].
+fn time_value(raw: [u8; 32]) -> Option<SystemTime> {
+    let n = i128::from_be_bytes(raw[16..].try_into().ok()?);
+    let duration = Duration::new(
+        u64::try_from(n.unsigned_abs() / 1_000_000_000).ok()?,
+        (n.unsigned_abs() % 1_000_000_000) as u32,
+    );
+    if n < 0 {
+        UNIX_EPOCH.checked_sub(duration)
+    } else {
+        UNIX_EPOCH.checked_add(duration)
+    }
+}
+
+/// What a path holds, as far as `diff` compares it.
+#[derive(Debug, PartialEq, Eq)]
+enum State {
+    File([u8; 32]),
+    Symlink(String),
+}
+
+fn diff(
+    pile: PathBuf,
+    id: Option<String>,
+    name: Option<String>,
+    commits: Vec<String>,
+    against: Option<PathBuf>,
+) -> Result<()> {
+    let commits = commits
+        .iter()
+        .map(|c| parse_blake3_handle(c))
+        .collect::<Result<Vec<_>>>()?;
+    match (commits.len(), &against) {
+        (0 | 1, Some(_)) | (0 | 2, None) => {}
+        (1, None) => {
+            return Err(invalid_argument(
+                "pass a second --commit or --against DIR to compare with",
+            ))
+        }
+        _ => {
+            return Err(invalid_argument(
+                "pass at most two --commit, or one with --against",
+            ))
+        }
+    }
+    ClosingPile::open(&pile)?.run(|pile| {
+        super::refresh_for_read(pile)?;
+        let branch_id = select_branch(pile, id.as_deref(), name.as_deref())?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let missing =
+            || crate::cli::error::not_found(format!("no snapshot on branch {branch_id:X}"));
+        // The latest snapshot checked out at each commit.
+        let at = |commit| -> Result<BTreeMap<String, State>> {
+            let content = commit_content(&reader, commit)?;
+            let snapshot = *snapshots(&content).last().ok_or_else(missing)?;
+            Ok(states(snapshot_entries(&reader, &content, snapshot)))
+        };
+        let (old, new) = match (commits.as_slice(), &against) {
+            ([a, b], None) => (at(*a)?, at(*b)?),
+            ([commit], Some(dir)) => (at(*commit)?, disk_states(dir)?),
+            _ => {
+                let content = head_content(pile, branch_id)?;
+                let snapshots = snapshots(&content);
+                let load = |snapshot| states(snapshot_entries(&reader, &content, snapshot));
+                match (&against, snapshots.as_slice()) {
+                    (_, []) => return Err(missing()),
+                    (Some(dir), [.., latest]) => (load(*latest), disk_states(dir)?),
+                    (None, [only]) => (BTreeMap::new(), load(*only)),
+                    (None, [.., previous, latest]) => (load(*previous), load(*latest)),
+                }
+            }
+        };
+
+        let changes = changes(&old, &new);
+        let mut out = Output::stdout();
+        for (change, path) in &changes {
+            out.record(&[
+                ("change", Field::from(change.to_string())),
+                ("path", Field::from(path.as_str())),
+            ])?;
+        }
+        let count = |c| changes.iter().filter(|(change, _)| *change == c).count();
+        note!(
+            "{} added, {} removed, {} modified",
+            count('A'),
+            count('D'),
+            count('M')
+        );
+        Ok(())
+    })
+}
+
+/// Stored entries by path; entries without a readable path are dropped.
+fn states(entries: Vec<Stored>) -> BTreeMap<String, State> {
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let state = match (entry.target, entry.content) {
+                (Some(target), _) => State::Symlink(target),
+                (None, Some(hash)) => State::File(hash),
+                (None, None) => return None,
+            };
+            Some((entry.path?, state))
+        })
+        .collect()
+}
+
+/// The files and symlinks below `dir`, with file contents hashed the way
+/// snapshot content handles are.
+fn disk_states(dir: &Path) -> Result<BTreeMap<String, State>> {
+    let mut entries = Vec::new();
+    walk(dir, "", &mut entries)?;
+    let mut states = BTreeMap::new();
+    for entry in entries {
+        let state = match entry.kind {
+            EntryKind::File { .. } => {
+                let data = fs::read(&entry.full)
+                    .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", entry.full.display()))?;
+                State::File(*blake3::hash(&data).as_bytes())
+            }
+            EntryKind::Symlink { target } => State::Symlink(target),
+        };
+        states.insert(entry.path, state);
+    }
+    Ok(states)
+}
+
+/// `A`dded, `D`eleted and `M`odified paths from `old` to `new`, by path.
+fn changes(old: &BTreeMap<String, State>, new: &BTreeMap<String, State>) -> Vec<(char, String)> {
+    let mut changes: Vec<(char, String)> = Vec::new();
+    for (path, state) in new {
+        match old.get(path) {
+            None => changes.push(('A', path.clone())),
+            Some(before) if before != state => changes.push(('M', path.clone())),
+            Some(_) => {}
+        }
+    }
+    for path in old.keys().filter(|path| !new.contains_key(*path)) {
+        changes.push(('D', path.clone()));
+    }
+    changes.sort_by(|a, b| a.1.cmp(&b.1));
+    changes
+}
+
+/// Collect the files and symlinks below `dir` in path order. Symlinks are
+/// recorded, not followed.
+fn walk(dir: &Path, prefix: &str, entries: &mut Vec<Entry>) -> Result<()> {
+    let mut children: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", dir.display()))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", dir.display()))?;
+    children.sort_by_key(|child| child.file_name());
+    for child in children {
+        let full = child.path();
+        let Some(name) = child.file_name().to_str().map(str::to_string) else {
+            eprintln!("skipping {}: not a valid UTF-8 path", full.display());
+            continue;
+        };
+        let path = format!("{prefix}{name}");
+        let meta = fs::symlink_metadata(&full)
+            .map_err(|e| anyhow::anyhow!("failed to stat {}: {e}", full.display()))?;
+        let kind = if meta.is_dir() {
+            walk(&full, &format!("{path}/"), entries)?;
+            continue;
+        } else if meta.is_file() {
+            EntryKind::File { size: meta.len() }
+        } else if meta.file_type().is_symlink() {
+            let target = fs::read_link(&full)
+                .map_err(|e| anyhow::anyhow!("failed to read link {}: {e}", full.display()))?;
+            let Some(target) = target.to_str() else {
+                eprintln!(
+                    "skipping {}: link target is not valid UTF-8",
+                    full.display()
+                );
+                continue;
+            };
+            EntryKind::Symlink {
+                target: target.to_string(),
+            }
+        } else {
+            eprintln!(
+                "skipping {}: not a file, directory or symlink",
+                full.display()
+            );
+            continue;
+        };
+        entries.push(Entry {
+            path,
+            full,
+            kind,
+            modified: meta.modified().ok(),
+        });
+    }
+    Ok(())
+}
+
+/// A reference to `entity` as a GenId value.
+fn genid(entity: &ExclusiveId) -> [u8; 32] {
+    let mut raw = [0u8; 32];
+    raw[16..].copy_from_slice(&entity.raw());
+    raw
+}
+
+pub(super) fn unsigned(n: u64) -> Value<U256BE> {
+    let mut raw = [0u8; 32];
+    raw[24..].copy_from_slice(&n.to_be_bytes());
+    Value::new(raw)
+}
+
+/// Nanoseconds since the Unix epoch, negative before it.
+fn nanos(time: SystemTime) -> Value<I256BE> {
+    let n: i128 = match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_nanos() as i128,
+        Err(before) => -(before.duration().as_nanos() as i128),
+    };
+    let mut raw = if n < 0 { [0xFF; 32] } else { [0u8; 32] };
+    raw[16..].copy_from_slice(&n.to_be_bytes());
+    Value::new(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn walk_records_files_and_symlinks_in_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("b/c")).unwrap();
+        fs::write(dir.path().join("b/c/deep.txt"), "deep").unwrap();
+        fs::write(dir.path().join("a.txt"), "top").unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        std::os::unix::fs::symlink("a.txt", dir.path().join("link")).unwrap();
+
+        let mut entries = Vec::new();
+        walk(dir.path(), "", &mut entries).unwrap();
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "b/c/deep.txt", "link"]);
+        assert_eq!(entries[0].kind, EntryKind::File { size: 3 });
+        assert_eq!(
+            entries[2].kind,
+            EntryKind::Symlink {
+                target: "a.txt".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn times_are_signed_nanoseconds() {
+        let after = nanos(UNIX_EPOCH + Duration::from_nanos(258)).raw;
+        assert_eq!(after[..30], [0; 30]);
+        assert_eq!(after[30..], [1, 2]);
+        let before = nanos(UNIX_EPOCH - Duration::from_nanos(1)).raw;
+        assert_eq!(before, [0xFF; 32]);
+        for time in [
+            UNIX_EPOCH + Duration::new(1_700_000_000, 123),
+            UNIX_EPOCH - Duration::new(5, 7),
+        ] {
+            assert_eq!(time_value(nanos(time).raw), Some(time));
+        }
+    }
+
+    #[test]
+    fn changes_cover_added_removed_and_modified_paths() {
+        let tree = |entries: &[(&str, State)]| -> BTreeMap<String, State> {
+            entries
+                .iter()
+                .map(|(path, state)| {
+                    let state = match state {
+                        State::File(hash) => State::File(*hash),
+                        State::Symlink(target) => State::Symlink(target.clone()),
+                    };
+                    (path.to_string(), state)
+                })
+                .collect()
+        };
+        let old = tree(&[
+            ("kept", State::File([1; 32])),
+            ("edited", State::File([2; 32])),
+            ("gone", State::File([3; 32])),
+            ("relinked", State::Symlink("a".into())),
+            ("retyped", State::File([4; 32])),
+        ]);
+        let new = tree(&[
+            ("kept", State::File([1; 32])),
+            ("edited", State::File([9; 32])),
+            ("fresh", State::File([5; 32])),
+            ("relinked", State::Symlink("b".into())),
+            ("retyped", State::Symlink("kept".into())),
+        ]);
+        let changes: Vec<String> = changes(&old, &new)
+            .into_iter()
+            .map(|(change, path)| format!("{change} {path}"))
+            .collect();
+        assert_eq!(
+            changes,
+            ["M edited", "A fresh", "D gone", "M relinked", "M retyped"]
+        );
+    }
+
+    #[test]
+    fn stored_paths_must_stay_below_the_destination() {
+        assert!(safe_relative("docs/readme.txt").is_ok());
+        for bad in [
+            "",
+            "../escape",
+            "a/../../b",
+            "/etc/passwd",
+            "./a",
+            "a//b/..",
+            "a\\..\\b",
+        ] {
+            assert!(safe_relative(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use std::path::PathBuf;
 
 use triblespace::prelude::*;
@@ -6,12 +6,15 @@ use triblespace_core::blob::schemas::UnknownBlob;
 use triblespace_core::blob::schemas::simplearchive::SimpleArchive;
 use triblespace_core::blob::Blob;
 use triblespace_core::repo;
-use triblespace_core::repo::pile::Pile;
 use triblespace_core::value::schemas::hash::Blake3;
 use triblespace_core::value::schemas::hash::Handle;
 use triblespace_core::value::Value;
 
-use super::signing::load_signing_key;
+use super::signing::SigningArgs;
+use super::{ClosingPile, ClosingRepo};
+use crate::cli::remote_state;
+use crate::cli::util::skip_present;
+use crate::cli::verify;
 
 /// 2^24 tribles × 64 bytes = exactly 1 GiB per chunk.
 const CHUNK_TRIBLES: usize = 1 << 24;
@@ -20,25 +23,28 @@ const TRIBLE_LEN: usize = 64;
 pub fn run(
     source: PathBuf,
     dest: PathBuf,
-    signing_key: Option<PathBuf>,
+    signing: SigningArgs,
     include: Vec<String>,
     exclude: Vec<String>,
 ) -> Result<()> {
-    let key = load_signing_key(&signing_key)?;
+    let key = signing.load()?;
 
     // Open source pile.
-    let mut src_pile: Pile<Blake3> = Pile::open(&source)?;
+    let mut src_pile = ClosingPile::open(&source)?;
     src_pile.restore().map_err(|e| anyhow!("restore source: {e:?}"))?;
 
-    // Enumerate branches.
-    let branch_ids: Vec<Id> = src_pile
-        .branches()
-        .map_err(|e| anyhow!("branches: {e:?}"))?
-        .collect::<std::result::Result<Vec<_>, _>>()
-        .map_err(|e| anyhow!("branch iter: {e:?}"))?;
+    // Enumerate branches, leaving out push records and shallow boundaries.
+    let branch_ids: Vec<Id> = {
+        let reader = src_pile
+            .reader()
+            .map_err(|e| anyhow!("source reader: {e:?}"))?;
+        remote_state::branch_ids(&mut src_pile, &reader).context("branches")?
+    };
 
-    let mut src_repo = Repository::new(src_pile, key.clone(), TribleSet::new())
-        .map_err(|e| anyhow!("source repo: {e:?}"))?;
+    let mut src_repo = ClosingRepo::new(
+        Repository::new(src_pile.into_inner(), key.clone(), TribleSet::new())
+            .map_err(|e| anyhow!("source repo: {e:?}"))?,
+    );
 
     // Create source reader (self-contained via Arc<Mmap> clone).
     let src_reader = src_repo
@@ -54,7 +60,7 @@ pub fn run(
         std::fs::create_dir_all(parent)?;
     }
     std::fs::File::create(&dest)?;
-    let mut dst_pile: Pile<Blake3> = Pile::open(&dest)?;
+    let mut dst_pile = ClosingPile::open(&dest)?;
 
     let mut total_blobs = 0usize;
     let mut total_branches = 0usize;
@@ -141,9 +147,14 @@ pub fn run(
             roots.push(Value::<Handle<Blake3, UnknownBlob>>::new(raw));
         }
 
-        let reachable = repo::reachable(&src_reader, roots);
+        // Branches often share blobs; each only needs to be written once.
+        let dst_reader = dst_pile
+            .reader()
+            .map_err(|e| anyhow!("dest pile reader error: {e:?}"))?;
+        let skipped = std::cell::Cell::new(0usize);
+        let reachable = skip_present(repo::reachable(&src_reader, roots), &dst_reader, &skipped);
         let mut branch_blobs = 0usize;
-        for r in repo::transfer(&src_reader, &mut dst_pile, reachable) {
+        for r in repo::transfer(&src_reader, &mut *dst_pile, reachable) {
             match r {
                 Ok(_) => branch_blobs += 1,
                 Err(repo::TransferError::Store(e)) => {
@@ -154,9 +165,8 @@ pub fn run(
         }
 
         // 2. Store metadata blob.
-        let metadata_handle: Value<Handle<Blake3, SimpleArchive>> = dst_pile
-            .put(metadata.to_blob())
-            .map_err(|e| anyhow!("put metadata: {e:?}"))?;
+        let metadata_handle: Value<Handle<Blake3, SimpleArchive>> =
+            verify::put(&mut dst_pile, metadata.to_blob()).context("put metadata")?;
 
         // 3. Build chunked commits directly from raw trible bytes.
         let total_bytes = num_tribles * TRIBLE_LEN;
@@ -172,9 +182,8 @@ pub fn run(
             let chunk_blob: Blob<SimpleArchive> = Blob::new(chunk_bytes);
 
             // Store the chunk content blob.
-            let _content_handle: Value<Handle<Blake3, SimpleArchive>> = dst_pile
-                .put(chunk_blob.clone())
-                .map_err(|e| anyhow!("put chunk: {e:?}"))?;
+            let _content_handle: Value<Handle<Blake3, SimpleArchive>> =
+                verify::put(&mut dst_pile, chunk_blob.clone()).context("put chunk")?;
 
             // Build commit metadata.
             let msg_text = if num_chunks == 1 {
@@ -184,9 +193,7 @@ pub fn run(
             };
             let msg_blob: Blob<triblespace_core::blob::schemas::longstring::LongString> =
                 triblespace_core::blob::ToBlob::to_blob(msg_text);
-            let msg_handle = dst_pile
-                .put(msg_blob)
-                .map_err(|e| anyhow!("put message: {e:?}"))?;
+            let msg_handle = verify::put(&mut dst_pile, msg_blob).context("put message")?;
 
             let parents = prev_commit.iter().copied();
             let commit_set = repo::commit::commit_metadata(
@@ -197,9 +204,7 @@ pub fn run(
                 Some(metadata_handle),
             );
 
-            let commit_handle = dst_pile
-                .put(commit_set)
-                .map_err(|e| anyhow!("put commit: {e:?}"))?;
+            let commit_handle = verify::put(&mut dst_pile, commit_set).context("put commit")?;
 
             prev_commit = Some(commit_handle.transmute());
 
@@ -217,11 +222,14 @@ pub fn run(
             .get(head_commit)
             .map_err(|e| anyhow!("get commit: {e:?}"))?;
 
-        let name_handle = dst_pile
-            .put(triblespace_core::blob::ToBlob::<
-                triblespace_core::blob::schemas::longstring::LongString,
-            >::to_blob(name.clone()))
-            .map_err(|e| anyhow!("put name: {e:?}"))?;
+        let name_handle =
+            verify::put(
+                &mut dst_pile,
+                triblespace_core::blob::ToBlob::<
+                    triblespace_core::blob::schemas::longstring::LongString,
+                >::to_blob(name.clone()),
+            )
+            .context("put name")?;
 
         let branch_id = triblespace_core::id::genid();
         let branch_meta = repo::branch::branch_metadata(
@@ -231,25 +239,25 @@ pub fn run(
             Some(head_blob.to_blob()),
         );
 
-        let branch_meta_handle = dst_pile
-            .put(branch_meta)
-            .map_err(|e| anyhow!("put branch meta: {e:?}"))?;
+        let branch_meta_handle =
+            verify::put(&mut dst_pile, branch_meta).context("put branch meta")?;
 
         dst_pile
             .update(*branch_id, None, Some(branch_meta_handle))
             .map_err(|e| anyhow!("update branch: {e:?}"))?;
 
         println!(
-            "wrote {name}: {num_tribles} tribles ({num_chunks} chunk{}), {branch_blobs} blobs",
+            "wrote {name}: {num_tribles} tribles ({num_chunks} chunk{}), {branch_blobs} blobs, {} already present",
             if num_chunks != 1 { "s" } else { "" },
+            skipped.get(),
         );
 
         total_blobs += branch_blobs;
         total_branches += 1;
     }
 
-    dst_pile.close().map_err(|e| anyhow!("close dest: {e:?}"))?;
-    src_repo.close().map_err(|e| anyhow!("close source: {e:?}"))?;
+    dst_pile.close().context("close dest")?;
+    src_repo.close().context("close source")?;
 
     let src_size = std::fs::metadata(&source)?.len();
     let dst_size = std::fs::metadata(&dest)?.len();
@@ -0,0 +1,140 @@
+//! Depth-first traversal of commit parent links, shared by the commands that
+//! walk history (`diagnose check`, `commit diff`, checkouts and the ancestry
+//! checks behind `branch push`/`consolidate`).
+//!
+//! Honest commit graphs are acyclic because a parent's handle must exist
+//! before the child can name it, but a pile is only as trustworthy as the
+//! bytes in it. The walk therefore tracks the commits on the current path,
+//! reports a parent link back into that path as a cycle, and refuses to
+//! follow a chain deeper than a caller-chosen limit.
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+type CommitHandle = Value<Handle<Blake3, SimpleArchive>>;
+
+/// Depth limit for walks that do not expose `--max-depth`.
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 1_000_000;
+
+enum Frame {
+    Enter(CommitHandle),
+    Exit([u8; 32]),
+}
+
+/// Visit every commit reachable from `start` once, parents in the order
+/// `visit` returns them. `visit` may stop the walk early with a value, which
+/// is returned; a cycle or a chain deeper than `max_depth` is an error.
+pub(crate) fn walk_commits<B>(
+    start: CommitHandle,
+    max_depth: usize,
+    mut visit: impl FnMut(CommitHandle) -> ControlFlow<B, Vec<CommitHandle>>,
+) -> Result<Option<B>> {
+    let mut done: HashSet<[u8; 32]> = HashSet::new();
+    let mut path: HashSet<[u8; 32]> = HashSet::new();
+    let mut stack = vec![Frame::Enter(start)];
+    while let Some(frame) = stack.pop() {
+        let current = match frame {
+            Frame::Exit(raw) => {
+                path.remove(&raw);
+                done.insert(raw);
+                continue;
+            }
+            Frame::Enter(current) => current,
+        };
+        if done.contains(&current.raw) {
+            continue;
+        }
+        if path.contains(&current.raw) {
+            return Err(crate::cli::error::corrupt(format!(
+                "cycle detected at commit blake3:{}",
+                hex::encode(current.raw)
+            )));
+        }
+        if path.len() >= max_depth {
+            return Err(crate::cli::error::corrupt(format!(
+                "commit history deeper than {max_depth} commits at blake3:{}",
+                hex::encode(current.raw)
+            )));
+        }
+        let parents = match visit(current) {
+            ControlFlow::Break(found) => return Ok(Some(found)),
+            ControlFlow::Continue(parents) => parents,
+        };
+        path.insert(current.raw);
+        stack.push(Frame::Exit(current.raw));
+        stack.extend(parents.into_iter().map(Frame::Enter));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn commit(n: u8) -> CommitHandle {
+        Value::new([n; 32])
+    }
+
+    fn walk(
+        parents: &HashMap<u8, Vec<u8>>,
+        start: u8,
+        max_depth: usize,
+    ) -> (Vec<u8>, Result<Option<()>>) {
+        let mut order = Vec::new();
+        let result = walk_commits(commit(start), max_depth, |h| {
+            order.push(h.raw[0]);
+            ControlFlow::Continue(parents[&h.raw[0]].iter().map(|&p| commit(p)).collect())
+        });
+        (order, result)
+    }
+
+    #[test]
+    fn shared_ancestors_are_visited_once_in_stack_order() {
+        // 1 -> {2, 3}, 2 -> 4, 3 -> 4
+        let parents = HashMap::from([(1, vec![2, 3]), (2, vec![4]), (3, vec![4]), (4, vec![])]);
+        let (order, result) = walk(&parents, 1, DEFAULT_MAX_DEPTH);
+        assert!(matches!(result, Ok(None)));
+        assert_eq!(order, [1, 3, 4, 2]);
+    }
+
+    #[test]
+    fn two_commit_cycle_is_reported() {
+        let parents = HashMap::from([(1, vec![2]), (2, vec![1])]);
+        let (order, result) = walk(&parents, 1, DEFAULT_MAX_DEPTH);
+        assert_eq!(order, [1, 2]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("cycle detected at commit blake3:{}", hex::encode([1u8; 32]))
+        );
+    }
+
+    #[test]
+    fn chains_deeper_than_the_limit_are_refused() {
+        let parents = HashMap::from([(1, vec![2]), (2, vec![3]), (3, vec![4]), (4, vec![])]);
+        assert!(walk(&parents, 1, 4).1.is_ok());
+        let (order, result) = walk(&parents, 1, 3);
+        assert_eq!(order, [1, 2, 3]);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("commit history deeper than 3 commits"));
+    }
+
+    #[test]
+    fn visit_can_stop_the_walk() {
+        let parents = HashMap::from([(1, vec![2]), (2, vec![3]), (3, vec![])]);
+        let found = walk_commits(commit(1), DEFAULT_MAX_DEPTH, |h| {
+            if h.raw[0] == 2 {
+                return ControlFlow::Break(h.raw[0]);
+            }
+            ControlFlow::Continue(parents[&h.raw[0]].iter().map(|&p| commit(p)).collect())
+        });
+        assert_eq!(found.unwrap(), Some(2));
+    }
+}
@@ -0,0 +1,185 @@
+//! `trible verify-replica`: check that a remote store holds a complete copy of
+//! a pile.
+//!
+//! Branch heads are compared first. Then every blob reachable from the pile's
+//! branches is looked up on the remote, a bounded number of requests at a
+//! time; existence checks never download a blob. `--deep` also downloads a
+//! random sample of the blobs and rehashes it, which catches truncated or
+//! corrupted uploads that an existence check cannot.
+
+use anyhow::Result;
+use futures::StreamExt;
+use object_store::path::Path;
+use object_store::{parse_url, ObjectStore};
+use std::path::PathBuf;
+
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BranchStore;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::repo;
+use triblespace_core::repo::objectstore::ObjectStoreRemote;
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::output::{note, Field, Output};
+use crate::cli::pile::resolve::all_branches;
+use crate::cli::pile::ClosingPile;
+
+type BlobHandle = Value<Handle<Blake3, UnknownBlob>>;
+
+/// Remote requests in flight at once unless the `concurrency` config key
+/// says otherwise.
+const CONCURRENCY: usize = 32;
+
+pub fn run(
+    url: String,
+    pile: PathBuf,
+    branches_only: bool,
+    deep: bool,
+    sample: usize,
+) -> Result<()> {
+    let url = crate::cli::config::remote_url(&url)?;
+    let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
+    let (store, base) = parse_url(&url)?;
+    let concurrency = crate::cli::config::get().concurrency.unwrap_or(CONCURRENCY);
+
+    ClosingPile::open(&pile)?.run(|pile| {
+        crate::cli::pile::refresh_for_read(pile)?;
+        let reader = pile
+            .reader()
+            .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+        let mut branches = all_branches(pile, &reader)?;
+        branches.sort_by_key(|info| info.id);
+
+        let mut out = Output::stdout();
+        let mut problems = 0usize;
+        for info in &branches {
+            let detail = match remote.head(info.id)? {
+                Some(meta) if meta == info.meta => continue,
+                Some(meta) => format!(
+                    "remote at blake3:{}, pile at blake3:{}",
+                    hex::encode(meta.raw),
+                    hex::encode(info.meta.raw)
+                ),
+                None => "missing on the remote".to_string(),
+            };
+            problems += 1;
+            out.record(&[
+                ("kind", Field::from("head-mismatch")),
+                ("item", Field::from(info.label())),
+                ("detail", Field::from(detail)),
+            ])?;
+        }
+
+        let mut checked = 0usize;
+        let mut sampled = 0usize;
+        if !branches_only {
+            // Only blobs the pile itself holds can be expected remotely.
+            let roots: Vec<BlobHandle> = branches.iter().map(|info| info.meta.transmute()).collect();
+            let hashes: Vec<[u8; 32]> = repo::reachable(&reader, roots)
+                .filter(|h| matches!(reader.metadata(*h), Ok(Some(_))))
+                .map(|h| h.raw)
+                .collect();
+            checked = hashes.len();
+
+            let runtime = tokio::runtime::Runtime::new()?;
+            let mut missing: Vec<[u8; 32]> = runtime.block_on(async {
+                let mut lookups = futures::stream::iter(&hashes)
+                    .map(|hash| {
+                        let path = blob_path(&base, hash);
+                        let store = &store;
+                        async move { (hash, store.head(&path).await) }
+                    })
+                    .buffer_unordered(concurrency);
+                let mut missing = Vec::new();
+                while let Some((hash, found)) = lookups.next().await {
+                    match found {
+                        Ok(_) => {}
+                        Err(object_store::Error::NotFound { .. }) => missing.push(*hash),
+                        Err(e) => return Err(anyhow::Error::from(e)),
+                    }
+                }
+                Ok(missing)
+            })?;
+            missing.sort_unstable();
+            for hash in &missing {
+                problems += 1;
+                out.record(&[
+                    ("kind", Field::from("missing-blob")),
+                    ("item", Field::from(format!("blake3:{}", hex::encode(hash)))),
+                    ("detail", Field::missing("-")),
+                ])?;
+            }
+
+            if deep {
+                let present: Vec<[u8; 32]> = hashes
+                    .iter()
+                    .filter(|hash| missing.binary_search(*hash).is_err())
+                    .copied()
+                    .collect();
+                let chosen = pick_sample(present, sample)?;
+                sampled = chosen.len();
+                let mut bad: Vec<([u8; 32], String)> = runtime.block_on(async {
+                    let mut downloads = futures::stream::iter(&chosen)
+                        .map(|hash| {
+                            let path = blob_path(&base, hash);
+                            let store = &store;
+                            async move {
+                                let bytes = async { store.get(&path).await?.bytes().await };
+                                (hash, bytes.await)
+                            }
+                        })
+                        .buffer_unordered(concurrency);
+                    let mut bad = Vec::new();
+                    while let Some((hash, bytes)) = downloads.next().await {
+                        let actual = blake3::hash(&bytes?);
+                        if actual.as_bytes() != hash {
+                            bad.push((*hash, format!("hashed to blake3:{}", actual.to_hex())));
+                        }
+                    }
+                    Ok::<_, anyhow::Error>(bad)
+                })?;
+                bad.sort_unstable();
+                for (hash, detail) in bad {
+                    problems += 1;
+                    out.record(&[
+                        ("kind", Field::from("bad-blob")),
+                        ("item", Field::from(format!("blake3:{}", hex::encode(hash)))),
+                        ("detail", Field::from(detail)),
+                    ])?;
+                }
+            }
+        }
+
+        let sampled = if deep {
+            format!(", {sampled} rehashed")
+        } else {
+            String::new()
+        };
+        note!(
+            "{} branch(es) compared, {checked} blob(s) checked{sampled}, {problems} problem(s) found",
+            branches.len()
+        );
+        if problems > 0 {
+            return Err(crate::cli::error::corrupt(format!(
+                "the replica at {url} is incomplete: {problems} problem(s)"
+            )));
+        }
+        Ok(())
+    })
+}
+
+fn blob_path(base: &Path, hash: &[u8; 32]) -> Path {
+    base.child("blobs").child(hex::encode(hash))
+}
+
+/// Up to `count` of `hashes`, chosen at random: the ones that rank lowest
+/// under a freshly keyed hash.
+fn pick_sample(mut hashes: Vec<[u8; 32]>, count: usize) -> Result<Vec<[u8; 32]>> {
+    let mut key = [0u8; 32];
+    getrandom::fill(&mut key).map_err(|e| anyhow::anyhow!("failed to generate sample key: {e}"))?;
+    hashes.sort_by_cached_key(|hash| *blake3::keyed_hash(&key, hash).as_bytes());
+    hashes.truncate(count);
+    Ok(hashes)
+}
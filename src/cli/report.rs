@@ -0,0 +1,201 @@
+//! Versioned JSON reports for tooling.
+//!
+//! Commands whose result is one summary rather than a stream of records
+//! (`pile diagnose check`, `pile branch stats`) print a single [`Envelope`]
+//! under `--output json`: the report's fields next to a `schema` tag such as
+//! `trible.diagnose.v1`. The structs below are that schema. Adding a field
+//! keeps the version; removing, renaming or retyping one bumps it, so
+//! dashboards can tell a breaking change from the tag alone.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// A report together with the schema it follows.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Envelope<T> {
+    pub schema: String,
+    #[serde(flatten)]
+    pub report: T,
+}
+
+/// A report type and the schema tag it is published under.
+pub trait Report: Serialize {
+    const SCHEMA: &'static str;
+}
+
+/// Write `report` to stdout as one JSON line.
+pub fn emit<T: Report>(report: &T) -> Result<()> {
+    let envelope = Envelope {
+        schema: T::SCHEMA.to_string(),
+        report,
+    };
+    let mut out = std::io::stdout().lock();
+    serde_json::to_writer(&mut out, &envelope)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// `pile diagnose check`.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct DiagnoseReport {
+    /// No check found a problem.
+    pub healthy: bool,
+    pub blobs: BlobCheck,
+    /// Present with `--canonical`.
+    pub canonical: Option<CanonicalCheck>,
+    pub branches: Vec<BranchCheck>,
+    /// Present with `--space`.
+    pub space: Option<SpaceReport>,
+    /// Blobs appended while the check ran, which may not have been checked.
+    pub late_blobs: u64,
+}
+
+impl Report for DiagnoseReport {
+    const SCHEMA: &'static str = "trible.diagnose.v1";
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct BlobCheck {
+    pub total: u64,
+    /// Blobs whose bytes do not hash to their handle.
+    pub invalid: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct CanonicalCheck {
+    pub archives: u64,
+    pub not_canonical: Vec<NonCanonical>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct NonCanonical {
+    /// `blake3:` handle of the stored archive.
+    pub handle: String,
+    /// `blake3:` handle of its canonical encoding.
+    pub canonical: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct BranchCheck {
+    /// 32-char hex branch id.
+    pub id: String,
+    pub name: Option<String>,
+    /// `blake3:` handle of the branch metadata, absent when no head is set.
+    pub meta: Option<String>,
+    pub meta_present: bool,
+    pub meta_error: Option<String>,
+    /// `blake3:` handle of the head commit.
+    pub head: Option<String>,
+    /// Commits verified, when the chain is intact.
+    pub commits: Option<u64>,
+    /// The branch was pulled shallow; commits below the boundary are absent.
+    pub shallow: bool,
+    pub chain_error: Option<String>,
+}
+
+/// What `check --space` found worth reclaiming.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct SpaceReport {
+    pub invalid_bytes: u64,
+    pub unreachable_blobs: u64,
+    pub unreachable_bytes: u64,
+    /// Blobs no branch reaches that `pile pin` keeps.
+    pub pinned_blobs: u64,
+    pub pinned_bytes: u64,
+    pub duplicate_records: u64,
+    pub duplicate_bytes: u64,
+}
+
+/// `pile branch stats`.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct StatsReport {
+    /// 32-char hex branch id.
+    pub branch: String,
+    pub commits: u64,
+    /// Content blobs over all commits, counting shared ones once per commit.
+    pub content_blobs: u64,
+    pub content_bytes: u64,
+    /// Tribles implied by the content lengths, with the same double counting.
+    pub triples: u64,
+    /// Content blobs whose length is not a multiple of 64 bytes.
+    pub misaligned_content_blobs: u64,
+    /// Present with `--full`.
+    pub full: Option<FullStats>,
+}
+
+impl Report for StatsReport {
+    const SCHEMA: &'static str = "trible.stats.v1";
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct FullStats {
+    pub unique_triples: u64,
+    pub entities: u64,
+    pub attributes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T>(report: T)
+    where
+        T: Report + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug,
+    {
+        let json = serde_json::to_string(&Envelope {
+            schema: T::SCHEMA.to_string(),
+            report: &report,
+        })
+        .unwrap();
+        let back: Envelope<T> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.schema, T::SCHEMA);
+        assert_eq!(back.report, report);
+    }
+
+    #[test]
+    fn reports_round_trip_through_their_envelope() {
+        round_trip(DiagnoseReport {
+            healthy: false,
+            blobs: BlobCheck {
+                total: 3,
+                invalid: 1,
+            },
+            canonical: Some(CanonicalCheck {
+                archives: 1,
+                not_canonical: vec![NonCanonical {
+                    handle: "blake3:00".to_string(),
+                    canonical: "blake3:11".to_string(),
+                }],
+            }),
+            branches: vec![BranchCheck {
+                id: "AB".repeat(16),
+                name: Some("main".to_string()),
+                meta: Some("blake3:22".to_string()),
+                meta_present: true,
+                commits: Some(2),
+                ..Default::default()
+            }],
+            space: Some(SpaceReport::default()),
+            late_blobs: 0,
+        });
+        round_trip(StatsReport {
+            branch: "CD".repeat(16),
+            commits: 1,
+            full: Some(FullStats::default()),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn schema_tag_sits_next_to_the_fields() {
+        let json = serde_json::to_value(Envelope {
+            schema: StatsReport::SCHEMA.to_string(),
+            report: StatsReport::default(),
+        })
+        .unwrap();
+        assert_eq!(json["schema"], "trible.stats.v1");
+        assert_eq!(json["commits"], 0);
+        assert!(json["full"].is_null());
+    }
+}
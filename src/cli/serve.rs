@@ -0,0 +1,373 @@
+//! `trible serve`: expose a pile read-only over HTTP.
+//!
+//! The routes follow the object store layout `branch pull` and `fetch` read
+//! from, so an `http://` URL pointing at the server works as a remote:
+//!
+//! ```text
+//! GET  /blobs/HASH      blob bytes (HEAD: length only)
+//! GET  /blobs/          blob handles, one per line, ?after=HASH&limit=N
+//! GET  /branches/ID     the 32-byte branch metadata handle
+//! GET  /branches/       branch ids, one per line
+//! PROPFIND /, /blobs/, /branches/   WebDAV listings
+//! ```
+//!
+//! Every other method is answered with `405 Method Not Allowed`. The server
+//! handles one request at a time and refreshes the pile before a request once
+//! the refresh interval has passed, so new commits become visible without a
+//! restart. Blob listings come from a sorted index of the pile's hashes that
+//! is only rebuilt when a refresh brings in new blobs. `PROPFIND` always
+//! lists every blob: object store clients send a single request and do not
+//! follow `Link` headers.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tiny_http::{Header, Method, Request, Response, ResponseBox, Server, StatusCode};
+use tracing::{debug, warn};
+
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::BlobStoreList;
+use triblespace::prelude::BranchStore;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::blob::Bytes;
+use triblespace_core::id::Id;
+use triblespace_core::repo::pile::{Pile, PileReader};
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::output::note;
+use crate::cli::pile::{ClosingPile, LiveReader};
+use crate::cli::remote_state;
+
+type BlobHandle = Value<Handle<Blake3, UnknownBlob>>;
+
+/// Handles listed per page unless the client asks for fewer.
+const PAGE: usize = 1000;
+/// Last-Modified and WebDAV date format (RFC 1123).
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+pub fn run(pile: Option<PathBuf>, listen: String, refresh_interval: u64) -> Result<()> {
+    let path = crate::cli::pile::resolve_pile(pile)?;
+    ClosingPile::open(&path)?.run(|pile| {
+        let mut live = LiveReader::new(pile, &path)?;
+        let mut index = BlobIndex::new(live.reader())?;
+        let mut refreshed = Instant::now();
+        let interval = Duration::from_secs(refresh_interval);
+
+        let server = Server::http(&listen).map_err(|e| {
+            crate::cli::error::invalid_argument(format!("cannot listen on {listen}: {e}"))
+        })?;
+        let addr = server
+            .server_addr()
+            .to_ip()
+            .map(|addr| addr.to_string())
+            .unwrap_or(listen);
+        note!("serving {} on http://{addr}", path.display());
+
+        for request in server.incoming_requests() {
+            if refreshed.elapsed() >= interval {
+                let appended = live.refresh(pile)?;
+                if appended.blobs + appended.branch_updates > 0 {
+                    debug!(
+                        blobs = appended.blobs,
+                        branch_updates = appended.branch_updates,
+                        "picked up appended records"
+                    );
+                }
+                if appended.blobs > 0 {
+                    index = BlobIndex::new(live.reader())?;
+                }
+                refreshed = Instant::now();
+            }
+            let method = request.method().clone();
+            let url = request.url().to_string();
+            let reply = respond(pile, live.reader(), &index, &request).unwrap_or_else(|e| {
+                warn!(%method, %url, "request failed: {e:#}");
+                text(500, format!("{e:#}\n"))
+            });
+            debug!(%method, %url, status = reply.status_code().0, "served");
+            if let Err(e) = request.respond(reply) {
+                debug!(%url, "client went away: {e}");
+            }
+        }
+        Ok(())
+    })
+}
+
+fn respond(
+    pile: &mut Pile<Blake3>,
+    reader: &PileReader<Blake3>,
+    index: &BlobIndex,
+    request: &Request,
+) -> Result<ResponseBox> {
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (request.url(), ""),
+    };
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    match request.method() {
+        Method::Get | Method::Head => match segments.as_slice() {
+            ["blobs", ""] | ["blobs"] => list_blobs(index, query),
+            ["blobs", hash] => blob(reader, hash),
+            ["branches", ""] | ["branches"] => {
+                let ids = remote_state::branch_ids(pile, reader)?;
+                let body: String = ids.iter().map(|id| format!("{id:x}\n")).collect();
+                Ok(text(200, body))
+            }
+            ["branches", id] => branch(pile, reader, id),
+            _ => Ok(text(404, "not found\n")),
+        },
+        Method::NonStandard(method) if method.as_str() == "PROPFIND" => {
+            let depth_one = request
+                .headers()
+                .iter()
+                .any(|h| h.field.equiv("Depth") && h.value.as_str() == "1");
+            propfind(pile, reader, index, &segments, depth_one)
+        }
+        _ => {
+            let mut reply = text(405, "this server is read-only\n");
+            reply.add_header(header("Allow", "GET, HEAD, PROPFIND"));
+            Ok(reply)
+        }
+    }
+}
+
+fn blob(reader: &PileReader<Blake3>, hash: &str) -> Result<ResponseBox> {
+    let Some(handle) = parse_hash(hash) else {
+        return Ok(text(404, "not found\n"));
+    };
+    let Some(meta) = reader.metadata(handle)? else {
+        return Ok(text(404, "blob not found\n"));
+    };
+    let bytes: Bytes = reader.get(handle)?;
+    let length = bytes.len();
+    let headers = vec![
+        header("Content-Type", "application/octet-stream"),
+        header("Last-Modified", &http_date(meta.timestamp)),
+        header("ETag", &format!("\"{}\"", hex::encode(handle.raw))),
+        header("Cache-Control", "public, max-age=31536000, immutable"),
+    ];
+    // Blobs are memory mapped; stream them from the map without copying.
+    Ok(Response::new(
+        StatusCode(200),
+        headers,
+        Cursor::new(bytes),
+        Some(length),
+        None,
+    )
+    .boxed())
+}
+
+/// The pile's blob hashes in order, built once per reader generation so a
+/// page costs a binary search instead of a sort of every hash.
+struct BlobIndex {
+    hashes: Vec<[u8; 32]>,
+}
+
+impl BlobIndex {
+    fn new(reader: &PileReader<Blake3>) -> Result<Self> {
+        let mut hashes = Vec::new();
+        for handle in reader.blobs() {
+            let handle: BlobHandle = handle?;
+            hashes.push(handle.raw);
+        }
+        hashes.sort_unstable();
+        Ok(BlobIndex { hashes })
+    }
+
+    /// Up to `limit` hashes following `after`, and whether more follow them.
+    fn page(&self, after: Option<[u8; 32]>, limit: usize) -> (&[[u8; 32]], bool) {
+        let start = after.map_or(0, |after| self.hashes.partition_point(|raw| *raw <= after));
+        let rest = &self.hashes[start..];
+        (&rest[..rest.len().min(limit)], rest.len() > limit)
+    }
+}
+
+/// The `?after=HASH&limit=N` of a listing, or the reply for a bad one.
+fn page_query(query: &str) -> Result<(Option<[u8; 32]>, usize), ResponseBox> {
+    let mut after: Option<[u8; 32]> = None;
+    let mut limit = PAGE;
+    for (key, value) in query.split('&').filter_map(|kv| kv.split_once('=')) {
+        match key {
+            "after" => match parse_hash(value) {
+                Some(handle) => after = Some(handle.raw),
+                None => return Err(text(400, "after: expected a 64-char hex hash\n")),
+            },
+            "limit" => match value.parse::<usize>() {
+                Ok(n) if n > 0 => limit = n.min(PAGE),
+                _ => return Err(text(400, "limit: expected a positive number\n")),
+            },
+            _ => {}
+        }
+    }
+    Ok((after, limit))
+}
+
+/// `Link: <...>; rel="next"` to the page of `path` after `last`.
+fn next_link(path: &str, last: &[u8; 32], limit: usize) -> Header {
+    let next = format!(
+        "<{path}?after={}&limit={limit}>; rel=\"next\"",
+        hex::encode(last)
+    );
+    header("Link", &next)
+}
+
+/// One page of handles in hash order, starting after `?after=HASH`; a
+/// `Link: <...>; rel="next"` header points to the following page.
+fn list_blobs(index: &BlobIndex, query: &str) -> Result<ResponseBox> {
+    let (after, limit) = match page_query(query) {
+        Ok(page) => page,
+        Err(reply) => return Ok(reply),
+    };
+    let (handles, more) = index.page(after, limit);
+    let body: String = handles
+        .iter()
+        .map(|raw| format!("{}\n", hex::encode(raw)))
+        .collect();
+    let mut reply = text(200, body);
+    if let (true, Some(last)) = (more, handles.last()) {
+        reply.add_header(next_link("/blobs/", last, limit));
+    }
+    Ok(reply)
+}
+
+fn branch(pile: &mut Pile<Blake3>, reader: &PileReader<Blake3>, id: &str) -> Result<ResponseBox> {
+    let Some(id) = parse_id(id) else {
+        return Ok(text(404, "not found\n"));
+    };
+    // Push records and shallow boundaries are not branches.
+    if remote_state::is_remote_state_slot(pile, reader, id)? {
+        return Ok(text(404, "branch not found\n"));
+    }
+    match pile.head(id)? {
+        Some(meta) => Ok(Response::from_data(meta.raw.to_vec())
+            .with_header(header("Content-Type", "application/octet-stream"))
+            .with_header(header("Cache-Control", "no-cache"))
+            .boxed()),
+        None => Ok(text(404, "branch not found\n")),
+    }
+}
+
+/// A WebDAV multistatus listing, as object store clients expect from
+/// `PROPFIND`. Depth 1 on the root lists the two collections; otherwise the
+/// objects below the requested collection are listed in full.
+fn propfind(
+    pile: &mut Pile<Blake3>,
+    reader: &PileReader<Blake3>,
+    index: &BlobIndex,
+    segments: &[&str],
+    depth_one: bool,
+) -> Result<ResponseBox> {
+    let (blobs, branches) = match segments {
+        [""] => (true, true),
+        ["blobs", ""] | ["blobs"] => (true, false),
+        ["branches", ""] | ["branches"] => (false, true),
+        _ => return Ok(text(404, "not found\n")),
+    };
+    let now = http_date(Utc::now().timestamp_millis() as u64);
+    let mut entries: Vec<(String, Option<u64>, String)> = Vec::new();
+    if blobs && branches && depth_one {
+        entries.push(("/blobs/".to_string(), None, now.clone()));
+        entries.push(("/branches/".to_string(), None, now.clone()));
+    } else {
+        if blobs {
+            for raw in &index.hashes {
+                let handle: BlobHandle = Value::new(*raw);
+                if let Some(meta) = reader.metadata(handle)? {
+                    let href = format!("/blobs/{}", hex::encode(raw));
+                    entries.push((href, Some(meta.length), http_date(meta.timestamp)));
+                }
+            }
+        }
+        if branches {
+            for id in remote_state::branch_ids(pile, reader)? {
+                entries.push((format!("/branches/{id:x}"), Some(32), now.clone()));
+            }
+        }
+    }
+
+    let mut body =
+        String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<multistatus xmlns=\"DAV:\">\n");
+    for (href, length, modified) in entries {
+        let (kind, length) = match length {
+            Some(length) => ("<resourcetype/>".to_string(), length),
+            None => ("<resourcetype><collection/></resourcetype>".to_string(), 0),
+        };
+        body.push_str(&format!(
+            "<response><href>{href}</href><propstat><prop>\
+             <getcontentlength>{length}</getcontentlength>\
+             <getlastmodified>{modified}</getlastmodified>{kind}</prop>\
+             <status>HTTP/1.1 200 OK</status></propstat></response>\n"
+        ));
+    }
+    body.push_str("</multistatus>\n");
+    Ok(Response::from_string(body)
+        .with_status_code(StatusCode(207))
+        .with_header(header("Content-Type", "application/xml; charset=utf-8"))
+        .boxed())
+}
+
+/// A blob hash as 64 hex chars in either case.
+fn parse_hash(hex: &str) -> Option<BlobHandle> {
+    let mut raw = [0u8; 32];
+    hex::decode_to_slice(hex, &mut raw).ok()?;
+    Some(Value::new(raw))
+}
+
+/// A branch id as 32 hex chars in either case.
+fn parse_id(hex: &str) -> Option<Id> {
+    let mut raw = [0u8; 16];
+    hex::decode_to_slice(hex, &mut raw).ok()?;
+    Id::new(raw)
+}
+
+fn http_date(millis: u64) -> String {
+    let time: DateTime<Utc> = DateTime::from(UNIX_EPOCH + Duration::from_millis(millis));
+    time.format(HTTP_DATE).to_string()
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+fn text(status: u16, body: impl Into<String>) -> ResponseBox {
+    Response::from_string(body)
+        .with_status_code(StatusCode(status))
+        .with_header(header("Content-Type", "text/plain; charset=utf-8"))
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_and_ids_parse_in_either_case() {
+        let hash = "AB".repeat(32);
+        assert_eq!(parse_hash(&hash).unwrap().raw, [0xAB; 32]);
+        assert_eq!(parse_hash(&hash.to_lowercase()).unwrap().raw, [0xAB; 32]);
+        assert!(parse_hash("abc").is_none());
+        assert!(parse_id(&"cd".repeat(16)).is_some());
+        assert!(parse_id(&"0".repeat(32)).is_none());
+    }
+
+    #[test]
+    fn blob_pages_continue_after_the_last_hash() {
+        let index = BlobIndex {
+            hashes: (1..=5u8).map(|b| [b; 32]).collect(),
+        };
+        assert_eq!(index.page(None, 2), (&index.hashes[..2], true));
+        assert_eq!(index.page(Some([2; 32]), 2), (&index.hashes[2..4], true));
+        assert_eq!(index.page(Some([4; 32]), 2), (&index.hashes[4..], false));
+        // A cursor before the first hash starts the listing at it.
+        assert_eq!(index.page(Some([0; 32]), 10), (&index.hashes[..], false));
+    }
+
+    #[test]
+    fn http_dates_use_rfc_1123() {
+        assert_eq!(http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+    }
+}
@@ -0,0 +1,113 @@
+//! Timing and byte counters behind the global `--stats` flag.
+//!
+//! Handlers wrap their expensive phases in [`phase!`] spans. When `--stats` is
+//! given, a tracing layer sums the time spent inside each phase and [`report`]
+//! prints the breakdown to stderr once the command has finished. Byte counts
+//! are plain atomics fed by [`read`] and [`written`].
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::span::Id;
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Target of the spans opened by [`phase!`].
+pub const TARGET: &str = "trible::stats";
+
+/// Phases in report order.
+const PHASES: [&str; 5] = [
+    "pile open",
+    "reachability",
+    "hashing",
+    "transfer",
+    "head update",
+];
+
+static TIMES: Mutex<BTreeMap<&'static str, Duration>> = Mutex::new(BTreeMap::new());
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static READ: AtomicU64 = AtomicU64::new(0);
+static WRITTEN: AtomicU64 = AtomicU64::new(0);
+
+/// Enter a stats phase until the returned guard is dropped; the name must be
+/// one of the phases listed in the report.
+macro_rules! phase {
+    ($name:literal) => {
+        tracing::info_span!(target: $crate::cli::stats::TARGET, $name).entered()
+    };
+}
+pub(crate) use phase;
+
+/// Whether `--stats` was given; lets callers skip work done only for counting.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Count bytes read from a pile or remote.
+pub fn read(bytes: u64) {
+    READ.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Count bytes written to a pile or remote.
+pub fn written(bytes: u64) {
+    WRITTEN.fetch_add(bytes, Ordering::Relaxed);
+}
+
+struct Entered(Instant);
+
+/// Tracing layer that accumulates the time spent in each phase span.
+pub struct StatsLayer;
+
+/// The layer to install for `--stats`; also turns on [`enabled`].
+pub fn layer() -> StatsLayer {
+    ENABLED.store(true, Ordering::Relaxed);
+    StatsLayer
+}
+
+impl<S> Layer<S> for StatsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if span.metadata().target() == TARGET {
+                span.extensions_mut().replace(Entered(Instant::now()));
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(Entered(start)) = span.extensions_mut().remove::<Entered>() {
+            let mut times = TIMES.lock().unwrap_or_else(|e| e.into_inner());
+            *times.entry(span.metadata().name()).or_default() += start.elapsed();
+        }
+    }
+}
+
+/// Print the breakdown for a command that ran for `total`.
+pub fn report(total: Duration) {
+    let times = TIMES.lock().unwrap_or_else(|e| e.into_inner());
+    eprintln!("stats:");
+    for phase in PHASES {
+        let time = times.get(phase).copied().unwrap_or_default();
+        eprintln!("  {phase:<14}{:>12.3} ms", time.as_secs_f64() * 1000.0);
+    }
+    eprintln!("  {:<14}{:>12.3} ms", "total", total.as_secs_f64() * 1000.0);
+    eprintln!(
+        "  {:<14}{:>12} B",
+        "bytes read",
+        READ.load(Ordering::Relaxed)
+    );
+    eprintln!(
+        "  {:<14}{:>12} B",
+        "bytes written",
+        WRITTEN.load(Ordering::Relaxed)
+    );
+}
@@ -3,10 +3,10 @@ use clap::Parser;
 use std::fs::File;
 use std::path::PathBuf;
 
+use crate::cli::throttle::RateLimiter;
 use crate::cli::util::parse_blob_handle;
 use object_store::parse_url;
 use triblespace_core::blob::schemas::UnknownBlob;
-use triblespace_core::blob::Bytes;
 use triblespace_core::repo::objectstore::ObjectStoreRemote;
 use triblespace_core::repo::BlobStore;
 use triblespace_core::repo::BlobStoreForget;
@@ -15,21 +15,40 @@ use triblespace_core::repo::BlobStoreList;
 use triblespace_core::repo::BlobStoreMeta;
 use triblespace_core::value::schemas::hash::Blake3;
 use triblespace_core::value::schemas::hash::Handle;
-use url::Url;
 
 #[derive(Parser)]
 pub enum Command {
     /// List objects at the given URL.
+    #[command(visible_alias = "ls")]
     List {
         /// URL of the object store to inspect (e.g. "s3://bucket/path" or "file:///path")
         url: String,
     },
-    /// Upload a file to a remote object store.
+    /// Upload a file, or a downloaded HTTP(S) resource, to a remote object store.
     Put {
         /// URL of the destination object store (e.g. "s3://bucket/path" or "file:///path")
         url: String,
         /// File whose contents should be stored remotely
-        file: PathBuf,
+        #[arg(required_unless_present = "source_url")]
+        file: Option<PathBuf>,
+        /// Download an HTTP(S) resource and store it instead of a file
+        #[arg(
+            long = "url",
+            id = "source_url",
+            value_name = "URL",
+            conflicts_with = "file"
+        )]
+        source_url: Option<String>,
+        /// Fail without storing anything unless the download hashes to
+        /// this handle (e.g. "blake3:HEX...")
+        #[arg(long, value_name = "HANDLE", requires = "source_url")]
+        expected_handle: Option<String>,
+        /// Limit the transfer rate in bytes per second (e.g. `500k`, `5M`)
+        #[arg(long, value_parser = crate::cli::throttle::parse_rate)]
+        limit_rate: Option<u64>,
+        /// Also write the handle to PATH (replaced atomically)
+        #[arg(long, value_name = "PATH")]
+        handle_out: Option<PathBuf>,
     },
     /// Download a blob from a remote object store.
     Get {
@@ -39,6 +58,9 @@ pub enum Command {
         handle: String,
         /// Destination file path for the extracted blob
         output: PathBuf,
+        /// Limit the transfer rate in bytes per second (e.g. `500k`, `5M`)
+        #[arg(long, value_parser = crate::cli::throttle::parse_rate)]
+        limit_rate: Option<u64>,
     },
     /// Inspect a remote blob and print basic metadata.
     Inspect {
@@ -48,18 +70,22 @@ pub enum Command {
         handle: String,
     },
     /// Remove a blob from a remote object store.
+    #[command(visible_alias = "rm")]
     Forget {
         /// URL of the object store (e.g. "s3://bucket/path" or "file:///path")
         url: String,
         /// Handle of the blob to delete (e.g. "blake3:HEX...")
         handle: String,
+        /// Delete without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
     },
 }
 
 pub fn run(cmd: Command) -> Result<()> {
     match cmd {
         Command::List { url } => {
-            let url = Url::parse(&url)?;
+            let url = crate::cli::config::remote_url(&url)?;
 
             // Prefer the repo-managed blob listing. Do not fall back to raw
             // listing automatically — bare files were a bug, not a feature.
@@ -83,45 +109,103 @@ pub fn run(cmd: Command) -> Result<()> {
 
             Ok(())
         }
-        Command::Put { url, file } => {
+        Command::Put {
+            url,
+            file,
+            source_url,
+            expected_handle,
+            limit_rate,
+            handle_out,
+        } => {
             use triblespace::prelude::blobschemas::FileBytes;
             use triblespace::prelude::BlobStorePut;
             use triblespace_core::blob::Bytes;
 
             use triblespace_core::value::schemas::hash::Hash;
 
-            let url = Url::parse(&url)?;
+            let url = crate::cli::config::remote_url(&url)?;
             let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
-            let file_handle = File::open(&file)?;
-            let bytes = unsafe { Bytes::map_file(&file_handle)? };
-            let handle = remote.put::<FileBytes, _>(bytes)?;
-            let hash: triblespace_core::value::Value<Hash<Blake3>> = Handle::to_hash(handle);
-            let string: String = hash.from_value();
+            let bytes = match (&file, &source_url) {
+                (Some(file), _) => {
+                    let file_handle = File::open(file)?;
+                    unsafe { Bytes::map_file(&file_handle)? }
+                }
+                (None, Some(source)) => {
+                    crate::cli::download::fetch(source, expected_handle.as_deref())?
+                }
+                (None, None) => unreachable!("clap requires a file or --url"),
+            };
+            let string: String = match limit_rate {
+                Some(rate) if !bytes.is_empty() => {
+                    let hash = blake3::hash(&bytes).to_hex();
+                    put_throttled(&url, &hash, &bytes, &RateLimiter::new(rate))?;
+                    format!("blake3:{hash}")
+                }
+                _ => {
+                    let handle = remote.put::<FileBytes, _>(bytes)?;
+                    let hash: triblespace_core::value::Value<Hash<Blake3>> =
+                        Handle::to_hash(handle);
+                    hash.from_value()
+                }
+            };
             println!("{string}");
+            if let Some(path) = handle_out {
+                crate::cli::util::write_handles(&path, &[string])?;
+            }
             Ok(())
         }
         Command::Get {
             url,
             handle,
             output,
+            limit_rate,
         } => {
-            use std::io::Write;
+            use futures::StreamExt;
 
-            use triblespace::prelude::BlobStore;
-            use triblespace::prelude::BlobStoreGet;
+            use crate::cli::util::{copy_chunks, write_atomically};
 
-            let url = Url::parse(&url)?;
-            let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
+            let url = crate::cli::config::remote_url(&url)?;
             let hash_val = parse_blob_handle(&handle)?;
-            let handle_val: triblespace_core::value::Value<Handle<Blake3, UnknownBlob>> =
-                hash_val.into();
-            let reader = remote
-                .reader()
-                .map_err(|e| anyhow::anyhow!("remote reader error: {e:?}"))?;
-            let bytes: Bytes = reader.get(handle_val)?;
-            let mut file = File::create(&output)?;
-            file.write_all(&bytes)?;
-            Ok(())
+            let handle_str: String = hash_val.clone().from_value();
+            let handle_hex = handle_str
+                .split(':')
+                .next_back()
+                .ok_or_else(|| anyhow::anyhow!("invalid handle"))?;
+            let (store, base) = parse_url(&url)?;
+            let path = base.child("blobs").child(handle_hex);
+
+            // Stream the object body instead of going through the remote's
+            // reader, which would buffer the whole blob in memory.
+            let runtime = tokio::runtime::Runtime::new()?;
+            let body = match runtime.block_on(store.get(&path)) {
+                Ok(body) => body,
+                Err(object_store::Error::NotFound { .. }) => {
+                    return Err(crate::cli::error::not_found(format!(
+                        "blob {handle} not found"
+                    )));
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let mut stream = body.into_stream();
+            let chunks = std::iter::from_fn(|| runtime.block_on(stream.next()))
+                .map(|chunk| chunk.map_err(anyhow::Error::from));
+
+            let limiter = limit_rate.map(RateLimiter::new);
+            let mut hasher = blake3::Hasher::new();
+            write_atomically(&output, |file| {
+                copy_chunks(chunks, file, |chunk| {
+                    if let Some(limiter) = &limiter {
+                        limiter.acquire(chunk.len() as u64);
+                    }
+                    hasher.update(chunk);
+                })?;
+                if hasher.finalize().as_bytes() != &hash_val.raw {
+                    return Err(crate::cli::error::corrupt(format!(
+                        "blob {handle} does not match its hash"
+                    )));
+                }
+                Ok(())
+            })
         }
         Command::Inspect { url, handle } => {
             use file_type::FileType;
@@ -129,7 +213,7 @@ pub fn run(cmd: Command) -> Result<()> {
             
             use triblespace_core::blob::Blob;
 
-            let url = Url::parse(&url)?;
+            let url = crate::cli::config::remote_url(&url)?;
             let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
             let hash_val = parse_blob_handle(&handle)?;
             let handle_val: triblespace_core::value::Value<Handle<Blake3, UnknownBlob>> =
@@ -169,17 +253,51 @@ pub fn run(cmd: Command) -> Result<()> {
             );
             Ok(())
         }
-        Command::Forget { url, handle } => {
-            let url = Url::parse(&url)?;
+        Command::Forget { url, handle, yes } => {
+            let url = crate::cli::config::remote_url(&url)?;
             let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
             let (_store, _path) = parse_url(&url)?;
             let hash_val = parse_blob_handle(&handle)?;
             let handle_val: triblespace_core::value::Value<Handle<Blake3, UnknownBlob>> =
                 hash_val.into();
             let blob_handle = handle_val;
+            crate::cli::util::confirm(&format!("This will delete blob {handle}"), yes)?;
             // forget is idempotent
             remote.forget(blob_handle)?;
             Ok(())
         }
     }
 }
+
+/// Bytes per part of a throttled upload; the smallest part S3 accepts.
+const UPLOAD_PART: usize = 5 * 1024 * 1024;
+/// Bytes drawn from the rate limiter at a time.
+const THROTTLE_CHUNK: usize = 64 * 1024;
+
+/// Upload `bytes` to `blobs/HASH` as a multipart upload, drawing every chunk
+/// from `limiter` before handing it over. With one part in flight at most,
+/// the upload keeps pace with the limit instead of sending in one burst.
+fn put_throttled(url: &url::Url, hash: &str, bytes: &[u8], limiter: &RateLimiter) -> Result<()> {
+    use object_store::{ObjectStore, WriteMultipart};
+
+    let (store, base) = parse_url(url)?;
+    let path = base.child("blobs").child(hash);
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        // Blobs are content addressed; one already there is this one.
+        match store.head(&path).await {
+            Ok(_) => return Ok(()),
+            Err(object_store::Error::NotFound { .. }) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let upload = store.put_multipart(&path).await?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, UPLOAD_PART);
+        for chunk in bytes.chunks(THROTTLE_CHUNK) {
+            writer.wait_for_capacity(1).await?;
+            limiter.acquire(chunk.len() as u64);
+            writer.write(chunk);
+        }
+        writer.finish().await?;
+        Ok(())
+    })
+}
@@ -4,10 +4,35 @@ use clap::Parser;
 #[derive(Parser)]
 pub enum Command {
     /// List all branch identifiers at the given URL.
+    #[command(visible_alias = "ls")]
     List {
         /// URL of the object store to inspect (e.g. "s3://bucket/path" or "file:///path")
         url: String,
     },
+    /// Show a branch's name, head and creator.
+    Inspect {
+        /// URL of the object store to inspect (e.g. "s3://bucket/path" or "file:///path")
+        url: String,
+        /// Branch identifier (hex)
+        branch: String,
+    },
+    /// Set a branch's metadata handle on the remote (compare-and-swap).
+    Set {
+        /// URL of the object store to modify (e.g. "s3://bucket/path" or "file:///path")
+        url: String,
+        /// Branch identifier to set (hex encoded)
+        branch: String,
+        /// Branch metadata blob handle (64 hex chars, optionally prefixed with `blake3:`)
+        #[arg(required_unless_present = "delete", conflicts_with = "delete")]
+        meta: Option<String>,
+        /// Expected current branch metadata blob handle (CAS), or `none` for
+        /// a branch that must not exist yet. Uses the current head when omitted.
+        #[arg(long)]
+        expected: Option<String>,
+        /// Delete (tombstone) the branch instead of setting a handle
+        #[arg(long)]
+        delete: bool,
+    },
 }
 
 pub fn run(cmd: Command) -> Result<()> {
@@ -16,18 +41,130 @@ pub fn run(cmd: Command) -> Result<()> {
             use triblespace::prelude::BranchStore;
             use triblespace_core::repo::objectstore::ObjectStoreRemote;
             use triblespace_core::value::schemas::hash::Blake3;
-            use url::Url;
 
-            let url = Url::parse(&url)?;
+            use crate::cli::output::{Field, Output};
+
+            let url = crate::cli::config::remote_url(&url)?;
             let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
             // Ensure remote listing is up-to-date when needed; callers can
             // refresh explicitly if they prefer.
             let iter = remote.branches()?;
+            let mut out = Output::stdout();
             for branch_res in iter {
                 let id = branch_res?;
-                println!("{id:X}");
+                out.record(&[("id", Field::from(format!("{id:X}")))])?;
             }
             Ok(())
         }
+        Command::Inspect { url, branch } => {
+            use triblespace::prelude::blobschemas::SimpleArchive;
+            use triblespace::prelude::valueschemas::Handle;
+            use triblespace::prelude::BlobStore;
+            use triblespace::prelude::BlobStoreGet;
+            use triblespace::prelude::BranchStore;
+            use triblespace_core::repo::objectstore::ObjectStoreRemote;
+            use triblespace_core::trible::TribleSet;
+            use triblespace_core::value::schemas::hash::Blake3;
+            use triblespace_core::value::schemas::hash::Hash;
+            use triblespace_core::value::Value;
+
+            use crate::cli::output::{Field, Output, OutputFormat};
+            use crate::cli::pile::branch::{
+                extract_repo_head, load_branch_name, parse_branch_id_hex, provenance,
+            };
+
+            let url = crate::cli::config::remote_url(&url)?;
+            let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
+            let branch_id = parse_branch_id_hex(&branch)?;
+            let meta_handle = remote
+                .head(branch_id)?
+                .ok_or_else(|| crate::cli::error::not_found("branch not found"))?;
+            let reader = remote
+                .reader()
+                .map_err(|e| anyhow::anyhow!("remote reader error: {e:?}"))?;
+            let meta: TribleSet = reader
+                .get::<TribleSet, SimpleArchive>(meta_handle)
+                .map_err(|e| crate::cli::error::corrupt(format!("branch metadata: {e:?}")))?;
+            let name = load_branch_name(&reader, &meta)?;
+            let head = extract_repo_head(&meta).map(|h| {
+                let hash: Value<Hash<Blake3>> = Handle::to_hash(h);
+                hash.from_value::<String>()
+            });
+            let meta_hash: Value<Hash<Blake3>> = Handle::to_hash(meta_handle);
+            let meta_hex: String = meta_hash.from_value();
+            let creator = provenance::creator(&meta).map(|k| provenance::format(&k));
+
+            let mut out = Output::stdout();
+            if out.format() != OutputFormat::Plain {
+                return out.record(&[
+                    ("id", Field::from(format!("{branch_id:X}"))),
+                    ("name", name.map_or(Field::missing("-"), Field::from)),
+                    ("meta", Field::from(meta_hex)),
+                    ("head", head.map_or(Field::missing("-"), Field::from)),
+                    (
+                        "creator",
+                        creator.map_or(Field::missing("unknown"), Field::from),
+                    ),
+                ]);
+            }
+            println!("Id:        {branch_id:X}");
+            println!("Name:      {}", name.as_deref().unwrap_or("-"));
+            println!("Meta:      {meta_hex}");
+            println!("Head:      {}", head.as_deref().unwrap_or("-"));
+            println!("Creator:   {}", creator.as_deref().unwrap_or("unknown"));
+            Ok(())
+        }
+        Command::Set {
+            url,
+            branch,
+            meta,
+            expected,
+            delete,
+        } => {
+            use triblespace::prelude::BranchStore;
+            use triblespace_core::repo::objectstore::ObjectStoreRemote;
+            use triblespace_core::repo::PushResult;
+            use triblespace_core::value::schemas::hash::Blake3;
+
+            use crate::cli::pile::branch::{
+                parse_blake3_handle, parse_blake3_handle_opt, parse_branch_id_hex,
+            };
+
+            let branch_id = parse_branch_id_hex(&branch)?;
+            let new_meta = meta.as_deref().map(parse_blake3_handle).transpose()?;
+            let url = crate::cli::config::remote_url(&url)?;
+            let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
+
+            let expected_old = match expected {
+                Some(s) => parse_blake3_handle_opt(&s)?,
+                None => remote.head(branch_id)?,
+            };
+            if delete && expected_old.is_none() {
+                return Err(crate::cli::error::not_found(format!(
+                    "branch not found: {branch_id:X}"
+                )));
+            }
+
+            match remote.update(branch_id, expected_old, new_meta)? {
+                PushResult::Success() => {
+                    match new_meta {
+                        Some(h) => println!(
+                            "set branch {branch_id:X} meta blake3:{}",
+                            hex::encode(h.raw)
+                        ),
+                        None => println!("deleted branch {branch_id:X}"),
+                    }
+                    Ok(())
+                }
+                PushResult::Conflict(existing) => {
+                    let got = existing
+                        .map(|h| format!("blake3:{}", hex::encode(h.raw)))
+                        .unwrap_or_else(|| "-".to_string());
+                    Err(crate::cli::error::conflict(format!(
+                        "branch head changed concurrently; current={got}"
+                    )))
+                }
+            }
+        }
     }
 }
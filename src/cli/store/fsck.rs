@@ -0,0 +1,171 @@
+//! `store fsck`: check the object layout of a remote store.
+//!
+//! A store keeps each blob at `blobs/HASH` and each branch at
+//! `branches/ID`, both as lowercase hex, the names `ObjectStoreRemote`
+//! writes and looks up. Older CLI versions also left blobs at the store root
+//! and branch files under uppercase ids, which the library no longer finds.
+//! Every blob is read back and rehashed; a blob whose bytes do not match its
+//! key is reported but never touched, since there is no way to tell which
+//! half is wrong.
+
+use anyhow::Result;
+use futures::StreamExt;
+use object_store::path::Path;
+use object_store::{parse_url, ObjectStore};
+
+use crate::cli::output::{note, Field, Output};
+
+/// A layout problem and, when it can be fixed, the key the object belongs at.
+#[derive(Debug)]
+struct Finding {
+    key: String,
+    kind: &'static str,
+    /// Where `--fix` moves the object.
+    target: Option<String>,
+}
+
+pub fn run(url: String, fix: bool, yes: bool) -> Result<()> {
+    let url = crate::cli::config::remote_url(&url)?;
+    let (store, base) = parse_url(&url)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    let keys: Vec<Vec<String>> = runtime.block_on(async {
+        let mut keys = Vec::new();
+        let mut listing = store.list(Some(&base));
+        while let Some(meta) = listing.next().await {
+            let meta = meta?;
+            let Some(parts) = meta.location.prefix_match(&base) else {
+                continue;
+            };
+            keys.push(parts.map(|p| p.as_ref().to_string()).collect());
+        }
+        Ok::<_, object_store::Error>(keys)
+    })?;
+
+    let mut findings = Vec::new();
+    for parts in &keys {
+        let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+        let key = parts.join("/");
+        let content_hash = || -> Result<String> {
+            let hash = runtime.block_on(hash_object(&*store, &child(&base, &parts)))?;
+            Ok(hash.to_hex().to_string())
+        };
+        let finding = match parts.as_slice() {
+            ["blobs", name] if is_hex(name, 64) => {
+                let hash = content_hash()?;
+                if !name.eq_ignore_ascii_case(&hash) {
+                    Some(("bad-hash", None))
+                } else if *name != hash {
+                    Some(("misnamed-blob", Some(format!("blobs/{hash}"))))
+                } else {
+                    None
+                }
+            }
+            [name] if is_hex(name, 64) => {
+                let hash = content_hash()?;
+                if name.eq_ignore_ascii_case(&hash) {
+                    Some(("misplaced-blob", Some(format!("blobs/{hash}"))))
+                } else {
+                    Some(("unexpected", None))
+                }
+            }
+            ["branches", name] if is_hex(name, 32) => {
+                let lower = name.to_ascii_lowercase();
+                (*name != lower).then(|| ("misnamed-branch", Some(format!("branches/{lower}"))))
+            }
+            _ => Some(("unexpected", None)),
+        };
+        if let Some((kind, target)) = finding {
+            findings.push(Finding { key, kind, target });
+        }
+    }
+
+    let mut out = Output::stdout();
+    for f in &findings {
+        out.record(&[
+            ("kind", Field::from(f.kind)),
+            ("key", Field::from(f.key.as_str())),
+            (
+                "fix",
+                f.target
+                    .as_deref()
+                    .map_or(Field::missing("-"), |t| Field::from(format!("-> {t}"))),
+            ),
+        ])?;
+    }
+
+    let fixable: Vec<&Finding> = findings.iter().filter(|f| f.target.is_some()).collect();
+    let mut fixed = 0usize;
+    if fix && !fixable.is_empty() {
+        crate::cli::util::confirm(
+            &format!("This will move {} object(s) in {url}", fixable.len()),
+            yes,
+        )?;
+        for f in &fixable {
+            let target = f.target.as_deref().expect("fixable");
+            let from = child(&base, &f.key.split('/').collect::<Vec<_>>());
+            let to = child(&base, &target.split('/').collect::<Vec<_>>());
+            let exists = keys.iter().any(|parts| parts.join("/") == target);
+            match runtime.block_on(move_object(&*store, &from, &to, exists)) {
+                Ok(()) => fixed += 1,
+                Err(e) => eprintln!("{}: {e:#}", f.key),
+            }
+        }
+    }
+
+    let remaining = findings.len() - fixed;
+    note!(
+        "{} object(s) checked, {} problem(s) found, {fixed} fixed",
+        keys.len(),
+        findings.len()
+    );
+    if remaining > 0 {
+        let hint = if !fix && !fixable.is_empty() {
+            "; run with --fix to move misplaced objects"
+        } else {
+            ""
+        };
+        return Err(crate::cli::error::corrupt(format!(
+            "{remaining} layout problem(s) remain{hint}"
+        )));
+    }
+    Ok(())
+}
+
+/// Move `from` to `to`. When the listing already had an object at `to`, it
+/// is kept and the stray one is only dropped if both hold the same bytes.
+async fn move_object(store: &dyn ObjectStore, from: &Path, to: &Path, exists: bool) -> Result<()> {
+    if exists {
+        if hash_object(store, to).await? != hash_object(store, from).await? {
+            anyhow::bail!("conflicts with the different object at {to}");
+        }
+        store.delete(from).await?;
+    } else {
+        // On a case-insensitive file system a case-only rename would name
+        // the same file twice, so go through a temporary key.
+        let temporary = Path::from(format!("{to}.fsck"));
+        store.rename(from, &temporary).await?;
+        store.rename(&temporary, to).await?;
+    }
+    Ok(())
+}
+
+/// BLAKE3 of the object at `path`, hashed chunk by chunk as it streams in.
+async fn hash_object(store: &dyn ObjectStore, path: &Path) -> Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    let mut chunks = store.get(path).await?.into_stream();
+    while let Some(chunk) = chunks.next().await {
+        hasher.update(&chunk?);
+    }
+    Ok(hasher.finalize())
+}
+
+fn child(base: &Path, parts: &[&str]) -> Path {
+    parts
+        .iter()
+        .fold(base.clone(), |path, part| path.child(*part))
+}
+
+fn is_hex(name: &str, len: usize) -> bool {
+    name.len() == len && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
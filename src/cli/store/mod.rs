@@ -3,10 +3,12 @@ use clap::Parser;
 
 pub mod blob;
 pub mod branch;
+mod fsck;
 
 #[derive(Parser)]
 pub enum StoreCommand {
     /// Operations on branches stored in a remote object store.
+    #[command(visible_alias = "br")]
     Branch {
         #[command(subcommand)]
         cmd: branch::Command,
@@ -16,11 +18,29 @@ pub enum StoreCommand {
         #[command(subcommand)]
         cmd: blob::Command,
     },
+    /// Check that every object sits at `blobs/HASH` or `branches/ID` in
+    /// lowercase hex and that blobs match their hash.
+    ///
+    /// Lists one row per problem. With `--fix`, blobs found at the store
+    /// root or under uppercase keys and branch files with uppercase ids are
+    /// moved to their proper keys; blobs whose bytes do not match their key
+    /// are only reported.
+    Fsck {
+        /// URL of the object store to check (e.g. "s3://bucket/path" or "file:///path")
+        url: String,
+        /// Move misplaced objects to their proper keys
+        #[arg(long)]
+        fix: bool,
+        /// Move without asking for confirmation
+        #[arg(short, long, requires = "fix")]
+        yes: bool,
+    },
 }
 
 pub fn run(cmd: StoreCommand) -> Result<()> {
     match cmd {
         StoreCommand::Branch { cmd } => branch::run(cmd),
         StoreCommand::Blob { cmd } => blob::run(cmd),
+        StoreCommand::Fsck { url, fix, yes } => fsck::run(url, fix, yes),
     }
 }
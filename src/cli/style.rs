@@ -0,0 +1,72 @@
+//! ANSI colors for human-oriented output.
+//!
+//! Styling is only applied in plain output mode when the target stream is a
+//! terminal, and never when `--no-color` is passed or `NO_COLOR` is set, so
+//! pipes, JSON and porcelain output stay free of escape codes.
+
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+use crate::cli::output::{self, OutputFormat};
+
+static ENABLED: OnceLock<(bool, bool)> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Yellow => "33",
+        }
+    }
+}
+
+/// Decide once at startup whether stdout and stderr get colors.
+pub fn init(no_color: bool) {
+    let no_color = no_color
+        || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+        || output::format() != OutputFormat::Plain;
+    let _ = ENABLED.set((
+        !no_color && std::io::stdout().is_terminal(),
+        !no_color && std::io::stderr().is_terminal(),
+    ));
+}
+
+fn enabled() -> (bool, bool) {
+    *ENABLED.get_or_init(|| (false, false))
+}
+
+fn apply(on: bool, color: Color, text: impl Display) -> String {
+    if on {
+        format!("\x1b[{}m{text}\x1b[0m", color.code())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Color `text` for printing to stdout.
+pub fn paint(color: Color, text: impl Display) -> String {
+    apply(enabled().0, color, text)
+}
+
+/// Color `text` for printing to stderr.
+pub fn epaint(color: Color, text: impl Display) -> String {
+    apply(enabled().1, color, text)
+}
+
+/// `[present]` in green or `[missing]` in red, for stdout.
+pub fn presence(present: bool) -> String {
+    if present {
+        paint(Color::Green, "[present]")
+    } else {
+        paint(Color::Red, "[missing]")
+    }
+}
@@ -0,0 +1,566 @@
+//! `trible sync-daemon`: keep a pile and a remote object store in step.
+//!
+//! Every cycle compares the head commit of each branch on both sides. A
+//! side that lacks the branch, or whose head is an ancestor of the other
+//! side's, is fast-forwarded: the missing blobs are copied over and its head
+//! is compare-and-swapped to the other side's metadata. Branches that
+//! diverged are logged and left alone for `pile merge`. Outcomes are logged
+//! at info level, conflicts and failed cycles as warnings.
+//!
+//! The optional state file remembers the head each branch was last synced
+//! at, so the first cycle after a restart only walks newer history.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use triblespace::prelude::blobschemas::SimpleArchive;
+use triblespace::prelude::BlobStore;
+use triblespace::prelude::BlobStoreGet;
+use triblespace::prelude::BranchStore;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::id::Id;
+use triblespace_core::repo;
+use triblespace_core::repo::objectstore::ObjectStoreRemote;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::BlobStoreMeta;
+use triblespace_core::repo::PushResult;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::Value;
+
+use crate::cli::error::{self, ErrorKind};
+use crate::cli::pile::branch::{extract_repo_head, is_ancestor_of, parse_blake3_handle};
+use crate::cli::pile::resolve::{all_branches, resolve_branch, BranchRef};
+use crate::cli::pile::ClosingPile;
+use crate::cli::remote_state;
+use crate::cli::util::{skip_present, write_atomically};
+use tracing::{debug, info, warn};
+
+type BlobHandle = Value<Handle<Blake3, UnknownBlob>>;
+type MetaHandle = Value<Handle<Blake3, SimpleArchive>>;
+type CommitHandle = Value<Handle<Blake3, SimpleArchive>>;
+
+/// Longest wait between cycles while they keep failing.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// What one cycle did to one branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    UpToDate,
+    /// The local branch was created or moved to the remote head.
+    FastForwardedLocal,
+    /// The remote branch was created or moved to the local head.
+    PushedLocal,
+    /// The heads diverged, or one side moved during the cycle.
+    Conflict,
+    /// Syncing the branch failed with this error; the other branches were
+    /// still synced.
+    Failed(String),
+}
+
+impl Outcome {
+    fn describe(&self) -> &'static str {
+        match self {
+            Outcome::UpToDate => "up to date",
+            Outcome::FastForwardedLocal => "fast-forwarded local",
+            Outcome::PushedLocal => "pushed local",
+            Outcome::Conflict => "conflict left untouched",
+            Outcome::Failed(_) => "sync failed",
+        }
+    }
+}
+
+/// The `--state-file` contents: the head each branch was last synced at.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct SyncState {
+    /// Hex branch id to `blake3:` commit handle.
+    heads: BTreeMap<String, String>,
+}
+
+impl SyncState {
+    fn load(path: &Path) -> Result<SyncState> {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                error::corrupt(format!("unreadable state file {}: {e}", path.display()))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SyncState::default()),
+            Err(e) => Err(e).with_context(|| format!("read state file {}", path.display())),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        write_atomically(path, |file| {
+            serde_json::to_writer_pretty(&mut *file, self)?;
+            writeln!(file)?;
+            Ok(())
+        })
+        .with_context(|| format!("write state file {}", path.display()))
+    }
+
+    fn head(&self, branch: Id) -> Option<CommitHandle> {
+        let raw = self.heads.get(&format!("{branch:X}"))?;
+        parse_blake3_handle(raw).ok()
+    }
+
+    fn set(&mut self, branch: Id, head: Option<CommitHandle>) {
+        let key = format!("{branch:X}");
+        match head {
+            Some(head) => self
+                .heads
+                .insert(key, format!("blake3:{}", hex::encode(head.raw))),
+            None => self.heads.remove(&key),
+        };
+    }
+}
+
+/// Sync `pile` with the remote at `url` every `interval` seconds until
+/// SIGINT or SIGTERM.
+pub fn run(
+    url: String,
+    pile: PathBuf,
+    interval: u64,
+    branches: Vec<String>,
+    state_file: Option<PathBuf>,
+) -> Result<()> {
+    if interval == 0 {
+        return Err(error::invalid_argument("--interval must be at least 1"));
+    }
+    let url = crate::cli::config::remote_url(&url)?;
+    let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url)?;
+    let mut state = match &state_file {
+        Some(path) => SyncState::load(path)?,
+        None => SyncState::default(),
+    };
+
+    let (stop, stopped) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = stop.send(());
+    })
+    .map_err(|e| anyhow::anyhow!("cannot install the signal handler: {e}"))?;
+
+    let interval = Duration::from_secs(interval);
+    ClosingPile::open(&pile)?.run(|pile| {
+        info!(%url, "sync daemon started");
+        let mut failures = 0u32;
+        loop {
+            let wait = match sync_cycle(&mut remote, pile, &branches, &mut state) {
+                Ok(outcomes) => {
+                    failures = 0;
+                    for (label, outcome) in outcomes {
+                        match &outcome {
+                            Outcome::Conflict => warn!(branch = %label, "{}", outcome.describe()),
+                            Outcome::Failed(e) => {
+                                warn!(branch = %label, "{}: {e}", outcome.describe())
+                            }
+                            _ => info!(branch = %label, "{}", outcome.describe()),
+                        }
+                    }
+                    // The state stays current in memory, so the next cycle
+                    // writes it again.
+                    if let Some(path) = &state_file {
+                        if let Err(e) = state.save(path) {
+                            warn!("{e:#}; retrying after the next cycle");
+                        }
+                    }
+                    interval
+                }
+                Err(e) => {
+                    failures += 1;
+                    let wait = interval
+                        .saturating_mul(1 << failures.min(16))
+                        .min(MAX_BACKOFF.max(interval));
+                    warn!("sync cycle failed, retrying in {}s: {e:#}", wait.as_secs());
+                    wait
+                }
+            };
+            match stopped.recv_timeout(wait) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+                    info!("sync daemon stopped");
+                    return Ok(());
+                }
+            }
+        }
+    })
+}
+
+/// One pass over the selected branches, or over every branch on either side
+/// when `branches` is empty.
+pub(crate) fn sync_cycle(
+    remote: &mut ObjectStoreRemote<Blake3>,
+    pile: &mut Pile<Blake3>,
+    branches: &[String],
+    state: &mut SyncState,
+) -> Result<Vec<(String, Outcome)>> {
+    pile.refresh()?;
+    let local_reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let remote_reader = remote
+        .reader()
+        .map_err(|e| anyhow::anyhow!("remote reader error: {e:?}"))?;
+
+    let mut targets: Vec<(Id, String)> = Vec::new();
+    if branches.is_empty() {
+        let local = all_branches(pile, &local_reader)?;
+        let remote = all_branches(remote, &remote_reader)?;
+        for info in local.into_iter().chain(remote) {
+            if !targets.iter().any(|(id, _)| *id == info.id) {
+                let label = info.name.unwrap_or_else(|| format!("{:X}", info.id));
+                targets.push((info.id, label));
+            }
+        }
+        targets.sort_by_key(|(id, _)| *id);
+    } else {
+        for raw in branches {
+            // A branch only the remote has yet is looked up there.
+            let info = match resolve_branch(pile, &local_reader, BranchRef::Any(raw)) {
+                Err(e) if error::kind_of(&e) == Some(ErrorKind::NotFound) => {
+                    resolve_branch(remote, &remote_reader, BranchRef::Any(raw))?
+                }
+                res => res?,
+            };
+            if !targets.iter().any(|(id, _)| *id == info.id) {
+                targets.push((info.id, raw.clone()));
+            }
+        }
+    }
+
+    // One failing branch must not hold back the others.
+    let mut outcomes = Vec::new();
+    for (id, label) in targets {
+        let outcome = sync_branch(remote, pile, id, state)
+            .unwrap_or_else(|e| Outcome::Failed(format!("{e:#}")));
+        debug!(branch = %label, ?outcome, "synced branch");
+        outcomes.push((label, outcome));
+    }
+    Ok(outcomes)
+}
+
+fn sync_branch(
+    remote: &mut ObjectStoreRemote<Blake3>,
+    pile: &mut Pile<Blake3>,
+    id: Id,
+    state: &mut SyncState,
+) -> Result<Outcome> {
+    let local_meta = pile.head(id)?;
+    let remote_meta = remote.head(id)?;
+    let local_reader = pile
+        .reader()
+        .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+    let remote_reader = remote
+        .reader()
+        .map_err(|e| anyhow::anyhow!("remote reader error: {e:?}"))?;
+    // A metadata blob that cannot be read fails the branch; treating it as
+    // headless would overwrite the other side.
+    let local_head = match local_meta {
+        Some(meta) => commit_head(&local_reader, meta).context("read local branch metadata")?,
+        None => None,
+    };
+    let remote_head = match remote_meta {
+        Some(meta) => commit_head(&remote_reader, meta).context("read remote branch metadata")?,
+        None => None,
+    };
+    let parent_attr = repo::parent.id();
+    let synced = state.head(id);
+
+    let (push, pull) = match (local_meta, remote_meta) {
+        (None, None) => return Ok(Outcome::UpToDate),
+        (Some(meta), None) => (Some(meta), None),
+        (None, Some(meta)) => (None, Some(meta)),
+        (Some(local), Some(remote)) => match (local_head, remote_head) {
+            (l, r) if l == r => (None, None),
+            (_, None) => (Some(local), None),
+            (None, _) => (None, Some(remote)),
+            (Some(l), Some(r)) => {
+                if is_ancestor_of(r, l, &local_reader, &parent_attr)? {
+                    (Some(local), None)
+                } else if is_ancestor_of(l, r, &remote_reader, &parent_attr)? {
+                    (None, Some(remote))
+                } else {
+                    return Ok(Outcome::Conflict);
+                }
+            }
+        },
+    };
+
+    let outcome = if let Some(meta) = push {
+        let handles = blobs_since(&local_reader, &remote_reader, meta, local_head, synced)?;
+        let skipped = Cell::new(0usize);
+        let handles = skip_present(handles.into_iter(), &remote_reader, &skipped);
+        for r in repo::transfer(&local_reader, &mut *remote, handles) {
+            r?;
+        }
+        match remote.update(id, remote_meta, Some(meta))? {
+            PushResult::Success() => Outcome::PushedLocal,
+            PushResult::Conflict(_) => return Ok(Outcome::Conflict),
+        }
+    } else if let Some(meta) = pull {
+        let handles = blobs_since(&remote_reader, &local_reader, meta, remote_head, synced)?;
+        let skipped = Cell::new(0usize);
+        let handles = skip_present(handles.into_iter(), &local_reader, &skipped);
+        for r in repo::transfer(&remote_reader, &mut *pile, handles) {
+            r?;
+        }
+        match pile.update(id, local_meta, Some(meta))? {
+            PushResult::Success() => Outcome::FastForwardedLocal,
+            PushResult::Conflict(_) => return Ok(Outcome::Conflict),
+        }
+    } else {
+        Outcome::UpToDate
+    };
+    let head = match &outcome {
+        Outcome::FastForwardedLocal => remote_head,
+        _ => local_head,
+    };
+    state.set(id, head);
+    Ok(outcome)
+}
+
+/// The commit `meta` points at, or `None` when the metadata names no head.
+fn commit_head(
+    reader: &impl BlobStoreGet<Blake3>,
+    meta: MetaHandle,
+) -> Result<Option<CommitHandle>> {
+    let meta = reader
+        .get::<TribleSet, SimpleArchive>(meta)
+        .map_err(|e| anyhow::anyhow!("metadata blob error: {e:?}"))?;
+    Ok(extract_repo_head(&meta))
+}
+
+/// Blobs reachable from `meta`, leaving out the history below `synced` when
+/// it is still an ancestor of `head` and `dest` still holds it. A wiped or
+/// restored destination may have lost that history, so without `synced`
+/// there everything is sent.
+fn blobs_since<R, D>(
+    reader: &R,
+    dest: &D,
+    meta: MetaHandle,
+    head: Option<CommitHandle>,
+    synced: Option<CommitHandle>,
+) -> Result<Vec<BlobHandle>>
+where
+    R: BlobStoreGet<Blake3> + BlobStoreMeta<Blake3>,
+    D: BlobStoreMeta<Blake3>,
+{
+    let root: BlobHandle = meta.transmute();
+    let parent_attr = repo::parent.id();
+    let synced = match synced {
+        Some(synced) if matches!(dest.metadata(synced.transmute()), Ok(Some(_))) => Some(synced),
+        _ => None,
+    };
+    Ok(match (synced, head) {
+        (Some(synced), Some(head)) if is_ancestor_of(synced, head, reader, &parent_attr)? => {
+            remote_state::reachable_since(reader, root, synced)
+        }
+        _ => repo::reachable(reader, std::iter::once(root)).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+    use triblespace_core::repo::Repository;
+
+    fn commit(pile: Pile<Blake3>, branch: Option<Id>, text: &str) -> (Pile<Blake3>, Id) {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut repo = Repository::new(pile, key, TribleSet::new()).unwrap();
+        let branch = match branch {
+            Some(branch) => branch,
+            None => *repo.create_branch("main", None).unwrap(),
+        };
+        let mut ws = repo.pull(branch).unwrap();
+        let mut content = TribleSet::new();
+        let handle = ws.put::<LongString, _>(text.to_string());
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+        ws.commit(content, text);
+        assert!(repo.try_push(&mut ws).unwrap().is_none());
+        (repo.into_storage(), branch)
+    }
+
+    fn remote(dir: &Path) -> ObjectStoreRemote<Blake3> {
+        let root = dir.join("remote");
+        std::fs::create_dir_all(&root).unwrap();
+        let url = url::Url::from_directory_path(&root).unwrap();
+        ObjectStoreRemote::with_url(&url).unwrap()
+    }
+
+    fn cycle(
+        remote: &mut ObjectStoreRemote<Blake3>,
+        pile: &mut Pile<Blake3>,
+        state: &mut SyncState,
+    ) -> Vec<Outcome> {
+        sync_cycle(remote, pile, &[], state)
+            .unwrap()
+            .into_iter()
+            .map(|(_, outcome)| outcome)
+            .collect()
+    }
+
+    #[test]
+    fn fast_forwards_in_both_directions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = remote(dir.path());
+        let (mut a, branch) = commit(Pile::open(&dir.path().join("a.pile")).unwrap(), None, "one");
+        let mut b: Pile<Blake3> = Pile::open(&dir.path().join("b.pile")).unwrap();
+        let (mut state_a, mut state_b) = (SyncState::default(), SyncState::default());
+
+        assert_eq!(
+            cycle(&mut remote, &mut a, &mut state_a),
+            [Outcome::PushedLocal]
+        );
+        assert_eq!(
+            cycle(&mut remote, &mut a, &mut state_a),
+            [Outcome::UpToDate]
+        );
+        assert_eq!(
+            cycle(&mut remote, &mut b, &mut state_b),
+            [Outcome::FastForwardedLocal]
+        );
+
+        let (mut a, _) = commit(a, Some(branch), "two");
+        assert_eq!(
+            cycle(&mut remote, &mut a, &mut state_a),
+            [Outcome::PushedLocal]
+        );
+        assert_eq!(
+            cycle(&mut remote, &mut b, &mut state_b),
+            [Outcome::FastForwardedLocal]
+        );
+        assert_eq!(a.head(branch).unwrap(), b.head(branch).unwrap());
+        assert_eq!(state_a, state_b);
+        a.close().unwrap();
+        b.close().unwrap();
+    }
+
+    #[test]
+    fn diverged_branches_are_left_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = remote(dir.path());
+        let (mut a, branch) = commit(Pile::open(&dir.path().join("a.pile")).unwrap(), None, "one");
+        let mut b: Pile<Blake3> = Pile::open(&dir.path().join("b.pile")).unwrap();
+        let (mut state_a, mut state_b) = (SyncState::default(), SyncState::default());
+        cycle(&mut remote, &mut a, &mut state_a);
+        cycle(&mut remote, &mut b, &mut state_b);
+
+        let (mut a, _) = commit(a, Some(branch), "from a");
+        let (mut b, _) = commit(b, Some(branch), "from b");
+        assert_eq!(
+            cycle(&mut remote, &mut a, &mut state_a),
+            [Outcome::PushedLocal]
+        );
+        let before = b.head(branch).unwrap();
+        assert_eq!(
+            cycle(&mut remote, &mut b, &mut state_b),
+            [Outcome::Conflict]
+        );
+        assert_eq!(b.head(branch).unwrap(), before);
+        assert_eq!(remote.head(branch).unwrap(), a.head(branch).unwrap());
+        a.close().unwrap();
+        b.close().unwrap();
+    }
+
+    #[test]
+    fn a_failing_branch_does_not_stop_the_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = remote(dir.path());
+        let (pile, main) = commit(Pile::open(&dir.path().join("a.pile")).unwrap(), None, "one");
+        let mut repo =
+            Repository::new(pile, SigningKey::from_bytes(&[7; 32]), TribleSet::new()).unwrap();
+        let broken = *repo.create_branch("broken", None).unwrap();
+        let mut a = repo.into_storage();
+        // A remote branch entry that cannot be read.
+        let branches = dir.path().join("remote").join("branches");
+        std::fs::create_dir_all(&branches).unwrap();
+        std::fs::write(branches.join(format!("{broken:x}")), b"not a handle").unwrap();
+
+        let mut state = SyncState::default();
+        let outcomes = sync_cycle(
+            &mut remote,
+            &mut a,
+            &[format!("{broken:X}"), "main".to_string()],
+            &mut state,
+        )
+        .unwrap();
+        assert!(matches!(outcomes[0].1, Outcome::Failed(_)), "{outcomes:?}");
+        assert_eq!(outcomes[1], ("main".to_string(), Outcome::PushedLocal));
+        assert_eq!(remote.head(main).unwrap(), a.head(main).unwrap());
+        assert_eq!(state.head(broken), None);
+        a.close().unwrap();
+    }
+
+    #[test]
+    fn unreadable_remote_metadata_fails_the_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = remote(dir.path());
+        let (mut a, branch) = commit(Pile::open(&dir.path().join("a.pile")).unwrap(), None, "one");
+        let mut state = SyncState::default();
+        cycle(&mut remote, &mut a, &mut state);
+
+        let meta = remote.head(branch).unwrap().unwrap();
+        let blob = dir
+            .path()
+            .join("remote")
+            .join("blobs")
+            .join(hex::encode(meta.raw));
+        std::fs::write(&blob, b"not an archive").unwrap();
+        let (mut a, _) = commit(a, Some(branch), "two");
+        let outcomes = cycle(&mut remote, &mut a, &mut state);
+        assert!(matches!(outcomes[..], [Outcome::Failed(_)]), "{outcomes:?}");
+        assert_eq!(remote.head(branch).unwrap(), Some(meta));
+        a.close().unwrap();
+    }
+
+    #[test]
+    fn history_is_sent_again_to_a_wiped_remote() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote_store = remote(dir.path());
+        let (mut a, branch) = commit(Pile::open(&dir.path().join("a.pile")).unwrap(), None, "one");
+        let mut state_a = SyncState::default();
+        cycle(&mut remote_store, &mut a, &mut state_a);
+
+        // The state file still remembers the push, but the remote lost it.
+        std::fs::remove_dir_all(dir.path().join("remote")).unwrap();
+        let mut remote_store = remote(dir.path());
+        let (mut a, _) = commit(a, Some(branch), "two");
+        assert_eq!(
+            cycle(&mut remote_store, &mut a, &mut state_a),
+            [Outcome::PushedLocal]
+        );
+
+        let mut b: Pile<Blake3> = Pile::open(&dir.path().join("b.pile")).unwrap();
+        assert_eq!(
+            cycle(&mut remote_store, &mut b, &mut SyncState::default()),
+            [Outcome::FastForwardedLocal]
+        );
+        let first: BlobHandle = Value::new(*blake3::hash(b"one").as_bytes());
+        let reader = b.reader().unwrap();
+        assert!(reader.metadata(first).unwrap().is_some());
+        a.close().unwrap();
+        b.close().unwrap();
+    }
+
+    #[test]
+    fn state_round_trips_through_its_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        assert_eq!(SyncState::load(&path).unwrap(), SyncState::default());
+
+        let mut state = SyncState::default();
+        let branch = *ufoid();
+        state.set(branch, Some(Value::new([3; 32])));
+        state.save(&path).unwrap();
+        let loaded = SyncState::load(&path).unwrap();
+        assert_eq!(loaded.head(branch), Some(Value::new([3; 32])));
+        assert_eq!(loaded, state);
+    }
+}
@@ -0,0 +1,90 @@
+//! Bandwidth limiting for remote transfers (`--limit-rate`).
+//!
+//! A single [`RateLimiter`] is a token bucket that may be shared between
+//! threads, so concurrent transfers draw from one aggregate budget. Callers
+//! acquire the size of each blob or chunk before moving it; the bucket is allowed to go
+//! into debt and the caller sleeps until the debt is repaid, which keeps the
+//! long-run rate close to the limit even for blobs larger than one second's
+//! worth of budget.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1) as f64;
+        RateLimiter {
+            bytes_per_sec,
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_sec,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    /// Block until `bytes` may be transferred without exceeding the limit.
+    pub fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().expect("rate limiter poisoned");
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.last).as_secs_f64() * self.bytes_per_sec;
+            bucket.tokens = (bucket.tokens + refill).min(self.bytes_per_sec);
+            bucket.last = now;
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / self.bytes_per_sec)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+/// Parse a rate such as `500k`, `5M` or `1048576` into bytes per second.
+/// Suffixes are binary (`k` = 1024) and case-insensitive.
+pub fn parse_rate(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, mult) = match s.chars().last() {
+        Some('k' | 'K') => (&s[..s.len() - 1], 1u64 << 10),
+        Some('m' | 'M') => (&s[..s.len() - 1], 1u64 << 20),
+        Some('g' | 'G') => (&s[..s.len() - 1], 1u64 << 30),
+        _ => (s, 1),
+    };
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid rate {s:?} (expected e.g. 500k, 5M)"))?;
+    let rate = value
+        .checked_mul(mult)
+        .ok_or_else(|| format!("rate {s:?} is too large"))?;
+    if rate == 0 {
+        return Err("rate must be greater than zero".to_string());
+    }
+    Ok(rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rate_accepts_suffixes() {
+        assert_eq!(parse_rate("1024"), Ok(1024));
+        assert_eq!(parse_rate("500k"), Ok(500 * 1024));
+        assert_eq!(parse_rate("5M"), Ok(5 * 1024 * 1024));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+}
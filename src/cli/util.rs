@@ -1,8 +1,248 @@
 use anyhow::Result;
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
 use triblespace::prelude::TryToValue;
+use triblespace_core::blob::schemas::UnknownBlob;
+use triblespace_core::repo::BlobStoreMeta;
 use triblespace_core::value::schemas::hash::Blake3;
-use triblespace_core::value::schemas::hash::Hash;
+use triblespace_core::value::schemas::hash::{Handle, Hash};
+use triblespace_core::value::Value;
 
 pub fn parse_blob_handle(handle: &str) -> Result<triblespace_core::value::Value<Hash<Blake3>>> {
     handle.try_to_value().map_err(|e| anyhow::anyhow!("{e:?}"))
 }
+
+/// Ask before a destructive operation, e.g. `confirm("This will delete 1 branch", yes)`.
+///
+/// Proceeds without asking when `yes` is set or `TRIBLES_ASSUME_YES` is set to
+/// a non-empty value other than `0`. Otherwise prompts on stderr when stdin is
+/// a terminal and refuses outright when it is not, so scripts never hang on a
+/// prompt nobody will answer.
+pub fn confirm(action: &str, yes: bool) -> Result<()> {
+    let assume_yes = std::env::var("TRIBLES_ASSUME_YES").is_ok_and(|v| !v.is_empty() && v != "0");
+    if yes || assume_yes {
+        return Ok(());
+    }
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Err(crate::cli::error::invalid_argument(format!(
+            "{action}; refusing to continue without a terminal (pass --yes to confirm)"
+        )));
+    }
+    eprint!("{action}, continue? [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    stdin.lock().read_line(&mut answer)?;
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => anyhow::bail!("aborted"),
+    }
+}
+
+/// Narrow the handles passed to `repo::transfer` to the blobs `dst` does not
+/// hold yet, counting the others in `skipped`.
+///
+/// Piles are append-only, so copying a blob the destination already has only
+/// adds a duplicate record; on remotes it is a wasted upload. A failed lookup
+/// counts as absent and the blob is copied.
+pub fn skip_present<'a>(
+    handles: impl Iterator<Item = Value<Handle<Blake3, UnknownBlob>>> + 'a,
+    dst: &'a impl BlobStoreMeta<Blake3>,
+    skipped: &'a Cell<usize>,
+) -> impl Iterator<Item = Value<Handle<Blake3, UnknownBlob>>> + 'a {
+    handles.filter(move |&handle| {
+        let present = matches!(dst.metadata(handle), Ok(Some(_)));
+        if present {
+            skipped.set(skipped.get() + 1);
+        }
+        !present
+    })
+}
+
+/// Size of the slices blob extraction writes at a time, so the resident part
+/// of a multi-gigabyte blob stays bounded.
+pub const COPY_CHUNK: usize = 1 << 20;
+
+/// Create `output` through a temporary file in the same directory that is
+/// renamed into place only once `write` succeeded, so an interrupted or failed
+/// extraction never leaves a partial file under the requested name. The file
+/// ends up with the permissions `File::create` would give it: those of the
+/// file it replaces, or the umask default.
+pub fn write_atomically(output: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let dir = match output.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".trible-partial-");
+    // Temporary files are created private (0600); the umask still applies.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o666));
+    }
+    let mut partial = builder.tempfile_in(dir)?;
+    if let Ok(existing) = std::fs::metadata(output) {
+        partial.as_file().set_permissions(existing.permissions())?;
+    }
+    write(partial.as_file_mut())?;
+    partial.as_file_mut().flush()?;
+    partial.persist(output).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Write `handles` to `output`, one per line, for `--handle-out`.
+pub fn write_handles(output: &Path, handles: &[String]) -> Result<()> {
+    write_atomically(output, |file| {
+        for handle in handles {
+            writeln!(file, "{handle}")?;
+        }
+        Ok(())
+    })
+}
+
+/// Write `chunks` to `out` one at a time, calling `each` with the length of
+/// every chunk before it is written (for rate limiting and hashing).
+/// Returns the number of bytes written.
+pub fn copy_chunks<C: AsRef<[u8]>>(
+    chunks: impl IntoIterator<Item = Result<C>>,
+    out: &mut impl Write,
+    mut each: impl FnMut(&[u8]),
+) -> Result<u64> {
+    let mut written = 0u64;
+    for chunk in chunks {
+        let chunk = chunk?;
+        let chunk = chunk.as_ref();
+        each(chunk);
+        out.write_all(chunk)?;
+        written += chunk.len() as u64;
+    }
+    Ok(written)
+}
+
+/// Parse a `--since`/`--until` bound into Unix milliseconds: an RFC 3339
+/// timestamp, a `YYYY-MM-DD` date (midnight UTC) or plain milliseconds.
+pub fn parse_time(raw: &str) -> Result<u64> {
+    use chrono::{DateTime, NaiveDate, Utc};
+
+    let raw = raw.trim();
+    if let Ok(millis) = raw.parse::<u64>() {
+        return Ok(millis);
+    }
+    let time: DateTime<Utc> = if let Ok(time) = DateTime::parse_from_rfc3339(raw) {
+        time.with_timezone(&Utc)
+    } else if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        date.and_hms_opt(0, 0, 0)
+            .expect("midnight exists")
+            .and_utc()
+    } else {
+        return Err(crate::cli::error::invalid_argument(format!(
+            "invalid time {raw:?}: expected RFC 3339, YYYY-MM-DD or Unix milliseconds"
+        )));
+    };
+    u64::try_from(time.timestamp_millis())
+        .map_err(|_| crate::cli::error::invalid_argument(format!("time {raw:?} is before 1970")))
+}
+
+/// Unix milliseconds as an RFC 3339 timestamp in UTC.
+pub fn format_millis(millis: u64) -> String {
+    use chrono::{DateTime, Utc};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    DateTime::<Utc>::from(UNIX_EPOCH + Duration::from_millis(millis)).to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn failed_copy_leaves_no_partial_output() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("blob.bin");
+        let chunks = vec![
+            Ok(vec![1u8; 16]),
+            Ok(vec![2u8; 16]),
+            Err(anyhow::anyhow!("connection reset")),
+            Ok(vec![3u8; 16]),
+        ];
+
+        let err = write_atomically(&output, |file| {
+            copy_chunks(chunks, file, |_| {}).map(|_| ())
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "connection reset");
+        assert!(!output.exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        std::fs::write(&output, b"previous").unwrap();
+        let failed = write_atomically(&output, |file| {
+            file.write_all(b"half")?;
+            anyhow::bail!("interrupted")
+        });
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read(&output).unwrap(), b"previous");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn chunked_copy_renames_complete_output() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("blob.bin");
+        let data: Vec<u8> = (0..=255u8).cycle().take(COPY_CHUNK * 2 + 7).collect();
+        let mut seen = Vec::new();
+
+        write_atomically(&output, |file| {
+            let written = copy_chunks(data.chunks(COPY_CHUNK).map(Ok), file, |c| {
+                seen.push(c.len())
+            })?;
+            assert_eq!(written, data.len() as u64);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(seen, [COPY_CHUNK, COPY_CHUNK, 7]);
+        assert_eq!(std::fs::read(&output).unwrap(), data);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_gets_the_permissions_of_a_plain_create() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        let plain = dir.path().join("plain.bin");
+        File::create(&plain).unwrap();
+        let output = dir.path().join("blob.bin");
+        write_atomically(&output, |file| Ok(file.write_all(b"new")?)).unwrap();
+        assert_eq!(mode(&output), mode(&plain));
+
+        std::fs::set_permissions(&output, std::fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomically(&output, |file| Ok(file.write_all(b"again")?)).unwrap();
+        assert_eq!(mode(&output), 0o640);
+    }
+
+    #[test]
+    fn times_parse_from_rfc3339_dates_and_millis() {
+        assert_eq!(parse_time("1700000000000").unwrap(), 1_700_000_000_000);
+        assert_eq!(parse_time("1970-01-02").unwrap(), 86_400_000);
+        assert_eq!(
+            parse_time("2023-11-14T22:13:20+00:00").unwrap(),
+            1_700_000_000_000
+        );
+        assert_eq!(
+            parse_time("2023-11-14T23:13:20.5+01:00").unwrap(),
+            1_700_000_000_500
+        );
+        assert!(parse_time("yesterday").is_err());
+        assert!(parse_time("1969-12-31").is_err());
+        assert_eq!(
+            format_millis(1_700_000_000_000),
+            "2023-11-14T22:13:20+00:00"
+        );
+    }
+}
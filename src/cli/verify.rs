@@ -0,0 +1,99 @@
+//! Read-back verification behind the global `--verify-writes` flag (or the
+//! `verify_writes` config key).
+//!
+//! Commands that store blobs in a pile go through [`put`]. With verification
+//! on, every stored blob is read back through a fresh reader and rehashed
+//! before `put` returns, so a bad write fails the command before any branch
+//! update can point at it. With verification off `put` is a plain
+//! `Pile::put`. Blobs committed through a workspace are written by
+//! `Repository::push` and are not covered.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use triblespace::prelude::blobschemas::UnknownBlob;
+use triblespace::prelude::{BlobStore, BlobStoreGet, BlobStorePut};
+use triblespace_core::blob::{Blob, BlobSchema, ToBlob};
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::value::schemas::hash::{Blake3, Handle};
+use triblespace_core::value::{Value, ValueSchema};
+
+use crate::cli::config;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn verification on if `--verify-writes` was given or the config asks
+/// for it.
+pub fn init(flag: bool) {
+    let enabled = config::layered(flag.then_some(true), None, config::get().verify_writes);
+    ENABLED.store(enabled.unwrap_or(false), Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Store `item` in `pile`, reading it back first when verification is on.
+pub(crate) fn put<S, T>(pile: &mut Pile<Blake3>, item: T) -> Result<Value<Handle<Blake3, S>>>
+where
+    S: BlobSchema + 'static,
+    T: ToBlob<S>,
+    Handle<Blake3, S>: ValueSchema,
+{
+    let handle = pile.put::<S, T>(item)?;
+    if enabled() {
+        let raw: Value<Handle<Blake3, UnknownBlob>> = handle.transmute();
+        check(raw.raw, || {
+            let reader = pile
+                .reader()
+                .map_err(|e| anyhow::anyhow!("pile reader error: {e:?}"))?;
+            let blob: Blob<UnknownBlob> = reader.get(raw)?;
+            Ok(blob)
+        })?;
+    }
+    Ok(handle)
+}
+
+/// Fail unless `read` returns a blob whose hash is `hash`.
+fn check(hash: [u8; 32], read: impl FnOnce() -> Result<Blob<UnknownBlob>>) -> Result<()> {
+    let blob = read().map_err(|e| {
+        crate::cli::error::corrupt(format!(
+            "write verification failed: could not read back blake3:{}: {e}",
+            hex::encode(hash)
+        ))
+    })?;
+    let actual = blake3::hash(&blob.bytes);
+    if actual.as_bytes() != &hash {
+        return Err(crate::cli::error::corrupt(format!(
+            "write verification failed: blake3:{} read back as blake3:{}",
+            hex::encode(hash),
+            actual.to_hex()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anybytes::Bytes;
+
+    fn blob(data: &'static [u8]) -> Blob<UnknownBlob> {
+        Blob::new(Bytes::from_source(data))
+    }
+
+    #[test]
+    fn matching_read_back_passes() {
+        let hash = *blake3::hash(b"hello").as_bytes();
+        assert!(check(hash, || Ok(blob(b"hello"))).is_ok());
+    }
+
+    #[test]
+    fn failed_or_altered_read_back_is_an_error() {
+        let hash = *blake3::hash(b"hello").as_bytes();
+        let err = check(hash, || anyhow::bail!("injected read failure")).unwrap_err();
+        assert!(err.to_string().contains("injected read failure"), "{err}");
+        let err = check(hash, || Ok(blob(b"hellp"))).unwrap_err();
+        assert!(err.to_string().contains("read back as blake3:"), "{err}");
+    }
+}
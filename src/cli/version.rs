@@ -0,0 +1,91 @@
+//! `trible version`: build and compatibility information for bug reports.
+
+use anyhow::Result;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use triblespace_core::id::id_hex;
+
+use crate::cli::output::{Field, Output, OutputFormat};
+
+/// Pile formats this binary can read.
+///
+/// Piles carry no file header; the format is identified by the magic marker
+/// that starts every 64-byte aligned record.
+const PILE_FORMATS: &[u32] = &[1];
+
+enum PileFormat {
+    /// No records yet; readable by every version.
+    Empty,
+    Known(u32),
+    Unknown,
+}
+
+fn pile_format(path: &Path) -> Result<PileFormat> {
+    let mut magic = [0u8; 16];
+    let mut file = File::open(path)
+        .map_err(|e| crate::cli::error::not_found(format!("{}: {e}", path.display())))?;
+    let read = file.read(&mut magic)?;
+    if read == 0 {
+        return Ok(PileFormat::Empty);
+    }
+    // Record markers from `triblespace_core::repo::pile` (blob, branch,
+    // branch tombstone); see also `pile diagnose locate-hash`.
+    let known = [
+        id_hex!("1E08B022FF2F47B6EBACF1D68EB35D96").raw(),
+        id_hex!("2BC991A7F5D5D2A3A468C53B0AA03504").raw(),
+        id_hex!("E888CC787202D2AE4C654BFE9699C430").raw(),
+    ];
+    Ok(if read == magic.len() && known.contains(&magic) {
+        PileFormat::Known(1)
+    } else {
+        PileFormat::Unknown
+    })
+}
+
+pub fn run(verbose: bool, pile: Option<PathBuf>) -> Result<()> {
+    let mut fields: Vec<(&str, Field)> = vec![("trible", Field::from(env!("CARGO_PKG_VERSION")))];
+    if verbose {
+        let features = env!("TRIBLE_BUILD_FEATURES");
+        let formats: Vec<String> = PILE_FORMATS.iter().map(u32::to_string).collect();
+        fields.extend([
+            ("triblespace", Field::from(env!("TRIBLE_BUILD_TRIBLESPACE"))),
+            (
+                "triblespace-core",
+                Field::from(env!("TRIBLE_BUILD_TRIBLESPACE_CORE")),
+            ),
+            ("pile_formats", Field::from(formats.join(", "))),
+            (
+                "features",
+                if features.is_empty() {
+                    Field::missing("none")
+                } else {
+                    Field::from(features)
+                },
+            ),
+            ("target", Field::from(env!("TRIBLE_BUILD_TARGET"))),
+        ]);
+    }
+    if let Some(path) = pile {
+        let (format, readable) = match pile_format(&path)? {
+            PileFormat::Empty => (Field::missing("empty"), true),
+            PileFormat::Known(v) => (Field::from(v.to_string()), PILE_FORMATS.contains(&v)),
+            PileFormat::Unknown => (Field::missing("unknown"), false),
+        };
+        fields.extend([
+            ("pile", Field::from(path.display().to_string())),
+            ("pile_format", format),
+            ("readable", Field::from(if readable { "yes" } else { "no" })),
+        ]);
+    }
+
+    let mut out = Output::stdout();
+    if out.format() != OutputFormat::Plain {
+        return out.record(&fields);
+    }
+    for (key, value) in &fields {
+        println!("{}: {}", key.replace('_', " "), value.plain());
+    }
+    Ok(())
+}
@@ -1,34 +1,203 @@
 use anyhow::Result;
 use clap::CommandFactory;
 use clap::Parser;
+use clap::Subcommand;
 use clap_complete::Shell;
 use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
 
 pub const DEFAULT_MAX_PILE_SIZE: usize = 1 << 44; // 16 TiB
 
 mod cli;
+use cli::attr::AttrCommand;
 use cli::branch::BranchCommand;
+use cli::complete::CompleteCommand;
+use cli::doctor::Severity;
+use cli::error::ErrorKind;
+use cli::id::{IdCommand, IdFormat};
+use cli::key::KeyCommand;
+use cli::output::OutputFormat;
+use cli::pile::signing::SigningArgs;
 use cli::pile::PileCommand;
 use cli::store::StoreCommand;
 
 #[derive(Parser)]
 /// A knowledge graph and meta file system for object stores.
 ///
+#[command(after_long_help = EXIT_CODES_HELP)]
+struct Cli {
+    /// Configuration file (defaults to ~/.config/trible/config.toml)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Output format (defaults to TRIBLES_OUTPUT, then the config, then plain)
+    #[arg(long = "output", global = true, value_enum, value_name = "FORMAT")]
+    output_format: Option<OutputFormat>,
+    /// Log internal steps to stderr (-v for debug, -vv for trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Only print primary results and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Stable tab-separated output for scripts (same as --output porcelain)
+    #[arg(long, global = true, conflicts_with = "output_format")]
+    porcelain: bool,
+    /// Never color output (also honors NO_COLOR; pipes are never colored)
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Print a timing and byte-count breakdown to stderr when done
+    #[arg(long, global = true)]
+    stats: bool,
+    /// Read every blob written to a pile back and check its hash before
+    /// moving on (also the `verify_writes` config key)
+    #[arg(long, global = true)]
+    verify_writes: bool,
+    #[command(subcommand)]
+    cmd: TribleCli,
+}
+
+#[derive(Subcommand)]
 enum TribleCli {
-    /// Generate a new random identifier.
-    Genid,
+    /// Generate new random identifiers.
+    #[command(visible_alias = "id-gen")]
+    Genid {
+        /// Number of ids to generate, one per line
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        count: u64,
+        /// Output encoding
+        #[arg(long, value_enum, default_value_t = IdFormat::Hex)]
+        format: IdFormat,
+    },
+    /// Convert identifiers between encodings.
+    Id {
+        #[command(subcommand)]
+        cmd: IdCommand,
+    },
+    /// Manage signing keys.
+    Key {
+        #[command(subcommand)]
+        cmd: KeyCommand,
+    },
+    /// Name attribute ids for readable output.
+    Attr {
+        #[command(subcommand)]
+        cmd: AttrCommand,
+    },
     /// Generate shell completion scripts.
     Completion {
         #[arg(value_enum)]
         shell: Shell,
     },
+    /// Fetch remote branches into `<remote>/<name>` tracking branches.
+    ///
+    /// Local branches are left untouched; combine with `pile merge` to
+    /// integrate the fetched heads.
+    Fetch {
+        /// URL of the source object store, or a remote name from the config
+        url: String,
+        /// Path to the destination pile file
+        pile: PathBuf,
+        /// Only fetch this remote branch (name or hex id)
+        #[arg(long)]
+        branch: Option<String>,
+        /// Alias used as the tracking branch prefix
+        #[arg(long, default_value = "origin")]
+        remote: String,
+        /// Delete local tracking branches whose remote branch no longer exists
+        #[arg(long)]
+        prune: bool,
+        /// Report what would be fetched or pruned without modifying the pile
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+    /// Snapshot a directory onto a branch and optionally push it.
+    ///
+    /// Runs `pile snapshot create` and, with `--remote`, `branch push` for
+    /// that branch, then prints one summary. The branch is created on the
+    /// first run; a branch whose head is not a snapshot is refused.
+    Backup {
+        /// Directory to back up
+        dir: PathBuf,
+        /// Pile to commit the snapshot to (defaults to TRIBLES_PILE, then
+        /// the config)
+        #[arg(long)]
+        pile: Option<PathBuf>,
+        /// Branch to commit onto (name)
+        #[arg(long)]
+        branch: String,
+        /// URL of an object store, or a remote name from the config, to push
+        /// the branch to
+        #[arg(long)]
+        remote: Option<String>,
+        /// Commit even if the branch head is not a snapshot commit
+        #[arg(long)]
+        force: bool,
+        /// Limit the upload rate in bytes per second (e.g. `500k`, `5M`)
+        #[arg(long, value_parser = cli::throttle::parse_rate, requires = "remote")]
+        limit_rate: Option<u64>,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+    /// Keep a pile and a remote store in sync until SIGINT or SIGTERM.
+    ///
+    /// Each cycle fast-forwards whichever side of a branch is behind and
+    /// leaves diverged branches untouched. Outcomes are logged to stderr;
+    /// failed cycles are retried with exponential backoff.
+    SyncDaemon {
+        /// URL of the remote object store, or a remote name from the config
+        url: String,
+        /// Path to the pile file to keep in sync
+        pile: PathBuf,
+        /// Seconds between sync cycles
+        #[arg(long, default_value_t = 60, value_name = "SECS")]
+        interval: u64,
+        /// Only sync this branch (name or hex id); repeat for several
+        #[arg(long = "branch", value_name = "NAME")]
+        branches: Vec<String>,
+        /// File recording the last synced heads, so a restart does not
+        /// re-walk full histories
+        #[arg(long, value_name = "PATH")]
+        state_file: Option<PathBuf>,
+    },
+    /// Check that a remote store holds a complete copy of a pile.
+    ///
+    /// Compares every branch head with the remote, then checks that each
+    /// blob reachable from the pile's branches exists remotely. Missing
+    /// blobs and head mismatches are listed and make the command fail.
+    VerifyReplica {
+        /// URL of the remote object store, or a remote name from the config
+        url: String,
+        /// Path to the pile file the remote should mirror
+        pile: PathBuf,
+        /// Only compare branch heads
+        #[arg(long, conflicts_with = "deep")]
+        branches_only: bool,
+        /// Also download a random sample of blobs and rehash them
+        #[arg(long)]
+        deep: bool,
+        /// Number of blobs `--deep` downloads
+        #[arg(long, default_value_t = 100, value_name = "N", requires = "deep")]
+        sample: usize,
+    },
     /// Synchronize branches between piles and remote stores.
+    #[command(visible_alias = "br")]
     Branch {
         #[command(subcommand)]
         cmd: BranchCommand,
     },
     /// Commands for working with local pile files.
     Pile {
+        /// Pile used when a subcommand's path argument is omitted
+        /// (falls back to the TRIBLES_PILE environment variable)
+        #[arg(long = "pile", global = true, value_name = "PATH")]
+        default_pile: Option<PathBuf>,
+        /// Skip re-indexing the pile before read-only commands; only safe
+        /// when no other process is appending to it
+        #[arg(long, global = true)]
+        no_refresh: bool,
         #[command(subcommand)]
         cmd: PileCommand,
     },
@@ -37,25 +206,214 @@ enum TribleCli {
         #[command(subcommand)]
         cmd: StoreCommand,
     },
+    /// Run blob commands read from stdin against a single open pile.
+    ///
+    /// Accepts `put PATH`, `get HANDLE PATH`, `exists HANDLE` and
+    /// `inspect HANDLE`, one per line, and prints one result line per command.
+    Batch {
+        /// Pile to operate on (defaults to TRIBLES_PILE, then the config)
+        pile: Option<PathBuf>,
+    },
+    /// Mount a pile's blobs as a read-only filesystem until Ctrl-C.
+    ///
+    /// Every blob appears as `/by-hash/HEX` and the content blob of each
+    /// branch head as `/branches/NAME/content`.
+    #[cfg(feature = "fuse")]
+    Mount {
+        /// Path to the pile file to mount
+        pile: PathBuf,
+        /// Empty directory to mount at
+        mountpoint: PathBuf,
+        /// Seconds between pile refreshes that pick up new blobs and commits
+        #[arg(long, default_value_t = 2)]
+        refresh_interval: u64,
+    },
+    /// Serve a pile read-only over HTTP in the object store layout.
+    ///
+    /// Other machines can use the printed `http://` URL wherever a remote is
+    /// expected, e.g. `trible branch pull http://HOST:PORT local.pile ID`.
+    Serve {
+        /// Pile to serve (defaults to TRIBLES_PILE, then the config)
+        pile: Option<PathBuf>,
+        /// Address to listen on; port 0 picks a free port
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+        /// Seconds between pile refreshes that pick up new commits
+        #[arg(long, default_value_t = 2)]
+        refresh_interval: u64,
+    },
+    /// Print the version; with -v also library versions, supported pile
+    /// formats, enabled features and the build target.
+    Version {
+        /// Also report this pile's format and whether this binary can read it
+        #[arg(long, value_name = "PATH")]
+        pile: Option<PathBuf>,
+    },
+    /// Check the signing key, pile, remotes and disk space for common problems.
+    Doctor {
+        /// Pile to check (defaults to TRIBLES_PILE, then the config)
+        pile: Option<PathBuf>,
+        #[command(flatten)]
+        signing: SigningArgs,
+    },
+    /// Print completion candidates (used by the completion scripts).
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[command(subcommand)]
+        cmd: CompleteCommand,
+    },
+}
+
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success
+  1  other failure
+  2  not found (pile, branch, blob)
+  3  conflict (concurrent update, non-fast-forward)
+  4  corruption detected
+  5  invalid arguments
+  6  warnings only (doctor)";
+
+/// Process exit codes; see `EXIT_CODES_HELP`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum Exit {
+    Success = 0,
+    Failure = 1,
+    NotFound = 2,
+    Conflict = 3,
+    Corrupt = 4,
+    InvalidArgs = 5,
+    Warnings = 6,
+}
+
+impl Exit {
+    /// The single place that maps error kinds onto exit codes.
+    fn for_error(err: &anyhow::Error) -> Exit {
+        match cli::error::kind_of(err) {
+            Some(ErrorKind::NotFound) => Exit::NotFound,
+            Some(ErrorKind::Conflict) => Exit::Conflict,
+            Some(ErrorKind::Corrupt) => Exit::Corrupt,
+            Some(ErrorKind::InvalidArgument) => Exit::InvalidArgs,
+            None => Exit::Failure,
+        }
+    }
 }
 
-fn main() -> Result<()> {
-    let args = TribleCli::parse();
-    match args {
-        TribleCli::Genid => {
-            let mut id = [0u8; 16];
-            getrandom::fill(&mut id)?;
-            let encoded_id = hex::encode(id);
-            println!("{}", encoded_id.to_ascii_uppercase());
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> ExitCode {
+        ExitCode::from(exit as u8)
+    }
+}
+
+fn main() -> ExitCode {
+    // clap would exit with 2 on usage errors, which collides with "not found".
+    let args = match Cli::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            let _ = err.print();
+            return if err.use_stderr() {
+                Exit::InvalidArgs
+            } else {
+                Exit::Success
+            }
+            .into();
+        }
+    };
+    let stats = args.stats;
+    let start = Instant::now();
+    let result = run(args);
+    if stats {
+        cli::stats::report(start.elapsed());
+    }
+    match result {
+        Ok(exit) => exit.into(),
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            Exit::for_error(&err).into()
         }
+    }
+}
+
+fn run(args: Cli) -> Result<Exit> {
+    cli::logging::init(args.verbose, args.quiet, args.stats)?;
+    cli::config::init(args.config)?;
+    cli::output::init(args.output_format, args.porcelain, args.quiet)?;
+    cli::style::init(args.no_color);
+    cli::verify::init(args.verify_writes);
+    match args.cmd {
+        TribleCli::Genid { count, format } => cli::id::genid(count, format)?,
+        TribleCli::Id { cmd } => cli::id::run(cmd)?,
+        TribleCli::Key { cmd } => cli::key::run(cmd)?,
+        TribleCli::Attr { cmd } => cli::attr::run(cmd)?,
         TribleCli::Completion { shell } => {
-            let mut cmd = TribleCli::command();
+            let mut cmd = Cli::command();
             let bin_name = cmd.get_name().to_string();
             clap_complete::generate(shell, &mut cmd, bin_name, &mut io::stdout());
+            if let Some(script) = cli::complete::dynamic_script(shell) {
+                print!("{script}");
+            }
         }
+        TribleCli::Fetch {
+            url,
+            pile,
+            branch,
+            remote,
+            prune,
+            dry_run,
+            signing,
+        } => cli::fetch::run(url, pile, branch, remote, prune, dry_run, signing)?,
+        TribleCli::Backup {
+            dir,
+            pile,
+            branch,
+            remote,
+            force,
+            limit_rate,
+            signing,
+        } => cli::backup::run(dir, pile, branch, remote, force, limit_rate, signing)?,
+        TribleCli::SyncDaemon {
+            url,
+            pile,
+            interval,
+            branches,
+            state_file,
+        } => cli::sync::run(url, pile, interval, branches, state_file)?,
+        TribleCli::VerifyReplica {
+            url,
+            pile,
+            branches_only,
+            deep,
+            sample,
+        } => cli::replica::run(url, pile, branches_only, deep, sample)?,
         TribleCli::Branch { cmd } => cli::branch::run(cmd)?,
-        TribleCli::Pile { cmd } => cli::pile::run(cmd)?,
+        TribleCli::Pile {
+            default_pile,
+            no_refresh,
+            cmd,
+        } => cli::pile::run(cmd, default_pile, no_refresh)?,
         TribleCli::Store { cmd } => cli::store::run(cmd)?,
+        TribleCli::Batch { pile } => cli::batch::run(pile)?,
+        #[cfg(feature = "fuse")]
+        TribleCli::Mount {
+            pile,
+            mountpoint,
+            refresh_interval,
+        } => cli::mount::run(pile, mountpoint, refresh_interval)?,
+        TribleCli::Serve {
+            pile,
+            listen,
+            refresh_interval,
+        } => cli::serve::run(pile, listen, refresh_interval)?,
+        TribleCli::Version { pile } => cli::version::run(args.verbose > 0, pile)?,
+        TribleCli::Doctor { pile, signing } => {
+            return Ok(match cli::doctor::run(pile, signing)? {
+                Severity::Pass => Exit::Success,
+                Severity::Warn => Exit::Warnings,
+                Severity::Fail => Exit::Failure,
+            });
+        }
+        TribleCli::Complete { cmd } => cli::complete::run(cmd)?,
     }
-    Ok(())
+    Ok(Exit::Success)
 }
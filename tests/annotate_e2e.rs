@@ -0,0 +1,88 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::tempdir;
+
+const SOURCE: &str = "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F";
+
+fn trible(config: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("trible").unwrap();
+    cmd.env("XDG_CONFIG_HOME", config);
+    cmd
+}
+
+#[test]
+fn annotations_accumulate_across_commits() {
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("blobs.pile");
+    let pile_arg = pile.to_str().unwrap();
+    let file = dir.path().join("invoice-2024.pdf");
+    std::fs::write(&file, b"%PDF-1.7 pretend invoice").unwrap();
+
+    let out = trible(dir.path())
+        .args(["pile", "blob", "put", pile_arg])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let handle = String::from_utf8(out.stdout).unwrap();
+    let handle = handle.split('\t').next().unwrap().to_string();
+
+    let annotate = |set: &str| {
+        trible(dir.path())
+            .args(["pile", "blob", "annotate", pile_arg, &handle])
+            .args(["--set", set, "--ephemeral"])
+            .assert()
+            .success();
+    };
+    annotate(&format!("{SOURCE}=imported from invoice-2024.pdf by alice"));
+    annotate("metadata::name=invoice");
+    // A later value for the same attribute is added, not substituted.
+    annotate("metadata::name=march invoice");
+
+    let out = trible(dir.path())
+        .args(["pile", "blob", "annotations", pile_arg, &handle])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            format!("{SOURCE}\timported from invoice-2024.pdf by alice"),
+            "metadata::name\tinvoice".to_string(),
+            "metadata::name\tmarch invoice".to_string(),
+        ],
+        "{stdout}"
+    );
+
+    // The annotations live on their own branch.
+    trible(dir.path())
+        .args(["pile", "branch", "list", pile_arg])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("_annotations"));
+}
+
+#[test]
+fn annotating_an_absent_blob_fails() {
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("blobs.pile");
+    let missing = format!("blake3:{}", "ab".repeat(32));
+    trible(dir.path())
+        .args(["pile", "blob", "annotate", pile.to_str().unwrap(), &missing])
+        .args(["--set", "metadata::name=ghost", "--ephemeral"])
+        .assert()
+        .code(2);
+    trible(dir.path())
+        .args([
+            "pile",
+            "blob",
+            "annotations",
+            pile.to_str().unwrap(),
+            &missing,
+        ])
+        .assert()
+        .success()
+        .stdout("");
+}
@@ -0,0 +1,117 @@
+use assert_cmd::Command;
+use ed25519_dalek::SigningKey;
+use predicates::prelude::*;
+use tempfile::tempdir;
+use triblespace::prelude::blobschemas::LongString;
+use triblespace::prelude::*;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::Blake3;
+
+fn backup(dir: &std::path::Path, pile: &std::path::Path, remote: &str) -> serde_json::Value {
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--output", "json", "backup"])
+        .arg(dir)
+        .args(["--pile", pile.to_str().unwrap()])
+        .args(["--branch", "home", "--remote", remote, "--ephemeral"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    serde_json::from_slice(&out).unwrap()
+}
+
+#[test]
+fn second_backup_uploads_almost_nothing() {
+    let tmp = tempdir().unwrap();
+    let dir = tmp.path().join("home");
+    std::fs::create_dir_all(dir.join("docs")).unwrap();
+    let big: Vec<u8> = (0..256 * 1024u32).map(|i| (i * 7919 % 251) as u8).collect();
+    std::fs::write(dir.join("docs/data.bin"), &big).unwrap();
+    std::fs::write(dir.join("notes.txt"), b"remember the milk\n").unwrap();
+    let remote = tmp.path().join("remote");
+    std::fs::create_dir_all(&remote).unwrap();
+    let remote = url::Url::from_directory_path(&remote).unwrap().to_string();
+    let pile = tmp.path().join("backup.pile");
+
+    let first = backup(&dir, &pile, &remote);
+    assert_eq!(first["files"], 2);
+    assert!(first["new_blobs"].as_u64().unwrap() >= 2, "{first}");
+    let first_bytes = first["uploaded_bytes"].as_u64().unwrap();
+    assert!(first_bytes >= big.len() as u64, "{first}");
+
+    let second = backup(&dir, &pile, &remote);
+    assert_eq!(second["files"], 2);
+    assert_eq!(second["new_blobs"], 0, "{second}");
+    let second_bytes = second["uploaded_bytes"].as_u64().unwrap();
+    assert!(second_bytes < first_bytes / 20, "{first} then {second}");
+    assert_ne!(first["commit"], second["commit"]);
+}
+
+#[test]
+fn backup_refuses_a_branch_without_snapshots() {
+    let tmp = tempdir().unwrap();
+    let dir = tmp.path().join("home");
+    std::fs::create_dir_all(&dir).unwrap();
+    let pile_path = tmp.path().join("work.pile");
+    {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let key = SigningKey::from_bytes(&[9; 32]);
+        let mut repo = Repository::new(pile, key, TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("home", None).unwrap();
+        let mut ws = repo.pull(branch_id).unwrap();
+        let mut content = TribleSet::new();
+        let handle = ws.put::<LongString, _>("project work".to_string());
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+        ws.commit(content, "work");
+        assert!(repo.try_push(&mut ws).unwrap().is_none());
+        repo.into_storage().close().unwrap();
+    }
+
+    let run = |force: bool| {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.arg("backup")
+            .arg(&dir)
+            .args(["--pile", pile_path.to_str().unwrap()])
+            .args(["--branch", "home", "--ephemeral"]);
+        if force {
+            cmd.arg("--force");
+        }
+        cmd.assert()
+    };
+    run(false)
+        .code(5)
+        .stderr(predicate::str::contains("not a snapshot commit"));
+    run(true)
+        .success()
+        .stdout(predicate::str::contains("0 file(s) scanned"));
+}
+
+#[test]
+fn branch_push_after_backup_has_nothing_left_to_send() {
+    let tmp = tempdir().unwrap();
+    let dir = tmp.path().join("home");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("notes.txt"), b"remember the milk\n").unwrap();
+    let remote = tmp.path().join("remote");
+    std::fs::create_dir_all(&remote).unwrap();
+    let remote = url::Url::from_directory_path(&remote).unwrap().to_string();
+    let pile = tmp.path().join("backup.pile");
+    backup(&dir, &pile, &remote);
+
+    // The backup recorded the head it pushed, so only the branch metadata is
+    // walked, and the remote already holds it.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["branch", "push", &remote])
+        .arg(&pile)
+        .arg("home")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("discovered 1 blob(s)"))
+        .stdout(predicate::str::contains("skipped 1 blob(s)"))
+        .stdout(predicate::str::contains("pushed"));
+}
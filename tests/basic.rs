@@ -20,3 +20,339 @@ fn completion_generates_script() {
         .success()
         .stdout(predicate::str::contains("_trible()"));
 }
+
+#[test]
+fn genid_count_and_formats_round_trip() {
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["genid", "--count", "3", "--format", "uuid"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let uuids: Vec<&str> = stdout.lines().collect();
+    assert_eq!(uuids.len(), 3);
+
+    for uuid in uuids {
+        let hex = uuid.replace('-', "").to_ascii_uppercase();
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["id", "parse", uuid])
+            .assert()
+            .success()
+            .stdout(format!("{hex}\n"));
+    }
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["genid", "--format", "base32"])
+        .output()
+        .unwrap();
+    let base32 = String::from_utf8(out.stdout).unwrap();
+    assert!(predicate::str::is_match("^[0-9A-HJKMNP-TV-Z]{26}\\n$")
+        .unwrap()
+        .eval(&base32));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["id", "parse", base32.trim()])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^[A-F0-9]{32}\\n$").unwrap());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["id", "parse", "not-an-id"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn genid_rejects_zero_count() {
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["genid", "--count", "0"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn genid_json_output() {
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["genid", "--output", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^\\{\"id\":\"[A-F0-9]{32}\"\\}\\n$").unwrap());
+}
+
+#[test]
+fn verbose_logs_to_stderr_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let pile = dir.path().join("verbose.pile");
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env_remove("RUST_LOG")
+        .args(["-vv", "pile", "create", pile.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("resolved pile path"));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env_remove("RUST_LOG")
+        .args(["pile", "create", pile.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("resolved pile path").not());
+}
+
+#[test]
+fn completion_helper_lists_branch_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let pile = dir.path().join("complete.pile");
+    for name in ["main", "feature"] {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "create", pile.to_str().unwrap(), name])
+            .arg("--ephemeral")
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["__complete", "branch-names", pile.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout("feature\nmain\n");
+
+    // A missing pile yields no candidates, no errors and no new file.
+    let missing = dir.path().join("missing.pile");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["__complete", "branch-names", missing.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::is_empty());
+    assert!(!missing.exists());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["completion", "bash"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("__complete branch-names"));
+}
+
+#[test]
+fn doctor_reports_healthy_pile_and_bad_signing_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let pile = dir.path().join("doctor.pile");
+    let key = dir.path().join("signing.key");
+    std::fs::write(&key, "11".repeat(32)).unwrap();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "create", pile.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let doctor = || {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.env("XDG_CONFIG_HOME", dir.path())
+            .env_remove("TRIBLES_SIGNING_KEY")
+            .env_remove("TRIBLES_PILE")
+            .args(["doctor", pile.to_str().unwrap()]);
+        cmd
+    };
+
+    doctor()
+        .args(["--signing-key", key.to_str().unwrap()])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("pass  signing key"))
+        .stdout(predicate::str::contains("pass  pile"))
+        .stdout(predicate::str::contains("fail").not());
+
+    let missing = dir.path().join("missing.key");
+    doctor()
+        .args(["--signing-key", missing.to_str().unwrap()])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("fail  signing key"))
+        .stdout(predicate::str::contains("fix:"));
+}
+
+#[test]
+fn aliases_behave_like_canonical_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let pile = dir.path().join("alias.pile");
+    let pile = pile.to_str().unwrap();
+    let store = tempfile::tempdir().unwrap();
+    let url = format!("file://{}", store.path().display());
+    let input = dir.path().join("input.bin");
+    std::fs::write(&input, b"alias").unwrap();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "create", pile, "main", "--ephemeral"])
+        .assert()
+        .success();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", pile])
+        .arg(&input)
+        .assert()
+        .success();
+
+    let run = |args: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{args:?} failed");
+        out.stdout
+    };
+
+    let matrix: &[(&[&str], &[&str])] = &[
+        (
+            &["pile", "br", "ls", pile],
+            &["pile", "branch", "list", pile],
+        ),
+        (
+            &["pile", "blob", "ls", pile],
+            &["pile", "blob", "list", pile],
+        ),
+        (
+            &["store", "br", "ls", &url],
+            &["store", "branch", "list", &url],
+        ),
+        (
+            &["store", "blob", "ls", &url],
+            &["store", "blob", "list", &url],
+        ),
+        (
+            &["pile", "branch", "mi", "--help"],
+            &["pile", "branch", "merge-import", "--help"],
+        ),
+        (
+            &["pile", "branch", "rm", "--help"],
+            &["pile", "branch", "delete", "--help"],
+        ),
+        (
+            &["store", "blob", "rm", "--help"],
+            &["store", "blob", "forget", "--help"],
+        ),
+        (&["br", "--help"], &["branch", "--help"]),
+        (&["id-gen", "--help"], &["genid", "--help"]),
+    ];
+    for (alias, canonical) in matrix {
+        assert_eq!(run(alias), run(canonical), "{alias:?} vs {canonical:?}");
+    }
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["id-gen", "--count", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^([0-9A-F]{32}\n){2}$").unwrap());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("aliases: ls"))
+        .stdout(predicate::str::contains("aliases: mi"));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["completion", "bash"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("id-gen"));
+}
+
+#[test]
+fn version_reports_build_and_pile_compatibility() {
+    let dir = tempfile::tempdir().unwrap();
+    let pile = dir.path().join("version.pile");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "create", pile.to_str().unwrap(), "main"])
+        .arg("--ephemeral")
+        .assert()
+        .success();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .arg("version")
+        .assert()
+        .success()
+        .stdout(format!("trible: {}\n", env!("CARGO_PKG_VERSION")));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["version", "--verbose", "--pile", pile.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("triblespace: "))
+        .stdout(predicate::str::contains("triblespace-core: "))
+        .stdout(predicate::str::contains("pile formats: 1"))
+        .stdout(predicate::str::contains("features: "))
+        .stdout(predicate::str::contains("pile format: 1"))
+        .stdout(predicate::str::contains("readable: yes"));
+
+    let junk = dir.path().join("junk.pile");
+    std::fs::write(&junk, [0xAB; 64]).unwrap();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["version", "--pile", junk.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("readable: no"));
+}
+
+#[test]
+fn id_check_and_fmt() {
+    let lower = "0123456789abcdef0123456789abcdef";
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["id", "check", lower])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::is_empty());
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["id", "check", &lower[1..]])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("expected 32 hex chars, got 31"));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["id", "check", &"0".repeat(32)])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("nil id"));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["id", "fmt", lower])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", lower.to_ascii_uppercase()));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "id",
+            "fmt",
+            "01234567-89AB-CDEF-0123-456789ABCDEF",
+            "--case",
+            "lower",
+        ])
+        .assert()
+        .success()
+        .stdout(format!("{lower}\n"));
+}
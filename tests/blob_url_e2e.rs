@@ -0,0 +1,138 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::tempdir;
+use tiny_http::{Header, Response, Server};
+
+const BODY: &[u8] = b"upstream dataset v1\n";
+
+/// Serve `BODY` at `/data`, a redirect to it at `/moved` and 404 elsewhere,
+/// on a free port; returns the base URL.
+fn serve() -> String {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = match request.url() {
+                "/data" => Response::from_data(BODY).boxed(),
+                "/moved" => Response::empty(302)
+                    .with_header(Header::from_bytes("Location", "/data").unwrap())
+                    .boxed(),
+                _ => Response::empty(404).boxed(),
+            };
+            let _ = request.respond(response);
+        }
+    });
+    base
+}
+
+#[test]
+fn put_url_stores_downloaded_bytes_after_redirects() {
+    let base = serve();
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("mirror.pile");
+    let handle = format!("blake3:{}", blake3::hash(BODY).to_hex());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", pile.to_str().unwrap()])
+        .args(["--url", &format!("{base}/moved")])
+        .args(["--expected-handle", &handle])
+        .assert()
+        .success()
+        .stdout(format!("{handle}\tnew\n"));
+
+    let out = dir.path().join("out.bin");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "get", pile.to_str().unwrap(), &handle])
+        .arg(&out)
+        .assert()
+        .success();
+    assert_eq!(std::fs::read(&out).unwrap(), BODY);
+}
+
+#[test]
+fn put_url_with_mismatched_handle_stores_nothing() {
+    let base = serve();
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("mirror.pile");
+    let wrong = format!("blake3:{}", blake3::hash(b"something else").to_hex());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", pile.to_str().unwrap()])
+        .args(["--url", &format!("{base}/data")])
+        .args(["--expected-handle", &wrong])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("expected ").and(predicate::str::contains(&wrong)));
+    assert!(!pile.exists(), "a failed download must not create the pile");
+}
+
+#[test]
+fn put_url_reports_http_status() {
+    let base = serve();
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("mirror.pile");
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", pile.to_str().unwrap()])
+        .args(["--url", &format!("{base}/missing")])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("HTTP status 404"));
+}
+
+#[test]
+fn store_put_url_uploads_downloaded_bytes() {
+    let base = serve();
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store");
+    std::fs::create_dir_all(&store).unwrap();
+    let store_url = url::Url::from_directory_path(&store).unwrap().to_string();
+    let handle = format!("blake3:{}", blake3::hash(BODY).to_hex());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["store", "blob", "put", &store_url])
+        .args(["--url", &format!("{base}/data")])
+        .args(["--expected-handle", &handle])
+        .assert()
+        .success()
+        .stdout(format!("{handle}\n"));
+}
+
+#[test]
+fn put_url_survives_a_bogus_content_length() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\nConnection: close\r\n\r\n",
+            );
+            let _ = stream.write_all(BODY);
+        }
+    });
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("mirror.pile");
+
+    // The header must not size an allocation: the body ends early, which is
+    // an ordinary read error rather than a capacity overflow or an abort.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", pile.to_str().unwrap()])
+        .args(["--url", &format!("{base}/data")])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(format!(
+            "read response body of {base}/data"
+        )))
+        .stderr(predicate::str::contains("panicked").not());
+}
@@ -140,3 +140,136 @@ fn branch_list_all_deleted_lists_deleted_branches() {
         "expected branch id and delete state in list --all --deleted output, got:\n{stdout}"
     );
 }
+
+#[test]
+fn branch_list_all_resolves_branches_sharing_metadata() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("test-branch-shared-meta.pile");
+
+    let ids = [
+        id_hex!("44444444444444444444444444444441"),
+        id_hex!("44444444444444444444444444444442"),
+        id_hex!("44444444444444444444444444444443"),
+    ];
+
+    {
+        let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        pile.restore().unwrap();
+
+        let mut meta = TribleSet::new();
+        let name = pile
+            .put::<blobschemas::LongString, _>("shared".to_string())
+            .unwrap();
+        meta += entity! { &ufoid() @ triblespace_core::metadata::name: name };
+        let h = pile.put::<SimpleArchive, _>(meta).unwrap();
+
+        for id in ids {
+            pile.update(id, None, Some(h)).unwrap();
+        }
+        pile.update(ids[2], Some(h), None).unwrap();
+        pile.close().unwrap();
+    }
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "pile",
+            "branch",
+            "list",
+            pile_path.to_str().unwrap(),
+            "--all",
+        ])
+        .output()
+        .expect("run trible");
+
+    assert!(
+        out.status.success(),
+        "list --all failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let expected = [
+        format!("{:X}\tset\t-\tshared", ids[0]),
+        format!("{:X}\tset\t-\tshared", ids[1]),
+        format!("{:X}\tdelete\t-\tshared", ids[2]),
+    ];
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines, expected, "unexpected list --all output:\n{stdout}");
+}
+
+#[test]
+fn branch_journal_filters_by_branch_and_time() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("test-journal-filter.pile");
+
+    let a = id_hex!("55555555555555555555555555555555");
+    let b = id_hex!("66666666666666666666666666666666");
+    let meta = |pile: &mut Pile<Blake3>, name: &str| {
+        let name = pile
+            .put::<blobschemas::LongString, _>(name.to_string())
+            .unwrap();
+        let mut set = TribleSet::new();
+        set += entity! { &ufoid() @ triblespace_core::metadata::name: name };
+        pile.put::<SimpleArchive, _>(set).unwrap()
+    };
+    let (m1, m2) = {
+        let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let m1 = meta(&mut pile, "first");
+        pile.update(a, None, Some(m1)).unwrap();
+        // Give the later records a distinct millisecond timestamp.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let m2 = meta(&mut pile, "second");
+        pile.update(a, Some(m1), Some(m2)).unwrap();
+        pile.update(a, Some(m2), None).unwrap();
+        let m3 = meta(&mut pile, "other");
+        pile.update(b, None, Some(m3)).unwrap();
+        pile.close().unwrap();
+        (m1, m2)
+    };
+
+    let journal = |extra: &[&str]| -> Vec<serde_json::Value> {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["--output", "json", "pile", "branch", "journal"])
+            .arg(&pile_path)
+            .args(extra)
+            .output()
+            .expect("run trible");
+        assert!(
+            out.status.success(),
+            "journal failed: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    let all = journal(&[]);
+    assert_eq!(all.len(), 4);
+    let handle = |h: Value<_>| format!("blake3:{}", hex::encode(h.raw));
+    assert_eq!(all[0]["id"], format!("{a:X}"));
+    assert_eq!(all[0]["op"], "set");
+    assert_eq!(all[0]["old"], serde_json::Value::Null);
+    assert_eq!(all[0]["new"], handle(m1));
+    assert_eq!(all[2]["op"], "delete");
+    assert_eq!(all[2]["old"], handle(m2));
+    assert_eq!(all[2]["new"], serde_json::Value::Null);
+    assert!(all[0]["timestamp_ms"].is_u64());
+    assert!(all[0]["time"].as_str().unwrap().ends_with("+00:00"));
+
+    let only_b = journal(&["--branch", &format!("{b:X}")]);
+    assert_eq!(only_b.len(), 1);
+    assert_eq!(only_b[0]["id"], format!("{b:X}"));
+
+    let second = all[1]["timestamp_ms"].as_u64().unwrap();
+    assert!(second > all[0]["timestamp_ms"].as_u64().unwrap());
+    let since = journal(&["--since", &second.to_string()]);
+    assert_eq!(since.len(), 3);
+    assert_eq!(since[0]["new"], handle(m2));
+    let until = journal(&["--until", &second.to_string()]);
+    assert_eq!(until.len(), 1);
+    assert_eq!(until[0]["new"], handle(m1));
+}
@@ -661,3 +661,256 @@ fn consolidate_by_name_merges_active_same_name_branches() {
     drop(reader);
     pile.close().unwrap();
 }
+
+/// Two of three branches share a head: the merge commit lists that head once,
+/// and the dry run explains why the duplicate was dropped.
+#[test]
+fn consolidate_drops_shared_heads_from_the_merge() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("test-shared-head.pile");
+
+    let mut branch_ids: Vec<String> = Vec::new();
+    let mut heads: Vec<String> = Vec::new();
+    {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+
+        for i in 0..2 {
+            let bid = repo.create_branch("mem", None).expect("create branch");
+            branch_ids.push(format!("{:X}", *bid));
+            let mut ws = repo.pull(*bid).expect("pull");
+            let e = ufoid();
+            let mut content = TribleSet::new();
+            let label = ws.put::<blobschemas::LongString, _>(format!("branch-{i}"));
+            content += entity! { &e @ metadata::name: label };
+            ws.commit(content, &format!("commit-{i}"));
+            assert!(repo.try_push(&mut ws).expect("push").is_none());
+            let head = ws.head().expect("head present");
+            if i == 0 {
+                let copy = repo.create_branch("mem", Some(head)).expect("create copy");
+                branch_ids.push(format!("{:X}", *copy));
+            }
+            let hh: Value<triblespace_core::value::schemas::hash::Hash<Blake3>> =
+                Handle::to_hash(head);
+            heads.push(hh.from_value());
+        }
+        repo.close().unwrap();
+    }
+
+    let key_path = dir.path().join("signing.key");
+    std::fs::write(&key_path, hex::encode(random_signing_key().to_bytes())).unwrap();
+    let consolidate = |extra: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "consolidate", pile_path.to_str().unwrap()])
+            .args(&branch_ids)
+            .args(["--signing-key", key_path.to_str().unwrap()])
+            .args(extra)
+            .output()
+            .expect("run trible");
+        let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+        assert!(
+            out.status.success(),
+            "consolidate failed:\nstdout: {stdout}\nstderr: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        stdout
+    };
+
+    let stdout = consolidate(&["--dry-run"]);
+    assert!(
+        stdout.contains(&format!(
+            "dropping commit {}: another branch has the same head",
+            heads[0]
+        )),
+        "expected the shared head to be explained:\n{stdout}"
+    );
+    assert!(stdout.contains("would merge 2 head(s)"), "{stdout}");
+
+    let stdout = consolidate(&[]);
+    let id_hex = stdout
+        .split_whitespace()
+        .rev()
+        .find(|tok| tok.len() == 32 && tok.chars().all(|c| c.is_ascii_hexdigit()))
+        .expect("new branch id in output");
+
+    let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+    pile.refresh().unwrap();
+    let raw16: [u8; 16] = hex::decode(id_hex).unwrap().as_slice().try_into().unwrap();
+    let bid = triblespace_core::id::Id::new(raw16).unwrap();
+    let reader = pile.reader().unwrap();
+    let meta: TribleSet = reader
+        .get(pile.head(bid).unwrap().expect("new branch metadata"))
+        .unwrap();
+
+    let repo_head_attr: triblespace_core::id::Id = id_hex!("272FBC56108F336C4D2E17289468C35F");
+    let repo_parent_attr: triblespace_core::id::Id = id_hex!("317044B612C690000D798CA660ECFD2A");
+    let head = meta
+        .iter()
+        .find(|t| t.a() == &repo_head_attr)
+        .map(|t| *t.v::<Handle<Blake3, SimpleArchive>>())
+        .expect("branch head set");
+    let commit: TribleSet = reader.get(head).unwrap();
+    let parents: Vec<String> = commit
+        .iter()
+        .filter(|t| t.a() == &repo_parent_attr)
+        .map(|t| {
+            let hh: Value<triblespace_core::value::schemas::hash::Hash<Blake3>> =
+                Handle::to_hash(*t.v::<Handle<Blake3, SimpleArchive>>());
+            hh.from_value()
+        })
+        .collect();
+    assert_eq!(parents.len(), 2, "each distinct head is a parent once");
+    assert_eq!(
+        parents.into_iter().collect::<HashSet<_>>(),
+        heads.into_iter().collect::<HashSet<_>>()
+    );
+    drop(reader);
+    pile.close().unwrap();
+}
+
+#[test]
+fn consolidate_include_orphans_merges_into_the_canonical_branch() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("test-orphans.pile");
+
+    let (canonical, orphan, orphan_head) = {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let mut heads = Vec::new();
+        let mut ids = Vec::new();
+        for name in ["mem", "mem--orphan-ABCD"] {
+            let bid = repo.create_branch(name, None).expect("create branch");
+            let mut ws = repo.pull(*bid).expect("pull");
+            let e = ufoid();
+            let mut content = TribleSet::new();
+            let label = ws.put::<blobschemas::LongString, _>(name.to_string());
+            content += entity! { &e @ metadata::name: label };
+            ws.commit(content, name);
+            assert!(repo.try_push(&mut ws).expect("push").is_none());
+            heads.push(ws.head().expect("head present"));
+            ids.push(*bid);
+        }
+        repo.close().unwrap();
+        (ids[0], ids[1], heads[1])
+    };
+
+    let key_path = dir.path().join("signing.key");
+    std::fs::write(&key_path, hex::encode(random_signing_key().to_bytes())).unwrap();
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "consolidate", pile_path.to_str().unwrap()])
+        .args(["mem", "--include-orphans", "--retire"])
+        .args(["--signing-key", key_path.to_str().unwrap()])
+        .output()
+        .expect("run trible");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        out.status.success(),
+        "consolidate failed:\nstdout: {stdout}\nstderr: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(
+        stdout.contains("merged 1 orphan head(s) into mem"),
+        "{stdout}"
+    );
+
+    let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+    pile.refresh().unwrap();
+    let reader = pile.reader().unwrap();
+    let meta: TribleSet = reader
+        .get(pile.head(canonical).unwrap().expect("canonical metadata"))
+        .unwrap();
+    let repo_head_attr: triblespace_core::id::Id = id_hex!("272FBC56108F336C4D2E17289468C35F");
+    let repo_parent_attr: triblespace_core::id::Id = id_hex!("317044B612C690000D798CA660ECFD2A");
+    let head = meta
+        .iter()
+        .find(|t| t.a() == &repo_head_attr)
+        .map(|t| *t.v::<Handle<Blake3, SimpleArchive>>())
+        .expect("branch head set");
+    let commit: TribleSet = reader.get(head).unwrap();
+    assert!(
+        commit
+            .iter()
+            .filter(|t| t.a() == &repo_parent_attr)
+            .any(|t| *t.v::<Handle<Blake3, SimpleArchive>>() == orphan_head),
+        "the orphan head should be a parent of the canonical head"
+    );
+    assert!(pile.head(orphan).unwrap().is_none(), "orphan was retired");
+    drop(reader);
+    pile.close().unwrap();
+}
+
+#[test]
+fn consolidate_message_round_trips_through_branch_show() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("test-message.pile");
+
+    let mut branch_ids: Vec<String> = Vec::new();
+    {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        for i in 0..2 {
+            let bid = repo.create_branch("mem", None).expect("create branch");
+            branch_ids.push(format!("{:X}", *bid));
+            let mut ws = repo.pull(*bid).expect("pull");
+            let e = ufoid();
+            let mut content = TribleSet::new();
+            let label = ws.put::<blobschemas::LongString, _>(format!("branch-{i}"));
+            content += entity! { &e @ metadata::name: label };
+            ws.commit(content, &format!("commit-{i}"));
+            assert!(repo.try_push(&mut ws).expect("push").is_none());
+        }
+        repo.close().unwrap();
+    }
+
+    // Longer than a ShortString can hold.
+    let message = "fold the two mem branches back together after the split";
+    let key_path = dir.path().join("signing.key");
+    std::fs::write(&key_path, hex::encode(random_signing_key().to_bytes())).unwrap();
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "consolidate", pile_path.to_str().unwrap()])
+        .args(&branch_ids)
+        .args(["--out-name", "mem-out", "--message", message])
+        .args(["--signing-key", key_path.to_str().unwrap()])
+        .output()
+        .expect("run trible");
+    let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(out.status.success(), "consolidate failed: {stdout}");
+    let id_hex = stdout
+        .split_whitespace()
+        .rev()
+        .find(|tok| tok.len() == 32 && tok.chars().all(|c| c.is_ascii_hexdigit()))
+        .expect("new branch id in output");
+
+    let head = {
+        let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        pile.refresh().unwrap();
+        let raw16: [u8; 16] = hex::decode(id_hex).unwrap().as_slice().try_into().unwrap();
+        let bid = triblespace_core::id::Id::new(raw16).unwrap();
+        let reader = pile.reader().unwrap();
+        let meta: TribleSet = reader
+            .get(pile.head(bid).unwrap().expect("new branch metadata"))
+            .unwrap();
+        let repo_head_attr: triblespace_core::id::Id = id_hex!("272FBC56108F336C4D2E17289468C35F");
+        let head = meta
+            .iter()
+            .find(|t| t.a() == &repo_head_attr)
+            .map(|t| *t.v::<Handle<Blake3, SimpleArchive>>())
+            .expect("branch head set");
+        drop(reader);
+        pile.close().unwrap();
+        head
+    };
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "show", pile_path.to_str().unwrap()])
+        .arg(format!("blake3:{}", hex::encode(head.raw)))
+        .output()
+        .expect("run trible");
+    let shown = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "show failed: {shown}");
+    assert!(shown.contains(&format!("Message: {message}")), "{shown}");
+}
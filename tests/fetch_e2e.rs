@@ -0,0 +1,210 @@
+use assert_cmd::Command;
+use ed25519_dalek::SigningKey;
+use tempfile::tempdir;
+use triblespace::prelude::blobschemas::LongString;
+use triblespace::prelude::*;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::Blake3;
+
+fn random_signing_key() -> SigningKey {
+    let mut seed = [0u8; 32];
+    getrandom::fill(&mut seed).expect("getrandom");
+    SigningKey::from_bytes(&seed)
+}
+
+fn commit_label(path: &std::path::Path, branch_id: Id, label: &str) {
+    let pile: Pile<Blake3> = Pile::open(path).unwrap();
+    let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+    let mut ws = repo.pull(branch_id).expect("pull");
+    let mut content = TribleSet::new();
+    let handle = ws.put::<LongString, _>(label.to_string());
+    content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+    ws.commit(content, label);
+    assert!(repo.try_push(&mut ws).expect("push").is_none());
+    repo.into_storage().close().unwrap();
+}
+
+/// Return the head column of the `pile branch list` row named `name`.
+fn listed_head(pile: &std::path::Path, name: &str) -> Option<String> {
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "list", pile.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .find_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            (cols.len() == 3 && cols[2] == name).then(|| cols[1].to_string())
+        })
+}
+
+#[test]
+fn fetch_updates_tracking_branch_without_touching_local() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("source.pile");
+    let local = dir.path().join("local.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&source).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    commit_label(&source, branch_id, "first");
+    let branch_hex = format!("{branch_id:X}");
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "branch",
+            "push",
+            &url,
+            source.to_str().unwrap(),
+            &branch_hex,
+        ])
+        .arg("--create")
+        .assert()
+        .success();
+
+    // The local pile has its own, unrelated `main`.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "create", local.to_str().unwrap(), "main"])
+        .arg("--ephemeral")
+        .assert()
+        .success();
+    let local_main_before = listed_head(&local, "main").expect("local main");
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["fetch", &url, local.to_str().unwrap(), "--ephemeral"])
+        .assert()
+        .success();
+    let first = listed_head(&local, "origin/main").expect("tracking branch");
+    assert_ne!(first, "-");
+
+    commit_label(&source, branch_id, "second");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "branch",
+            "push",
+            &url,
+            source.to_str().unwrap(),
+            &branch_hex,
+        ])
+        .assert()
+        .success();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["fetch", &url, local.to_str().unwrap(), "--branch", "main"])
+        .arg("--ephemeral")
+        .assert()
+        .success();
+    let second = listed_head(&local, "origin/main").expect("tracking branch");
+
+    assert_ne!(first, second, "tracking head should advance");
+    assert_eq!(listed_head(&local, "main").unwrap(), local_main_before);
+}
+
+#[test]
+fn fetch_prune_removes_only_stale_tracking_branches() {
+    use triblespace_core::repo::objectstore::ObjectStoreRemote;
+
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("source.pile");
+    let local = dir.path().join("local.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+
+    let (keep_id, gone_id) = {
+        let pile: Pile<Blake3> = Pile::open(&source).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let keep = *repo.create_branch("keep", None).unwrap();
+        let gone = *repo.create_branch("gone", None).unwrap();
+        repo.into_storage().close().unwrap();
+        (keep, gone)
+    };
+    commit_label(&source, keep_id, "keep");
+    commit_label(&source, gone_id, "gone");
+    for id in [keep_id, gone_id] {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args([
+                "branch",
+                "push",
+                &url,
+                source.to_str().unwrap(),
+                &format!("{id:X}"),
+            ])
+            .arg("--create")
+            .assert()
+            .success();
+    }
+
+    // A local branch that merely looks remote-ish must survive pruning.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "create", local.to_str().unwrap(), "gone"])
+        .arg("--ephemeral")
+        .assert()
+        .success();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["fetch", &url, local.to_str().unwrap(), "--ephemeral"])
+        .assert()
+        .success();
+    assert!(listed_head(&local, "origin/keep").is_some());
+    assert!(listed_head(&local, "origin/gone").is_some());
+
+    {
+        let url = url::Url::parse(&url).unwrap();
+        let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&url).unwrap();
+        let old = remote.head(gone_id).unwrap();
+        remote.update(gone_id, old, None).unwrap();
+    }
+
+    // A dry run writes nothing, so it needs no signing key.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env("XDG_CONFIG_HOME", local.parent().unwrap())
+        .env_remove("TRIBLES_SIGNING_KEY")
+        .env_remove("TRIBLES_SIGNING_KEY_HEX")
+        .args([
+            "fetch",
+            &url,
+            local.to_str().unwrap(),
+            "--prune",
+            "--dry-run",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("origin/gone: would prune"));
+    assert!(listed_head(&local, "origin/gone").is_some());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["fetch", &url, local.to_str().unwrap(), "--prune"])
+        .arg("--ephemeral")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("origin/gone: pruned"));
+
+    assert!(listed_head(&local, "origin/keep").is_some());
+    assert!(listed_head(&local, "origin/gone").is_none());
+    assert!(listed_head(&local, "gone").is_some());
+}
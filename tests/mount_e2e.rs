@@ -0,0 +1,90 @@
+//! Needs a working FUSE setup (`/dev/fuse` and `fusermount3`), which CI
+//! machines often lack; set `TRIBLE_FUSE_TESTS=1` to run it.
+#![cfg(feature = "fuse")]
+
+use ed25519_dalek::SigningKey;
+use std::process::{Child, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+use triblespace::prelude::blobschemas::LongString;
+use triblespace::prelude::*;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::Blake3;
+
+fn random_signing_key() -> SigningKey {
+    let mut seed = [0u8; 32];
+    getrandom::fill(&mut seed).expect("getrandom");
+    SigningKey::from_bytes(&seed)
+}
+
+/// Interrupts the mount when the test ends, even on panic.
+struct Mounted(Child);
+
+impl Drop for Mounted {
+    fn drop(&mut self) {
+        if let Ok(None) = self.0.try_wait() {
+            interrupt(&self.0);
+            let _ = self.0.wait();
+        }
+    }
+}
+
+fn interrupt(child: &Child) {
+    let _ = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status();
+}
+
+#[test]
+fn mounted_pile_exposes_blobs_and_branch_content() {
+    if std::env::var_os("TRIBLE_FUSE_TESTS").is_none() {
+        eprintln!("skipping: set TRIBLE_FUSE_TESTS=1 to run FUSE tests");
+        return;
+    }
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("mount.pile");
+    let mountpoint = dir.path().join("mnt");
+    std::fs::create_dir(&mountpoint).unwrap();
+
+    let text = "archived file contents";
+    let handle = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let handle = ws.put::<LongString, _>(text.to_string());
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+        ws.commit(content, "archive");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+        handle
+    };
+
+    let mut mounted = Mounted(
+        std::process::Command::new(assert_cmd::cargo::cargo_bin("trible"))
+            .arg("mount")
+            .args([&path, &mountpoint])
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    let blob = mountpoint.join("by-hash").join(hex::encode(handle.raw));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !blob.exists() {
+        assert!(Instant::now() < deadline, "mount did not come up");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    assert_eq!(std::fs::read_to_string(&blob).unwrap(), text);
+    let content = mountpoint.join("branches").join("main").join("content");
+    assert!(std::fs::metadata(&content).unwrap().len() > 0);
+    let err = std::fs::write(mountpoint.join("by-hash").join("new"), b"x").unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(30), "{err}"); // EROFS
+
+    interrupt(&mounted.0);
+    assert!(mounted.0.wait().unwrap().success());
+    assert!(!blob.exists());
+}
@@ -50,15 +50,32 @@ fn delete_branch_removes_branch_id_from_list() {
         *branch_id
     };
 
+    let delete_args = [
+        "pile".to_string(),
+        "branch".to_string(),
+        "delete".to_string(),
+        path.to_str().unwrap().to_string(),
+        format!("{branch_id:X}"),
+    ];
+
+    // Without a terminal to prompt on, deletion needs an explicit --yes.
     Command::cargo_bin("trible")
         .unwrap()
-        .args([
-            "pile",
-            "branch",
-            "delete",
-            path.to_str().unwrap(),
-            &format!("{branch_id:X}"),
-        ])
+        .args(&delete_args)
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--yes"));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "list", path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{branch_id:X}")));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(&delete_args)
+        .arg("--yes")
         .assert()
         .success();
 
@@ -134,6 +151,21 @@ fn branch_stats_reports_fast_and_full_counts() {
         .stdout(predicate::str::contains("Triples (unique): 1"))
         .stdout(predicate::str::contains("Entities: 1"))
         .stdout(predicate::str::contains("Attributes: 1"));
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--output", "json", "pile", "branch", "stats"])
+        .arg(&path)
+        .args([&format!("{branch_id:X}"), "--full"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(report["schema"], "trible.stats.v1");
+    assert_eq!(report["branch"], format!("{branch_id:X}"));
+    assert_eq!(report["commits"], 1);
+    assert_eq!(report["content_bytes"], 64);
+    assert_eq!(report["full"]["entities"], 1);
 }
 
 #[test]
@@ -206,6 +238,233 @@ fn put_ingests_file() {
     pile.close().unwrap();
 }
 
+#[test]
+fn put_many_files_in_parallel_matches_sequential_order() {
+    let dir = tempdir().unwrap();
+    let files: Vec<_> = (0..100)
+        .map(|i| {
+            let path = dir.path().join(format!("file{i:03}.bin"));
+            // A few repeated contents exercise the in-batch deduplication.
+            std::fs::write(&path, format!("contents {}", i % 90).repeat(i % 90 + 1)).unwrap();
+            path
+        })
+        .collect();
+    // Files 90..100 repeat earlier contents and are already stored by then.
+    let expected_with = |status: &dyn Fn(usize) -> &'static str| -> String {
+        files
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                format!(
+                    "blake3:{}\t{}\n",
+                    blake3::hash(&std::fs::read(f).unwrap()).to_hex(),
+                    status(i)
+                )
+            })
+            .collect()
+    };
+    let expected = expected_with(&|i| if i < 90 { "new" } else { "existing" });
+
+    let put = |pile: &str, threads: &str| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "blob", "put", "--threads", threads])
+            .arg(dir.path().join(pile))
+            .args(&files)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let sequential = put("sequential.pile", "1");
+    let parallel = put("parallel.pile", "8");
+    assert_eq!(sequential, expected);
+    assert_eq!(parallel, expected);
+
+    // Without --threads the pool size comes from the `concurrency` key.
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(&config_path, "concurrency = 2\n").unwrap();
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--config", config_path.to_str().unwrap()])
+        .args(["pile", "blob", "put"])
+        .arg(dir.path().join("configured.pile"))
+        .args(&files)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+    assert_eq!(
+        std::fs::metadata(dir.path().join("sequential.pile"))
+            .unwrap()
+            .len(),
+        std::fs::metadata(dir.path().join("parallel.pile"))
+            .unwrap()
+            .len()
+    );
+
+    // Putting the same files again appends nothing.
+    let size = std::fs::metadata(dir.path().join("parallel.pile"))
+        .unwrap()
+        .len();
+    assert_eq!(put("parallel.pile", "4"), expected_with(&|_| "existing"));
+    assert_eq!(
+        std::fs::metadata(dir.path().join("parallel.pile"))
+            .unwrap()
+            .len(),
+        size
+    );
+}
+
+#[test]
+fn put_reports_existing_blobs() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("dedup.pile");
+    let input = dir.path().join("input.bin");
+    std::fs::write(&input, b"submitted once").unwrap();
+    let handle = format!("blake3:{}", blake3::hash(b"submitted once").to_hex());
+    let put = |extra: &[&str]| {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "blob", "put"])
+            .args(extra)
+            .arg(&pile_path)
+            .arg(&input)
+            .assert()
+    };
+
+    put(&[]).success().stdout(format!("{handle}\tnew\n"));
+    let size = std::fs::metadata(&pile_path).unwrap().len();
+    put(&[]).success().stdout(format!("{handle}\texisting\n"));
+    assert_eq!(std::fs::metadata(&pile_path).unwrap().len(), size);
+
+    put(&["--fail-if-present"])
+        .code(3)
+        .stdout(format!("{handle}\texisting\n"))
+        .stderr(predicate::str::contains(
+            "1 of 1 file(s) already in the pile",
+        ));
+}
+
+#[test]
+fn put_with_verify_writes_succeeds() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("verified.pile");
+    let input = dir.path().join("input.bin");
+    std::fs::write(&input, b"read me back").unwrap();
+    let handle = format!("blake3:{}", blake3::hash(b"read me back").to_hex());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--verify-writes", "pile", "blob", "put"])
+        .arg(&pile_path)
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(format!("{handle}\tnew\n"));
+}
+
+#[test]
+fn put_handle_out_matches_stdout_handles() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("handles.pile");
+    let handle_out = dir.path().join("handles.txt");
+    let inputs: Vec<_> = ["one", "two", "three"]
+        .iter()
+        .map(|name| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, name).unwrap();
+            path
+        })
+        .collect();
+    let put = |files: &[std::path::PathBuf]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "blob", "put", "--handle-out"])
+            .arg(&handle_out)
+            .arg(&pile_path)
+            .args(files)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        let handles: String = String::from_utf8(out.stdout)
+            .unwrap()
+            .lines()
+            .map(|l| format!("{}\n", l.split('\t').next().unwrap()))
+            .collect();
+        assert_eq!(std::fs::read_to_string(&handle_out).unwrap(), handles);
+    };
+    put(&inputs[..1]);
+    put(&inputs);
+}
+
+#[test]
+fn put_archive_stores_each_member() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("archive.pile");
+    let tarball = dir.path().join("build.tar.gz");
+    let members: [(&str, &[u8]); 3] = [
+        ("bin/tool", b"\x7fELF not really"),
+        ("README", b"read me"),
+        ("share/empty", b""),
+    ];
+    {
+        let gz = flate2::write::GzEncoder::new(
+            std::fs::File::create(&tarball).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(0o755);
+        builder
+            .append_data(&mut header, "bin/", std::io::empty())
+            .unwrap();
+        for (path, data) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", "--archive", "--manifest-blob"])
+        .arg(&pile_path)
+        .arg(&tarball)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    for (i, (path, data)) in members.iter().enumerate() {
+        assert_eq!(
+            lines[i],
+            format!("blake3:{}\t{path}", blake3::hash(data).to_hex()),
+            "{stdout}"
+        );
+    }
+    assert!(lines[3].starts_with("manifest blake3:"), "{stdout}");
+
+    // Members are retrievable on their own.
+    let restored = dir.path().join("README");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "get", pile_path.to_str().unwrap()])
+        .arg(format!("blake3:{}", blake3::hash(b"read me").to_hex()))
+        .arg(&restored)
+        .assert()
+        .success();
+    assert_eq!(std::fs::read(&restored).unwrap(), b"read me");
+}
+
 #[test]
 fn get_restores_blob() {
     let dir = tempdir().unwrap();
@@ -318,6 +577,58 @@ fn list_blobs_with_metadata_outputs_details() {
         .stdout(predicate::str::is_match(&pattern).unwrap());
 }
 
+#[test]
+fn read_commands_see_blobs_appended_by_an_open_writer() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::BlobStorePut;
+
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("live.pile");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "create", pile_path.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // The writer stays open while the CLI reads, like a concurrent process.
+    let mut writer: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+    writer.refresh().unwrap();
+    let handle = writer.put::<LongString, _>("appended".to_string()).unwrap();
+    let handle = format!("blake3:{}", hex::encode(handle.raw));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "list", pile_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(handle.as_str()));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "pile",
+            "blob",
+            "inspect",
+            pile_path.to_str().unwrap(),
+            &handle,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Length: 8 bytes"));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "pile",
+            "--no-refresh",
+            "blob",
+            "list",
+            pile_path.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    writer.close().unwrap();
+}
+
 #[test]
 fn diagnose_reports_healthy() {
     let dir = tempdir().unwrap();
@@ -338,6 +649,96 @@ fn diagnose_reports_healthy() {
         .stdout(predicate::str::contains("healthy"));
 }
 
+#[test]
+fn canonical_archives_round_trip_unchanged() {
+    use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
+    use triblespace::prelude::*;
+
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("canonical.pile");
+    let handle = {
+        let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let name = pile.put::<LongString, _>("archived".to_string()).unwrap();
+        let mut set = TribleSet::new();
+        set += entity! { &ufoid() @ triblespace_core::metadata::name: name };
+        set += entity! { &ufoid() @ triblespace_core::metadata::name: name };
+        let handle = pile.put::<SimpleArchive, _>(set).unwrap();
+        pile.close().unwrap();
+        format!("blake3:{}", hex::encode(handle.raw))
+    };
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "pile",
+            "blob",
+            "canonical-check",
+            pile_path.to_str().unwrap(),
+        ])
+        .arg(&handle)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{handle} is canonical")));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "diagnose", "check", "--canonical"])
+        .arg(&pile_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "1 archive(s) checked, 0 not canonical",
+        ));
+}
+
+#[test]
+fn diagnose_space_reports_unreachable_blob_bytes() {
+    use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
+    use triblespace::prelude::*;
+
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("space.pile");
+    {
+        let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let name = pile.put::<LongString, _>("main".to_string()).unwrap();
+        let mut meta = TribleSet::new();
+        meta += entity! { &ufoid() @ triblespace_core::metadata::name: name };
+        let meta = pile.put::<SimpleArchive, _>(meta).unwrap();
+        pile.update(*ufoid(), None, Some(meta)).unwrap();
+        pile.put::<LongString, _>("o".repeat(1000)).unwrap();
+        pile.close().unwrap();
+    }
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--output", "json", "pile", "diagnose", "check", "--space"])
+        .arg(&pile_path)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let report: serde_json::Value = serde_json::from_str(&stdout).expect("one JSON report");
+    assert_eq!(report["schema"], "trible.diagnose.v1");
+    assert_eq!(report["healthy"], true);
+    assert_eq!(report["blobs"]["invalid"], 0);
+    assert_eq!(report["branches"].as_array().unwrap().len(), 1);
+    let space = &report["space"];
+    assert_eq!(space["unreachable_blobs"], 1);
+    assert_eq!(space["unreachable_bytes"], 1000);
+    assert_eq!(space["invalid_bytes"], 0);
+    assert_eq!(space["duplicate_records"], 0);
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "diagnose", "check", "--space"])
+        .arg(&pile_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "unreachable blobs: 1000 bytes in 1 blob(s)",
+        ));
+}
+
 #[test]
 fn diagnose_reports_invalid_hash() {
     use std::io::Seek;
@@ -487,9 +888,113 @@ fn diagnose_locate_hash_reports_header_and_payload_refs() {
 fn pile_branch_create_outputs_id() {
     let dir = tempdir().unwrap();
     let pile_path = dir.path().join("create_branch.pile");
+    let create = || {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.env("XDG_CONFIG_HOME", dir.path())
+            .env_remove("TRIBLES_SIGNING_KEY")
+            .args([
+                "pile",
+                "branch",
+                "create",
+                pile_path.to_str().unwrap(),
+                "main",
+            ]);
+        cmd
+    };
+
+    // Without a configured key nothing is signed unless asked for explicitly.
+    create()
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains(
+            "no signing key configured; pass --signing-key, set TRIBLES_SIGNING_KEY, or use --ephemeral",
+        ));
+    create()
+        .args(["--ephemeral", "--signing-key", "signing.key"])
+        .assert()
+        .code(5);
+
+    create()
+        .arg("--ephemeral")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^[A-F0-9]{32}\\n$").unwrap());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "list", pile_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^[A-F0-9]{32}\\t-\\tmain\\n$").unwrap());
+}
+
+#[test]
+fn pile_path_defaults_to_env_and_flag() {
+    let dir = tempdir().unwrap();
+    let env_pile = dir.path().join("env.pile");
+    let flag_pile = dir.path().join("flag.pile");
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env("TRIBLES_PILE", &env_pile)
+        .args(["pile", "branch", "create", "main", "--ephemeral"])
+        .assert()
+        .success();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env("TRIBLES_PILE", &env_pile)
+        .args(["pile", "branch", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^[A-F0-9]{32}\\t-\\tmain\\n$").unwrap());
+
+    // `--pile` overrides the environment; a positional path overrides both.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env("TRIBLES_PILE", &env_pile)
+        .args(["pile", "--pile", flag_pile.to_str().unwrap()])
+        .args(["branch", "create", "other", "--ephemeral"])
+        .assert()
+        .success();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env("TRIBLES_PILE", &env_pile)
+        .args(["pile", "branch", "list", flag_pile.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^[A-F0-9]{32}\\t-\\tother\\n$").unwrap());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env_remove("TRIBLES_PILE")
+        .args(["pile", "branch", "list"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains(
+            "no pile specified: pass a path or set TRIBLES_PILE",
+        ));
+}
 
+#[test]
+fn config_file_supplies_signing_key() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("config.pile");
+    let config_path = dir.path().join("config.toml");
+    let missing_key = dir.path().join("missing.key");
+    let key_path = dir.path().join("good.key");
+    std::fs::write(&key_path, hex::encode(random_signing_key().to_bytes())).unwrap();
+
+    std::fs::write(
+        &config_path,
+        format!("signing_key = {:?}\n", missing_key.to_str().unwrap()),
+    )
+    .unwrap();
+
+    // The configured key is used when neither flag nor env var is given.
     Command::cargo_bin("trible")
         .unwrap()
+        .env_remove("TRIBLES_SIGNING_KEY")
+        .args(["--config", config_path.to_str().unwrap()])
         .args([
             "pile",
             "branch",
@@ -498,13 +1003,2249 @@ fn pile_branch_create_outputs_id() {
             "main",
         ])
         .assert()
-        .success()
-        .stdout(predicate::str::is_match("^[A-F0-9]{32}\\n$").unwrap());
+        .failure()
+        .stderr(predicate::str::contains("missing.key"));
 
+    // The environment variable and the flag both take precedence over it.
     Command::cargo_bin("trible")
         .unwrap()
-        .args(["pile", "branch", "list", pile_path.to_str().unwrap()])
+        .env("TRIBLES_SIGNING_KEY", &key_path)
+        .args(["--config", config_path.to_str().unwrap()])
+        .args([
+            "pile",
+            "branch",
+            "create",
+            pile_path.to_str().unwrap(),
+            "env",
+        ])
         .assert()
-        .success()
-        .stdout(predicate::str::is_match("^[A-F0-9]{32}\\t-\\tmain\\n$").unwrap());
+        .success();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .env_remove("TRIBLES_SIGNING_KEY")
+        .args(["--config", config_path.to_str().unwrap()])
+        .args([
+            "pile",
+            "branch",
+            "create",
+            pile_path.to_str().unwrap(),
+            "flag",
+        ])
+        .args(["--signing-key", key_path.to_str().unwrap()])
+        .assert()
+        .success();
+
+    std::fs::write(&config_path, "signing_kee = \"x\"\n").unwrap();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--config", config_path.to_str().unwrap(), "genid"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("signing_kee"));
+}
+
+#[test]
+fn list_commands_support_json_output() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("json.pile");
+    let input_path = dir.path().join("input.bin");
+    std::fs::write(&input_path, b"json").unwrap();
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let id = *repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+        id
+    };
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", pile_path.to_str().unwrap()])
+        .arg(&input_path)
+        .assert()
+        .success();
+
+    let json_lines = |args: &[&str]| -> Vec<serde_json::Value> {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["--output", "json"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("one JSON object per line"))
+            .collect()
+    };
+
+    let branches = json_lines(&["pile", "branch", "list", pile_path.to_str().unwrap()]);
+    assert_eq!(
+        branches,
+        [serde_json::json!({
+            "id": format!("{branch_id:X}"),
+            "head": null,
+            "name": "main",
+        })]
+    );
+
+    let digest = blake3::hash(b"json").to_hex().to_string();
+    let blobs = json_lines(&["pile", "blob", "list", pile_path.to_str().unwrap()]);
+    assert!(blobs.contains(&serde_json::json!({ "handle": format!("blake3:{digest}") })));
+    let blobs = json_lines(&[
+        "pile",
+        "blob",
+        "list",
+        "--metadata",
+        pile_path.to_str().unwrap(),
+    ]);
+    assert!(blobs
+        .iter()
+        .any(|b| b["handle"] == format!("blake3:{digest}") && b["length"] == 4));
+}
+
+#[test]
+fn exit_codes_distinguish_failure_classes() {
+    use std::io::Seek;
+    use std::io::Write;
+
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("codes.pile");
+    let blob_path = dir.path().join("blob.bin");
+    std::fs::write(&blob_path, b"good data").unwrap();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", pile_path.to_str().unwrap()])
+        .arg(&blob_path)
+        .assert()
+        .success();
+
+    // Not found: a well-formed id that names no branch.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "inspect", pile_path.to_str().unwrap()])
+        .arg("0123456789ABCDEF0123456789ABCDEF")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("branch not found"));
+
+    // Usage errors are reported as invalid arguments rather than clap's 2.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "no-such-command"])
+        .assert()
+        .code(5);
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&pile_path)
+        .unwrap();
+    file.seek(std::io::SeekFrom::Start(64)).unwrap();
+    file.write_all(b"X").unwrap();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "diagnose", "check", pile_path.to_str().unwrap()])
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn porcelain_output_is_stable() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("porcelain.pile");
+    let blob_pile = dir.path().join("blobs.pile");
+    let input_path = dir.path().join("input.bin");
+    std::fs::write(&input_path, b"porcelain").unwrap();
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let id = *repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+        id
+    };
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "blob", "put", blob_pile.to_str().unwrap()])
+        .arg(&input_path)
+        .assert()
+        .success();
+
+    let porcelain = |args: &[&str]| -> String {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .arg("--porcelain")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    // id, name, meta, meta_state, meta_error, head, head_state, creator
+    let inspect = porcelain(&[
+        "pile",
+        "branch",
+        "inspect",
+        pile_path.to_str().unwrap(),
+        &format!("{branch_id:X}"),
+    ]);
+    let line = inspect.strip_suffix('\n').expect("single line");
+    let columns: Vec<&str> = line.split('\t').collect();
+    assert_eq!(columns.len(), 8, "{line:?}");
+    assert_eq!(columns[0], format!("{branch_id:X}"));
+    assert_eq!(columns[1], "main");
+    assert!(columns[2].starts_with("blake3:"), "{line:?}");
+    assert_eq!(&columns[3..], ["present", "", "", "", ""]);
+
+    let digest = blake3::hash(b"porcelain").to_hex().to_string();
+    let blobs = porcelain(&["pile", "blob", "list", blob_pile.to_str().unwrap()]);
+    assert_eq!(blobs, format!("blake3:{digest}\n"));
+    let blobs = porcelain(&[
+        "pile",
+        "blob",
+        "list",
+        "--metadata",
+        blob_pile.to_str().unwrap(),
+    ]);
+    let columns: Vec<&str> = blobs.trim_end().split('\t').collect();
+    assert_eq!(columns.len(), 3, "{blobs:?}");
+    assert_eq!(columns[0], format!("blake3:{digest}"));
+    assert_eq!(columns[2], "9");
+}
+
+#[test]
+fn quiet_suppresses_narration() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("quiet.pile");
+    let key_path = dir.path().join("signing.key");
+    std::fs::write(&key_path, hex::encode(random_signing_key().to_bytes())).unwrap();
+    {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        repo.create_branch("solo", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+    }
+
+    let consolidate = |quiet: bool| {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        if quiet {
+            cmd.arg("-q");
+        }
+        cmd.args([
+            "pile",
+            "branch",
+            "consolidate",
+            pile_path.to_str().unwrap(),
+            "--by-name",
+            "--dry-run",
+            "--signing-key",
+            key_path.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+    };
+
+    consolidate(false).stdout(predicate::str::contains("found 1 active branch"));
+    consolidate(true).stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn piped_output_has_no_ansi_escapes() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("color.pile");
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let id = *repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+        id
+    };
+    let pile = pile_path.to_str().unwrap();
+    let id = format!("{branch_id:X}");
+
+    let commands: [&[&str]; 3] = [
+        &["pile", "diagnose", "check", pile],
+        &["pile", "branch", "inspect", pile, &id],
+        &["--no-color", "pile", "branch", "inspect", pile, &id],
+    ];
+    for args in commands {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .env_remove("NO_COLOR")
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("[present]"))
+            .stdout(predicate::str::contains("\x1b[").not());
+    }
+}
+
+#[test]
+fn batch_runs_mixed_commands_against_one_pile() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("batch.pile");
+    let a = dir.path().join("a.bin");
+    let b = dir.path().join("b with space.bin");
+    let out = dir.path().join("out.bin");
+    std::fs::write(&a, b"alpha").unwrap();
+    std::fs::write(&b, b"bravo!").unwrap();
+    let a_handle = format!("blake3:{}", blake3::hash(b"alpha").to_hex());
+    let b_handle = format!("blake3:{}", blake3::hash(b"bravo!").to_hex());
+    let missing = format!("blake3:{}", blake3::hash(b"missing").to_hex());
+
+    let script = format!(
+        "put {a}\n\
+         exists {a_handle}\n\
+         exists {missing}\n\
+         frobnicate\n\
+         \n\
+         put {b}\n\
+         get {b_handle} {out}\n\
+         inspect {missing}\n",
+        a = a.display(),
+        b = b.display(),
+        out = out.display(),
+    );
+    let output = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["batch", pile_path.to_str().unwrap()])
+        .write_stdin(script)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 7, "{stdout}");
+    assert_eq!(lines[0], format!("{a_handle}\tnew"));
+    assert_eq!(lines[1], "yes");
+    assert_eq!(lines[2], "no");
+    assert!(lines[3].starts_with("error: unknown command"), "{stdout}");
+    assert_eq!(lines[4], format!("{b_handle}\tnew"));
+    assert_eq!(lines[5], "ok 6");
+    assert_eq!(lines[6], "error: blob not found");
+    assert_eq!(std::fs::read(&out).unwrap(), b"bravo!");
+
+    let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+    pile.refresh().unwrap();
+    let reader = pile.reader().unwrap();
+    assert_eq!(reader.blobs().count(), 2);
+    pile.close().unwrap();
+}
+
+#[test]
+fn stats_flag_reports_phases_on_stderr() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("stats.pile");
+    let input = dir.path().join("input.bin");
+    std::fs::write(&input, b"stats").unwrap();
+    let handle = format!("blake3:{}\tnew\n", blake3::hash(b"stats").to_hex());
+
+    let mut assert = Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "--stats",
+            "pile",
+            "blob",
+            "put",
+            pile_path.to_str().unwrap(),
+        ])
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(handle);
+    for header in [
+        "stats:",
+        "pile open",
+        "reachability",
+        "hashing",
+        "transfer",
+        "head update",
+        "total",
+        "bytes read",
+        "bytes written",
+    ] {
+        assert = assert.stderr(predicate::str::contains(header));
+    }
+    assert.stderr(predicate::str::is_match(r"bytes written +5 B").unwrap());
+}
+
+#[test]
+fn key_generate_writes_private_seed_file() {
+    let dir = tempdir().unwrap();
+    let key_path = dir.path().join("signing.key");
+    let pile_path = dir.path().join("keyed.pile");
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["key", "generate", "--out", key_path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let public = String::from_utf8(out.stdout).unwrap();
+    assert!(
+        public.trim().len() == 64 && public.trim().chars().all(|c| c.is_ascii_hexdigit()),
+        "{public:?}"
+    );
+    let seed = std::fs::read_to_string(&key_path).unwrap();
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(seed.trim(), &mut bytes).unwrap();
+    assert_eq!(
+        hex::encode(SigningKey::from_bytes(&bytes).verifying_key().to_bytes()),
+        public.trim()
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&key_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["key", "generate", "--out", key_path.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--force"));
+    assert_eq!(std::fs::read_to_string(&key_path).unwrap(), seed);
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "key",
+            "generate",
+            "--force",
+            "--out",
+            key_path.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    assert_ne!(std::fs::read_to_string(&key_path).unwrap(), seed);
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "pile",
+            "branch",
+            "create",
+            pile_path.to_str().unwrap(),
+            "main",
+        ])
+        .args(["--signing-key", key_path.to_str().unwrap()])
+        .assert()
+        .success();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["key", "generate"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^[0-9a-f]{64}\n$").unwrap())
+        .stderr(predicate::str::contains("warning"));
+}
+
+#[test]
+fn key_pubkey_prints_verifying_key() {
+    let dir = tempdir().unwrap();
+    let key_path = dir.path().join("signing.key");
+    let seed = [7u8; 32];
+    std::fs::write(&key_path, hex::encode(seed)).unwrap();
+    let expected = hex::encode(SigningKey::from_bytes(&seed).verifying_key().to_bytes());
+
+    let pubkey = || {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.env("XDG_CONFIG_HOME", dir.path())
+            .env_remove("TRIBLES_SIGNING_KEY")
+            .args(["key", "pubkey"]);
+        cmd
+    };
+
+    pubkey()
+        .args(["--signing-key", key_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(format!("{expected}\n"));
+    pubkey()
+        .env("TRIBLES_SIGNING_KEY", &key_path)
+        .assert()
+        .success()
+        .stdout(format!("{expected}\n"));
+
+    // Matches `ssh-keygen -y` for the checked-in OpenSSH fixture.
+    let fixture = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/openssh_ed25519"
+    );
+    pubkey()
+        .args(["--signing-key", fixture, "--format", "openssh"])
+        .assert()
+        .success()
+        .stdout(
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAICu5kQ956LAUZTAboFJf0ElfMi0gmICdcllI+Qa1GbNg\n",
+        );
+
+    pubkey()
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("no signing key configured"));
+}
+
+#[test]
+fn keyring_add_list_remove_and_sign_by_name() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("keyring.pile");
+    let imported = dir.path().join("imported.key");
+    let seed = [9u8; 32];
+    std::fs::write(&imported, hex::encode(seed)).unwrap();
+    let imported_public = hex::encode(SigningKey::from_bytes(&seed).verifying_key().to_bytes());
+
+    let trible = || {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.current_dir(dir.path())
+            .env("XDG_CONFIG_HOME", dir.path())
+            .env_remove("TRIBLES_SIGNING_KEY");
+        cmd
+    };
+
+    trible()
+        .args(["key", "add", "work", "--file", imported.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(format!("{imported_public}\n"));
+    let out = trible()
+        .args(["key", "add", "ci", "--generate"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let ci_public = String::from_utf8(out.stdout).unwrap();
+    trible()
+        .args(["key", "add", "work", "--generate"])
+        .assert()
+        .code(3);
+    trible().args(["key", "add", "other"]).assert().code(5);
+
+    let stored = dir.path().join("trible").join("keys").join("work.key");
+    assert!(stored.is_file());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&stored).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    trible()
+        .args(["key", "list"])
+        .assert()
+        .success()
+        .stdout(format!("ci\t{ci_public}work\t{imported_public}\n"));
+
+    // A keyring name works wherever a key file is expected.
+    trible()
+        .args([
+            "pile",
+            "branch",
+            "create",
+            pile_path.to_str().unwrap(),
+            "main",
+        ])
+        .args(["--signing-key", "work"])
+        .assert()
+        .success();
+    trible()
+        .args(["key", "pubkey", "--signing-key", "work"])
+        .assert()
+        .success()
+        .stdout(format!("{imported_public}\n"));
+
+    trible()
+        .args(["key", "remove", "work", "--yes"])
+        .assert()
+        .success();
+    assert!(!stored.exists());
+    trible()
+        .args(["key", "remove", "work", "--yes"])
+        .assert()
+        .code(2);
+    trible()
+        .args(["key", "list"])
+        .assert()
+        .success()
+        .stdout(format!("ci\t{ci_public}"));
+}
+
+#[test]
+fn encrypted_key_files_use_passphrase_from_env() {
+    let dir = tempdir().unwrap();
+    let key_path = dir.path().join("sealed.key");
+    let trible = || {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.env("XDG_CONFIG_HOME", dir.path())
+            .env_remove("TRIBLES_SIGNING_KEY")
+            .env_remove("TRIBLES_KEY_PASSPHRASE");
+        cmd
+    };
+
+    let out = trible()
+        .env("TRIBLES_KEY_PASSPHRASE", "hunter2")
+        .args(["key", "generate", "--encrypt", "--out"])
+        .arg(&key_path)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let public = String::from_utf8(out.stdout).unwrap();
+    let sealed = std::fs::read_to_string(&key_path).unwrap();
+    assert!(sealed.starts_with("-----BEGIN TRIBLE ENCRYPTED SIGNING KEY-----"));
+
+    trible()
+        .env("TRIBLES_KEY_PASSPHRASE", "hunter2")
+        .args(["key", "pubkey", "--signing-key"])
+        .arg(&key_path)
+        .assert()
+        .success()
+        .stdout(public);
+    trible()
+        .env("TRIBLES_KEY_PASSPHRASE", "hunter3")
+        .args(["key", "pubkey", "--signing-key"])
+        .arg(&key_path)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("wrong passphrase"));
+    // Without a terminal the passphrase can only come from the environment.
+    trible()
+        .args(["key", "pubkey", "--signing-key"])
+        .arg(&key_path)
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("TRIBLES_KEY_PASSPHRASE"));
+}
+
+#[test]
+fn log_filters_commits_by_time_and_author() {
+    use triblespace::prelude::blobschemas::SimpleArchive;
+    use triblespace::prelude::*;
+    use triblespace_core::repo;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("dated.pile");
+    let author = SigningKey::from_bytes(&[6u8; 32]);
+
+    let branch_id = {
+        let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        // A linear chain with one commit at the start of each year.
+        let mut parent: Option<Value<valueschemas::Handle<Blake3, SimpleArchive>>> = None;
+        for year in [2020, 2021, 2022] {
+            let at = hifitime::Epoch::from_gregorian_utc_at_midnight(year, 1, 1);
+            let id = ufoid();
+            let mut commit = TribleSet::new();
+            commit += entity! { &id @
+                repo::short_message: format!("year {year}").as_str(),
+                repo::timestamp: (at, at),
+            };
+            if let Some(parent) = parent {
+                commit += entity! { &id @ repo::parent: parent };
+            }
+            if year == 2021 {
+                commit += entity! { &id @ repo::signed_by: author.verifying_key() };
+            }
+            parent = Some(pile.put::<SimpleArchive, _>(commit).unwrap());
+        }
+        let branch_id = ufoid();
+        let meta = entity! { &branch_id @ repo::head: parent.unwrap() };
+        let meta = pile.put::<SimpleArchive, _>(meta).unwrap();
+        pile.update(*branch_id, None, Some(meta)).unwrap();
+        pile.close().unwrap();
+        format!("{:X}", *branch_id)
+    };
+    let log = |extra: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "log", path.to_str().unwrap(), &branch_id])
+            .arg("--oneline")
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let all = log(&[]);
+    assert!(
+        all.contains("year 2020") && all.contains("year 2022"),
+        "{all}"
+    );
+
+    let window = log(&["--since", "2020-06-01", "--until", "2022-01-01"]);
+    assert!(window.contains("year 2021"), "{window}");
+    assert!(!window.contains("year 2020"), "{window}");
+    assert!(!window.contains("year 2022"), "{window}");
+
+    let signer = hex::encode(author.verifying_key().to_bytes());
+    let authored = log(&["--author", &signer]);
+    assert_eq!(authored.lines().count(), 1, "{authored}");
+    assert!(authored.contains("year 2021"), "{authored}");
+}
+
+#[test]
+fn log_and_show_verify_commit_signers() {
+    use ed25519_dalek::Signer;
+    use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
+    use triblespace::prelude::*;
+    use triblespace_core::repo;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("signed.pile");
+    let key = SigningKey::from_bytes(&[5u8; 32]);
+    let signer = hex::encode(key.verifying_key().to_bytes());
+
+    let (branch_id, forged) = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, key.clone(), TribleSet::new()).unwrap();
+        let branch_id = repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(*branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let label = ws.put::<LongString, _>("signed".to_string());
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+        ws.commit(content, "signed commit");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+
+        // A commit whose signature does not cover its content.
+        let mut pile = repo.into_storage();
+        let mut content = TribleSet::new();
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+        let content = pile.put::<SimpleArchive, _>(content).unwrap();
+        let signature = key.sign(b"something else");
+        let commit = entity! { &ufoid() @
+            repo::content: content,
+            repo::signed_by: key.verifying_key(),
+            repo::signature_r: signature,
+            repo::signature_s: signature,
+        };
+        let forged = pile.put::<SimpleArchive, _>(commit).unwrap();
+        pile.close().unwrap();
+        (*branch_id, hex::encode(forged.raw))
+    };
+    let branch = format!("{branch_id:X}");
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "pile",
+            "branch",
+            "log",
+            path.to_str().unwrap(),
+            &branch,
+            "-v",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Signer: {signer}")))
+        .stdout(predicate::str::contains("Signature: valid"));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "log", path.to_str().unwrap(), &branch])
+        .args(["--require-signer", &signer])
+        .assert()
+        .success();
+    let other = hex::encode(random_signing_key().verifying_key().to_bytes());
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "log", path.to_str().unwrap(), &branch])
+        .args(["--require-signer", &other])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("not signed by the required key"));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "show", path.to_str().unwrap(), &forged])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Signed by: {signer}")))
+        .stdout(predicate::str::contains("Signature: invalid"));
+}
+
+#[test]
+fn resign_replaces_branch_metadata_signer() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("resign.pile");
+    let new_key_path = dir.path().join("new.key");
+    let new_key = SigningKey::from_bytes(&[6u8; 32]);
+    std::fs::write(&new_key_path, hex::encode(new_key.to_bytes())).unwrap();
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(*branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let label = ws.put::<LongString, _>("resign".to_string());
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+        ws.commit(content, "seed");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.create_branch("side", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+        *branch_id
+    };
+
+    let list = || {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "list", path.to_str().unwrap()])
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let before = list();
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "resign", path.to_str().unwrap()])
+        .args([
+            "--name",
+            "main",
+            "--signing-key",
+            new_key_path.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let fields: Vec<&str> = stdout.trim_end().split('\t').collect();
+    assert_eq!(fields.len(), 3, "{stdout:?}");
+    assert_eq!(fields[0], format!("{branch_id:X}"));
+    assert_ne!(fields[1], fields[2]);
+    // Same names and heads; only the metadata blob changed.
+    assert_eq!(list(), before);
+
+    let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+    pile.refresh().unwrap();
+    let meta_handle = pile.head(branch_id).unwrap().unwrap();
+    let meta: TribleSet = pile.reader().unwrap().get(meta_handle).unwrap();
+    pile.close().unwrap();
+    let signed_by = triblespace_core::repo::signed_by.id();
+    assert!(meta
+        .iter()
+        .any(|t| t.a() == &signed_by && t.data[32..64] == new_key.verifying_key().to_bytes()));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "resign", path.to_str().unwrap(), "--all"])
+        .args(["--signing-key", new_key_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^([A-F0-9]{32}\t[^\t]+\t[^\t]+\n){2}$").unwrap());
+    assert_eq!(list(), before);
+}
+
+#[test]
+fn agent_signing_keys_are_rejected_clearly() {
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("agent.pile");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "pile",
+            "branch",
+            "create",
+            pile_path.to_str().unwrap(),
+            "main",
+        ])
+        .args(["--signing-key", "agent:SHA256:abc"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains(
+            "ssh-agent signing is not supported yet",
+        ));
+}
+
+#[test]
+fn branch_inspect_shows_creator() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("creator.pile");
+    let key_path = dir.path().join("creator.key");
+    let key = SigningKey::from_bytes(&[8u8; 32]);
+    std::fs::write(&key_path, hex::encode(key.to_bytes())).unwrap();
+    let expected = format!("ed25519:{}", hex::encode(key.verifying_key().to_bytes()));
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "create", path.to_str().unwrap(), "main"])
+        .args(["--signing-key", key_path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let id = String::from_utf8(out.stdout).unwrap();
+    let id = id.trim().trim_start_matches("0x");
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "inspect", path.to_str().unwrap(), id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Creator:   {expected}\n")));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--output", "json", "pile", "branch", "inspect"])
+        .args([path.to_str().unwrap(), id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "\"creator\":\"{expected}\""
+        )));
+
+    // Renaming keeps the creator even when someone else signs.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "pile",
+            "branch",
+            "rename",
+            path.to_str().unwrap(),
+            id,
+            "trunk",
+        ])
+        .arg("--ephemeral")
+        .assert()
+        .success();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "inspect", path.to_str().unwrap(), id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Creator:   {expected}\n")));
+
+    // Branches created without the CLI carry no creator.
+    let other = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let id = *repo.create_branch("side", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+        id
+    };
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "inspect", path.to_str().unwrap()])
+        .arg(format!("{other:X}"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Creator:   unknown\n"));
+}
+
+#[test]
+fn merge_import_enforces_required_signers() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    let dir = tempdir().unwrap();
+    let src_path = dir.path().join("src.pile");
+    let dst_path = dir.path().join("dst.pile");
+    let alice = SigningKey::from_bytes(&[11u8; 32]);
+    let bob = SigningKey::from_bytes(&[12u8; 32]);
+    let public = |key: &SigningKey| hex::encode(key.verifying_key().to_bytes());
+
+    let src_id = {
+        let pile: Pile<Blake3> = Pile::open(&src_path).unwrap();
+        let mut repo = Repository::new(pile, alice.clone(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let label = ws.put::<LongString, _>("by alice".to_string());
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+        ws.commit(content, "by alice");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    let dst_id = {
+        let pile: Pile<Blake3> = Pile::open(&dst_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    let dst_head = || {
+        let mut pile: Pile<Blake3> = Pile::open(&dst_path).unwrap();
+        pile.refresh().unwrap();
+        let head = pile.head(dst_id).unwrap().map(|h| h.raw);
+        pile.close().unwrap();
+        head
+    };
+    let merge_import = |allowed: &SigningKey| {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "merge-import", "--ephemeral"])
+            .args(["--from-pile", src_path.to_str().unwrap()])
+            .args(["--from-id", &format!("{src_id:X}")])
+            .args(["--to-pile", dst_path.to_str().unwrap()])
+            .args(["--to-id", &format!("{dst_id:X}")])
+            .args(["--require-signed-by", &public(allowed)])
+            .output()
+            .unwrap()
+    };
+    let before = dst_head();
+
+    let out = merge_import(&bob);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("which is not allowed"), "{stderr}");
+    assert_eq!(dst_head(), before);
+
+    let out = merge_import(&alice);
+    assert!(out.status.success(), "{out:?}");
+    assert_ne!(dst_head(), before);
+}
+
+#[test]
+fn repeated_merge_import_skips_blobs_already_in_the_destination() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    let dir = tempdir().unwrap();
+    let src_path = dir.path().join("src.pile");
+    let dst_path = dir.path().join("dst.pile");
+
+    let src_id = {
+        let pile: Pile<Blake3> = Pile::open(&src_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let label = ws.put::<LongString, _>("x".repeat(256 * 1024));
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+        ws.commit(content, "large");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    let dst_id = {
+        let pile: Pile<Blake3> = Pile::open(&dst_path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    let merge_import = || {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "merge-import", "--ephemeral"])
+            .args(["--from-pile", src_path.to_str().unwrap()])
+            .args(["--from-id", &format!("{src_id:X}")])
+            .args(["--to-pile", dst_path.to_str().unwrap()])
+            .args(["--to-id", &format!("{dst_id:X}")])
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        let text = String::from_utf8_lossy(&out.stdout).to_string()
+            + &String::from_utf8_lossy(&out.stderr);
+        (text, std::fs::metadata(&dst_path).unwrap().len())
+    };
+
+    let before = std::fs::metadata(&dst_path).unwrap().len();
+    let (first, after_first) = merge_import();
+    assert!(first.contains("skipped=0"), "{first}");
+    assert!(after_first - before > 256 * 1024);
+
+    let (second, after_second) = merge_import();
+    assert!(second.contains("stored=0 skipped="), "{second}");
+    // Only the new merge commit and branch metadata are appended.
+    assert!(
+        after_second - after_first < 16 * 1024,
+        "{after_first} -> {after_second}"
+    );
+}
+
+#[test]
+fn query_joins_clauses_over_branch_content() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    attributes! {
+        "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F" as kind: valueschemas::ShortString;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("query.pile");
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[attributes]\nkind = \"5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F\"\n",
+    )
+    .unwrap();
+
+    let (alice, bob, robot) = (ufoid(), ufoid(), ufoid());
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(*branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        for (id, what, label) in [
+            (&alice, "person", "Alice"),
+            (&bob, "person", "Bob"),
+            (&robot, "robot", "R2"),
+        ] {
+            let label = ws.put::<LongString, _>(label.to_string());
+            content += entity! { id @ kind: what, triblespace_core::metadata::name: label };
+        }
+        ws.commit(content, "people");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+    }
+
+    let name_attr = format!("{:X}", triblespace_core::metadata::name.id());
+    let expr = format!("?who kind \"person\"; ?who {name_attr} ?label");
+    let query = |extra: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["--config", config_path.to_str().unwrap()])
+            .args(["pile", "query", path.to_str().unwrap(), "--name", "main"])
+            .arg(&expr)
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let mut rows: Vec<String> = query(&[]).lines().map(str::to_string).collect();
+    rows.sort();
+    let label = |text: &str| blake3::hash(text.as_bytes()).to_hex().to_string();
+    let mut expected = vec![
+        format!("{:X}\t{}", *alice, label("Alice")),
+        format!("{:X}\t{}", *bob, label("Bob")),
+    ];
+    expected.sort();
+    assert_eq!(rows, expected);
+
+    assert_eq!(query(&["--limit", "1"]).lines().count(), 1);
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "--config",
+            config_path.to_str().unwrap(),
+            "--output",
+            "json",
+        ])
+        .args(["pile", "query", path.to_str().unwrap(), "--name", "main"])
+        .arg(format!("?r kind \"robot\"; ?r {name_attr} ?label"))
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!("{{\"r\":\"{:X}\",\"label\":\"{}\"}}\n", *robot, label("R2"))
+    );
+}
+
+#[test]
+fn export_rdf_writes_sorted_statements() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    attributes! {
+        "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F" as kind: valueschemas::ShortString;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("rdf.pile");
+    let (alice, bob) = (ufoid(), ufoid());
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let label = ws.put::<LongString, _>("Alice \"A\"".to_string());
+        content += entity! { &alice @ kind: "person", triblespace_core::metadata::name: label };
+        let label = ws.put::<LongString, _>("Bob".to_string());
+        content += entity! { &bob @ triblespace_core::metadata::name: label };
+        ws.commit(content, "people");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+
+    let export = |args: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "export-rdf", path.to_str().unwrap()])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let iri = |id: triblespace_core::id::Id| format!("<urn:trible:id:{id:X}>");
+    let name_attr = iri(triblespace_core::metadata::name.id());
+
+    let quads = export(&["--name", "main"]);
+    assert_eq!(quads.lines().count(), 3, "{quads}");
+    let graph = format!(" {} .", iri(branch_id));
+    assert!(quads.lines().all(|l| l.ends_with(&graph)), "{quads}");
+    let mut sorted: Vec<&str> = quads.lines().collect();
+    sorted.sort_by_key(|l| l.split(' ').take(2).collect::<Vec<_>>().join(" "));
+    assert_eq!(sorted, quads.lines().collect::<Vec<_>>());
+
+    let mut person = [0u8; 32];
+    person[..6].copy_from_slice(b"person");
+    let triples = export(&[
+        "--id",
+        &format!("{branch_id:X}"),
+        "--format",
+        "ntriples",
+        "--resolve-strings",
+    ]);
+    assert_eq!(triples.lines().count(), 3, "{triples}");
+    let lines: Vec<&str> = triples.lines().collect();
+    assert!(lines.contains(
+        &format!(
+            "{} {} \"{}\"^^<http://www.w3.org/2001/XMLSchema#hexBinary> .",
+            iri(*alice),
+            iri(kind.id()),
+            hex::encode_upper(person)
+        )
+        .as_str()
+    ));
+    assert!(lines.contains(&format!("{} {name_attr} \"Alice \\\"A\\\"\" .", iri(*alice)).as_str()));
+    assert!(lines.contains(&format!("{} {name_attr} \"Bob\" .", iri(*bob)).as_str()));
+
+    let out_path = dir.path().join("export.nt");
+    export(&[
+        "--name",
+        "main",
+        "--format",
+        "ntriples",
+        "--out",
+        out_path.to_str().unwrap(),
+    ]);
+    let written = std::fs::read_to_string(&out_path).unwrap();
+    assert_eq!(written, export(&["--name", "main", "--format", "ntriples"]));
+}
+
+#[test]
+fn export_json_groups_values_by_entity() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    attributes! {
+        "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F" as kind: valueschemas::ShortString;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("json.pile");
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[attributes]\nkind = \"5a7c15e1ba1e4e7d87f1bb1a6bbd0c4f\"\n",
+    )
+    .unwrap();
+    let (alice, bob) = (ufoid(), ufoid());
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let label = ws.put::<LongString, _>("Alice".to_string());
+        content += entity! { &alice @ kind: "person", triblespace_core::metadata::name: label };
+        let label = ws.put::<LongString, _>("Bob".to_string());
+        content += entity! { &bob @ triblespace_core::metadata::name: label };
+        ws.commit(content, "people");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+    }
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--config", config_path.to_str().unwrap()])
+        .args(["pile", "branch", "export-json", path.to_str().unwrap()])
+        .args(["--name", "main"])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let objects: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    let mut person = [0u8; 32];
+    person[..6].copy_from_slice(b"person");
+    let mut expected = vec![
+        serde_json::json!({
+            "id": format!("{:X}", *alice),
+            "kind": [{"schema": "unknown", "hex": hex::encode_upper(person)}],
+            "metadata::name": ["Alice"],
+        }),
+        serde_json::json!({
+            "id": format!("{:X}", *bob),
+            "metadata::name": ["Bob"],
+        }),
+    ];
+    expected.sort_by_key(|o| o["id"].as_str().unwrap().to_string());
+    assert_eq!(objects, expected);
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--config", config_path.to_str().unwrap()])
+        .args(["pile", "branch", "export-json", path.to_str().unwrap()])
+        .args(["--name", "main", "--pretty"])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let pretty = String::from_utf8(out.stdout).unwrap();
+    assert!(pretty.lines().count() > objects.len());
+    let reparsed: Vec<serde_json::Value> = serde_json::Deserializer::from_str(&pretty)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(reparsed, objects);
+}
+
+#[test]
+fn import_csv_commits_converted_rows() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("import.pile");
+    let csv_path = dir.path().join("people.csv");
+    std::fs::write(
+        &csv_path,
+        "key,name,age\na,Alice,30\nb,Bob,41\nc,Carol,-5\nd,Dave,old\n",
+    )
+    .unwrap();
+
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+    }
+
+    let name_attr = format!("{:X}", triblespace_core::metadata::name.id());
+    let age_attr = "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F";
+    let import = |extra: &[&str]| {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args([
+                "pile",
+                "import-csv",
+                path.to_str().unwrap(),
+                "--name",
+                "main",
+            ])
+            .arg(&csv_path)
+            .args(["--entity-column", "key", "--ephemeral", "--stable-ids"])
+            .args(["--attr", &format!("name={name_attr}:longstring")])
+            .args(["--attr", &format!("age={age_attr}:i64")])
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+    let query = |expr: &str| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "query", path.to_str().unwrap(), "--name", "main"])
+            .arg(expr)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let out = import(&["--strict"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("line 5"));
+
+    let out = import(&[]);
+    assert!(out.status.success(), "{out:?}");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("line 5: column \"age\""), "{stderr}");
+    assert_eq!(query("?e ?a ?v").lines().count(), 6);
+
+    let carol = blake3::hash(b"Carol").to_hex().to_string();
+    let rows = query(&format!("?e {name_attr} ?name; ?e {age_attr} ?age"));
+    let carol_row = rows
+        .lines()
+        .find(|row| row.contains(&carol))
+        .expect("Carol imported");
+    assert!(carol_row.ends_with(&format!("\t{}fffffffffffffffb", "ff".repeat(24))));
+
+    // Stable ids make a re-import describe the same three entities.
+    assert!(import(&[]).status.success());
+    assert_eq!(query("?e ?a ?v").lines().count(), 6);
+}
+
+#[test]
+fn branch_graph_emits_each_commit_once() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("graph.pile");
+
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        macro_rules! commit {
+            ($ws:expr, $label:expr) => {{
+                let mut content = TribleSet::new();
+                let handle = $ws.put::<LongString, _>($label.to_string());
+                content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+                $ws.commit(content, $label);
+            }};
+        }
+
+        // left: L1 <- L2; right: R1 <- M, with M also merging L1.
+        let left = *repo.create_branch("left", None).expect("create left");
+        let mut ws_left = repo.pull(left).expect("pull left");
+        commit!(ws_left, "L1");
+        assert!(repo.try_push(&mut ws_left).expect("push").is_none());
+        let l1 = ws_left.head().expect("head");
+        commit!(ws_left, "L2");
+        assert!(repo.try_push(&mut ws_left).expect("push").is_none());
+
+        let right = *repo.create_branch("right", None).expect("create right");
+        let mut ws_right = repo.pull(right).expect("pull right");
+        commit!(ws_right, "R1");
+        ws_right.merge_commit(l1).expect("merge L1");
+        assert!(repo.try_push(&mut ws_right).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+    }
+
+    let graph = |args: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "graph", path.to_str().unwrap()])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        let dot = String::from_utf8(out.stdout).unwrap();
+        assert!(dot.starts_with("digraph commits {"), "{dot}");
+        let count = |needle: &str| dot.lines().filter(|l| l.contains(needle)).count();
+        (count("[label="), count(" -> "), count("shape=box"))
+    };
+
+    assert_eq!(graph(&["--all"]), (4, 3, 2));
+    assert_eq!(graph(&["--name", "right"]), (3, 2, 1));
+    assert_eq!(graph(&["--name", "left", "--name", "right"]), (4, 3, 2));
+    assert_eq!(graph(&["--all", "--max-depth", "0"]), (2, 0, 2));
+}
+
+#[test]
+fn branch_attrs_counts_tribles_and_entities() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    attributes! {
+        "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F" as kind: valueschemas::ShortString;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("attrs.pile");
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[attributes]\nkind = \"5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F\"\n",
+    )
+    .unwrap();
+
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        for label in ["Alice", "Bob"] {
+            let label = ws.put::<LongString, _>(label.to_string());
+            content +=
+                entity! { &ufoid() @ kind: "person", triblespace_core::metadata::name: label };
+        }
+        content += entity! { &ufoid() @ kind: "robot" };
+        ws.commit(content, "people");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+    }
+
+    let attrs = |sort: &str| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args([
+                "--config",
+                config_path.to_str().unwrap(),
+                "--output",
+                "json",
+            ])
+            // The pile comes from the environment like any other pile command.
+            .env("TRIBLES_PILE", &path)
+            .args(["pile", "branch", "attrs", "--name", "main", "--sort", sort])
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    let name_attr = format!("{:X}", triblespace_core::metadata::name.id());
+    let kind_row = serde_json::json!({
+        "attribute": "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F",
+        "tribles": 3,
+        "entities": 3,
+        "label": "kind",
+    });
+    let name_row = serde_json::json!({
+        "attribute": name_attr,
+        "tribles": 2,
+        "entities": 2,
+        "label": "metadata::name",
+    });
+    assert_eq!(attrs("count"), [kind_row.clone(), name_row.clone()]);
+
+    let mut by_id = vec![kind_row, name_row];
+    by_id.sort_by_key(|row| row["attribute"].as_str().unwrap().to_string());
+    assert_eq!(attrs("id"), by_id);
+}
+
+#[test]
+fn entity_show_lists_tribles_and_inbound_references() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    attributes! {
+        "6B1E0B9F5D3C4A2E8F7A1C3D5E7F9A1B" as knows: valueschemas::GenId;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("entity.pile");
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[attributes]\nknows = \"6B1E0B9F5D3C4A2E8F7A1C3D5E7F9A1B\"\n",
+    )
+    .unwrap();
+
+    let (alice, bob) = (ufoid(), ufoid());
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let label = ws.put::<LongString, _>("Alice".to_string());
+        content += entity! { &alice @ triblespace_core::metadata::name: label };
+        content += entity! { &bob @ knows: *alice };
+        ws.commit(content, "people");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+    }
+
+    let show = |entity: String| {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args([
+                "--config",
+                config_path.to_str().unwrap(),
+                "--output",
+                "json",
+            ])
+            .args(["pile", "entity", "show", path.to_str().unwrap()])
+            .args(["--name", "main"])
+            .arg(entity)
+            .output()
+            .unwrap()
+    };
+
+    let out = show(format!("{:X}", *alice));
+    assert!(out.status.success(), "{out:?}");
+    let rows: Vec<serde_json::Value> = String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            serde_json::json!({
+                "section": "attribute",
+                "attribute": format!("{:X}", triblespace_core::metadata::name.id()),
+                "label": "metadata::name",
+                "value": "Alice",
+            }),
+            serde_json::json!({
+                "section": "referenced_by",
+                "attribute": "6B1E0B9F5D3C4A2E8F7A1C3D5E7F9A1B",
+                "label": "knows",
+                "value": format!("{:X}", *bob),
+            }),
+        ]
+    );
+
+    let out = show("0123456789ABCDEF0123456789ABCDEF".to_string());
+    assert_eq!(out.status.code(), Some(2), "{out:?}");
+    assert!(String::from_utf8_lossy(&out.stderr).contains("not found"));
+}
+
+#[test]
+fn commit_diff_reports_added_and_removed_tribles() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("diff.pile");
+
+    let bob = ufoid();
+    let (first, second) = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let label = ws.put::<LongString, _>("Alice".to_string());
+        let mut content = TribleSet::new();
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+        ws.commit(content, "alice");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        let first = ws.head().expect("head");
+        let label = ws.put::<LongString, _>("Bob".to_string());
+        let mut content = TribleSet::new();
+        content += entity! { &bob @ triblespace_core::metadata::name: label };
+        ws.commit(content, "bob");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        let second = ws.head().expect("head");
+        repo.into_storage().close().unwrap();
+        (hex::encode(first.raw), hex::encode(second.raw))
+    };
+
+    let diff = |commits: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["--output", "json", "pile", "commit", "diff"])
+            .arg(&path)
+            .args(commits)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        let rows: Vec<serde_json::Value> = String::from_utf8(out.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (rows, String::from_utf8(out.stderr).unwrap())
+    };
+    let bob_row = |change: &str| {
+        serde_json::json!({
+            "change": change,
+            "entity": format!("{:X}", *bob),
+            "attribute": format!("{:X}", triblespace_core::metadata::name.id()),
+            "label": "metadata::name",
+            "value": "Bob",
+        })
+    };
+
+    // Defaults to the first parent.
+    let (rows, summary) = diff(&[&second]);
+    assert_eq!(rows, [bob_row("added")]);
+    assert!(summary.contains("1 added, 0 removed"), "{summary}");
+
+    let (rows, _) = diff(&[&first, &second]);
+    assert_eq!(rows, [bob_row("removed")]);
+
+    // The root commit is compared with the empty set.
+    let (rows, _) = diff(&[&first]);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["value"], "Alice");
+}
+
+#[test]
+fn registered_attribute_names_label_decoded_blobs() {
+    use triblespace::prelude::blobschemas::SimpleArchive;
+    use triblespace::prelude::*;
+
+    attributes! {
+        "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F" as kind: valueschemas::ShortString;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("attr.pile");
+    let handle = {
+        let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut content = TribleSet::new();
+        content += entity! { &ufoid() @ kind: "person" };
+        let handle = pile.put::<SimpleArchive, _>(content).unwrap();
+        pile.close().unwrap();
+        format!("blake3:{}", hex::encode(handle.raw))
+    };
+    let trible = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.env("XDG_CONFIG_HOME", dir.path()).args(args);
+        cmd
+    };
+
+    trible(&["attr", "register", "kind", "not-an-id"])
+        .assert()
+        .code(5);
+    trible(&[
+        "attr",
+        "register",
+        "kind",
+        "5a7c15e1ba1e4e7d87f1bb1a6bbd0c4f",
+    ])
+    .args(["--schema", "shortstring"])
+    .assert()
+    .success();
+    trible(&["attr", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "kind\t5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F\tshortstring\tregistry",
+        ));
+    trible(&[
+        "attr",
+        "register",
+        "kind",
+        "6B1E0B9F5D3C4A2E8F7A1C3D5E7F9A1B",
+    ])
+    .assert()
+    .code(3);
+
+    trible(&[
+        "pile",
+        "blob",
+        "inspect",
+        path.to_str().unwrap(),
+        &handle,
+        "--decode",
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Tribles: 1"))
+    .stdout(predicate::str::contains(
+        "kind (5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F)",
+    ));
+}
+
+#[test]
+fn search_finds_marker_in_string_values() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    attributes! {
+        "5A7C15E1BA1E4E7D87F1BB1A6BBD0C4F" as kind: valueschemas::ShortString;
+        "6B1E0B9F5D3C4A2E8F7A1C3D5E7F9A1B" as body: valueschemas::Handle<valueschemas::Blake3, LongString>;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("search.pile");
+    let (report, memo) = (ufoid(), ufoid());
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let title = ws.put::<LongString, _>("Quarterly report".to_string());
+        let text =
+            ws.put::<LongString, _>("Revenue grew. See the Q3 Forecast for details.".to_string());
+        content += entity! { &report @ kind: "report", triblespace_core::metadata::name: title, body: text };
+        let title = ws.put::<LongString, _>("Memo".to_string());
+        let text = ws.put::<LongString, _>("Lunch is at noon.".to_string());
+        content +=
+            entity! { &memo @ kind: "memo", triblespace_core::metadata::name: title, body: text };
+        ws.commit(content, "documents");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+    }
+
+    let search = |args: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["--output", "json", "pile", "search", path.to_str().unwrap()])
+            .args(["--name", "main"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    assert!(search(&["q3 forecast"]).is_empty());
+    let rows = search(&["q3 forecast", "--ignore-case"]);
+    assert_eq!(rows.len(), 1, "{rows:?}");
+    assert_eq!(rows[0]["entity"], format!("{:X}", *report));
+    assert_eq!(rows[0]["attribute"], "6B1E0B9F5D3C4A2E8F7A1C3D5E7F9A1B");
+    assert!(rows[0]["snippet"]
+        .as_str()
+        .unwrap()
+        .contains("the Q3 Forecast for"));
+
+    // Short strings are searched too.
+    let rows = search(&["(?i)^mem", "--regex"]);
+    assert_eq!(rows.len(), 2, "{rows:?}");
+    assert!(rows
+        .iter()
+        .all(|row| row["entity"] == format!("{:X}", *memo)));
+}
+
+#[test]
+fn snapshot_reuses_blobs_of_unchanged_files() {
+    use triblespace::prelude::blobschemas::SimpleArchive;
+    use triblespace::prelude::*;
+    use triblespace_core::value::schemas::hash::Handle;
+
+    const CONTENT_ATTR: &str = "9C5B7D3F1E0A2C4B6D8F0E2A4C6B8D59";
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("snap.pile");
+    let tree = dir.path().join("tree");
+    std::fs::create_dir_all(tree.join("docs")).unwrap();
+    std::fs::write(tree.join("docs/kept.txt"), "unchanged").unwrap();
+    std::fs::write(tree.join("notes.txt"), "first draft").unwrap();
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+    }
+    let snapshot = || {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "snapshot", "create", path.to_str().unwrap()])
+            .args(["--name", "main", "--ephemeral", tree.to_str().unwrap()])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("2 file(s)"));
+    };
+
+    // The content handles referenced by the head commit's own content blob.
+    let head_content_handles = || -> Vec<[u8; 32]> {
+        let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        pile.refresh().unwrap();
+        let branch = pile.branches().unwrap().next().unwrap().unwrap();
+        let meta = pile.head(branch).unwrap().unwrap();
+        let reader = pile.reader().unwrap();
+        let raw_value = |set: &TribleSet, attr: Id| {
+            set.iter()
+                .find(|t| t.a() == &attr)
+                .map(|t| <[u8; 32]>::try_from(&t.data[32..]).unwrap())
+                .expect("attribute present")
+        };
+        let meta: TribleSet = reader.get(meta).unwrap();
+        let head: Value<Handle<Blake3, SimpleArchive>> =
+            Value::new(raw_value(&meta, triblespace_core::repo::head.id()));
+        let commit: TribleSet = reader.get(head).unwrap();
+        let content: Value<Handle<Blake3, SimpleArchive>> =
+            Value::new(raw_value(&commit, triblespace_core::repo::content.id()));
+        let content: TribleSet = reader.get(content).unwrap();
+        let mut handles: Vec<[u8; 32]> = content
+            .iter()
+            .filter(|t| format!("{:X}", t.a()) == CONTENT_ATTR)
+            .map(|t| t.data[32..].try_into().unwrap())
+            .collect();
+        handles.sort();
+        drop(reader);
+        pile.close().unwrap();
+        handles
+    };
+    let hash = |text: &str| *blake3::hash(text.as_bytes()).as_bytes();
+
+    snapshot();
+    let mut expected = vec![hash("unchanged"), hash("first draft")];
+    expected.sort();
+    assert_eq!(head_content_handles(), expected);
+
+    std::fs::write(tree.join("notes.txt"), "second draft").unwrap();
+    snapshot();
+    let mut expected = vec![hash("unchanged"), hash("second draft")];
+    expected.sort();
+    assert_eq!(head_content_handles(), expected);
+}
+
+#[test]
+fn snapshot_checkout_round_trips_a_tree() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("snap.pile");
+    let tree = dir.path().join("tree");
+    let dest = dir.path().join("restored");
+    std::fs::create_dir_all(tree.join("a/b")).unwrap();
+    std::fs::write(tree.join("a/b/deep.txt"), "deep").unwrap();
+    std::fs::write(tree.join("top.bin"), [0u8, 1, 2, 255]).unwrap();
+    std::fs::write(tree.join("empty"), "").unwrap();
+    std::os::unix::fs::symlink("top.bin", tree.join("link")).unwrap();
+    let old = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+    std::fs::File::options()
+        .write(true)
+        .open(tree.join("top.bin"))
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+    }
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "snapshot", "create", path.to_str().unwrap()])
+        .args(["--name", "main", "--ephemeral", tree.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let checkout = |extra: &[&str]| {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "snapshot", "checkout", path.to_str().unwrap()])
+            .args(["--name", "main", dest.to_str().unwrap()])
+            .args(extra)
+            .assert()
+            .success()
+    };
+    checkout(&[]).stdout(predicate::str::contains("4 written, 0 skipped, 0 failed"));
+
+    // Walk both trees and compare every entry.
+    fn listing(root: &std::path::Path) -> Vec<(String, Option<Vec<u8>>, Option<String>)> {
+        let mut out = Vec::new();
+        let mut stack = vec![root.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = path.strip_prefix(root).unwrap().display().to_string();
+                let meta = std::fs::symlink_metadata(&path).unwrap();
+                if meta.file_type().is_symlink() {
+                    let target = std::fs::read_link(&path).unwrap().display().to_string();
+                    out.push((relative, None, Some(target)));
+                } else if meta.is_dir() {
+                    stack.push(path);
+                } else {
+                    out.push((relative, Some(std::fs::read(&path).unwrap()), None));
+                }
+            }
+        }
+        out.sort();
+        out
+    }
+    assert_eq!(listing(&tree), listing(&dest));
+    let restored = std::fs::metadata(dest.join("top.bin")).unwrap();
+    assert_eq!(restored.modified().unwrap(), old);
+
+    checkout(&[]).stdout(predicate::str::contains("0 written, 4 skipped, 0 failed"));
+    std::fs::write(dest.join("a/b/deep.txt"), "edited").unwrap();
+    checkout(&[]).stdout(predicate::str::contains("1 written, 3 skipped"));
+    assert_eq!(
+        std::fs::read_to_string(dest.join("a/b/deep.txt")).unwrap(),
+        "deep"
+    );
+    checkout(&["--force"]).stdout(predicate::str::contains("4 written, 0 skipped"));
+}
+
+#[test]
+fn snapshot_diff_lists_added_removed_and_modified_paths() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("snap.pile");
+    let tree = dir.path().join("tree");
+    std::fs::create_dir_all(tree.join("sub")).unwrap();
+    std::fs::write(tree.join("kept.txt"), "same").unwrap();
+    std::fs::write(tree.join("edited.txt"), "before").unwrap();
+    std::fs::write(tree.join("sub/gone.txt"), "bye").unwrap();
+    {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        repo.create_branch("main", None).expect("create branch");
+        repo.into_storage().close().unwrap();
+    }
+    let snapshot = || {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "snapshot", "create", path.to_str().unwrap()])
+            .args(["--name", "main", "--ephemeral", tree.to_str().unwrap()])
+            .assert()
+            .success();
+    };
+    let diff = |extra: &[&str]| -> Vec<String> {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args([
+                "--porcelain",
+                "pile",
+                "snapshot",
+                "diff",
+                path.to_str().unwrap(),
+            ])
+            .args(["--name", "main"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    };
+
+    snapshot();
+    std::fs::write(tree.join("edited.txt"), "after").unwrap();
+    std::fs::remove_file(tree.join("sub/gone.txt")).unwrap();
+    std::fs::write(tree.join("new.txt"), "hello").unwrap();
+    snapshot();
+
+    assert_eq!(
+        diff(&[]),
+        ["M\tedited.txt", "A\tnew.txt", "D\tsub/gone.txt"]
+    );
+    assert!(diff(&["--against", tree.to_str().unwrap()]).is_empty());
+
+    std::fs::write(tree.join("kept.txt"), "changed on disk").unwrap();
+    std::fs::remove_file(tree.join("new.txt")).unwrap();
+    std::fs::write(tree.join("sub/extra.txt"), "more").unwrap();
+    assert_eq!(
+        diff(&["--against", tree.to_str().unwrap()]),
+        ["M\tkept.txt", "D\tnew.txt", "A\tsub/extra.txt"]
+    );
+}
+
+#[test]
+fn merge_records_message_and_skips_already_merged_heads() {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("merge.pile");
+
+    let (main, feature) = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let main = *repo.create_branch("main", None).unwrap();
+        let feature = *repo.create_branch("feature", None).unwrap();
+        for (branch, text) in [(main, "on main"), (feature, "on feature")] {
+            let mut ws = repo.pull(branch).unwrap();
+            let label = ws.put::<LongString, _>(text.to_string());
+            let mut content = TribleSet::new();
+            content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+            ws.commit(content, text);
+            assert!(repo.try_push(&mut ws).unwrap().is_none());
+        }
+        repo.into_storage().close().unwrap();
+        (format!("{main:X}"), format!("{feature:X}"))
+    };
+    // The commit the main branch points at, as `blake3:<hex>`.
+    let head = || {
+        let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        pile.refresh().unwrap();
+        let id = triblespace_core::id::Id::new(
+            hex::decode(&main).unwrap().as_slice().try_into().unwrap(),
+        )
+        .unwrap();
+        let reader = pile.reader().unwrap();
+        let meta: TribleSet = reader.get(pile.head(id).unwrap().unwrap()).unwrap();
+        let head_attr = triblespace_core::id::id_hex!("272FBC56108F336C4D2E17289468C35F");
+        let head = meta.iter().find(|t| t.a() == &head_attr).unwrap();
+        let head: &Value<valueschemas::Handle<Blake3, blobschemas::SimpleArchive>> = head.v();
+        let head = format!("blake3:{}", hex::encode(head.raw));
+        drop(reader);
+        pile.close().unwrap();
+        head
+    };
+    let merge = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.args(["pile", "merge", path.to_str().unwrap(), &main, &feature])
+            .args(extra)
+            .arg("--ephemeral");
+        cmd.assert().success()
+    };
+
+    let before = head();
+    merge(&["--dry-run"]).stdout(predicate::str::contains("Would merge 1 head(s)"));
+    assert_eq!(head(), before);
+
+    merge(&["--message", "bring in feature"]).stdout(predicate::str::contains("Updated"));
+    let merged = head();
+    assert_ne!(merged, before);
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "show", path.to_str().unwrap()])
+        .arg(&merged)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Message: bring in feature"));
+
+    // Merging again finds nothing new and leaves the branch alone.
+    merge(&[])
+        .stdout(predicate::str::contains("already merged"))
+        .stdout(predicate::str::contains("Already up to date"));
+    assert_eq!(head(), merged);
+}
+
+#[test]
+fn migrate_list_details_legacy_and_duplicate_branches() {
+    use triblespace::prelude::*;
+
+    attributes! {
+        "2E26F8BA886495A8DF04ACF0ED3ACBD4" as legacy_name: valueschemas::ShortString;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("migrate.pile");
+    let list = |path: &std::path::Path, extra: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "migrate", path.to_str().unwrap(), "list"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let empty = dir.path().join("empty.pile");
+    Pile::<Blake3>::open(&empty).unwrap().close().unwrap();
+    assert!(list(&empty, &[]).contains("nothing to migrate"));
+
+    let legacy = ufoid();
+    let (first, second) = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let first = *repo.create_branch("dup", None).expect("create branch");
+        let second = *repo.create_branch("dup", None).expect("create branch");
+        let mut pile = repo.into_storage();
+        let mut meta = TribleSet::new();
+        meta += entity! { &legacy @ legacy_name: "old-style" };
+        let meta = pile.put::<blobschemas::SimpleArchive, _>(meta).unwrap();
+        pile.update(*legacy, None, Some(meta)).unwrap();
+        pile.close().unwrap();
+        (first, second)
+    };
+
+    let terse = list(&path, &[]);
+    assert!(
+        terse.contains("branch-metadata-name: needed (1 branch(es))"),
+        "{terse}"
+    );
+    assert!(!terse.contains("old-style"), "{terse}");
+
+    let verbose = list(&path, &["-v"]);
+    assert!(
+        verbose.contains(&format!(
+            "{:X} legacy name \"old-style\" (modern name: no)",
+            *legacy
+        )),
+        "{verbose}"
+    );
+    let group = verbose
+        .lines()
+        .find(|line| line.trim_start().starts_with("\"dup\":"))
+        .unwrap_or_else(|| panic!("duplicate group listed:\n{verbose}"));
+    assert!(group.contains(&format!("{first:X}")) && group.contains(&format!("{second:X}")));
+
+    let json = list(&path, &["--output", "json"]);
+    let records: Vec<serde_json::Value> = json
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records[0]["status"], "needed");
+    assert!(records.iter().any(|r| r["kind"] == "legacy-name"
+        && r["branch"] == format!("{:X}", *legacy)
+        && r["modern_name"] == "no"));
+    assert!(records
+        .iter()
+        .any(|r| r["kind"] == "duplicate-name" && r["name"] == "dup"));
+}
+
+#[test]
+fn migrate_content_short_to_long_string_commits_long_strings() {
+    use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
+    use triblespace::prelude::*;
+    use triblespace_core::value::schemas::hash::Handle;
+
+    attributes! {
+        "8C2A6E9D0B1F4A3C9E7D5B3A1F0E2D4C" as title: valueschemas::ShortString;
+        "4D1E7A9C3B5F2E8D0A6C4B2E9F1D3A5B" as long_title: valueschemas::Handle<Blake3, LongString>;
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("content.pile");
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).expect("create branch");
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        for text in ["first title", "second title"] {
+            content += entity! { &ufoid() @ title: text };
+        }
+        ws.commit(content, "legacy titles");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+
+    let migrate = |extra: &[&str]| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "migrate", path.to_str().unwrap(), "run"])
+            .arg("content-short-to-long-string")
+            .args(["--attr", "8C2A6E9D0B1F4A3C9E7D5B3A1F0E2D4C"])
+            .args(["--to-attr", "4D1E7A9C3B5F2E8D0A6C4B2E9F1D3A5B"])
+            .arg("--ephemeral")
+            .args(extra)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(
+            out.status.success(),
+            "{stdout}{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        stdout
+    };
+    let dry = migrate(&["--dry-run"]);
+    assert!(dry.contains("Would rewrite 2 trible(s)"), "{dry}");
+    let stdout = migrate(&[]);
+    assert!(stdout.contains("Rewrote 2 trible(s)"), "{stdout}");
+
+    let mut pile: Pile<Blake3> = Pile::open(&path).unwrap();
+    pile.refresh().unwrap();
+    let reader = pile.reader().unwrap();
+    let meta: TribleSet = reader.get(pile.head(branch_id).unwrap().unwrap()).unwrap();
+    let head = meta
+        .iter()
+        .find(|t| t.a() == &triblespace_core::repo::head.id())
+        .map(|t| *t.v::<Handle<Blake3, SimpleArchive>>())
+        .expect("branch head");
+    let commit: TribleSet = reader.get(head).unwrap();
+    let content = commit
+        .iter()
+        .find(|t| t.a() == &triblespace_core::repo::content.id())
+        .map(|t| *t.v::<Handle<Blake3, SimpleArchive>>())
+        .expect("commit content");
+    let content: TribleSet = reader.get(content).unwrap();
+    let mut texts: Vec<String> = content
+        .iter()
+        .filter(|t| t.a() == &long_title.id())
+        .map(|t| {
+            let text: View<str> = reader.get(*t.v::<Handle<Blake3, LongString>>()).unwrap();
+            text.to_string()
+        })
+        .collect();
+    texts.sort();
+    assert_eq!(texts, ["first title", "second title"]);
+    drop(reader);
+    pile.close().unwrap();
 }
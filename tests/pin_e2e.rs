@@ -0,0 +1,105 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::tempdir;
+
+fn trible(config: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("trible").unwrap();
+    cmd.env("XDG_CONFIG_HOME", config);
+    cmd
+}
+
+/// The `space` section of `pile diagnose check --space` as JSON.
+fn space(config: &std::path::Path, pile: &str) -> serde_json::Value {
+    let out = trible(config)
+        .args([
+            "--output", "json", "pile", "diagnose", "check", "--space", pile,
+        ])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    report["space"].clone()
+}
+
+#[test]
+fn pinned_orphans_leave_the_unreachable_report() {
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("blobs.pile");
+    let pile_arg = pile.to_str().unwrap();
+    let file = dir.path().join("orphan.bin");
+    std::fs::write(&file, "o".repeat(1000)).unwrap();
+
+    let out = trible(dir.path())
+        .args(["pile", "blob", "put", pile_arg])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let handle = String::from_utf8(out.stdout).unwrap();
+    let handle = handle.split('\t').next().unwrap().trim().to_string();
+
+    let before = space(dir.path(), pile_arg);
+    assert_eq!(before["unreachable_blobs"], 1, "{before}");
+    assert_eq!(before["pinned_blobs"], 0, "{before}");
+
+    trible(dir.path())
+        .args(["pile", "pin", "add", pile_arg, &handle, "--ephemeral"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warning").not());
+    let pinned = space(dir.path(), pile_arg);
+    assert_eq!(pinned["unreachable_blobs"], 0, "{pinned}");
+    assert_eq!(pinned["pinned_blobs"], 1, "{pinned}");
+    assert_eq!(pinned["pinned_bytes"], 1000, "{pinned}");
+
+    trible(dir.path())
+        .args(["pile", "pin", "list", pile_arg])
+        .assert()
+        .success()
+        .stdout(format!("{handle}\tyes\n"));
+
+    // Further pin changes leave nothing of their own behind.
+    let ghost = format!("blake3:{}", "ab".repeat(32));
+    for action in ["add", "remove"] {
+        trible(dir.path())
+            .args(["pile", "pin", action, pile_arg, &ghost, "--ephemeral"])
+            .assert()
+            .success();
+    }
+    let again = space(dir.path(), pile_arg);
+    assert_eq!(again["unreachable_blobs"], 0, "{again}");
+    assert_eq!(again["pinned_blobs"], 1, "{again}");
+
+    trible(dir.path())
+        .args(["pile", "pin", "remove", pile_arg, &handle, "--ephemeral"])
+        .assert()
+        .success();
+    let unpinned = space(dir.path(), pile_arg);
+    assert_eq!(unpinned["unreachable_blobs"], 1, "{unpinned}");
+    assert_eq!(unpinned["pinned_blobs"], 0, "{unpinned}");
+    trible(dir.path())
+        .args(["pile", "pin", "list", pile_arg])
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn pinning_an_absent_blob_warns_but_records_the_pin() {
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("blobs.pile");
+    let pile_arg = pile.to_str().unwrap();
+    let missing = format!("blake3:{}", "ab".repeat(32));
+
+    trible(dir.path())
+        .args(["pile", "pin", "add", pile_arg, &missing, "--ephemeral"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("not in the pile"));
+    trible(dir.path())
+        .env("TRIBLES_PILE", &pile)
+        .args(["pile", "pin", "list"])
+        .assert()
+        .success()
+        .stdout(format!("{missing}\tno\n"));
+}
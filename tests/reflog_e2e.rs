@@ -72,3 +72,278 @@ fn reflog_lists_branch_updates_and_tombstones() {
         "expected set entry in reflog output, got:\n{stdout}"
     );
 }
+
+#[test]
+fn restore_points_a_branch_back_one_step() {
+    use triblespace::prelude::{BlobStore, BlobStoreGet, BranchStore};
+    use triblespace_core::repo::Repository;
+
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("restore.pile");
+
+    // The commit a branch currently points at, as hex.
+    let head_of = |branch_id: triblespace_core::id::Id| {
+        let mut pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        pile.refresh().unwrap();
+        let reader = pile.reader().unwrap();
+        let meta: TribleSet = reader.get(pile.head(branch_id).unwrap().unwrap()).unwrap();
+        let head_attr = id_hex!("272FBC56108F336C4D2E17289468C35F");
+        let head = meta.iter().find(|t| t.a() == &head_attr).unwrap();
+        let head: &Value<valueschemas::Handle<Blake3, SimpleArchive>> = head.v();
+        let head = hex::encode(head.raw);
+        drop(reader);
+        pile.close().unwrap();
+        head
+    };
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let mut repo = Repository::new(pile, key, TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        for text in ["first", "second"] {
+            let mut ws = repo.pull(branch_id).unwrap();
+            let label = ws.put::<blobschemas::LongString, _>(text.to_string());
+            let mut content = TribleSet::new();
+            content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+            ws.commit(content, text);
+            assert!(repo.try_push(&mut ws).unwrap().is_none());
+        }
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    let second = head_of(branch_id);
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "restore", pile_path.to_str().unwrap()])
+        .args(["--name", "main", "--steps", "1"])
+        .output()
+        .expect("run trible");
+    assert!(
+        out.status.success(),
+        "restore failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains(&format!("old head: blake3:{second}")),
+        "{stdout}"
+    );
+    let first = head_of(branch_id);
+    assert_ne!(first, second);
+    assert!(
+        stdout.contains(&format!("new head: blake3:{first}")),
+        "{stdout}"
+    );
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "inspect", pile_path.to_str().unwrap()])
+        .arg(format!("{branch_id:X}"))
+        .output()
+        .expect("run trible");
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.to_lowercase().contains(&first), "{stdout}");
+
+    // A handle that was never in the reflog needs --force.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "restore", pile_path.to_str().unwrap()])
+        .args(["--name", "main", "--to", &"00".repeat(32)])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn prune_history_keeps_the_most_recent_records() {
+    use predicates::prelude::*;
+    use triblespace_core::repo::Repository;
+
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("prune.pile");
+    let pile_arg = pile_path.to_str().unwrap();
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let mut repo = Repository::new(pile, key, TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        for text in ["one", "two", "three", "four", "five"] {
+            let mut ws = repo.pull(branch_id).unwrap();
+            let label = ws.put::<blobschemas::LongString, _>(text.to_string());
+            let mut content = TribleSet::new();
+            content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+            ws.commit(content, text);
+            assert!(repo.try_push(&mut ws).unwrap().is_none());
+        }
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    let reflog = || {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "reflog", pile_arg])
+            .arg(format!("{branch_id:X}"))
+            .output()
+            .expect("run trible");
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let before = reflog();
+    assert!(before.lines().count() >= 5, "{before}");
+    let prune = || {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.args(["pile", "branch", "prune-history", pile_arg])
+            .args(["--name", "main", "--keep", "2"]);
+        cmd
+    };
+
+    let size = std::fs::metadata(&pile_path).unwrap().len();
+    prune()
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("would drop"));
+    // Dropping history needs confirmation.
+    prune().write_stdin("").assert().code(5);
+    assert_eq!(std::fs::metadata(&pile_path).unwrap().len(), size);
+
+    prune()
+        .arg("--yes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "dropped {} record(s)",
+            before.lines().count() - 2
+        )));
+    assert!(std::fs::metadata(&pile_path).unwrap().len() < size);
+
+    let after = reflog();
+    assert_eq!(after.lines().count(), 2, "{after}");
+    // The two newest entries survive unchanged.
+    let newest: Vec<&str> = before
+        .lines()
+        .take(2)
+        .map(|l| l.split('\t').nth(3).unwrap())
+        .collect();
+    let kept: Vec<&str> = after
+        .lines()
+        .map(|l| l.split('\t').nth(3).unwrap())
+        .collect();
+    assert_eq!(kept, newest);
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "log", pile_arg, "main", "--oneline"])
+        .assert()
+        .success()
+        .stdout(predicate::function(|out: &str| out.lines().count() == 5));
+}
+
+#[test]
+fn prune_history_keeps_record_times_across_prunes() {
+    use predicates::prelude::*;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use triblespace_core::repo::Repository;
+
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("prune-since.pile");
+    let pile_arg = pile_path.to_str().unwrap();
+    let now = || {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+            .to_string()
+    };
+
+    // Creation and "one" before the first cutoff, "two" between the
+    // cutoffs, "three" after the second.
+    let mut cutoffs = Vec::new();
+    {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let mut repo = Repository::new(pile, key, TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        for (i, text) in ["one", "two", "three"].into_iter().enumerate() {
+            if i > 0 {
+                std::thread::sleep(Duration::from_millis(50));
+                cutoffs.push(now());
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            let mut ws = repo.pull(branch_id).unwrap();
+            let label = ws.put::<blobschemas::LongString, _>(text.to_string());
+            let mut content = TribleSet::new();
+            content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+            ws.commit(content, text);
+            assert!(repo.try_push(&mut ws).unwrap().is_none());
+        }
+        repo.into_storage().close().unwrap();
+    }
+    let prune = |since: &str| {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "branch", "prune-history", pile_arg])
+            .args(["--name", "main", "--keep-since", since, "--yes"])
+            .assert()
+            .success()
+    };
+
+    prune(&cutoffs[0]).stdout(predicate::str::contains("dropped 2 record(s)"));
+    // The surviving records still carry their own times, so a later cutoff
+    // separates them again.
+    prune(&cutoffs[1]).stdout(predicate::str::contains("dropped 1 record(s)"));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "log", pile_arg, "main", "--oneline"])
+        .assert()
+        .success()
+        .stdout(predicate::function(|out: &str| out.lines().count() == 3));
+}
+
+#[test]
+fn prune_history_refuses_a_pile_with_an_unreadable_tail() {
+    use predicates::prelude::*;
+    use std::io::Write;
+    use triblespace_core::repo::Repository;
+
+    let dir = tempdir().unwrap();
+    let pile_path = dir.path().join("prune-tail.pile");
+    let pile_arg = pile_path.to_str().unwrap();
+
+    {
+        let pile: Pile<Blake3> = Pile::open(&pile_path).unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let mut repo = Repository::new(pile, key, TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        for text in ["one", "two", "three"] {
+            let mut ws = repo.pull(branch_id).unwrap();
+            let label = ws.put::<blobschemas::LongString, _>(text.to_string());
+            let mut content = TribleSet::new();
+            content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+            ws.commit(content, text);
+            assert!(repo.try_push(&mut ws).unwrap().is_none());
+        }
+        repo.into_storage().close().unwrap();
+    }
+    // Half a record header the scan cannot read past.
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&pile_path)
+        .unwrap()
+        .write_all(&[0xAB; 32])
+        .unwrap();
+    let size = std::fs::metadata(&pile_path).unwrap().len();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "branch", "prune-history", pile_arg])
+        .args(["--all", "--keep", "1", "--yes"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("unreadable byte(s)"));
+    assert_eq!(std::fs::metadata(&pile_path).unwrap().len(), size);
+}
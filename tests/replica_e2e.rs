@@ -0,0 +1,109 @@
+use assert_cmd::Command;
+use ed25519_dalek::SigningKey;
+use predicates::prelude::*;
+use tempfile::tempdir;
+use triblespace::prelude::blobschemas::LongString;
+use triblespace::prelude::*;
+use triblespace_core::id::Id;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::Blake3;
+
+fn commit(path: &std::path::Path, branch: Option<Id>, text: &str) -> Id {
+    let pile: Pile<Blake3> = Pile::open(path).unwrap();
+    let mut repo =
+        Repository::new(pile, SigningKey::from_bytes(&[3; 32]), TribleSet::new()).unwrap();
+    let branch_id = match branch {
+        Some(id) => id,
+        None => *repo.create_branch("main", None).unwrap(),
+    };
+    let mut ws = repo.pull(branch_id).unwrap();
+    let handle = ws.put::<LongString, _>(text.to_string());
+    let mut content = TribleSet::new();
+    content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+    ws.commit(content, text);
+    assert!(repo.try_push(&mut ws).unwrap().is_none());
+    repo.into_storage().close().unwrap();
+    branch_id
+}
+
+fn verify(url: &str, pile: &std::path::Path, extra: &[&str]) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["verify-replica", url, pile.to_str().unwrap()])
+        .args(extra)
+        .assert()
+}
+
+#[test]
+fn verify_replica_catches_a_missing_blob() {
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("local.pile");
+    let remote = dir.path().join("remote");
+    std::fs::create_dir_all(&remote).unwrap();
+    let url = url::Url::from_directory_path(&remote).unwrap().to_string();
+    let text = "the blob that goes missing";
+    commit(&pile, None, text);
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "branch",
+            "push",
+            &url,
+            pile.to_str().unwrap(),
+            "main",
+            "--create",
+        ])
+        .assert()
+        .success();
+    verify(&url, &pile, &["--deep"])
+        .success()
+        .stdout(predicate::str::contains("0 problem(s) found"));
+
+    let hash = blake3::hash(text.as_bytes()).to_hex().to_string();
+    std::fs::remove_file(remote.join("blobs").join(&hash)).unwrap();
+    verify(&url, &pile, &[])
+        .code(4)
+        .stdout(predicate::str::contains(format!(
+            "missing-blob\tblake3:{hash}"
+        )))
+        .stderr(predicate::str::contains("1 problem(s)"));
+    // Heads still match, so a heads-only check passes.
+    verify(&url, &pile, &["--branches-only"]).success();
+}
+
+#[test]
+fn verify_replica_reports_head_mismatches_and_bad_blobs() {
+    let dir = tempdir().unwrap();
+    let pile = dir.path().join("local.pile");
+    let remote = dir.path().join("remote");
+    std::fs::create_dir_all(&remote).unwrap();
+    let url = url::Url::from_directory_path(&remote).unwrap().to_string();
+    let text = "bytes that get corrupted";
+    let branch_id = commit(&pile, None, text);
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "branch",
+            "push",
+            &url,
+            pile.to_str().unwrap(),
+            "main",
+            "--create",
+        ])
+        .assert()
+        .success();
+
+    let hash = blake3::hash(text.as_bytes()).to_hex().to_string();
+    std::fs::write(remote.join("blobs").join(&hash), b"garbage").unwrap();
+    verify(&url, &pile, &["--deep"])
+        .code(4)
+        .stdout(predicate::str::contains(format!("bad-blob\tblake3:{hash}")));
+
+    commit(&pile, Some(branch_id), "not pushed yet");
+    verify(&url, &pile, &["--branches-only"])
+        .code(4)
+        .stdout(predicate::str::contains("head-mismatch\tmain"));
+}
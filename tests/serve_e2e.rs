@@ -0,0 +1,144 @@
+use assert_cmd::Command;
+use ed25519_dalek::SigningKey;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Stdio};
+use tempfile::tempdir;
+use triblespace::prelude::blobschemas::LongString;
+use triblespace::prelude::*;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::Blake3;
+
+fn random_signing_key() -> SigningKey {
+    let mut seed = [0u8; 32];
+    getrandom::fill(&mut seed).expect("getrandom");
+    SigningKey::from_bytes(&seed)
+}
+
+/// Kills the server when the test ends, even on panic.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Start `trible serve` on a free port and return it with its address.
+fn serve(pile: &std::path::Path) -> (Server, String) {
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("trible"))
+        .args(["serve", pile.to_str().unwrap(), "--listen", "127.0.0.1:0"])
+        .args(["--refresh-interval", "0"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let addr = line
+        .trim()
+        .rsplit_once("http://")
+        .map(|(_, addr)| addr.to_string())
+        .unwrap_or_else(|| panic!("unexpected banner {line:?}"));
+    (Server(child), addr)
+}
+
+/// A bare HTTP/1.0 request, returning the status line and the body.
+fn request(addr: &str, method: &str, path: &str) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "{method} {path} HTTP/1.0\r\nHost: {addr}\r\n\r\n").unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .expect("header terminator");
+    let head = String::from_utf8_lossy(&response[..split]).to_string();
+    let status = head.lines().next().unwrap().to_string();
+    (status, response[split + 4..].to_vec())
+}
+
+#[test]
+fn branch_pull_over_http_from_served_pile() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("source.pile");
+    let local = dir.path().join("local.pile");
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&source).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        let mut ws = repo.pull(branch_id).expect("pull");
+        let mut content = TribleSet::new();
+        let handle = ws.put::<LongString, _>("served".to_string());
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+        ws.commit(content, "first");
+        assert!(repo.try_push(&mut ws).expect("push").is_none());
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    let branch_hex = format!("{branch_id:X}");
+
+    let (_server, addr) = serve(&source);
+
+    let (status, body) = request(&addr, "GET", &format!("/branches/{branch_hex}"));
+    assert!(status.contains("200"), "{status}");
+    assert_eq!(body.len(), 32);
+    let meta_hex = hex::encode(&body);
+    let (status, body) = request(&addr, "HEAD", &format!("/blobs/{meta_hex}"));
+    assert!(status.contains("200"), "{status}");
+    assert!(body.is_empty());
+    let (_, listing) = request(&addr, "GET", "/blobs/");
+    assert!(String::from_utf8(listing).unwrap().contains(&meta_hex));
+    let (status, _) = request(&addr, "PUT", &format!("/branches/{branch_hex}"));
+    assert!(status.contains("405"), "{status}");
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "branch",
+            "pull",
+            &format!("http://{addr}"),
+            local.to_str().unwrap(),
+            &branch_hex,
+        ])
+        .assert()
+        .success();
+
+    let mut pile: Pile<Blake3> = Pile::open(&local).unwrap();
+    pile.refresh().unwrap();
+    let head = pile.head(branch_id).unwrap().expect("pulled branch");
+    assert_eq!(hex::encode(head.raw), meta_hex);
+    pile.close().unwrap();
+}
+
+#[test]
+fn store_listing_over_http_includes_every_blob() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("source.pile");
+    let count = 1200;
+    {
+        let mut pile: Pile<Blake3> = Pile::open(&source).unwrap();
+        for i in 0..count {
+            pile.put::<LongString, _>(format!("blob {i}")).unwrap();
+        }
+        pile.close().unwrap();
+    }
+
+    let (_server, addr) = serve(&source);
+
+    // `store blob list` reads through ObjectStoreRemote, whose HTTP store
+    // sends one PROPFIND and never follows pagination links.
+    let output = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["store", "blob", "list", &format!("http://{addr}")])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(listing.lines().count(), count);
+}
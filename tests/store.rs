@@ -65,6 +65,19 @@ fn store_blob_put_uploads_file() {
 
     let blob_path = dir.path().join("blobs").join(digest);
     assert!(blob_path.exists());
+
+    let handle_out = dir.path().join("handle.txt");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["store", "blob", "put", &url, file_path.to_str().unwrap()])
+        .arg("--handle-out")
+        .arg(&handle_out)
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read_to_string(&handle_out).unwrap(),
+        format!("{handle}\n")
+    );
 }
 
 #[test]
@@ -90,6 +103,7 @@ fn store_blob_forget_removes_blob() {
     Command::cargo_bin("trible")
         .unwrap()
         .args(["store", "blob", "forget", &url, &handle])
+        .env("TRIBLES_ASSUME_YES", "1")
         .assert()
         .success();
 
@@ -138,6 +152,35 @@ fn store_blob_get_downloads_file() {
     assert_eq!(contents, &out[..]);
 }
 
+#[test]
+fn store_blob_get_missing_blob_leaves_no_output() {
+    let dir = tempdir().unwrap();
+    let output_path = dir.path().join("out.bin");
+    let url = format!("file://{}", dir.path().display());
+    let digest = blake3::hash(b"never stored").to_hex().to_string();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "store",
+            "blob",
+            "get",
+            &url,
+            &format!("blake3:{digest}"),
+            output_path.to_str().unwrap(),
+        ])
+        .assert()
+        .code(2);
+
+    assert!(!output_path.exists());
+    let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().starts_with(".trible-partial-"))
+        .collect();
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+}
+
 #[test]
 fn store_blob_inspect_outputs_metadata() {
     let dir = tempdir().unwrap();
@@ -183,6 +226,16 @@ fn store_branch_list_outputs_id() {
         .assert()
         .success()
         .stdout(predicate::str::contains(branch_hex.to_ascii_uppercase()));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--output", "json", "store", "branch", "list", &url])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{{\"id\":\"{}\"}}\n",
+            branch_hex.to_ascii_uppercase()
+        ));
 }
 
 #[test]
@@ -208,6 +261,7 @@ fn branch_push_pull_transfers_branch() {
     Command::cargo_bin("trible")
         .unwrap()
         .args(["branch", "push", &url, local.to_str().unwrap(), &branch_hex])
+        .arg("--create")
         .assert()
         .success();
 
@@ -225,3 +279,671 @@ fn branch_push_pull_transfers_branch() {
         .success()
         .stdout(predicate::str::contains(branch_hex.to_ascii_uppercase()));
 }
+
+#[test]
+fn branch_push_requires_create_for_a_new_remote_branch() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    commit_label(&local, branch_id, "first");
+    let push = |create: bool| {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.args(["branch", "push", &url, local.to_str().unwrap(), "main"]);
+        if create {
+            cmd.arg("--create");
+        }
+        cmd.assert()
+    };
+
+    push(false)
+        .code(2)
+        .stderr(predicate::str::contains("does not exist; pass --create"));
+    assert_eq!(
+        std::fs::read_dir(remote_dir.join("blobs")).unwrap().count(),
+        0,
+        "nothing is uploaded for a refused push"
+    );
+
+    push(true).success();
+    commit_label(&local, branch_id, "second");
+    push(false).success();
+}
+
+#[test]
+fn push_records_are_not_reported_as_branches() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    commit_label(&local, branch_id, "first");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "branch",
+            "push",
+            &url,
+            local.to_str().unwrap(),
+            "main",
+            "--create",
+        ])
+        .assert()
+        .success();
+
+    // The push left a record in the pile's branch store next to `main`.
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--output", "json", "pile", "diagnose", "check"])
+        .arg(&local)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let branches = report["branches"].as_array().unwrap();
+    assert_eq!(branches.len(), 1, "{report}");
+    assert_eq!(branches[0]["id"], format!("{branch_id:X}"));
+}
+
+#[test]
+fn squash_leaves_push_records_behind() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let squashed = dir.path().join("squashed.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(&remote_dir).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+    let key_path = dir.path().join("signing.key");
+    std::fs::write(&key_path, "11".repeat(32)).unwrap();
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        repo.into_storage().close().unwrap();
+        branch_id
+    };
+    commit_label(&local, branch_id, "first");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["branch", "push", &url, local.to_str().unwrap(), "main"])
+        .arg("--create")
+        .assert()
+        .success();
+
+    // The push record sits in the branch store but is not squashed.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "squash", local.to_str().unwrap()])
+        .arg(&squashed)
+        .args(["--signing-key", key_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("skip").not());
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["--output", "porcelain", "pile", "branch", "list"])
+        .arg(&squashed)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let listing = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(listing.lines().count(), 1, "{listing}");
+    assert!(listing.contains("main"), "{listing}");
+}
+
+fn commit_label(path: &std::path::Path, branch_id: triblespace_core::id::Id, label: &str) {
+    commit_label_as(path, branch_id, label, random_signing_key());
+}
+
+fn commit_label_as(
+    path: &std::path::Path,
+    branch_id: triblespace_core::id::Id,
+    label: &str,
+    key: SigningKey,
+) {
+    use triblespace::prelude::blobschemas::LongString;
+    use triblespace::prelude::*;
+
+    let pile: Pile<Blake3> = Pile::open(path).unwrap();
+    let mut repo = Repository::new(pile, key, TribleSet::new()).unwrap();
+    let mut ws = repo.pull(branch_id).expect("pull");
+    let mut content = TribleSet::new();
+    let handle = ws.put::<LongString, _>(label.to_string());
+    content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+    ws.commit(content, label);
+    assert!(repo.try_push(&mut ws).expect("push").is_none());
+    repo.into_storage().close().unwrap();
+}
+
+fn pushed_blob_count(url: &str, pile: &std::path::Path, branch: &str, extra: &[&str]) -> usize {
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["branch", "push", url, pile.to_str().unwrap(), branch])
+        .arg("--create")
+        .args(extra)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "push failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("discovered ")?.strip_suffix(" blob(s)"))
+        .expect("discovered line")
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn branch_push_only_walks_commits_since_last_push() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        repo.close().unwrap();
+        branch_id
+    };
+    for label in ["one", "two", "three", "four"] {
+        commit_label(&local, branch_id, label);
+    }
+    let branch_hex = format!("{branch_id:X}");
+
+    let full = pushed_blob_count(&url, &local, &branch_hex, &[]);
+
+    commit_label(&local, branch_id, "five");
+    let incremental = pushed_blob_count(&url, &local, &branch_hex, &[]);
+    assert!(
+        incremental < full,
+        "incremental push discovered {incremental} blobs, full push {full}"
+    );
+
+    let uncached = pushed_blob_count(&url, &local, &branch_hex, &["--no-cache"]);
+    assert!(uncached > incremental);
+}
+
+#[test]
+fn branch_push_walks_everything_again_after_the_remote_was_wiped() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        repo.close().unwrap();
+        branch_id
+    };
+    commit_label(&local, branch_id, "one");
+    let branch_hex = format!("{branch_id:X}");
+    pushed_blob_count(&url, &local, &branch_hex, &[]);
+
+    // The push record survives locally, but the remote lost the history.
+    std::fs::remove_dir_all(&remote_dir).unwrap();
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    commit_label(&local, branch_id, "two");
+    let after_wipe = pushed_blob_count(&url, &local, &branch_hex, &[]);
+    let uncached = pushed_blob_count(&url, &local, &branch_hex, &["--no-cache"]);
+    assert_eq!(after_wipe, uncached);
+}
+
+#[test]
+fn store_blob_put_respects_limit_rate() {
+    const MIB: u64 = 1024 * 1024;
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("slow.bin");
+    std::fs::write(&file_path, vec![7u8; 15 * MIB as usize]).unwrap();
+    let store = dir.path().join("store");
+    let blobs = store.join("blobs");
+    std::fs::create_dir_all(&blobs).unwrap();
+    let url = format!("file://{}", store.display());
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("trible"))
+        .args(["store", "blob", "put", &url])
+        .arg(&file_path)
+        .args(["--limit-rate", "5M"])
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    // Sample how many bytes have reached the store while the upload runs.
+    let start = std::time::Instant::now();
+    let mut samples: Vec<(std::time::Duration, u64)> = Vec::new();
+    let status = loop {
+        let landed: u64 = std::fs::read_dir(&blobs)
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.metadata().ok())
+            .map(|meta| meta.len())
+            .sum();
+        samples.push((start.elapsed(), landed));
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    };
+    assert!(status.success());
+
+    // A burst of one second's budget is allowed; past that, no half second
+    // may see much more than half a second's worth of data arrive.
+    for (i, &(at, before)) in samples.iter().enumerate() {
+        let window = samples[i..]
+            .iter()
+            .take_while(|(later, _)| *later - at <= std::time::Duration::from_millis(500))
+            .map(|&(_, landed)| landed - before.min(landed))
+            .max()
+            .unwrap_or(0);
+        assert!(
+            window <= 5 * MIB + 5 * MIB / 2 + 64 * 1024,
+            "{window} bytes arrived within 500ms of {at:?}: {samples:?}"
+        );
+    }
+    assert!(start.elapsed() >= std::time::Duration::from_millis(1600));
+}
+
+#[test]
+fn branch_push_multiple_branches_sends_shared_history_once() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let url_for = |name: &str| {
+        let remote_dir = dir.path().join(name);
+        std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+        std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+        format!("file://{}", remote_dir.display())
+    };
+
+    let left = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let left = *repo.create_branch("left", None).unwrap();
+        repo.close().unwrap();
+        left
+    };
+    for label in ["base-one", "base-two", "base-three"] {
+        commit_label(&local, left, label);
+    }
+    // Fork `right` off the shared history, then let both sides diverge.
+    let right = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let head = repo.pull(left).expect("pull").head().expect("head");
+        let right = *repo.create_branch("right", Some(head)).unwrap();
+        repo.close().unwrap();
+        right
+    };
+    commit_label(&local, left, "left-only");
+    commit_label(&local, right, "right-only");
+
+    let left_alone = pushed_blob_count(&url_for("a"), &local, "left", &[]);
+    let right_alone = pushed_blob_count(&url_for("b"), &local, "right", &[]);
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["branch", "push", &url_for("c"), local.to_str().unwrap()])
+        .arg("--create")
+        .args(["left", "right"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let together: usize = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("discovered ")?.strip_suffix(" blob(s)"))
+        .expect("discovered line")
+        .parse()
+        .unwrap();
+    assert!(
+        together < left_alone + right_alone,
+        "combined push discovered {together} blobs, separately {left_alone} + {right_alone}"
+    );
+    assert!(stdout.contains(&format!("left: pushed {left:X}")));
+    assert!(stdout.contains(&format!("right: pushed {right:X}")));
+}
+
+#[test]
+fn branch_push_all_leaves_remote_consistent() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let other = dir.path().join("other.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+
+    let left = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let left = *repo.create_branch("left", None).unwrap();
+        repo.close().unwrap();
+        left
+    };
+    commit_label(&local, left, "shared");
+    let right = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let head = repo.pull(left).expect("pull").head().expect("head");
+        let right = *repo.create_branch("right", Some(head)).unwrap();
+        repo.close().unwrap();
+        right
+    };
+    commit_label(&local, left, "left-only");
+    commit_label(&local, right, "right-only");
+
+    let out = Command::cargo_bin("trible")
+        .unwrap()
+        .args(["branch", "push", &url, local.to_str().unwrap(), "--all"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!("left: pushed {left:X}")),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!("right: pushed {right:X}")),
+        "{stdout}"
+    );
+    assert_eq!(
+        stdout.matches("not shared with an earlier branch").count(),
+        2,
+        "{stdout}"
+    );
+
+    // Both remote heads resolve to complete histories.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args([
+            "branch",
+            "pull",
+            &url,
+            other.to_str().unwrap(),
+            "left",
+            "right",
+        ])
+        .assert()
+        .success();
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "diagnose", "check", other.to_str().unwrap()])
+        .assert()
+        .success();
+}
+
+/// Append a commit that carries content but no signature.
+fn commit_unsigned(path: &std::path::Path, branch_id: triblespace_core::id::Id) {
+    use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
+    use triblespace::prelude::valueschemas::Handle;
+    use triblespace::prelude::*;
+    use triblespace_core::blob::ToBlob;
+    use triblespace_core::repo;
+
+    let mut pile: Pile<Blake3> = Pile::open(path).unwrap();
+    pile.refresh().unwrap();
+    let old_meta = pile.head(branch_id).unwrap().unwrap();
+    let meta: TribleSet = pile.reader().unwrap().get(old_meta).unwrap();
+    let head_attr = repo::head.id();
+    let parent = *meta
+        .iter()
+        .find(|t| t.a() == &head_attr)
+        .unwrap()
+        .v::<Handle<Blake3, SimpleArchive>>();
+
+    let label = pile.put::<LongString, _>("unsigned".to_string()).unwrap();
+    let mut content = TribleSet::new();
+    content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+    let content = pile.put(content).unwrap();
+    let mut commit = TribleSet::new();
+    commit += entity! { &ufoid() @ repo::content: content, repo::parent: parent };
+    pile.put(commit.clone()).unwrap();
+
+    let name = pile.put::<LongString, _>("main".to_string()).unwrap();
+    let new_meta = repo::branch::branch_metadata(
+        &random_signing_key(),
+        branch_id,
+        name,
+        Some(commit.to_blob()),
+    );
+    let new_meta = pile.put(new_meta).unwrap();
+    assert!(matches!(
+        pile.update(branch_id, Some(old_meta), Some(new_meta))
+            .unwrap(),
+        repo::PushResult::Success()
+    ));
+    pile.close().unwrap();
+}
+
+fn local_head(path: &std::path::Path, branch_id: triblespace_core::id::Id) -> Option<[u8; 32]> {
+    use triblespace::prelude::BranchStore;
+
+    let mut pile: Pile<Blake3> = Pile::open(path).unwrap();
+    pile.refresh().unwrap();
+    let head = pile.head(branch_id).unwrap().map(|h| h.raw);
+    pile.close().unwrap();
+    head
+}
+
+#[test]
+fn branch_pull_enforces_required_signers() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let other = dir.path().join("other.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+    let alice = SigningKey::from_bytes(&[11u8; 32]);
+    let bob = SigningKey::from_bytes(&[12u8; 32]);
+    let public = |key: &SigningKey| hex::encode(key.verifying_key().to_bytes());
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        repo.close().unwrap();
+        branch_id
+    };
+    commit_label_as(&local, branch_id, "by alice", alice.clone());
+    let branch_hex = format!("{branch_id:X}");
+    let push = || {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["branch", "push", &url, local.to_str().unwrap(), &branch_hex])
+            .arg("--create")
+            .assert()
+            .success();
+    };
+    let pull = |allowed: &[&SigningKey]| {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.args(["branch", "pull", &url, other.to_str().unwrap(), &branch_hex]);
+        for key in allowed {
+            cmd.args(["--require-signed-by", &public(key)]);
+        }
+        cmd.output().unwrap()
+    };
+    push();
+
+    // A commit by a key outside the allowed set is refused.
+    let out = pull(&[&bob]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains(&format!(
+            "signed by ed25519:{}, which is not allowed",
+            public(&alice)
+        )),
+        "{stderr}"
+    );
+    assert_eq!(local_head(&other, branch_id), None);
+
+    let out = pull(&[&bob, &alice]);
+    assert!(out.status.success(), "{out:?}");
+    let pulled = local_head(&other, branch_id);
+    assert!(pulled.is_some());
+
+    // Commits already present locally are not checked again, but a new
+    // unsigned one is refused and the head stays put.
+    commit_unsigned(&local, branch_id);
+    push();
+    let out = pull(&[&alice]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains(": unsigned"), "{stderr}");
+    assert_eq!(local_head(&other, branch_id), pulled);
+}
+
+#[test]
+fn store_branch_set_uses_compare_and_swap() {
+    use triblespace::prelude::blobschemas::{LongString, SimpleArchive};
+    use triblespace::prelude::*;
+    use triblespace_core::repo::objectstore::ObjectStoreRemote;
+
+    let dir = tempdir().unwrap();
+    let url = format!("file://{}", dir.path().display());
+    let parsed = url::Url::parse(&url).unwrap();
+    let branch_id = triblespace_core::id::id_hex!("44444444444444444444444444444444");
+    let branch = format!("{branch_id:X}");
+
+    let mut remote: ObjectStoreRemote<Blake3> = ObjectStoreRemote::with_url(&parsed).unwrap();
+    let mut metas = Vec::new();
+    for name in ["a", "b"] {
+        let name = remote.put::<LongString, _>(name.to_string()).unwrap();
+        let mut meta = TribleSet::new();
+        meta += entity! { &ufoid() @ triblespace_core::metadata::name: name };
+        let handle = remote.put::<SimpleArchive, _>(meta).unwrap();
+        metas.push((handle, format!("blake3:{}", hex::encode(handle.raw))));
+    }
+    let set = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("trible").unwrap();
+        cmd.args(["store", "branch", "set", &url, &branch])
+            .args(extra);
+        cmd.assert()
+    };
+
+    // Create from none, then update using the current head as expected.
+    set(&[&metas[0].1]).success();
+    assert_eq!(remote.head(branch_id).unwrap(), Some(metas[0].0));
+    set(&[&metas[1].1, "--expected", &metas[0].1]).success();
+    assert_eq!(remote.head(branch_id).unwrap(), Some(metas[1].0));
+
+    // A stale expected handle is a conflict and leaves the head alone.
+    set(&[&metas[0].1, "--expected", &metas[0].1])
+        .code(3)
+        .stderr(predicate::str::contains(format!("current={}", metas[1].1)));
+    set(&[&metas[0].1, "--expected", "none"]).code(3);
+    assert_eq!(remote.head(branch_id).unwrap(), Some(metas[1].0));
+
+    set(&["--delete"])
+        .success()
+        .stdout(predicate::str::contains("deleted branch"));
+    assert_eq!(remote.head(branch_id).unwrap(), None);
+    set(&["--delete"]).code(2);
+}
+
+#[test]
+fn branch_pull_depth_fetches_only_recent_commits() {
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let other = dir.path().join("other.pile");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("branches")).unwrap();
+    std::fs::create_dir_all(remote_dir.join("blobs")).unwrap();
+    let url = format!("file://{}", remote_dir.display());
+
+    let branch_id = {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        repo.close().unwrap();
+        branch_id
+    };
+    for label in ["first", "second", "third"] {
+        commit_label(&local, branch_id, label);
+    }
+    let branch_hex = format!("{branch_id:X}");
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["branch", "push", &url, local.to_str().unwrap(), &branch_hex])
+        .arg("--create")
+        .assert()
+        .success();
+
+    let pull = |extra: &[&str]| {
+        Command::cargo_bin("trible")
+            .unwrap()
+            .args(["branch", "pull", &url, other.to_str().unwrap(), &branch_hex])
+            .args(extra)
+            .assert()
+            .success()
+    };
+    // Each commit's content names its label as a LongString blob.
+    let has_label = |label: &str| {
+        let out = Command::cargo_bin("trible")
+            .unwrap()
+            .args(["pile", "blob", "list", other.to_str().unwrap()])
+            .output()
+            .unwrap();
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .contains(&blake3::hash(label.as_bytes()).to_hex().to_string())
+    };
+
+    pull(&["--depth", "1"]).stdout(predicate::str::contains("shallow"));
+    assert!(has_label("third"));
+    assert!(!has_label("second"));
+    assert!(!has_label("first"));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "diagnose", "check", other.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "commit chain: 1 commits (shallow)",
+        ));
+
+    pull(&["--unshallow"]);
+    assert!(has_label("first") && has_label("second"));
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["pile", "diagnose", "check", other.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("commit chain: 3 commits"));
+}
@@ -0,0 +1,144 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::tempdir;
+
+fn hex(data: &[u8]) -> String {
+    blake3::hash(data).to_hex().to_string()
+}
+
+#[test]
+fn fsck_reports_and_moves_misplaced_objects() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("store");
+    std::fs::create_dir_all(root.join("blobs")).unwrap();
+    std::fs::create_dir_all(root.join("branches")).unwrap();
+    let url = url::Url::from_directory_path(&root).unwrap().to_string();
+
+    let good = b"well placed";
+    std::fs::write(root.join("blobs").join(hex(good)), good).unwrap();
+    let stray = b"left at the root by an old client";
+    std::fs::write(root.join(hex(stray)), stray).unwrap();
+    let bad_key = hex(b"what the key promises");
+    std::fs::write(root.join("blobs").join(&bad_key), b"something else").unwrap();
+    let branch = "A1B2C3D4E5F60718293A4B5C6D7E8F90";
+    std::fs::write(root.join("branches").join(branch), [7u8; 32]).unwrap();
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["store", "fsck", &url])
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains(format!(
+            "misplaced-blob\t{}\t-> blobs/{}",
+            hex(stray),
+            hex(stray)
+        )))
+        .stdout(predicate::str::contains(format!(
+            "bad-hash\tblobs/{bad_key}\t-"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "misnamed-branch\tbranches/{branch}\t-> branches/{}",
+            branch.to_ascii_lowercase()
+        )))
+        .stdout(predicate::str::contains(hex(good)).not());
+
+    // Moving objects needs confirmation.
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["store", "fsck", &url, "--fix"])
+        .write_stdin("")
+        .assert()
+        .code(5);
+    assert!(root.join(hex(stray)).exists());
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["store", "fsck", &url, "--fix", "--yes"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("1 layout problem(s) remain"));
+    assert!(!root.join(hex(stray)).exists());
+    assert_eq!(
+        std::fs::read(root.join("blobs").join(hex(stray))).unwrap(),
+        stray
+    );
+    let names: Vec<String> = std::fs::read_dir(root.join("branches"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(names, [branch.to_ascii_lowercase()]);
+    // The bad blob is reported, never touched.
+    assert_eq!(
+        std::fs::read(root.join("blobs").join(&bad_key)).unwrap(),
+        b"something else"
+    );
+}
+
+#[test]
+fn fsck_finds_nothing_to_fix_after_a_push() {
+    use triblespace::prelude::*;
+    use triblespace_core::repo::pile::Pile;
+    use triblespace_core::repo::Repository;
+    use triblespace_core::trible::TribleSet;
+    use triblespace_core::value::schemas::hash::Blake3;
+
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("local.pile");
+    let root = dir.path().join("store");
+    std::fs::create_dir_all(&root).unwrap();
+    let url = url::Url::from_directory_path(&root).unwrap().to_string();
+
+    {
+        let pile: Pile<Blake3> = Pile::open(&local).unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let mut repo = Repository::new(pile, key, TribleSet::new()).unwrap();
+        let branch_id = *repo.create_branch("main", None).unwrap();
+        let mut ws = repo.pull(branch_id).unwrap();
+        let label = ws.put::<blobschemas::LongString, _>("pushed".to_string());
+        let mut content = TribleSet::new();
+        content += entity! { &ufoid() @ triblespace_core::metadata::name: label };
+        ws.commit(content, "pushed");
+        assert!(repo.try_push(&mut ws).unwrap().is_none());
+        repo.into_storage().close().unwrap();
+    }
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["branch", "push", &url, local.to_str().unwrap(), "main"])
+        .arg("--create")
+        .assert()
+        .success();
+    let listing = || {
+        let mut names: Vec<String> = store_keys(&root);
+        names.sort();
+        names
+    };
+    let before = listing();
+    assert!(before.iter().any(|name| name.starts_with("branches/")));
+
+    Command::cargo_bin("trible")
+        .unwrap()
+        .args(["store", "fsck", &url, "--fix", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 problem(s) found, 0 fixed"));
+    assert_eq!(listing(), before);
+}
+
+/// Every file below `root` as a `/`-separated relative key.
+fn store_keys(root: &std::path::Path) -> Vec<String> {
+    let mut keys = Vec::new();
+    for entry in std::fs::read_dir(root).unwrap() {
+        let entry = entry.unwrap();
+        let name = entry.file_name().into_string().unwrap();
+        if entry.file_type().unwrap().is_dir() {
+            keys.extend(
+                store_keys(&entry.path())
+                    .into_iter()
+                    .map(|key| format!("{name}/{key}")),
+            );
+        } else {
+            keys.push(name);
+        }
+    }
+    keys
+}
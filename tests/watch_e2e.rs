@@ -0,0 +1,68 @@
+use ed25519_dalek::SigningKey;
+use std::io::{BufRead, BufReader, Read};
+use std::process::Stdio;
+use tempfile::tempdir;
+use triblespace::prelude::blobschemas::LongString;
+use triblespace::prelude::*;
+use triblespace_core::repo::pile::Pile;
+use triblespace_core::repo::Repository;
+use triblespace_core::trible::TribleSet;
+use triblespace_core::value::schemas::hash::Blake3;
+
+fn random_signing_key() -> SigningKey {
+    let mut seed = [0u8; 32];
+    getrandom::fill(&mut seed).expect("getrandom");
+    SigningKey::from_bytes(&seed)
+}
+
+fn commit(repo: &mut Repository<Pile<Blake3>>, branch_id: Id, text: &str) {
+    let mut ws = repo.pull(branch_id).expect("pull");
+    let mut content = TribleSet::new();
+    let handle = ws.put::<LongString, _>(text.to_string());
+    content += entity! { &ufoid() @ triblespace_core::metadata::name: handle };
+    ws.commit(content, text);
+    assert!(repo.try_push(&mut ws).expect("push").is_none());
+}
+
+#[test]
+fn watch_once_reports_a_head_change_from_another_handle() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("watch.pile");
+
+    let pile: Pile<Blake3> = Pile::open(&path).unwrap();
+    let mut repo = Repository::new(pile, random_signing_key(), TribleSet::new()).unwrap();
+    let branch_id = *repo.create_branch("main", None).unwrap();
+    commit(&mut repo, branch_id, "first");
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("trible"))
+        .args(["pile", "branch", "watch", path.to_str().unwrap()])
+        .args(["--name", "main", "--once", "--interval", "20"])
+        .args(["--exec", "echo \"exec $TRIBLE_BRANCH_ID $TRIBLE_NEW_HEAD\""])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut banner = String::new();
+    stdout.read_line(&mut banner).unwrap();
+    assert!(banner.starts_with("watching branch"), "{banner:?}");
+
+    commit(&mut repo, branch_id, "second");
+    repo.into_storage().close().unwrap();
+
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let lines: Vec<&str> = rest.lines().collect();
+    assert_eq!(lines.len(), 2, "{rest}");
+    let fields: Vec<&str> = lines[0].split('\t').collect();
+    assert_eq!(fields[0], format!("{branch_id:X}"));
+    assert!(fields[1].starts_with("blake3:"), "{rest}");
+    assert!(fields[2].starts_with("blake3:"), "{rest}");
+    assert_ne!(fields[1], fields[2]);
+    assert_eq!(
+        lines[1],
+        format!("exec {branch_id:X} {}", fields[2]),
+        "{rest}"
+    );
+}